@@ -1,29 +1,31 @@
 #![allow(non_camel_case_types)]
 
+use hgl_sim::impl_sim_struct;
 use hgl_sim::prelude::component::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct t_apb_rom_request {
     pub enable: Bit,
     pub address: Bv<16>,
 }
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct t_apb_processor_request {
     pub valid: Bit,
     pub address: Bv<16>,
 }
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct t_apb_processor_response {
     pub acknowledge: Bit,
     pub rom_busy: Bit,
 }
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct t_apb_response {
     pub prdata: Bv<32>,
     pub pready: Bit,
     pub perr: Bit,
 }
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct t_apb_request {
     pub paddr: Bv<32>,
     pub penable: Bit,
@@ -31,3 +33,109 @@ pub struct t_apb_request {
     pub pwrite: Bit,
     pub pwdata: Bv<32>,
 }
+
+impl_sim_struct!(t_apb_rom_request { enable: Bit, address: Bv<16> });
+impl_sim_struct!(t_apb_processor_request { valid: Bit, address: Bv<16> });
+impl_sim_struct!(t_apb_processor_response {
+    acknowledge: Bit,
+    rom_busy: Bit
+});
+impl_sim_struct!(t_apb_response { prdata: Bv<32>, pready: Bit, perr: Bit });
+impl_sim_struct!(t_apb_request {
+    paddr: Bv<32>,
+    penable: Bit,
+    psel: Bit,
+    pwrite: Bit,
+    pwdata: Bv<32>,
+});
+
+//a APB protocol assertions
+//fp add_apb_assertions
+/// Add assertions for the APB protocol rules to a simulation, for an
+/// APB request and response given by hierarchical path (such as
+/// "dut.apb_request" and "dut.apb_response"), checked on a clock
+///
+/// The rules are:
+///
+/// * a setup phase (psel without penable) is followed by an access
+///   phase (psel with penable) in the next cycle
+///
+/// * penable is only asserted with psel
+///
+/// * an access phase that is not ready continues in the next cycle
+///
+/// * an access phase completes (pready) within `max_wait` cycles
+///
+/// The assertions are named "<request>.<rule>"
+pub fn add_apb_assertions(
+    sim: &Simulation,
+    clock: ClockIndex,
+    request: &str,
+    response: &str,
+    max_wait: usize,
+) -> Result<(), String> {
+    let psel = format!("{request}.psel");
+    let penable = format!("{request}.penable");
+    let pready = format!("{response}.pready");
+    sim.add_assertion(
+        &format!("{request}.setup_then_access"),
+        clock,
+        Property::implies_next(
+            &format!("{psel} && !{penable}"),
+            &format!("{psel} && {penable}"),
+        )?,
+    )?;
+    sim.add_assertion(
+        &format!("{request}.penable_needs_psel"),
+        clock,
+        Property::always(&format!("!{penable} || {psel}"))?,
+    )?;
+    sim.add_assertion(
+        &format!("{request}.access_held_until_ready"),
+        clock,
+        Property::implies_next(
+            &format!("{psel} && {penable} && !{pready}"),
+            &format!("{psel} && {penable}"),
+        )?,
+    )?;
+    sim.add_assertion(
+        &format!("{request}.access_completes"),
+        clock,
+        Property::implies_within(&format!("{psel} && {penable}"), &pready, max_wait)?,
+    )?;
+    Ok(())
+}
+
+//a APB functional coverage
+//fp apb_covergroup
+/// Create a cover group for the transactions of an APB request and
+/// response given by hierarchical path, sampled on the clock when an
+/// access phase completes
+///
+/// The registers are the names and addresses of the target; the
+/// group has points "direction" (read or write), "response" (ok or
+/// err) and "register", with crosses of the direction with the
+/// register and the response
+pub fn apb_covergroup(
+    name: &str,
+    clock: ClockIndex,
+    request: &str,
+    response: &str,
+    registers: &[(&str, u64)],
+) -> CoverGroup {
+    CoverGroup::new(name, clock)
+        .when(&format!(
+            "{request}.psel && {request}.penable && {response}.pready"
+        ))
+        .point(
+            CoverPoint::new("direction", &format!("{request}.pwrite"))
+                .variants(&[("read", 0), ("write", 1)]),
+        )
+        .point(
+            CoverPoint::new("response", &format!("{response}.perr"))
+                .variants(&[("ok", 0), ("err", 1)]),
+        )
+        .point(CoverPoint::new("register", &format!("{request}.paddr")).variants(registers))
+        .cross("direction_x_register", &["direction", "register"])
+        .cross("direction_x_response", &["direction", "response"])
+}
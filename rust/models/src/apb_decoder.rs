@@ -0,0 +1,374 @@
+//a Documentation
+//! An APB interconnect with an address decoder
+//!
+//! The [ApbDecoder] takes a `t_apb_request` from a master and fans it
+//! out to a number of targets, selecting (with psel) the target whose
+//! region of an [ApbAddressMap] contains the address; the address
+//! presented to the target is the offset within its region, and the
+//! other targets are presented with an idle request. The
+//! `t_apb_response` of the selected target is returned to the
+//! master; a request to an unmapped address completes immediately
+//! with perr, and asserts the decode_error output.
+//!
+//! The decoder is combinational; it is connected to the request and
+//! response states of its targets with [ApbDecoder::connect], which
+//! adds a combinational function to the simulation to copy the
+//! responses into the decoder, evaluate it, and copy its requests out
+//! to the targets. The master side is not connected by the decoder.
+//!
+//! The state of the decoder is the master side `apb_request` and
+//! `apb_response`, the `decode_error` output, and for each target
+//! region a `<name>_request` output and `<name>_response` input.
+
+//a Imports
+use hgl_sim::prelude::component::*;
+
+use crate::apb_monitor::find_typed;
+use crate::{t_apb_request, t_apb_response};
+
+//a ApbAddressMap
+//tp ApbRegion
+/// A region of an [ApbAddressMap], for one target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApbRegion {
+    /// Name of the region, used for the names of the target states
+    pub name: String,
+    /// Base address of the region
+    pub base: u32,
+    /// Size of the region in bytes
+    pub size: u32,
+}
+
+//ip ApbRegion
+impl ApbRegion {
+    //ap contains
+    /// Return true if an address is within the region
+    pub fn contains(&self, address: u32) -> bool {
+        address >= self.base && (address - self.base) < self.size
+    }
+}
+
+//tp ApbAddressMap
+/// The address map of an [ApbDecoder]; the regions are the targets
+/// of the decoder, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApbAddressMap {
+    regions: Vec<ApbRegion>,
+}
+
+//ip ApbAddressMap
+impl ApbAddressMap {
+    //cp target
+    /// Add a region for a target to the map
+    pub fn target(mut self, name: &str, base: u32, size: u32) -> Self {
+        self.regions.push(ApbRegion {
+            name: name.into(),
+            base,
+            size,
+        });
+        self
+    }
+
+    //ap regions
+    /// The regions of the map
+    pub fn regions(&self) -> &[ApbRegion] {
+        &self.regions
+    }
+
+    //ap decode
+    /// Return the index of the region containing an address, if any
+    pub fn decode(&self, address: u32) -> Option<usize> {
+        self.regions.iter().position(|r| r.contains(address))
+    }
+
+    //mp validate
+    /// Check that the regions are not empty, do not wrap the address
+    /// space, do not overlap, and have distinct names (other than
+    /// 'apb', which is used for the master side of the decoder)
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, r) in self.regions.iter().enumerate() {
+            if r.name == "apb" {
+                return Err("APB region may not be named 'apb'".into());
+            }
+            if r.size == 0 {
+                return Err(format!("APB region '{}' is empty", r.name));
+            }
+            if r.base.checked_add(r.size - 1).is_none() {
+                return Err(format!(
+                    "APB region '{}' extends beyond the address space",
+                    r.name
+                ));
+            }
+            for o in &self.regions[..i] {
+                if o.name == r.name {
+                    return Err(format!("Duplicate APB region name '{}'", r.name));
+                }
+                if o.contains(r.base) || r.contains(o.base) {
+                    return Err(format!("APB regions '{}' and '{}' overlap", o.name, r.name));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+//a Inputs, Outputs
+//tp Inputs
+#[derive(Debug, Default)]
+pub struct Inputs {
+    pub apb_request: t_apb_request,
+    pub target_responses: Vec<t_apb_response>,
+}
+
+//tp Outputs
+#[derive(Debug, Default)]
+pub struct Outputs {
+    pub apb_response: t_apb_response,
+    pub decode_error: Bit,
+    pub target_requests: Vec<t_apb_request>,
+}
+
+//a ApbDecoder
+//tp ApbDecoder
+/// An APB interconnect, decoding the address of a request to select
+/// one of a number of targets
+#[derive(Debug, Default)]
+pub struct ApbDecoder {
+    pub inputs: Inputs,
+    pub outputs: Outputs,
+    map: ApbAddressMap,
+    state_names: Vec<String>,
+}
+
+//ip ApbDecoder
+impl ApbDecoder {
+    //ap map
+    /// The address map of the decoder
+    pub fn map(&self) -> &ApbAddressMap {
+        &self.map
+    }
+
+    //mp evaluate
+    /// Evaluate the decoder from its inputs
+    pub fn evaluate(&mut self) {
+        let request = self.inputs.apb_request;
+        let address = request.paddr.try_as_u64().unwrap_or(0) as u32;
+        let selected = self.map.decode(address);
+        for (i, (region, target)) in self
+            .map
+            .regions
+            .iter()
+            .zip(self.outputs.target_requests.iter_mut())
+            .enumerate()
+        {
+            *target = {
+                if request.psel.is_true() && selected == Some(i) {
+                    t_apb_request {
+                        paddr: ((address - region.base) as u64).into(),
+                        ..request
+                    }
+                } else {
+                    t_apb_request::default()
+                }
+            };
+        }
+        let decode_error = request.psel.is_true() && selected.is_none();
+        self.outputs.decode_error = decode_error.into();
+        self.outputs.apb_response = {
+            if decode_error {
+                t_apb_response {
+                    pready: true.into(),
+                    perr: true.into(),
+                    ..Default::default()
+                }
+            } else if request.psel.is_true() {
+                self.inputs.target_responses[selected.unwrap()]
+            } else {
+                t_apb_response::default()
+            }
+        };
+    }
+
+    //fp connect
+    /// Connect an instance of the decoder to the request and response
+    /// of each of its targets, given by path (such as
+    /// "gpio.apb_request" and "gpio.apb_response") in the order of
+    /// the regions of its address map
+    ///
+    /// Only the target side of the decoder is connected; the master
+    /// must itself drive the `apb_request` input of the decoder and
+    /// sample its `apb_response` output (as an [crate::ApbMaster]
+    /// attached to them with [crate::ApbMaster::attach] does).
+    ///
+    /// The target requests must be the whole of a state of the
+    /// target. This can only be used after prepare_simulation
+    pub fn connect(
+        sim: &Simulation,
+        decoder: InstanceHandle,
+        targets: &[(&str, &str)],
+    ) -> Result<(), String> {
+        let num_regions = {
+            let instances = sim.instances();
+            let s = instances.instance(decoder).borrow_sim();
+            let Some(d) = s
+                .as_ref()
+                .and_then(|s| s.as_any().downcast_ref::<ApbDecoder>())
+            else {
+                return Err("Instance to connect is not an ApbDecoder".into());
+            };
+            d.map.regions.len()
+        };
+        if targets.len() != num_regions {
+            return Err(format!(
+                "APB decoder has {num_regions} regions but {} targets were given",
+                targets.len()
+            ));
+        }
+        let mut connections = vec![];
+        for (request, response) in targets {
+            let (req_h, req_s, req_e) = find_typed::<t_apb_request>(sim, request)?;
+            if !req_e.is_empty() {
+                return Err(format!(
+                    "APB request '{request}' must be a state of the target, not part of one"
+                ));
+            }
+            let response = find_typed::<t_apb_response>(sim, response)?;
+            connections.push(((req_h, req_s), response));
+        }
+        sim.add_combinational(move |sim| {
+            let responses: Vec<_> = connections
+                .iter()
+                .map(|(_, (h, s, e))| {
+                    sim.with_value(*h, *s, e, |v| {
+                        v.as_any().downcast_ref::<t_apb_response>().copied()
+                    })
+                    .flatten()
+                    .unwrap_or_default()
+                })
+                .collect();
+            let instances = sim.instances();
+            let requests = {
+                let mut d = instances.inst_mut::<ApbDecoder>(decoder);
+                d.inputs.target_responses.copy_from_slice(&responses);
+                d.evaluate();
+                d.outputs.target_requests.clone()
+            };
+            for (((h, s), _), request) in connections.iter().zip(requests.iter()) {
+                sim.with_state_mut(*h, *s, |mut v| v.try_copy_from(&SimValueRef::of(request)));
+            }
+        });
+        Ok(())
+    }
+}
+
+//ip Simulatable for ApbDecoder
+impl Simulatable for ApbDecoder {
+    //mp as_any
+    /// Return a reference as an Any so it can be downcast
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    //mp as_mut_any
+    /// Return a mutable reference as an Any so it can be downcast
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    //mp propagate
+    /// The decoder is purely combinational
+    fn propagate(&mut self, _stage: usize) {
+        self.evaluate();
+    }
+
+    fn state_info(&self, index: SimStateIndex) -> Option<SimStateInfo> {
+        let name = self.state_names.get(index.as_usize())?;
+        match index.as_usize() {
+            0 => Some(SimStateInfo::input(name, 0)),
+            1 | 2 => Some(SimStateInfo::output(name, index.as_usize() - 1)),
+            n if n % 2 == 1 => Some(SimStateInfo::output(name, n / 2 + 1)),
+            n => Some(SimStateInfo::input(name, n / 2 - 1)),
+        }
+    }
+    fn try_state_data(&self, index: SimStateIndex) -> Option<SimValueRef> {
+        match index.as_usize() {
+            0 => Some(SimValueRef::of(&self.inputs.apb_request)),
+            1 => Some(SimValueRef::of(&self.outputs.apb_response)),
+            2 => Some(SimValueRef::of(&self.outputs.decode_error)),
+            n if n % 2 == 1 => self
+                .outputs
+                .target_requests
+                .get(n / 2 - 1)
+                .map(|v| SimValueRef::of(v)),
+            n => self
+                .inputs
+                .target_responses
+                .get(n / 2 - 2)
+                .map(|v| SimValueRef::of(v)),
+        }
+    }
+    fn try_state_data_mut(&mut self, index: SimStateIndex) -> Option<SimValueRefMut> {
+        match index.as_usize() {
+            0 => Some(SimValueRefMut::of(&mut self.inputs.apb_request)),
+            1 => Some(SimValueRefMut::of(&mut self.outputs.apb_response)),
+            2 => Some(SimValueRefMut::of(&mut self.outputs.decode_error)),
+            n if n % 2 == 1 => self
+                .outputs
+                .target_requests
+                .get_mut(n / 2 - 1)
+                .map(|v| SimValueRefMut::of(v)),
+            n => self
+                .inputs
+                .target_responses
+                .get_mut(n / 2 - 2)
+                .map(|v| SimValueRefMut::of(v)),
+        }
+    }
+}
+
+//ip Component for ApbDecoder
+impl Component for ApbDecoder {
+    type Config = ApbAddressMap;
+    type InputsMut<'a> = &'a mut Inputs;
+    type Inputs<'a> = &'a Inputs;
+    type Outputs<'a> = &'a Outputs;
+    fn inputs(&self) -> &Inputs {
+        &self.inputs
+    }
+    fn outputs(&self) -> &Outputs {
+        &self.outputs
+    }
+    fn inputs_mut(&mut self) -> &mut Inputs {
+        &mut self.inputs
+    }
+    fn configure<S: SimRegister>(
+        &mut self,
+        _sim: &mut S,
+        _handle: S::Handle,
+        map: ApbAddressMap,
+    ) -> Result<(), String> {
+        map.validate()?;
+        let n = map.regions.len();
+        self.state_names = vec![
+            "apb_request".into(),
+            "apb_response".into(),
+            "decode_error".into(),
+        ];
+        for r in &map.regions {
+            self.state_names.push(format!("{}_request", r.name));
+            self.state_names.push(format!("{}_response", r.name));
+        }
+        self.inputs.target_responses = vec![t_apb_response::default(); n];
+        self.outputs.target_requests = vec![t_apb_request::default(); n];
+        self.map = map;
+        Ok(())
+    }
+}
+
+//ip ComponentBuilder for ApbDecoder
+impl ComponentBuilder for ApbDecoder {
+    type Build = Self;
+    fn instantiate<S: SimRegister>(_sim: &mut S, _name: SimNsName) -> Self {
+        Self::default()
+    }
+}
@@ -0,0 +1,404 @@
+//a Documentation
+//! An APB master bus-functional model
+//!
+//! The [ApbMaster] accepts a queue of read and write operations from
+//! a testbench, and drives a `t_apb_request` with a setup phase and
+//! an access phase for each, holding the access phase while the
+//! target is not ready; completed operations are returned as
+//! [ApbMasterResult]s, with the read data, error, wait states and
+//! latency.
+//!
+//! The master is clocked (with `connect_clock`) on the posedge of its
+//! `clk` input, at which it completes the cycle for the request it
+//! drove; its request for the next cycle depends on the response of
+//! the target to that request, and is driven by [ApbMaster::evaluate]
+//! (its combinational path, which is also used for `propagate`).
+//!
+//! The master is wired to a target (by the hierarchical paths of its
+//! request and response) with [ApbMaster::attach]; after every
+//! posedge of a clock - after the target has been clocked, and after
+//! any monitors have sampled the bus - the response of the target is
+//! copied to the master, which is evaluated, and its request is
+//! copied to the target.
+
+//a Imports
+use std::collections::VecDeque;
+
+use hgl_sim::prelude::component::*;
+
+use crate::apb_monitor::find_typed;
+use crate::{t_apb_request, t_apb_response};
+
+//a STATE_INFO, Inputs, Outputs
+//ci STATE_INFO
+const STATE_INFO: &[SimStateInfo] = &[
+    SimStateInfo::clk("clk", 0),
+    SimStateInfo::input("apb_response", 0),
+    SimStateInfo::output("apb_request", 0),
+];
+
+//tp Inputs
+#[derive(Debug, Default)]
+pub struct Inputs {
+    pub apb_response: t_apb_response,
+}
+
+//tp Outputs
+#[derive(Debug, Default)]
+pub struct Outputs {
+    pub apb_request: t_apb_request,
+}
+
+//a ApbOp, ApbMasterResult
+//tp ApbOp
+/// An operation for an [ApbMaster] to perform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApbOp {
+    Read { address: u32 },
+    Write { address: u32, data: u32 },
+}
+
+//ip ApbOp
+impl ApbOp {
+    //ap address
+    /// The address of the operation
+    pub fn address(&self) -> u32 {
+        match self {
+            Self::Read { address } => *address,
+            Self::Write { address, .. } => *address,
+        }
+    }
+
+    //ap is_write
+    /// True if the operation is a write
+    pub fn is_write(&self) -> bool {
+        matches!(self, Self::Write { .. })
+    }
+}
+
+//tp ApbMasterResult
+/// A completed operation of an [ApbMaster]
+///
+/// The times are counts of the cycles of the clock of the master
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApbMasterResult {
+    pub op: ApbOp,
+    /// Data read, for a read
+    pub rdata: u32,
+    /// True if the target responded with perr
+    pub error: bool,
+    /// Number of access phase cycles without pready
+    pub wait_states: usize,
+    /// Cycle at which the operation was queued
+    pub queued: usize,
+    /// Cycle at which the setup phase was driven
+    pub started: usize,
+    /// Cycle at which the operation completed
+    pub completed: usize,
+}
+
+//ip ApbMasterResult
+impl ApbMasterResult {
+    //ap latency
+    /// Number of cycles from the operation being queued to it
+    /// completing
+    pub fn latency(&self) -> usize {
+        self.completed - self.queued
+    }
+}
+
+//a ApbMaster
+//ti Phase
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    #[default]
+    Idle,
+    Setup,
+    Access,
+}
+
+//tp ApbMaster
+/// An APB master bus-functional model
+#[derive(Debug, Default)]
+pub struct ApbMaster {
+    pub inputs: Inputs,
+    pub outputs: Outputs,
+    phase: Phase,
+    cycle: usize,
+    queue: VecDeque<(ApbOp, usize)>,
+    current: Option<ApbMasterResult>,
+    completed: VecDeque<ApbMasterResult>,
+}
+
+//ip ApbMaster
+impl ApbMaster {
+    //mp queue
+    /// Queue an operation
+    pub fn queue(&mut self, op: ApbOp) {
+        self.queue.push_back((op, self.cycle));
+    }
+
+    //mp read
+    /// Queue a read of an address
+    pub fn read(&mut self, address: u32) {
+        self.queue(ApbOp::Read { address });
+    }
+
+    //mp write
+    /// Queue a write of data to an address
+    pub fn write(&mut self, address: u32, data: u32) {
+        self.queue(ApbOp::Write { address, data });
+    }
+
+    //ap is_idle
+    /// Return true if there are no operations queued or in progress
+    pub fn is_idle(&self) -> bool {
+        self.queue.is_empty() && self.current.is_none()
+    }
+
+    //ap cycle
+    /// The number of cycles the master has been clocked for
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    //mp take_completed
+    /// Take the operations that have completed, in order
+    pub fn take_completed(&mut self) -> Vec<ApbMasterResult> {
+        self.completed.drain(..).collect()
+    }
+
+    //mp step
+    /// Step the master by one cycle, as it is clocked
+    ///
+    /// The request output must be that driven during the cycle just
+    /// completed (by [ApbMaster::evaluate]), and the response input
+    /// the response of the target to it
+    pub fn step(&mut self) {
+        match self.phase {
+            Phase::Idle => self.start_next(),
+            Phase::Setup => {
+                self.phase = Phase::Access;
+            }
+            Phase::Access => {
+                let response = self.inputs.apb_response;
+                let mut current = self.current.take().unwrap();
+                if response.pready.is_true() {
+                    current.completed = self.cycle;
+                    current.error = response.perr.is_true();
+                    if !current.op.is_write() {
+                        current.rdata = response.prdata.try_as_u64().unwrap_or(0) as u32;
+                    }
+                    self.completed.push_back(current);
+                    self.start_next();
+                } else {
+                    current.wait_states += 1;
+                    self.current = Some(current);
+                }
+            }
+        }
+        self.cycle += 1;
+    }
+
+    //mp evaluate
+    /// Drive the request for the next cycle, given the response of
+    /// the target to the request of this cycle
+    ///
+    /// After a setup phase the access phase is driven, which is held
+    /// until the target is ready; the setup phase of the next queued
+    /// operation (if any) is driven when the bus is idle or an access
+    /// completes.
+    pub fn evaluate(&mut self) {
+        match self.phase {
+            Phase::Setup => {
+                self.outputs.apb_request.penable = true.into();
+            }
+            Phase::Access if self.inputs.apb_response.pready.is_false() => (),
+            _ => {
+                self.outputs.apb_request = {
+                    if let Some((op, _)) = self.queue.front() {
+                        let (write, data) = match op {
+                            ApbOp::Read { .. } => (false, 0),
+                            ApbOp::Write { data, .. } => (true, *data),
+                        };
+                        t_apb_request {
+                            paddr: (op.address() as u64).into(),
+                            penable: false.into(),
+                            psel: true.into(),
+                            pwrite: write.into(),
+                            pwdata: (data as u64).into(),
+                        }
+                    } else {
+                        t_apb_request::default()
+                    }
+                };
+            }
+        }
+    }
+
+    //mi start_next
+    /// Start the operation whose setup phase was driven in the cycle
+    /// just completed, if any
+    fn start_next(&mut self) {
+        let setup = self.outputs.apb_request.psel.is_true();
+        let next = if setup { self.queue.pop_front() } else { None };
+        let Some((op, queued)) = next else {
+            self.phase = Phase::Idle;
+            return;
+        };
+        self.current = Some(ApbMasterResult {
+            op,
+            rdata: 0,
+            error: false,
+            wait_states: 0,
+            queued,
+            started: self.cycle,
+            completed: 0,
+        });
+        self.phase = Phase::Setup;
+    }
+
+    //fp attach
+    /// Attach an instance of the master to the request and response
+    /// of a target, given by path (such as "dut.apb_request" and
+    /// "dut.apb_response"), copying the response to the master,
+    /// evaluating it, and copying its request to the target after
+    /// every posedge of a clock
+    ///
+    /// This only wires the master to the target; the master must
+    /// also be clocked by the same clock, with `connect_clock`.
+    ///
+    /// The target request must be the whole of a state of the target.
+    /// This can only be used after prepare_simulation
+    pub fn attach(
+        sim: &Simulation,
+        clock: ClockIndex,
+        master: InstanceHandle,
+        request: &str,
+        response: &str,
+    ) -> Result<(), String> {
+        let (req_h, req_s, req_e) = find_typed::<t_apb_request>(sim, request)?;
+        if !req_e.is_empty() {
+            return Err(format!(
+                "APB request '{request}' must be a state of the target, not part of one"
+            ));
+        }
+        let (resp_h, resp_s, resp_e) = find_typed::<t_apb_response>(sim, response)?;
+        let is_master = sim
+            .instances()
+            .instance(master)
+            .borrow_sim()
+            .is_some_and(|s| s.as_any().is::<ApbMaster>());
+        if !is_master {
+            return Err("Instance to attach is not an ApbMaster".into());
+        }
+        sim.add_driver(clock, move |sim| {
+            let response = sim
+                .with_value(resp_h, resp_s, &resp_e, |v| {
+                    v.as_any().downcast_ref::<t_apb_response>().copied()
+                })
+                .flatten()
+                .unwrap_or_default();
+            let instances = sim.instances();
+            let request = {
+                let mut m = instances.inst_mut::<ApbMaster>(master);
+                m.inputs.apb_response = response;
+                m.evaluate();
+                m.outputs.apb_request
+            };
+            sim.with_state_mut(req_h, req_s, |mut v| {
+                v.try_copy_from(&SimValueRef::of(&request))
+            });
+        });
+        Ok(())
+    }
+}
+
+//ip Simulatable for ApbMaster
+impl Simulatable for ApbMaster {
+    //mp as_any
+    /// Return a reference as an Any so it can be downcast
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    //mp as_mut_any
+    /// Return a mutable reference as an Any so it can be downcast
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    //mp reset
+    /// Reset the master, abandoning any operation in progress; queued
+    /// operations are kept
+    fn reset(&mut self, _reason: SimReset) {
+        self.outputs.apb_request = t_apb_request::default();
+        self.phase = Phase::Idle;
+        self.current = None;
+    }
+
+    //mp clock
+    /// Step the master on the posedge of its clock
+    fn clock(&mut self, mask: SimEdgeMask) {
+        if mask.is_posedge(0) {
+            self.step();
+        }
+    }
+
+    //mp propagate
+    /// Drive the request for the next cycle
+    fn propagate(&mut self, _stage: usize) {
+        self.evaluate();
+    }
+    fn state_info(&self, index: SimStateIndex) -> Option<SimStateInfo> {
+        STATE_INFO.get(index.as_usize()).copied()
+    }
+    fn try_state_data(&self, index: SimStateIndex) -> Option<SimValueRef> {
+        match index.as_usize() {
+            1 => Some(SimValueRef::of(&self.inputs.apb_response)),
+            2 => Some(SimValueRef::of(&self.outputs.apb_request)),
+            _ => None,
+        }
+    }
+    fn try_state_data_mut(&mut self, index: SimStateIndex) -> Option<SimValueRefMut> {
+        match index.as_usize() {
+            1 => Some(SimValueRefMut::of(&mut self.inputs.apb_response)),
+            2 => Some(SimValueRefMut::of(&mut self.outputs.apb_request)),
+            _ => None,
+        }
+    }
+}
+
+//ip Component for ApbMaster
+impl Component for ApbMaster {
+    type Config = ();
+    type InputsMut<'a> = &'a mut Inputs;
+    type Inputs<'a> = &'a Inputs;
+    type Outputs<'a> = &'a Outputs;
+    fn inputs(&self) -> &Inputs {
+        &self.inputs
+    }
+    fn outputs(&self) -> &Outputs {
+        &self.outputs
+    }
+    fn inputs_mut(&mut self) -> &mut Inputs {
+        &mut self.inputs
+    }
+    fn configure<S: SimRegister>(
+        &mut self,
+        sim: &mut S,
+        handle: S::Handle,
+        _config: (),
+    ) -> Result<(), String> {
+        sim.register_input_edge(handle, 0, true, false);
+        Ok(())
+    }
+}
+
+//ip ComponentBuilder for ApbMaster
+impl ComponentBuilder for ApbMaster {
+    type Build = Self;
+    fn instantiate<S: SimRegister>(_sim: &mut S, _name: SimNsName) -> Self {
+        Self::default()
+    }
+}
@@ -0,0 +1,441 @@
+//a Documentation
+//! Transaction monitors and a scoreboard for APB
+//!
+//! An [ApbMonitor] passively watches the `t_apb_request` and
+//! `t_apb_response` state of a simulation (given by hierarchical
+//! path) on every posedge of a clock, and turns the phases of the
+//! bus into [ApbTransaction]s.
+//!
+//! An [ApbScoreboard] compares transactions against an
+//! [ApbReference] - an expected model of the target, such as an
+//! [ApbRegisterMap] - and records an [ApbMismatch] (with the
+//! simulation time) for every transaction that differs.
+
+//a Imports
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use hgl_sim::prelude::component::*;
+
+use crate::{t_apb_request, t_apb_response};
+
+//a ApbTransaction
+//tp ApbTransaction
+/// A completed APB transaction
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ApbTransaction {
+    /// Simulation time of the setup phase
+    pub start: usize,
+    /// Simulation time at which the transaction completed
+    pub end: usize,
+    pub address: u32,
+    pub write: bool,
+    /// Data written, for a write
+    pub wdata: u32,
+    /// Data read, for a read
+    pub rdata: u32,
+    /// Number of access phase cycles without pready
+    pub wait_states: usize,
+    /// True if the target responded with perr
+    pub error: bool,
+}
+
+//ip Display for ApbTransaction
+impl std::fmt::Display for ApbTransaction {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        if self.write {
+            write!(fmt, "write {:#x} <- {:#x}", self.address, self.wdata)?;
+        } else {
+            write!(fmt, "read {:#x} -> {:#x}", self.address, self.rdata)?;
+        }
+        write!(fmt, " ({} wait states", self.wait_states)?;
+        if self.error {
+            fmt.write_str(", error")?;
+        }
+        fmt.write_str(")")
+    }
+}
+
+//a ApbMonitor
+//tp ApbMonitor
+/// A passive monitor of an APB request and response
+///
+/// The monitor is sampled after every posedge of a clock; a
+/// transaction starts with its setup phase (psel without penable),
+/// and completes when the access phase (psel with penable) has
+/// pready.
+#[derive(Debug)]
+pub struct ApbMonitor {
+    request: (InstanceHandle, SimStateIndex, Vec<usize>),
+    response: (InstanceHandle, SimStateIndex, Vec<usize>),
+    /// The transaction in progress
+    pending: Option<ApbTransaction>,
+    transactions: Vec<ApbTransaction>,
+    /// Protocol errors seen, with the simulation time
+    protocol_errors: Vec<(usize, String)>,
+}
+
+//ip ApbMonitor
+impl ApbMonitor {
+    //cp new
+    /// Create a monitor for a request and response given by path,
+    /// such as "dut.apb_request" and "dut.apb_response"
+    ///
+    /// This can only be used after prepare_simulation
+    pub fn new(sim: &Simulation, request: &str, response: &str) -> Result<Self, String> {
+        let request = find_typed::<t_apb_request>(sim, request)?;
+        let response = find_typed::<t_apb_response>(sim, response)?;
+        Ok(Self {
+            request,
+            response,
+            pending: None,
+            transactions: vec![],
+            protocol_errors: vec![],
+        })
+    }
+
+    //cp add_to_simulation
+    /// Create a monitor and add it to a simulation so that it is
+    /// sampled after every posedge of a clock
+    ///
+    /// The returned monitor is shared with the simulation; the
+    /// testbench can take transactions from it as they complete
+    pub fn add_to_simulation(
+        sim: &Simulation,
+        clock: ClockIndex,
+        request: &str,
+        response: &str,
+    ) -> Result<Rc<RefCell<Self>>, String> {
+        let monitor = Rc::new(RefCell::new(Self::new(sim, request, response)?));
+        let m = monitor.clone();
+        sim.add_monitor(clock, move |sim| m.borrow_mut().sample(sim));
+        Ok(monitor)
+    }
+
+    //mp sample
+    /// Sample the request and response
+    pub fn sample(&mut self, sim: &Simulation) {
+        let (h, s, e) = &self.request;
+        let Some(req) = sim
+            .with_value(*h, *s, e, |v| v.as_any().downcast_ref().copied())
+            .flatten()
+        else {
+            return;
+        };
+        let (h, s, e) = &self.response;
+        let Some(resp) = sim
+            .with_value(*h, *s, e, |v| v.as_any().downcast_ref().copied())
+            .flatten()
+        else {
+            return;
+        };
+        self.sample_values(sim.time(), &req, &resp);
+    }
+
+    //mp sample_values
+    /// Sample values of the request and response at a time
+    pub fn sample_values(&mut self, time: usize, req: &t_apb_request, resp: &t_apb_response) {
+        let psel = req.psel.is_true();
+        let penable = req.penable.is_true();
+        let address = req.paddr.try_as_u64().unwrap_or(0) as u32;
+        let write = req.pwrite.is_true();
+        let wdata = req.pwdata.try_as_u64().unwrap_or(0) as u32;
+        match (psel, penable, self.pending.as_mut()) {
+            (false, false, None) => {}
+            (false, _, Some(t)) => {
+                self.protocol_errors
+                    .push((time, format!("psel deasserted before completion of {t}")));
+                self.pending = None;
+            }
+            (false, true, None) => {
+                self.protocol_errors
+                    .push((time, "penable without psel".into()));
+            }
+            (true, false, pending) => {
+                if let Some(t) = pending {
+                    self.protocol_errors
+                        .push((time, format!("setup phase during access phase of {t}")));
+                }
+                self.pending = Some(ApbTransaction {
+                    start: time,
+                    address,
+                    write,
+                    wdata,
+                    ..Default::default()
+                });
+            }
+            (true, true, None) => {
+                self.protocol_errors
+                    .push((time, "access phase without setup phase".into()));
+            }
+            (true, true, Some(t)) => {
+                if t.address != address || t.write != write || (write && t.wdata != wdata) {
+                    self.protocol_errors
+                        .push((time, format!("request changed during access phase of {t}")));
+                }
+                if resp.pready.is_true() {
+                    t.end = time;
+                    t.error = resp.perr.is_true();
+                    if !write {
+                        t.rdata = resp.prdata.try_as_u64().unwrap_or(0) as u32;
+                    }
+                    self.transactions.push(*t);
+                    self.pending = None;
+                } else {
+                    t.wait_states += 1;
+                }
+            }
+        }
+    }
+
+    //ap transactions
+    /// Get the completed transactions that have not been taken
+    pub fn transactions(&self) -> &[ApbTransaction] {
+        &self.transactions
+    }
+
+    //mp take_transactions
+    /// Take the completed transactions
+    pub fn take_transactions(&mut self) -> Vec<ApbTransaction> {
+        std::mem::take(&mut self.transactions)
+    }
+
+    //ap protocol_errors
+    /// Get the protocol errors seen, with the simulation time
+    pub fn protocol_errors(&self) -> &[(usize, String)] {
+        &self.protocol_errors
+    }
+}
+
+//fi find_typed
+/// Find a value by path, checking it has the expected type
+pub(crate) fn find_typed<T: 'static>(
+    sim: &Simulation,
+    path: &str,
+) -> Result<(InstanceHandle, SimStateIndex, Vec<usize>), String> {
+    let Some((h, s, e)) = sim.find_value(path) else {
+        return Err(format!("Failed to find '{path}' in the simulation"));
+    };
+    if sim
+        .with_value(h, s, &e, |v| v.as_any().is::<T>())
+        .unwrap_or(false)
+    {
+        Ok((h, s, e))
+    } else {
+        Err(format!("'{path}' is not a {}", std::any::type_name::<T>()))
+    }
+}
+
+//a ApbReference, ApbRegisterMap
+//tt ApbReference
+/// An expected model of an APB target
+pub trait ApbReference {
+    /// Check a transaction against the model (updating the model for
+    /// a write), returning a description of any mismatch
+    fn check(&mut self, transaction: &ApbTransaction) -> Result<(), String>;
+}
+
+//tp ApbRegister
+/// A register of an [ApbRegisterMap]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApbRegister {
+    pub name: String,
+    pub address: u32,
+    /// Value after reset
+    pub reset: u32,
+    /// Bits that are changed by a write
+    pub write_mask: u32,
+    /// Bits whose read data is compared with the model
+    pub read_mask: u32,
+    /// Value of the register
+    pub value: u32,
+}
+
+//tp ApbRegisterMap
+/// A reference model of an APB target that is a map of registers
+///
+/// A write updates the writable bits of a register; a read must
+/// return the value of the register for the bits that are compared.
+/// An access to an address that is not mapped must respond with an
+/// error if `error_on_unmapped` is set, and is otherwise ignored.
+#[derive(Debug, Clone, Default)]
+pub struct ApbRegisterMap {
+    registers: Vec<ApbRegister>,
+    error_on_unmapped: bool,
+}
+
+//ip ApbRegisterMap
+impl ApbRegisterMap {
+    //cp new
+    /// Create a new empty register map
+    pub fn new(error_on_unmapped: bool) -> Self {
+        Self {
+            registers: vec![],
+            error_on_unmapped,
+        }
+    }
+
+    //cp register
+    /// Add a register to the map
+    #[must_use]
+    pub fn register(
+        mut self,
+        name: &str,
+        address: u32,
+        reset: u32,
+        write_mask: u32,
+        read_mask: u32,
+    ) -> Self {
+        self.registers.push(ApbRegister {
+            name: name.into(),
+            address,
+            reset,
+            write_mask,
+            read_mask,
+            value: reset,
+        });
+        self
+    }
+
+    //mp reset
+    /// Reset the registers to their reset values
+    pub fn reset(&mut self) {
+        for r in self.registers.iter_mut() {
+            r.value = r.reset;
+        }
+    }
+
+    //ap value
+    /// Get the value of a register by name
+    pub fn value(&self, name: &str) -> Option<u32> {
+        self.registers
+            .iter()
+            .find(|r| r.name == name)
+            .map(|r| r.value)
+    }
+}
+
+//ip ApbReference for ApbRegisterMap
+impl ApbReference for ApbRegisterMap {
+    fn check(&mut self, t: &ApbTransaction) -> Result<(), String> {
+        let Some(r) = self.registers.iter_mut().find(|r| r.address == t.address) else {
+            if self.error_on_unmapped && !t.error {
+                return Err(format!(
+                    "expected error for unmapped address {:#x}",
+                    t.address
+                ));
+            }
+            return Ok(());
+        };
+        if t.error {
+            return Err(format!("unexpected error from register '{}'", r.name));
+        }
+        if t.write {
+            r.value = (r.value & !r.write_mask) | (t.wdata & r.write_mask);
+        } else if (t.rdata ^ r.value) & r.read_mask != 0 {
+            return Err(format!(
+                "read of register '{}' expected {:#x} (mask {:#x})",
+                r.name, r.value, r.read_mask
+            ));
+        }
+        Ok(())
+    }
+}
+
+//a ApbScoreboard
+//tp ApbMismatch
+/// A transaction that did not match the expected model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApbMismatch {
+    /// Simulation time at which the transaction completed
+    pub time: usize,
+    pub transaction: ApbTransaction,
+    pub message: String,
+}
+
+//ip Display for ApbMismatch
+impl std::fmt::Display for ApbMismatch {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(fmt, "{}: {}: {}", self.time, self.transaction, self.message)
+    }
+}
+
+//tp ApbScoreboard
+/// A scoreboard comparing APB transactions with an expected model
+#[derive(Debug)]
+pub struct ApbScoreboard<R: ApbReference> {
+    reference: R,
+    num_checked: usize,
+    mismatches: Vec<ApbMismatch>,
+}
+
+//ip ApbScoreboard
+impl<R: ApbReference> ApbScoreboard<R> {
+    //cp new
+    /// Create a scoreboard with an expected model
+    pub fn new(reference: R) -> Self {
+        Self {
+            reference,
+            num_checked: 0,
+            mismatches: vec![],
+        }
+    }
+
+    //mp check
+    /// Check transactions (in order) against the expected model
+    pub fn check(&mut self, transactions: &[ApbTransaction]) {
+        for t in transactions {
+            self.num_checked += 1;
+            if let Err(message) = self.reference.check(t) {
+                self.mismatches.push(ApbMismatch {
+                    time: t.end,
+                    transaction: *t,
+                    message,
+                });
+            }
+        }
+    }
+
+    //mp check_monitor
+    /// Take the completed transactions of a monitor and check them
+    pub fn check_monitor(&mut self, monitor: &RefCell<ApbMonitor>) {
+        let transactions = monitor.borrow_mut().take_transactions();
+        self.check(&transactions);
+    }
+
+    //ap reference
+    /// Borrow the expected model
+    pub fn reference(&self) -> &R {
+        &self.reference
+    }
+
+    //ap num_checked
+    /// Number of transactions checked
+    pub fn num_checked(&self) -> usize {
+        self.num_checked
+    }
+
+    //ap mismatches
+    /// Mismatches found
+    pub fn mismatches(&self) -> &[ApbMismatch] {
+        &self.mismatches
+    }
+
+    //mp result
+    /// Return an error describing all the mismatches, if there are
+    /// any
+    pub fn result(&self) -> Result<(), String> {
+        if self.mismatches.is_empty() {
+            return Ok(());
+        }
+        let mut s = format!(
+            "{} of {} APB transactions mismatched:",
+            self.mismatches.len(),
+            self.num_checked
+        );
+        for m in &self.mismatches {
+            s.push_str(&format!("\n  {m}"));
+        }
+        Err(s)
+    }
+}
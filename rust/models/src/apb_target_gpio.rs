@@ -1,4 +1,5 @@
 //a Note: created by cyclicity CDL 2.0.0wip1 - do not hand edit without adding a comment line here
+//a Note: hand edited to expose the inputs and outputs as state
 //a Imports
 #![allow(unused_parens)]
 #![allow(non_camel_case_types)]
@@ -1555,16 +1556,41 @@ impl Simulatable for apb_target_gpio {
     fn state_info(&self, index: SimStateIndex) -> Option<SimStateInfo> {
         match index.as_usize() {
             0 => Some(SimStateInfo::clk("clk", 0)),
+            1 => Some(SimStateInfo::input("reset_n", 1)),
+            2 => Some(SimStateInfo::input("gpio_input", 2)),
+            3 => Some(SimStateInfo::input("apb_request", 3)),
+            4 => Some(SimStateInfo::output("gpio_input_event", 0)),
+            5 => Some(SimStateInfo::output("gpio_output_enable", 1)),
+            6 => Some(SimStateInfo::output("gpio_output", 2)),
+            7 => Some(SimStateInfo::output("apb_response", 3)),
             _ => None,
         }
     }
 
     fn try_state_data(&self, index: SimStateIndex) -> Option<SimValueRef> {
-        None
+        match index.as_usize() {
+            1 => Some(SimValueRef::of(&self.inputs.reset_n)),
+            2 => Some(SimValueRef::of(&self.inputs.gpio_input)),
+            3 => Some(SimValueRef::of(&self.inputs.apb_request)),
+            4 => Some(SimValueRef::of(&self.outputs.gpio_input_event)),
+            5 => Some(SimValueRef::of(&self.outputs.gpio_output_enable)),
+            6 => Some(SimValueRef::of(&self.outputs.gpio_output)),
+            7 => Some(SimValueRef::of(&self.outputs.apb_response)),
+            _ => None,
+        }
     }
 
     fn try_state_data_mut(&mut self, index: SimStateIndex) -> Option<SimValueRefMut> {
-        None
+        match index.as_usize() {
+            1 => Some(SimValueRefMut::of(&mut self.inputs.reset_n)),
+            2 => Some(SimValueRefMut::of(&mut self.inputs.gpio_input)),
+            3 => Some(SimValueRefMut::of(&mut self.inputs.apb_request)),
+            4 => Some(SimValueRefMut::of(&mut self.outputs.gpio_input_event)),
+            5 => Some(SimValueRefMut::of(&mut self.outputs.gpio_output_enable)),
+            6 => Some(SimValueRefMut::of(&mut self.outputs.gpio_output)),
+            7 => Some(SimValueRefMut::of(&mut self.outputs.apb_response)),
+            _ => None,
+        }
     }
 }
 //ip Component for apb_target_gpio
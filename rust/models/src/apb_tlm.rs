@@ -0,0 +1,428 @@
+//a Documentation
+//! Transaction-level APB
+//!
+//! An [ApbPayload] is a transaction-level APB read or write, carrying
+//! its response (the read data and error). Register models such as
+//! the [crate::Timer] and [crate::InterruptController] implement
+//! [TlmTarget] for it, so that software-focused runs can access them
+//! through a [TlmSocket] without any clocking.
+//!
+//! Two adaptors convert between transactions and the pin-level APB
+//! signals, so that cycle-accurate runs can swap in pin-level targets
+//! (such as the `apb_target_gpio`) for the transaction-level models:
+//!
+//! * An [ApbTlmBridge] is a [TlmTransport] target that performs each
+//!   transaction on the pins using an [ApbMaster] (which must be
+//!   clocked, and attached to the pin-level target with
+//!   [ApbMaster::attach]); its
+//!   blocking transport first fires clock edges for the annotated
+//!   delay, and then fires edges until the access completes
+//!
+//! * An [ApbTlmTarget] is a pin-level APB target that performs each
+//!   access as a blocking transport call through a socket (bound with
+//!   [ApbTlmTarget::connect]), holding pready low until the delay
+//!   annotated by the transaction-level target has elapsed
+
+//a Imports
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+use hgl_sim::prelude::component::*;
+
+use crate::{t_apb_request, t_apb_response};
+use crate::{ApbMaster, ApbMasterResult, ApbOp};
+
+//a ApbPayload
+//tp ApbPayload
+/// A transaction-level APB access, with its response
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ApbPayload {
+    /// The address (paddr) of the access
+    pub address: u32,
+    /// True for a write
+    pub write: bool,
+    /// The data to write, or the data read
+    pub data: u32,
+    /// Set by the target if the access has an error (perr)
+    pub error: bool,
+}
+
+//ip ApbPayload
+impl ApbPayload {
+    //cp read
+    /// Create a read of an address
+    pub fn read(address: u32) -> Self {
+        Self {
+            address,
+            ..Default::default()
+        }
+    }
+
+    //cp write
+    /// Create a write of data to an address
+    pub fn write(address: u32, data: u32) -> Self {
+        Self {
+            address,
+            write: true,
+            data,
+            error: false,
+        }
+    }
+
+    //ap request
+    /// The access phase of the pin-level request for the access
+    pub fn request(&self) -> t_apb_request {
+        t_apb_request {
+            paddr: (self.address as u64).into(),
+            penable: true.into(),
+            psel: true.into(),
+            pwrite: self.write.into(),
+            pwdata: (self.data as u64).into(),
+        }
+    }
+
+    //mp complete
+    /// Complete the access with read data (ignored for a write) and
+    /// an error indication
+    pub fn complete(&mut self, rdata: u32, error: bool) {
+        if !self.write {
+            self.data = rdata;
+        }
+        self.error = error;
+    }
+}
+
+//ip From<&t_apb_request> for ApbPayload
+impl From<&t_apb_request> for ApbPayload {
+    fn from(request: &t_apb_request) -> Self {
+        Self {
+            address: request.paddr.try_as_u64().unwrap_or(0) as u32,
+            write: request.pwrite.is_true(),
+            data: request.pwdata.try_as_u64().unwrap_or(0) as u32,
+            error: false,
+        }
+    }
+}
+
+//ip From<ApbMasterResult> for ApbPayload
+impl From<ApbMasterResult> for ApbPayload {
+    fn from(result: ApbMasterResult) -> Self {
+        let mut payload = match result.op {
+            ApbOp::Read { address } => Self::read(address),
+            ApbOp::Write { address, data } => Self::write(address, data),
+        };
+        payload.complete(result.rdata, result.error);
+        payload
+    }
+}
+
+//a ApbTlmBridge
+//tp ApbTlmBridge
+/// A transaction-level target that performs accesses on pin-level APB
+/// with an [ApbMaster]
+///
+/// The blocking transport fires clock edges of the simulation, so it
+/// must not be used from within a simulation hook
+#[derive(Debug)]
+pub struct ApbTlmBridge {
+    master: InstanceHandle,
+    /// Completed accesses not yet returned on the backward path
+    completed: RefCell<VecDeque<ApbMasterResult>>,
+}
+
+//ip ApbTlmBridge
+impl ApbTlmBridge {
+    //cp new
+    /// Create a bridge using an instance of an [ApbMaster], which
+    /// should be attached to the pin-level target
+    pub fn new(sim: &Simulation, master: InstanceHandle) -> Result<Self, String> {
+        let is_master = sim
+            .instances()
+            .instance(master)
+            .borrow_sim()
+            .is_some_and(|s| s.as_any().is::<ApbMaster>());
+        if !is_master {
+            return Err("Instance for APB bridge is not an ApbMaster".into());
+        }
+        Ok(Self {
+            master,
+            completed: RefCell::new(VecDeque::new()),
+        })
+    }
+
+    //mi take_completed
+    /// Move the completed accesses from the master to the bridge
+    fn take_completed(&self, sim: &Simulation) {
+        let instances = sim.instances();
+        let completed = instances
+            .inst_mut::<ApbMaster>(self.master)
+            .take_completed();
+        self.completed.borrow_mut().extend(completed);
+    }
+
+    //mi queue
+    /// Queue an access on the master
+    fn queue(&self, sim: &Simulation, payload: &ApbPayload) {
+        let op = match payload.write {
+            false => ApbOp::Read {
+                address: payload.address,
+            },
+            true => ApbOp::Write {
+                address: payload.address,
+                data: payload.data,
+            },
+        };
+        sim.instances().inst_mut::<ApbMaster>(self.master).queue(op);
+    }
+
+    //mi is_idle
+    fn is_idle(&self, sim: &Simulation) -> bool {
+        sim.instances().inst::<ApbMaster>(self.master).is_idle()
+    }
+}
+
+//ip TlmTransport for ApbTlmBridge
+impl TlmTransport<ApbPayload> for ApbTlmBridge {
+    //mp b_transport
+    /// Fire clock edges until the delay has elapsed, and then until
+    /// the access (and any accepted before it) completes; the delay
+    /// is then zero
+    fn b_transport(&self, sim: &Simulation, payload: &mut ApbPayload, delay: &mut usize) {
+        let start = sim.time() + *delay;
+        while sim.time() < start {
+            sim.fire_next_edges();
+        }
+        self.queue(sim, payload);
+        while !self.is_idle(sim) {
+            sim.fire_next_edges();
+        }
+        self.take_completed(sim);
+        if let Some(result) = self.completed.borrow_mut().pop_back() {
+            payload.complete(result.rdata, result.error);
+        }
+        *delay = 0;
+    }
+
+    //mp nb_transport_fw
+    /// Queue an access on the master, accepting it
+    fn nb_transport_fw(
+        &self,
+        sim: &Simulation,
+        payload: &mut ApbPayload,
+        phase: &mut TlmPhase,
+        _delay: &mut usize,
+    ) -> TlmSync {
+        match *phase {
+            TlmPhase::BeginReq => {
+                self.queue(sim, payload);
+                TlmSync::Accepted
+            }
+            _ => TlmSync::Completed,
+        }
+    }
+
+    //mp nb_transport_bw
+    /// Return the next completed access, if any
+    fn nb_transport_bw(&self, sim: &Simulation) -> Option<(ApbPayload, TlmPhase, usize)> {
+        self.take_completed(sim);
+        self.completed
+            .borrow_mut()
+            .pop_front()
+            .map(|r| (r.into(), TlmPhase::BeginResp, 0))
+    }
+}
+
+//a ApbTlmTarget
+//ci STATE_INFO
+const STATE_INFO: &[SimStateInfo] = &[
+    SimStateInfo::clk("clk", 0),
+    SimStateInfo::input("apb_request", 0),
+    SimStateInfo::output("apb_response", 0),
+];
+
+//tp ApbTlmTargetInputs
+#[derive(Debug, Default)]
+pub struct ApbTlmTargetInputs {
+    pub apb_request: t_apb_request,
+}
+
+//tp ApbTlmTargetOutputs
+#[derive(Debug, Default)]
+pub struct ApbTlmTargetOutputs {
+    pub apb_response: t_apb_response,
+}
+
+//tp ApbTlmTarget
+/// A pin-level APB target that performs accesses through a
+/// transaction-level socket
+#[derive(Debug, Default)]
+pub struct ApbTlmTarget {
+    pub inputs: ApbTlmTargetInputs,
+    pub outputs: ApbTlmTargetOutputs,
+    /// The completed transaction for the current access phase
+    pending: Option<ApbPayload>,
+    /// The simulation time at which the pending access is complete
+    ready_at: usize,
+    /// True if the pending access is complete
+    ready: bool,
+    /// True if the current access phase has been responded to
+    served: bool,
+    /// Number of accesses performed
+    accesses: usize,
+}
+
+//ip ApbTlmTarget
+impl ApbTlmTarget {
+    //ap accesses
+    /// The number of accesses performed through the socket
+    pub fn accesses(&self) -> usize {
+        self.accesses
+    }
+
+    //fp connect
+    /// Connect an instance of the target to a socket
+    ///
+    /// When the target sees a new access phase the access is performed
+    /// with a blocking transport call, and pready is held low until
+    /// the delay added by the socket target has elapsed. The socket
+    /// target must not fire clock edges itself.
+    ///
+    /// This can only be used after prepare_simulation
+    pub fn connect(
+        sim: &Simulation,
+        target: InstanceHandle,
+        socket: TlmSocket<'static, ApbPayload>,
+    ) -> Result<(), String> {
+        let is_target = sim
+            .instances()
+            .instance(target)
+            .borrow_sim()
+            .is_some_and(|s| s.as_any().is::<ApbTlmTarget>());
+        if !is_target {
+            return Err("Instance to connect is not an ApbTlmTarget".into());
+        }
+        sim.add_combinational(move |sim| {
+            let instances = sim.instances();
+            let (request, idle) = {
+                let t = instances.inst::<ApbTlmTarget>(target);
+                (t.inputs.apb_request, t.pending.is_none() && !t.served)
+            };
+            if idle && request.psel.is_true() && request.penable.is_true() {
+                let mut payload = ApbPayload::from(&request);
+                let mut delay = 0;
+                socket.b_transport(sim, &mut payload, &mut delay);
+                let mut t = instances.inst_mut::<ApbTlmTarget>(target);
+                t.pending = Some(payload);
+                t.ready_at = sim.time() + delay;
+                t.accesses += 1;
+            }
+            let mut t = instances.inst_mut::<ApbTlmTarget>(target);
+            t.ready = t.pending.is_some() && sim.time() >= t.ready_at;
+        });
+        Ok(())
+    }
+}
+
+//ip Simulatable for ApbTlmTarget
+impl Simulatable for ApbTlmTarget {
+    //mp as_any
+    /// Return a reference as an Any so it can be downcast
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    //mp as_mut_any
+    /// Return a mutable reference as an Any so it can be downcast
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    //mp reset
+    /// Reset the target, abandoning any access in progress
+    fn reset(&mut self, _reason: SimReset) {
+        self.pending = None;
+        self.ready = false;
+        self.served = false;
+        self.outputs.apb_response = t_apb_response {
+            pready: true.into(),
+            ..Default::default()
+        };
+    }
+
+    //mp clock
+    /// Clock the target on the posedge of its clock, responding to an
+    /// access phase when the transaction is complete
+    fn clock(&mut self, _mask: SimEdgeMask) {
+        let request = self.inputs.apb_request;
+        let mut response = t_apb_response {
+            pready: true.into(),
+            ..Default::default()
+        };
+        if !(request.psel.is_true() && request.penable.is_true()) {
+            self.served = false;
+        } else if let Some(payload) = self.pending {
+            if self.ready {
+                response.prdata = (payload.data as u64).into();
+                response.perr = payload.error.into();
+                self.pending = None;
+                self.served = true;
+            } else {
+                response.pready = false.into();
+            }
+        }
+        self.outputs.apb_response = response;
+    }
+
+    fn propagate(&mut self, _stage: usize) {}
+    fn state_info(&self, index: SimStateIndex) -> Option<SimStateInfo> {
+        STATE_INFO.get(index.as_usize()).copied()
+    }
+    fn try_state_data(&self, index: SimStateIndex) -> Option<SimValueRef> {
+        match index.as_usize() {
+            1 => Some(SimValueRef::of(&self.inputs.apb_request)),
+            2 => Some(SimValueRef::of(&self.outputs.apb_response)),
+            _ => None,
+        }
+    }
+    fn try_state_data_mut(&mut self, index: SimStateIndex) -> Option<SimValueRefMut> {
+        match index.as_usize() {
+            1 => Some(SimValueRefMut::of(&mut self.inputs.apb_request)),
+            2 => Some(SimValueRefMut::of(&mut self.outputs.apb_response)),
+            _ => None,
+        }
+    }
+}
+
+//ip Component for ApbTlmTarget
+impl Component for ApbTlmTarget {
+    type Config = ();
+    type InputsMut<'a> = &'a mut ApbTlmTargetInputs;
+    type Inputs<'a> = &'a ApbTlmTargetInputs;
+    type Outputs<'a> = &'a ApbTlmTargetOutputs;
+    fn inputs(&self) -> &ApbTlmTargetInputs {
+        &self.inputs
+    }
+    fn outputs(&self) -> &ApbTlmTargetOutputs {
+        &self.outputs
+    }
+    fn inputs_mut(&mut self) -> &mut ApbTlmTargetInputs {
+        &mut self.inputs
+    }
+    fn configure<S: SimRegister>(
+        &mut self,
+        sim: &mut S,
+        handle: S::Handle,
+        _config: (),
+    ) -> Result<(), String> {
+        sim.register_input_edge(handle, 0, true, false);
+        Ok(())
+    }
+}
+
+//ip ComponentBuilder for ApbTlmTarget
+impl ComponentBuilder for ApbTlmTarget {
+    type Build = Self;
+    fn instantiate<S: SimRegister>(_sim: &mut S, _name: SimNsName) -> Self {
+        Self::default()
+    }
+}
@@ -0,0 +1,251 @@
+//a Documentation
+//! AXI4 and AXI4-Lite channel types
+//!
+//! The five AXI channels (write address AW, write data W, write
+//! response B, read address AR and read data R) are simulation
+//! structs, each with a 'valid'; the signals driven by a master are
+//! bundled in a `t_axi_request` (with the ready signals for the B and
+//! R channels), and those driven by a slave in a `t_axi_response`.
+//!
+//! A transfer on a channel occurs at a clock edge if both 'valid' and
+//! the corresponding 'ready' are asserted in the cycle before the
+//! edge; the source must hold the channel stable until then.
+//!
+//! The data bus is 32 bits wide; AXI4-Lite uses the same types, with
+//! single beat bursts and an ID of zero.
+#![allow(non_camel_case_types)]
+
+//a Imports
+use hgl_sim::impl_sim_struct;
+use hgl_sim::prelude::component::*;
+use serde::{Deserialize, Serialize};
+
+//a Channel types
+//tp t_axi_aw
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct t_axi_aw {
+    pub valid: Bit,
+    pub id: Bv<8>,
+    pub addr: Bv<32>,
+    pub len: Bv<8>,
+    pub size: Bv<3>,
+    pub burst: Bv<2>,
+}
+
+//tp t_axi_w
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct t_axi_w {
+    pub valid: Bit,
+    pub data: Bv<32>,
+    pub strb: Bv<4>,
+    pub last: Bit,
+}
+
+//tp t_axi_b
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct t_axi_b {
+    pub valid: Bit,
+    pub id: Bv<8>,
+    pub resp: Bv<2>,
+}
+
+//tp t_axi_ar
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct t_axi_ar {
+    pub valid: Bit,
+    pub id: Bv<8>,
+    pub addr: Bv<32>,
+    pub len: Bv<8>,
+    pub size: Bv<3>,
+    pub burst: Bv<2>,
+}
+
+//tp t_axi_r
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct t_axi_r {
+    pub valid: Bit,
+    pub id: Bv<8>,
+    pub data: Bv<32>,
+    pub resp: Bv<2>,
+    pub last: Bit,
+}
+
+//tp t_axi_request
+/// The signals driven by an AXI master
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct t_axi_request {
+    pub aw: t_axi_aw,
+    pub w: t_axi_w,
+    pub bready: Bit,
+    pub ar: t_axi_ar,
+    pub rready: Bit,
+}
+
+//tp t_axi_response
+/// The signals driven by an AXI slave
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct t_axi_response {
+    pub awready: Bit,
+    pub wready: Bit,
+    pub b: t_axi_b,
+    pub arready: Bit,
+    pub r: t_axi_r,
+}
+
+impl_sim_struct!(t_axi_aw {
+    valid: Bit,
+    id: Bv<8>,
+    addr: Bv<32>,
+    len: Bv<8>,
+    size: Bv<3>,
+    burst: Bv<2>,
+});
+impl_sim_struct!(t_axi_w {
+    valid: Bit,
+    data: Bv<32>,
+    strb: Bv<4>,
+    last: Bit,
+});
+impl_sim_struct!(t_axi_b {
+    valid: Bit,
+    id: Bv<8>,
+    resp: Bv<2>,
+});
+impl_sim_struct!(t_axi_ar {
+    valid: Bit,
+    id: Bv<8>,
+    addr: Bv<32>,
+    len: Bv<8>,
+    size: Bv<3>,
+    burst: Bv<2>,
+});
+impl_sim_struct!(t_axi_r {
+    valid: Bit,
+    id: Bv<8>,
+    data: Bv<32>,
+    resp: Bv<2>,
+    last: Bit,
+});
+impl_sim_struct!(t_axi_request {
+    aw: t_axi_aw,
+    w: t_axi_w,
+    bready: Bit,
+    ar: t_axi_ar,
+    rready: Bit,
+});
+impl_sim_struct!(t_axi_response {
+    awready: Bit,
+    wready: Bit,
+    b: t_axi_b,
+    arready: Bit,
+    r: t_axi_r,
+});
+
+//a AxiBurst, AxiResp
+//tp AxiBurst
+/// The burst type of an AXI transaction
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AxiBurst {
+    /// Every beat uses the same address
+    Fixed,
+    /// The address increments by the beat size
+    #[default]
+    Incr,
+    /// The address increments, wrapping at the boundary of the total
+    /// size of the burst
+    Wrap,
+}
+
+//ip AxiBurst
+impl AxiBurst {
+    //ap encoding
+    /// The encoding of the burst type on the 'burst' signal
+    pub fn encoding(&self) -> u64 {
+        match self {
+            Self::Fixed => 0,
+            Self::Incr => 1,
+            Self::Wrap => 2,
+        }
+    }
+
+    //fp of_encoding
+    /// The burst type for the 'burst' signal, if valid
+    pub fn of_encoding(burst: u64) -> Option<Self> {
+        match burst {
+            0 => Some(Self::Fixed),
+            1 => Some(Self::Incr),
+            2 => Some(Self::Wrap),
+            _ => None,
+        }
+    }
+
+    //mp check
+    /// Check that a number of beats is valid for the burst type
+    pub fn check(&self, beats: usize) -> Result<(), String> {
+        let valid = match self {
+            Self::Fixed => (1..=16).contains(&beats),
+            Self::Incr => (1..=256).contains(&beats),
+            Self::Wrap => [2, 4, 8, 16].contains(&beats),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("{beats} beats is not valid for a {self:?} burst"))
+        }
+    }
+
+    //mp addresses
+    /// The addresses of the beats of a burst of 32-bit words, if the
+    /// number of beats is valid for the burst type
+    ///
+    /// Incrementing bursts wrap at the top of the address space
+    pub fn addresses(&self, address: u32, beats: usize) -> Result<Vec<u32>, String> {
+        self.check(beats)?;
+        let total = beats as u32 * 4;
+        let boundary = address & !(total - 1);
+        Ok((0..beats as u32)
+            .map(|i| match self {
+                Self::Fixed => address,
+                Self::Incr => address.wrapping_add(4 * i),
+                Self::Wrap => boundary + ((address - boundary).wrapping_add(4 * i) % total),
+            })
+            .collect())
+    }
+}
+
+//tp AxiResp
+/// An AXI response
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AxiResp {
+    #[default]
+    Okay,
+    ExOkay,
+    SlvErr,
+    DecErr,
+}
+
+//ip AxiResp
+impl AxiResp {
+    //ap encoding
+    /// The encoding of the response on the 'resp' signal
+    pub fn encoding(&self) -> u64 {
+        *self as u64
+    }
+
+    //fp of_encoding
+    /// The response for the 'resp' signal
+    pub fn of_encoding(resp: u64) -> Self {
+        match resp & 3 {
+            0 => Self::Okay,
+            1 => Self::ExOkay,
+            2 => Self::SlvErr,
+            _ => Self::DecErr,
+        }
+    }
+
+    //ap is_error
+    /// True if the response is an error
+    pub fn is_error(&self) -> bool {
+        matches!(self, Self::SlvErr | Self::DecErr)
+    }
+}
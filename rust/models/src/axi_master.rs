@@ -0,0 +1,606 @@
+//a Documentation
+//! An AXI4 (and AXI4-Lite) master bus-functional model
+//!
+//! The [AxiMaster] accepts queues of read and write operations from a
+//! testbench, each a burst with an ID, and drives a `t_axi_request`;
+//! reads and writes are issued independently, with up to a
+//! configured number of each outstanding. The ready signals for the
+//! B and R channels may be randomly deasserted to apply backpressure
+//! to the slave. Completed operations are returned as
+//! [AxiMasterResult]s.
+//!
+//! The master is attached to a slave (by the hierarchical paths of
+//! its request and response) with [AxiMaster::attach], and is stepped
+//! as a simulation driver after every posedge of a clock. The
+//! response of the slave is expected to be registered - the value
+//! after a clock edge is what the slave drives in the following
+//! cycle - and the master records it to determine which transfers
+//! occur at the next clock edge.
+
+//a Imports
+use std::collections::VecDeque;
+
+use hgl_sim::prelude::component::*;
+use rand::RngCore;
+
+use crate::apb_monitor::find_typed;
+use crate::axi::{t_axi_ar, t_axi_aw, t_axi_request, t_axi_response, t_axi_w};
+use crate::axi::{AxiBurst, AxiResp};
+
+//a STATE_INFO, Inputs, Outputs
+//ci STATE_INFO
+const STATE_INFO: &[SimStateInfo] = &[
+    SimStateInfo::input("axi_response", 0),
+    SimStateInfo::output("axi_request", 0),
+];
+
+//tp Inputs
+#[derive(Debug, Default)]
+pub struct Inputs {
+    pub axi_response: t_axi_response,
+}
+
+//tp Outputs
+#[derive(Debug, Default)]
+pub struct Outputs {
+    pub axi_request: t_axi_request,
+}
+
+//a AxiOp, AxiMasterResult, AxiMasterConfig
+//tp AxiOp
+/// An operation for an [AxiMaster] to perform
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AxiOp {
+    Read {
+        id: u8,
+        address: u32,
+        beats: usize,
+        burst: AxiBurst,
+    },
+    Write {
+        id: u8,
+        address: u32,
+        data: Vec<u32>,
+        burst: AxiBurst,
+    },
+}
+
+//ip AxiOp
+impl AxiOp {
+    //cp read
+    /// An incrementing burst read
+    pub fn read(id: u8, address: u32, beats: usize) -> Self {
+        Self::Read {
+            id,
+            address,
+            beats,
+            burst: AxiBurst::Incr,
+        }
+    }
+
+    //cp write
+    /// An incrementing burst write
+    pub fn write(id: u8, address: u32, data: &[u32]) -> Self {
+        Self::Write {
+            id,
+            address,
+            data: data.to_vec(),
+            burst: AxiBurst::Incr,
+        }
+    }
+
+    //cp with_burst
+    /// Change the burst type of the operation
+    pub fn with_burst(mut self, burst_type: AxiBurst) -> Self {
+        match &mut self {
+            Self::Read { burst, .. } => *burst = burst_type,
+            Self::Write { burst, .. } => *burst = burst_type,
+        }
+        self
+    }
+
+    //ap id
+    /// The ID of the operation
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::Read { id, .. } => *id,
+            Self::Write { id, .. } => *id,
+        }
+    }
+
+    //ap beats
+    /// The number of beats of the burst
+    pub fn beats(&self) -> usize {
+        match self {
+            Self::Read { beats, .. } => *beats,
+            Self::Write { data, .. } => data.len(),
+        }
+    }
+
+    //ap address
+    /// The address of the first beat
+    pub fn address(&self) -> u32 {
+        match self {
+            Self::Read { address, .. } => *address,
+            Self::Write { address, .. } => *address,
+        }
+    }
+
+    //ap burst
+    /// The burst type
+    pub fn burst(&self) -> AxiBurst {
+        match self {
+            Self::Read { burst, .. } => *burst,
+            Self::Write { burst, .. } => *burst,
+        }
+    }
+}
+
+//tp AxiMasterResult
+/// A completed operation of an [AxiMaster]
+///
+/// The times are counts of the steps of the master (i.e. cycles of
+/// the clock it is attached to)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AxiMasterResult {
+    pub op: AxiOp,
+    /// Data read, for a read
+    pub rdata: Vec<u32>,
+    /// The write response, or the worst response of the read beats
+    pub resp: AxiResp,
+    /// Cycle at which the operation was queued
+    pub queued: usize,
+    /// Cycle at which the address was first driven
+    pub started: usize,
+    /// Cycle at which the operation completed
+    pub completed: usize,
+}
+
+//ip AxiMasterResult
+impl AxiMasterResult {
+    //ap latency
+    /// Number of cycles from the operation being queued to it
+    /// completing
+    pub fn latency(&self) -> usize {
+        self.completed - self.queued
+    }
+}
+
+//tp AxiMasterConfig
+/// Configuration of an [AxiMaster]
+#[derive(Debug, Clone, Copy)]
+pub struct AxiMasterConfig {
+    /// Restrict operations to AXI4-Lite (single beats with an ID of
+    /// zero)
+    pub lite: bool,
+    /// Maximum number of reads, and of writes, outstanding
+    pub max_outstanding: usize,
+    /// Percentage of cycles in which bready and rready are asserted
+    pub ready_percent: u32,
+    /// Seed for the backpressure
+    pub seed: u64,
+}
+
+//ip Default for AxiMasterConfig
+impl Default for AxiMasterConfig {
+    fn default() -> Self {
+        Self {
+            lite: false,
+            max_outstanding: 4,
+            ready_percent: 100,
+            seed: 0,
+        }
+    }
+}
+
+//a AxiMaster
+//ti Pending
+/// An operation whose address has been driven
+#[derive(Debug)]
+struct Pending {
+    result: AxiMasterResult,
+    /// True if the address has been accepted
+    address_done: bool,
+    /// Number of write data beats accepted
+    beats_done: usize,
+}
+
+//tp AxiMaster
+/// An AXI master bus-functional model
+#[derive(Debug, Default)]
+pub struct AxiMaster {
+    pub inputs: Inputs,
+    pub outputs: Outputs,
+    config: AxiMasterConfig,
+    rng: SimRng,
+    cycle: usize,
+    response: t_axi_response,
+    write_queue: VecDeque<(AxiOp, usize)>,
+    read_queue: VecDeque<(AxiOp, usize)>,
+    writes: VecDeque<Pending>,
+    reads: VecDeque<Pending>,
+    completed: VecDeque<AxiMasterResult>,
+    errors: Vec<String>,
+}
+
+//ip AxiMaster
+impl AxiMaster {
+    //mp queue
+    /// Queue an operation, checking it is valid
+    pub fn queue(&mut self, op: AxiOp) -> Result<(), String> {
+        let beats = op.beats();
+        op.burst().check(beats)?;
+        if op.address() & 3 != 0 {
+            return Err(format!("Address {:#x} is not word aligned", op.address()));
+        }
+        if self.config.lite && (beats != 1 || op.id() != 0) {
+            return Err("AXI4-Lite operations must be single beats with an ID of 0".into());
+        }
+        if op.burst() == AxiBurst::Incr && (op.address() & 0xfff) as usize + 4 * beats > 0x1000 {
+            return Err(format!(
+                "Burst at {:#x} of {beats} beats crosses a 4kB boundary",
+                op.address()
+            ));
+        }
+        match op {
+            AxiOp::Read { .. } => self.read_queue.push_back((op, self.cycle)),
+            AxiOp::Write { .. } => self.write_queue.push_back((op, self.cycle)),
+        }
+        Ok(())
+    }
+
+    //mp read
+    /// Queue an incrementing burst read
+    pub fn read(&mut self, id: u8, address: u32, beats: usize) -> Result<(), String> {
+        self.queue(AxiOp::read(id, address, beats))
+    }
+
+    //mp write
+    /// Queue an incrementing burst write
+    pub fn write(&mut self, id: u8, address: u32, data: &[u32]) -> Result<(), String> {
+        self.queue(AxiOp::write(id, address, data))
+    }
+
+    //ap is_idle
+    /// Return true if there are no operations queued or in progress
+    pub fn is_idle(&self) -> bool {
+        self.write_queue.is_empty()
+            && self.read_queue.is_empty()
+            && self.writes.is_empty()
+            && self.reads.is_empty()
+    }
+
+    //ap cycle
+    /// The number of steps the master has made
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    //ap protocol_errors
+    /// Responses from the slave that did not match an outstanding
+    /// operation
+    pub fn protocol_errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    //mp take_completed
+    /// Take the operations that have completed, in order of
+    /// completion
+    pub fn take_completed(&mut self) -> Vec<AxiMasterResult> {
+        self.completed.drain(..).collect()
+    }
+
+    //mp step
+    /// Step the master by one cycle
+    ///
+    /// The transfers at the clock edge are determined from the request
+    /// driven and the response recorded at the previous step; the
+    /// response input is then recorded for the next step
+    pub fn step(&mut self) {
+        self.cycle += 1;
+        self.transfers();
+        self.issue();
+        self.drive();
+        self.response = self.inputs.axi_response;
+    }
+
+    //mi transfers
+    /// Handle the transfers that occurred at the clock edge
+    fn transfers(&mut self) {
+        let request = self.outputs.axi_request;
+        let response = self.response;
+        if request.aw.valid.is_true() && response.awready.is_true() {
+            if let Some(p) = self.writes.iter_mut().find(|p| !p.address_done) {
+                p.address_done = true;
+            }
+        }
+        if request.w.valid.is_true() && response.wready.is_true() {
+            if let Some(p) = self
+                .writes
+                .iter_mut()
+                .find(|p| p.beats_done < p.result.op.beats())
+            {
+                p.beats_done += 1;
+            }
+        }
+        if response.b.valid.is_true() && request.bready.is_true() {
+            let id = response.b.id.try_as_u64().unwrap_or(0) as u8;
+            let n = self.writes.iter().position(|p| {
+                p.result.op.id() == id && p.address_done && p.beats_done == p.result.op.beats()
+            });
+            if let Some(n) = n {
+                let mut result = self.writes.remove(n).unwrap().result;
+                result.resp = AxiResp::of_encoding(response.b.resp.try_as_u64().unwrap_or(0));
+                result.completed = self.cycle;
+                self.completed.push_back(result);
+            } else {
+                self.errors.push(format!(
+                    "Write response for ID {id} with no completed write at cycle {}",
+                    self.cycle
+                ));
+            }
+        }
+        if request.ar.valid.is_true() && response.arready.is_true() {
+            if let Some(p) = self.reads.iter_mut().find(|p| !p.address_done) {
+                p.address_done = true;
+            }
+        }
+        if response.r.valid.is_true() && request.rready.is_true() {
+            let id = response.r.id.try_as_u64().unwrap_or(0) as u8;
+            let Some(n) = self
+                .reads
+                .iter()
+                .position(|p| p.result.op.id() == id && p.address_done)
+            else {
+                self.errors.push(format!(
+                    "Read data for ID {id} with no outstanding read at cycle {}",
+                    self.cycle
+                ));
+                return;
+            };
+            let p = &mut self.reads[n];
+            p.result
+                .rdata
+                .push(response.r.data.try_as_u64().unwrap_or(0) as u32);
+            let resp = AxiResp::of_encoding(response.r.resp.try_as_u64().unwrap_or(0));
+            p.result.resp = p.result.resp.max(resp);
+            let beats = p.result.rdata.len();
+            let last = beats == p.result.op.beats();
+            if last != response.r.last.is_true() {
+                self.errors.push(format!(
+                    "Read data for ID {id} has rlast {} at beat {beats} of {} at cycle {}",
+                    response.r.last.is_true(),
+                    p.result.op.beats(),
+                    self.cycle
+                ));
+            }
+            if last || response.r.last.is_true() {
+                let mut result = self.reads.remove(n).unwrap().result;
+                result.completed = self.cycle;
+                self.completed.push_back(result);
+            }
+        }
+    }
+
+    //mi issue
+    /// Start the next queued operations if their address channels are
+    /// free and there are not too many outstanding
+    fn issue(&mut self) {
+        let cycle = self.cycle;
+        let start = |(op, queued): (AxiOp, usize)| Pending {
+            result: AxiMasterResult {
+                op,
+                rdata: vec![],
+                resp: AxiResp::Okay,
+                queued,
+                started: cycle,
+                completed: 0,
+            },
+            address_done: false,
+            beats_done: 0,
+        };
+        if self.writes.iter().all(|p| p.address_done)
+            && self.writes.len() < self.config.max_outstanding
+        {
+            if let Some(op) = self.write_queue.pop_front() {
+                self.writes.push_back(start(op));
+            }
+        }
+        if self.reads.iter().all(|p| p.address_done)
+            && self.reads.len() < self.config.max_outstanding
+        {
+            if let Some(op) = self.read_queue.pop_front() {
+                self.reads.push_back(start(op));
+            }
+        }
+    }
+
+    //mi drive
+    /// Drive the request for the next cycle
+    fn drive(&mut self) {
+        let mut request = t_axi_request::default();
+        if let Some(p) = self.writes.iter().find(|p| !p.address_done) {
+            let op = &p.result.op;
+            request.aw = t_axi_aw {
+                valid: true.into(),
+                id: (op.id() as u64).into(),
+                addr: (op.address() as u64).into(),
+                len: ((op.beats() - 1) as u64).into(),
+                size: 2.into(),
+                burst: op.burst().encoding().into(),
+            };
+        }
+        if let Some(p) = self
+            .writes
+            .iter()
+            .find(|p| p.beats_done < p.result.op.beats())
+        {
+            let AxiOp::Write { data, .. } = &p.result.op else {
+                unreachable!();
+            };
+            request.w = t_axi_w {
+                valid: true.into(),
+                data: (data[p.beats_done] as u64).into(),
+                strb: 0xf.into(),
+                last: (p.beats_done + 1 == data.len()).into(),
+            };
+        }
+        if let Some(p) = self.reads.iter().find(|p| !p.address_done) {
+            let op = &p.result.op;
+            request.ar = t_axi_ar {
+                valid: true.into(),
+                id: (op.id() as u64).into(),
+                addr: (op.address() as u64).into(),
+                len: ((op.beats() - 1) as u64).into(),
+                size: 2.into(),
+                burst: op.burst().encoding().into(),
+            };
+        }
+        request.bready = self.ready().into();
+        request.rready = self.ready().into();
+        self.outputs.axi_request = request;
+    }
+
+    //mi ready
+    /// Determine whether to assert a ready signal in the next cycle
+    fn ready(&mut self) -> bool {
+        self.config.ready_percent >= 100 || self.rng.next_u32() % 100 < self.config.ready_percent
+    }
+
+    //fp attach
+    /// Attach an instance of the master to the request and response
+    /// of a slave, given by path (such as "mem.axi_request" and
+    /// "mem.axi_response"), stepping it after every posedge of a
+    /// clock
+    ///
+    /// The slave request must be the whole of a state of the slave.
+    /// This can only be used after prepare_simulation
+    pub fn attach(
+        sim: &Simulation,
+        clock: ClockIndex,
+        master: InstanceHandle,
+        request: &str,
+        response: &str,
+    ) -> Result<(), String> {
+        let (req_h, req_s, req_e) = find_typed::<t_axi_request>(sim, request)?;
+        if !req_e.is_empty() {
+            return Err(format!(
+                "AXI request '{request}' must be a state of the slave, not part of one"
+            ));
+        }
+        let (resp_h, resp_s, resp_e) = find_typed::<t_axi_response>(sim, response)?;
+        let is_master = sim
+            .instances()
+            .instance(master)
+            .borrow_sim()
+            .is_some_and(|s| s.as_any().is::<AxiMaster>());
+        if !is_master {
+            return Err("Instance to attach is not an AxiMaster".into());
+        }
+        sim.add_driver(clock, move |sim| {
+            let response = sim
+                .with_value(resp_h, resp_s, &resp_e, |v| {
+                    v.as_any().downcast_ref::<t_axi_response>().copied()
+                })
+                .flatten()
+                .unwrap_or_default();
+            let instances = sim.instances();
+            let request = {
+                let mut m = instances.inst_mut::<AxiMaster>(master);
+                m.inputs.axi_response = response;
+                m.step();
+                m.outputs.axi_request
+            };
+            sim.with_state_mut(req_h, req_s, |mut v| {
+                v.try_copy_from(&SimValueRef::of(&request))
+            });
+        });
+        Ok(())
+    }
+}
+
+//ip Simulatable for AxiMaster
+impl Simulatable for AxiMaster {
+    //mp as_any
+    /// Return a reference as an Any so it can be downcast
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    //mp as_mut_any
+    /// Return a mutable reference as an Any so it can be downcast
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    //mp reset
+    /// Reset the master, abandoning any operations in progress;
+    /// queued operations are kept
+    fn reset(&mut self, _reason: SimReset) {
+        self.outputs.axi_request = t_axi_request::default();
+        self.response = t_axi_response::default();
+        self.writes.clear();
+        self.reads.clear();
+    }
+
+    //mp clock
+    /// The master is stepped after clock edges, not by them
+    fn clock(&mut self, _mask: SimEdgeMask) {}
+
+    fn propagate(&mut self, _stage: usize) {}
+    fn state_info(&self, index: SimStateIndex) -> Option<SimStateInfo> {
+        STATE_INFO.get(index.as_usize()).copied()
+    }
+    fn try_state_data(&self, index: SimStateIndex) -> Option<SimValueRef> {
+        match index.as_usize() {
+            0 => Some(SimValueRef::of(&self.inputs.axi_response)),
+            1 => Some(SimValueRef::of(&self.outputs.axi_request)),
+            _ => None,
+        }
+    }
+    fn try_state_data_mut(&mut self, index: SimStateIndex) -> Option<SimValueRefMut> {
+        match index.as_usize() {
+            0 => Some(SimValueRefMut::of(&mut self.inputs.axi_response)),
+            1 => Some(SimValueRefMut::of(&mut self.outputs.axi_request)),
+            _ => None,
+        }
+    }
+}
+
+//ip Component for AxiMaster
+impl Component for AxiMaster {
+    type Config = AxiMasterConfig;
+    type InputsMut<'a> = &'a mut Inputs;
+    type Inputs<'a> = &'a Inputs;
+    type Outputs<'a> = &'a Outputs;
+    fn inputs(&self) -> &Inputs {
+        &self.inputs
+    }
+    fn outputs(&self) -> &Outputs {
+        &self.outputs
+    }
+    fn inputs_mut(&mut self) -> &mut Inputs {
+        &mut self.inputs
+    }
+    fn configure<S: SimRegister>(
+        &mut self,
+        _sim: &mut S,
+        _handle: S::Handle,
+        config: AxiMasterConfig,
+    ) -> Result<(), String> {
+        if config.max_outstanding == 0 {
+            return Err("AXI master must permit at least one outstanding operation".into());
+        }
+        self.config = config;
+        self.rng = SimRng::new(config.seed);
+        Ok(())
+    }
+}
+
+//ip ComponentBuilder for AxiMaster
+impl ComponentBuilder for AxiMaster {
+    type Build = Self;
+    fn instantiate<S: SimRegister>(_sim: &mut S, _name: SimNsName) -> Self {
+        Self::default()
+    }
+}
@@ -0,0 +1,457 @@
+//a Documentation
+//! An AXI4 slave bus-functional model, and an AXI memory target
+//!
+//! The [AxiSlave] is a clocked component with a `t_axi_request` input
+//! and a registered `t_axi_response` output; it accepts up to a
+//! configured number of outstanding reads, and of writes, with
+//! incrementing, fixed and wrapping bursts, and services them from an
+//! [AxiStorage] after a configured latency. Its ready signals may be
+//! randomly deasserted to apply backpressure to the master.
+//!
+//! Responses are returned in the order the addresses were accepted,
+//! whatever their IDs.
+//!
+//! An [AxiMemory] is an [AxiSlave] backed by a [SparseMemory].
+
+//a Imports
+use std::collections::{HashMap, VecDeque};
+
+use hgl_sim::prelude::component::*;
+use rand::RngCore;
+
+use crate::axi::{t_axi_b, t_axi_r, t_axi_request, t_axi_response};
+use crate::axi::{AxiBurst, AxiResp};
+
+//a AxiStorage, SparseMemory
+//tt AxiStorage
+/// The storage behind an [AxiSlave], accessed in 32-bit words
+pub trait AxiStorage: std::fmt::Debug + Default + 'static {
+    /// Read the word at a (word aligned) address, or None for a slave
+    /// error
+    fn read(&mut self, address: u32) -> Option<u32>;
+
+    /// Write the bytes of a word at a (word aligned) address selected
+    /// by a strobe, returning false for a slave error
+    fn write(&mut self, address: u32, data: u32, strobe: u8) -> bool;
+}
+
+//tp SparseMemory
+/// A memory of a given size in bytes, storing only the words that
+/// have been written; unwritten words read as zero
+#[derive(Debug, Default, Clone)]
+pub struct SparseMemory {
+    size: u64,
+    data: HashMap<u32, u32>,
+}
+
+//ip SparseMemory
+impl SparseMemory {
+    //cp new
+    /// Create a memory of a given size in bytes
+    pub fn new(size: u64) -> Self {
+        Self {
+            size,
+            data: HashMap::new(),
+        }
+    }
+
+    //ap size
+    /// The size of the memory in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    //ap peek
+    /// Read a word by backdoor, if the address is in range
+    pub fn peek(&self, address: u32) -> Option<u32> {
+        ((address as u64) < self.size).then(|| *self.data.get(&(address & !3)).unwrap_or(&0))
+    }
+
+    //mp poke
+    /// Write a word by backdoor, returning false if the address is
+    /// out of range
+    pub fn poke(&mut self, address: u32, data: u32) -> bool {
+        if (address as u64) < self.size {
+            self.data.insert(address & !3, data);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+//ip AxiStorage for SparseMemory
+impl AxiStorage for SparseMemory {
+    fn read(&mut self, address: u32) -> Option<u32> {
+        self.peek(address)
+    }
+    fn write(&mut self, address: u32, data: u32, strobe: u8) -> bool {
+        let Some(old) = self.peek(address) else {
+            return false;
+        };
+        let mask = (0..4)
+            .filter(|i| strobe & (1 << i) != 0)
+            .fold(0_u32, |m, i| m | (0xff << (8 * i)));
+        self.poke(address, (old & !mask) | (data & mask))
+    }
+}
+
+//a STATE_INFO, Inputs, Outputs, AxiSlaveConfig
+//ci STATE_INFO
+const STATE_INFO: &[SimStateInfo] = &[
+    SimStateInfo::clk("clk", 0),
+    SimStateInfo::input("axi_request", 0),
+    SimStateInfo::output("axi_response", 0),
+];
+
+//tp Inputs
+#[derive(Debug, Default)]
+pub struct Inputs {
+    pub axi_request: t_axi_request,
+}
+
+//tp Outputs
+#[derive(Debug, Default)]
+pub struct Outputs {
+    pub axi_response: t_axi_response,
+}
+
+//tp AxiSlaveConfig
+/// Configuration of an [AxiSlave]
+#[derive(Debug, Clone)]
+pub struct AxiSlaveConfig<S: AxiStorage> {
+    /// The storage the slave accesses
+    pub storage: S,
+    /// Maximum number of reads, and of writes, outstanding
+    pub max_outstanding: usize,
+    /// Number of cycles from the address (for a read) or the last
+    /// write data (for a write) being accepted to the response being
+    /// valid
+    pub latency: usize,
+    /// Percentage of cycles in which awready, wready and arready are
+    /// asserted (if the slave can accept a transfer)
+    pub ready_percent: u32,
+    /// Seed for the backpressure
+    pub seed: u64,
+}
+
+//ip AxiSlaveConfig
+impl<S: AxiStorage> AxiSlaveConfig<S> {
+    //cp new
+    /// Create a configuration for some storage, with up to four
+    /// reads and writes outstanding, a latency of one cycle and no
+    /// backpressure
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            max_outstanding: 4,
+            latency: 1,
+            ready_percent: 100,
+            seed: 0,
+        }
+    }
+}
+
+//ip Default for AxiSlaveConfig
+impl<S: AxiStorage> Default for AxiSlaveConfig<S> {
+    fn default() -> Self {
+        Self::new(S::default())
+    }
+}
+
+//a AxiSlave
+//ti Burst
+/// A burst whose address has been accepted
+#[derive(Debug)]
+struct Burst {
+    id: u64,
+    addresses: Vec<u32>,
+    beat: usize,
+    resp: AxiResp,
+    /// Cycle at which the response may be valid
+    ready_at: usize,
+}
+
+//ip Burst
+impl Burst {
+    //cp new
+    /// Create from the fields of an address channel
+    fn new(id: &Bv<8>, addr: &Bv<32>, len: &Bv<8>, burst: &Bv<2>, ready_at: usize) -> Self {
+        let address = addr.try_as_u64().unwrap_or(0) as u32;
+        let beats = len.try_as_u64().unwrap_or(0) as usize + 1;
+        let burst_type = AxiBurst::of_encoding(burst.try_as_u64().unwrap_or(3));
+        let (addresses, resp) = match burst_type.map(|b| b.addresses(address, beats)) {
+            Some(Ok(addresses)) if address & 3 == 0 => (addresses, AxiResp::Okay),
+            _ => (vec![address & !3; beats], AxiResp::SlvErr),
+        };
+        Self {
+            id: id.try_as_u64().unwrap_or(0),
+            addresses,
+            beat: 0,
+            resp,
+            ready_at,
+        }
+    }
+}
+
+//tp AxiSlave
+/// An AXI slave bus-functional model
+#[derive(Debug, Default)]
+pub struct AxiSlave<S: AxiStorage> {
+    pub inputs: Inputs,
+    pub outputs: Outputs,
+    storage: S,
+    max_outstanding: usize,
+    latency: usize,
+    ready_percent: u32,
+    rng: SimRng,
+    cycle: usize,
+    /// Writes awaiting their data
+    writes: VecDeque<Burst>,
+    /// Writes awaiting their response
+    write_responses: VecDeque<Burst>,
+    reads: VecDeque<Burst>,
+    max_writes: usize,
+    max_reads: usize,
+    errors: Vec<String>,
+}
+
+//tp AxiMemory
+/// An AXI memory target
+pub type AxiMemory = AxiSlave<SparseMemory>;
+
+//ip AxiSlave
+impl<S: AxiStorage> AxiSlave<S> {
+    //ap storage
+    /// The storage of the slave
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    //ap storage_mut
+    /// The storage of the slave, mutably (for backdoor access)
+    pub fn storage_mut(&mut self) -> &mut S {
+        &mut self.storage
+    }
+
+    //ap max_outstanding
+    /// The largest numbers of writes and reads that have been
+    /// outstanding at once
+    pub fn max_outstanding(&self) -> (usize, usize) {
+        (self.max_writes, self.max_reads)
+    }
+
+    //ap protocol_errors
+    /// Transfers from the master that were not expected
+    pub fn protocol_errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    //mi ready
+    /// Determine whether to assert a ready signal in the next cycle
+    fn ready(&mut self) -> bool {
+        self.ready_percent >= 100 || self.rng.next_u32() % 100 < self.ready_percent
+    }
+
+    //mi transfers
+    /// Handle the transfers that occur at a clock edge
+    fn transfers(&mut self) {
+        let request = self.inputs.axi_request;
+        let response = self.outputs.axi_response;
+        if request.aw.valid.is_true() && response.awready.is_true() {
+            let aw = &request.aw;
+            self.writes
+                .push_back(Burst::new(&aw.id, &aw.addr, &aw.len, &aw.burst, 0));
+        }
+        if request.w.valid.is_true() && response.wready.is_true() {
+            if let Some(w) = self.writes.front_mut() {
+                let data = request.w.data.try_as_u64().unwrap_or(0) as u32;
+                let strobe = request.w.strb.try_as_u64().unwrap_or(0) as u8;
+                if w.resp == AxiResp::Okay && !self.storage.write(w.addresses[w.beat], data, strobe)
+                {
+                    w.resp = AxiResp::SlvErr;
+                }
+                w.beat += 1;
+                let last = w.beat == w.addresses.len();
+                if last != request.w.last.is_true() {
+                    self.errors.push(format!(
+                        "Write data for ID {} has wlast {} at beat {} of {} at cycle {}",
+                        w.id,
+                        request.w.last.is_true(),
+                        w.beat,
+                        w.addresses.len(),
+                        self.cycle
+                    ));
+                }
+                if last {
+                    let mut w = self.writes.pop_front().unwrap();
+                    w.ready_at = self.cycle + self.latency;
+                    self.write_responses.push_back(w);
+                }
+            } else {
+                self.errors.push(format!(
+                    "Write data with no write address at cycle {}",
+                    self.cycle
+                ));
+            }
+        }
+        if response.b.valid.is_true() && request.bready.is_true() {
+            self.write_responses.pop_front();
+        }
+        if request.ar.valid.is_true() && response.arready.is_true() {
+            let ar = &request.ar;
+            self.reads.push_back(Burst::new(
+                &ar.id,
+                &ar.addr,
+                &ar.len,
+                &ar.burst,
+                self.cycle + self.latency,
+            ));
+        }
+        if response.r.valid.is_true() && request.rready.is_true() {
+            let r = self.reads.front_mut().unwrap();
+            r.beat += 1;
+            if r.beat == r.addresses.len() {
+                self.reads.pop_front();
+            }
+        }
+    }
+
+    //mi drive
+    /// Drive the response for the next cycle
+    fn drive(&mut self) {
+        let num_writes = self.writes.len() + self.write_responses.len();
+        self.max_writes = self.max_writes.max(num_writes);
+        self.max_reads = self.max_reads.max(self.reads.len());
+        let mut response = t_axi_response {
+            awready: (num_writes < self.max_outstanding && self.ready()).into(),
+            wready: (!self.writes.is_empty() && self.ready()).into(),
+            arready: (self.reads.len() < self.max_outstanding && self.ready()).into(),
+            ..Default::default()
+        };
+        if let Some(b) = self.write_responses.front() {
+            if b.ready_at <= self.cycle {
+                response.b = t_axi_b {
+                    valid: true.into(),
+                    id: b.id.into(),
+                    resp: b.resp.encoding().into(),
+                };
+            }
+        }
+        if let Some(r) = self.reads.front() {
+            if r.ready_at <= self.cycle {
+                let (data, resp) = {
+                    if r.resp != AxiResp::Okay {
+                        (0, r.resp)
+                    } else if let Some(data) = self.storage.read(r.addresses[r.beat]) {
+                        (data, AxiResp::Okay)
+                    } else {
+                        (0, AxiResp::SlvErr)
+                    }
+                };
+                response.r = t_axi_r {
+                    valid: true.into(),
+                    id: r.id.into(),
+                    data: (data as u64).into(),
+                    resp: resp.encoding().into(),
+                    last: (r.beat + 1 == r.addresses.len()).into(),
+                };
+            }
+        }
+        self.outputs.axi_response = response;
+    }
+}
+
+//ip Simulatable for AxiSlave
+impl<S: AxiStorage> Simulatable for AxiSlave<S> {
+    //mp as_any
+    /// Return a reference as an Any so it can be downcast
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    //mp as_mut_any
+    /// Return a mutable reference as an Any so it can be downcast
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    //mp reset
+    /// Reset the slave, abandoning any transactions in progress; the
+    /// storage is kept
+    fn reset(&mut self, _reason: SimReset) {
+        self.outputs.axi_response = t_axi_response::default();
+        self.writes.clear();
+        self.write_responses.clear();
+        self.reads.clear();
+    }
+
+    //mp clock
+    /// Clock the slave on the posedge of its clock
+    fn clock(&mut self, _mask: SimEdgeMask) {
+        self.cycle += 1;
+        self.transfers();
+        self.drive();
+    }
+
+    fn propagate(&mut self, _stage: usize) {}
+    fn state_info(&self, index: SimStateIndex) -> Option<SimStateInfo> {
+        STATE_INFO.get(index.as_usize()).copied()
+    }
+    fn try_state_data(&self, index: SimStateIndex) -> Option<SimValueRef> {
+        match index.as_usize() {
+            1 => Some(SimValueRef::of(&self.inputs.axi_request)),
+            2 => Some(SimValueRef::of(&self.outputs.axi_response)),
+            _ => None,
+        }
+    }
+    fn try_state_data_mut(&mut self, index: SimStateIndex) -> Option<SimValueRefMut> {
+        match index.as_usize() {
+            1 => Some(SimValueRefMut::of(&mut self.inputs.axi_request)),
+            2 => Some(SimValueRefMut::of(&mut self.outputs.axi_response)),
+            _ => None,
+        }
+    }
+}
+
+//ip Component for AxiSlave
+impl<S: AxiStorage> Component for AxiSlave<S> {
+    type Config = AxiSlaveConfig<S>;
+    type InputsMut<'a> = &'a mut Inputs;
+    type Inputs<'a> = &'a Inputs;
+    type Outputs<'a> = &'a Outputs;
+    fn inputs(&self) -> &Inputs {
+        &self.inputs
+    }
+    fn outputs(&self) -> &Outputs {
+        &self.outputs
+    }
+    fn inputs_mut(&mut self) -> &mut Inputs {
+        &mut self.inputs
+    }
+    fn configure<R: SimRegister>(
+        &mut self,
+        sim: &mut R,
+        handle: R::Handle,
+        config: AxiSlaveConfig<S>,
+    ) -> Result<(), String> {
+        if config.max_outstanding == 0 {
+            return Err("AXI slave must permit at least one outstanding operation".into());
+        }
+        self.storage = config.storage;
+        self.max_outstanding = config.max_outstanding;
+        self.latency = config.latency;
+        self.ready_percent = config.ready_percent;
+        self.rng = SimRng::new(config.seed);
+        sim.register_input_edge(handle, 0, true, false);
+        Ok(())
+    }
+}
+
+//ip ComponentBuilder for AxiSlave
+impl<S: AxiStorage> ComponentBuilder for AxiSlave<S> {
+    type Build = Self;
+    fn instantiate<R: SimRegister>(_sim: &mut R, _name: SimNsName) -> Self {
+        Self::default()
+    }
+}
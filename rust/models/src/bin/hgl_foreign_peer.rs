@@ -0,0 +1,79 @@
+//a Documentation
+//! A stand-in foreign model, served to a `ForeignComponent` in the
+//! process that started it
+//!
+//! The model is a 16-bit counter that increments by the step (the
+//! first argument, default 1) on rising clock edges when enabled; its
+//! output `sum` is combinationally the count plus the input `data`
+//!
+//! If a second argument is given the process exits abruptly after
+//! that many rising clock edges, to test the handling of a model
+//! that crashes
+
+//a Imports
+use hgl_models::{serve_foreign, ForeignModel, ForeignPort};
+
+//a Counter
+//tp Counter
+struct Counter {
+    step: u64,
+    crash_after: Option<u64>,
+}
+
+/// Indices of the ports
+const RESET_N: usize = 1;
+const ENABLE: usize = 2;
+const DATA: usize = 3;
+const COUNT: usize = 4;
+const SUM: usize = 5;
+const EDGES: usize = 6;
+
+//ip ForeignModel for Counter
+impl ForeignModel for Counter {
+    fn ports(&self) -> Vec<ForeignPort> {
+        vec![
+            ForeignPort::clock("clk", true, false),
+            ForeignPort::input("reset_n", 1),
+            ForeignPort::input("enable", 1),
+            ForeignPort::input("data", 8),
+            ForeignPort::output("count", 16),
+            ForeignPort::output("sum", 16),
+            ForeignPort::internal("edges", 32),
+        ]
+    }
+    fn reset(&mut self, values: &mut [u64]) {
+        values[COUNT] = 0;
+        values[EDGES] = 0;
+        self.propagate(values);
+    }
+    fn clock(&mut self, edges: &[u8], values: &mut [u64]) {
+        if edges[0] & 1 != 0 {
+            values[EDGES] += 1;
+            if Some(values[EDGES]) == self.crash_after {
+                std::process::exit(3);
+            }
+            if values[RESET_N] == 0 {
+                values[COUNT] = 0;
+            } else if values[ENABLE] != 0 {
+                values[COUNT] += self.step;
+            }
+        }
+        self.propagate(values);
+    }
+    fn propagate(&mut self, values: &mut [u64]) {
+        values[SUM] = values[COUNT] + values[DATA];
+    }
+}
+
+//a Main
+fn main() -> Result<(), String> {
+    let step = match std::env::args().nth(1) {
+        Some(step) => step.parse().map_err(|_| format!("Bad step {step}"))?,
+        None => 1,
+    };
+    let crash_after = match std::env::args().nth(2) {
+        Some(n) => Some(n.parse().map_err(|_| format!("Bad edge count {n}"))?),
+        None => None,
+    };
+    serve_foreign(&mut Counter { step, crash_after })
+}
@@ -0,0 +1,532 @@
+//a Documentation
+//! Synchronous and dual-clock FIFOs
+//!
+//! A [Fifo] is a first-word-fall-through FIFO of a [SimCopyValue]
+//! with a single clock: `data_out` is the oldest entry while `empty`
+//! is deasserted, and at a posedge of the clock an entry is pushed
+//! (`push`, if not full) and the oldest popped (`pop`, if not empty).
+//!
+//! An [AsyncFifo] has independent write and read clocks. Its
+//! pointers cross between the clock domains as gray codes, through a
+//! configurable number of synchronizing registers clocked by the
+//! destination domain; hence `empty` deasserts some read clock edges
+//! after a push, and `full` deasserts some write clock edges after a
+//! pop, as in hardware. Its depth must be a power of two.
+//!
+//! Both have `almost_full` and `almost_empty` outputs, asserted when
+//! the number of entries (as seen by the write side and read side
+//! respectively) is at least, or at most, configured thresholds, and
+//! `overflow` and `underflow` outputs that are asserted for a cycle
+//! after a push when full or a pop when empty (which are ignored).
+
+//a Imports
+use std::collections::VecDeque;
+
+use hgl_sim::prelude::component::*;
+
+//a FifoConfig
+//tp FifoConfig
+/// Configuration of a [Fifo] or [AsyncFifo]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FifoConfig {
+    /// Number of entries
+    pub depth: usize,
+    /// almost_full is asserted when there are at least this many
+    /// entries
+    pub almost_full: usize,
+    /// almost_empty is asserted when there are at most this many
+    /// entries
+    pub almost_empty: usize,
+    /// Number of synchronizing registers for the pointers of an
+    /// [AsyncFifo]
+    pub sync_stages: usize,
+}
+
+//ip Default for FifoConfig
+impl Default for FifoConfig {
+    fn default() -> Self {
+        Self::new(16)
+    }
+}
+
+//ip FifoConfig
+impl FifoConfig {
+    //cp new
+    /// Create a configuration for a depth, with almost_full one entry
+    /// short of full, almost_empty with one entry, and two stage
+    /// synchronizers
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            almost_full: depth.saturating_sub(1),
+            almost_empty: 1,
+            sync_stages: 2,
+        }
+    }
+
+    //mp validate
+    /// Check the configuration is valid
+    pub fn validate(&self) -> Result<(), String> {
+        if self.depth == 0 {
+            return Err("FIFO depth must be at least 1".into());
+        }
+        if self.almost_full > self.depth || self.almost_empty > self.depth {
+            return Err(format!(
+                "FIFO thresholds {} and {} must not exceed the depth {}",
+                self.almost_full, self.almost_empty, self.depth
+            ));
+        }
+        Ok(())
+    }
+}
+
+//a Fifo
+//ci STATE_INFO
+const STATE_INFO: &[SimStateInfo] = &[
+    SimStateInfo::clk("clk", 0),
+    SimStateInfo::input("push", 0),
+    SimStateInfo::input("data_in", 1),
+    SimStateInfo::input("pop", 2),
+    SimStateInfo::output("data_out", 0),
+    SimStateInfo::output("full", 1),
+    SimStateInfo::output("almost_full", 2),
+    SimStateInfo::output("empty", 3),
+    SimStateInfo::output("almost_empty", 4),
+    SimStateInfo::output("overflow", 5),
+    SimStateInfo::output("underflow", 6),
+];
+
+//tp Inputs
+#[derive(Debug, Default)]
+pub struct Inputs<V: SimCopyValue> {
+    pub push: Bit,
+    pub data_in: V,
+    pub pop: Bit,
+}
+
+//tp Outputs
+#[derive(Debug, Default)]
+pub struct Outputs<V: SimCopyValue> {
+    pub data_out: V,
+    pub full: Bit,
+    pub almost_full: Bit,
+    pub empty: Bit,
+    pub almost_empty: Bit,
+    pub overflow: Bit,
+    pub underflow: Bit,
+}
+
+//ip Outputs
+impl<V: SimCopyValue> Outputs<V> {
+    //mi set_write_status
+    /// Set the write side status from the number of entries
+    fn set_write_status(&mut self, config: &FifoConfig, count: usize) {
+        self.full = (count >= config.depth).into();
+        self.almost_full = (count >= config.almost_full).into();
+    }
+
+    //mi set_read_status
+    /// Set the read side status from the number of entries, and the
+    /// oldest entry
+    fn set_read_status(&mut self, config: &FifoConfig, count: usize, head: Option<V>) {
+        self.empty = (count == 0).into();
+        self.almost_empty = (count <= config.almost_empty).into();
+        self.data_out = head.unwrap_or_default();
+    }
+}
+
+//tp Fifo
+/// A synchronous FIFO
+#[derive(Debug, Default)]
+pub struct Fifo<V: SimCopyValue> {
+    pub inputs: Inputs<V>,
+    pub outputs: Outputs<V>,
+    config: FifoConfig,
+    data: VecDeque<V>,
+}
+
+//ip Fifo
+impl<V: SimCopyValue> Fifo<V> {
+    //ap len
+    /// The number of entries in the FIFO
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    //ap is_empty
+    /// Return true if the FIFO is empty
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    //mi generate_outputs
+    fn generate_outputs(&mut self) {
+        let count = self.data.len();
+        self.outputs.set_write_status(&self.config, count);
+        self.outputs
+            .set_read_status(&self.config, count, self.data.front().copied());
+    }
+}
+
+//ip Simulatable for Fifo
+impl<V: SimCopyValue> Simulatable for Fifo<V> {
+    //mp as_any
+    /// Return a reference as an Any so it can be downcast
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    //mp as_mut_any
+    /// Return a mutable reference as an Any so it can be downcast
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    //mp reset
+    /// Reset the FIFO to empty
+    fn reset(&mut self, _reason: SimReset) {
+        self.data.clear();
+        self.outputs.overflow = false.into();
+        self.outputs.underflow = false.into();
+        self.generate_outputs();
+    }
+
+    //mp clock
+    /// Clock the FIFO on the posedge of its clock
+    fn clock(&mut self, _mask: SimEdgeMask) {
+        let full = self.outputs.full.is_true();
+        let empty = self.outputs.empty.is_true();
+        let push = self.inputs.push.is_true();
+        let pop = self.inputs.pop.is_true();
+        if pop && !empty {
+            self.data.pop_front();
+        }
+        if push && !full {
+            self.data.push_back(self.inputs.data_in);
+        }
+        self.outputs.overflow = (push && full).into();
+        self.outputs.underflow = (pop && empty).into();
+        self.generate_outputs();
+    }
+
+    fn propagate(&mut self, _stage: usize) {}
+    fn state_info(&self, index: SimStateIndex) -> Option<SimStateInfo> {
+        STATE_INFO.get(index.as_usize()).copied()
+    }
+    fn try_state_data(&self, index: SimStateIndex) -> Option<SimValueRef> {
+        match index.as_usize() {
+            1 => Some(SimValueRef::of(&self.inputs.push)),
+            2 => Some(SimValueRef::of(&self.inputs.data_in)),
+            3 => Some(SimValueRef::of(&self.inputs.pop)),
+            4 => Some(SimValueRef::of(&self.outputs.data_out)),
+            5 => Some(SimValueRef::of(&self.outputs.full)),
+            6 => Some(SimValueRef::of(&self.outputs.almost_full)),
+            7 => Some(SimValueRef::of(&self.outputs.empty)),
+            8 => Some(SimValueRef::of(&self.outputs.almost_empty)),
+            9 => Some(SimValueRef::of(&self.outputs.overflow)),
+            10 => Some(SimValueRef::of(&self.outputs.underflow)),
+            _ => None,
+        }
+    }
+    fn try_state_data_mut(&mut self, index: SimStateIndex) -> Option<SimValueRefMut> {
+        match index.as_usize() {
+            1 => Some(SimValueRefMut::of(&mut self.inputs.push)),
+            2 => Some(SimValueRefMut::of(&mut self.inputs.data_in)),
+            3 => Some(SimValueRefMut::of(&mut self.inputs.pop)),
+            4 => Some(SimValueRefMut::of(&mut self.outputs.data_out)),
+            5 => Some(SimValueRefMut::of(&mut self.outputs.full)),
+            6 => Some(SimValueRefMut::of(&mut self.outputs.almost_full)),
+            7 => Some(SimValueRefMut::of(&mut self.outputs.empty)),
+            8 => Some(SimValueRefMut::of(&mut self.outputs.almost_empty)),
+            9 => Some(SimValueRefMut::of(&mut self.outputs.overflow)),
+            10 => Some(SimValueRefMut::of(&mut self.outputs.underflow)),
+            _ => None,
+        }
+    }
+}
+
+//ip Component for Fifo
+impl<V: SimCopyValue> Component for Fifo<V> {
+    type Config = FifoConfig;
+    type InputsMut<'a> = &'a mut Inputs<V>;
+    type Inputs<'a> = &'a Inputs<V>;
+    type Outputs<'a> = &'a Outputs<V>;
+    fn inputs(&self) -> &Inputs<V> {
+        &self.inputs
+    }
+    fn outputs(&self) -> &Outputs<V> {
+        &self.outputs
+    }
+    fn inputs_mut(&mut self) -> &mut Inputs<V> {
+        &mut self.inputs
+    }
+    fn configure<S: SimRegister>(
+        &mut self,
+        sim: &mut S,
+        handle: S::Handle,
+        config: FifoConfig,
+    ) -> Result<(), String> {
+        config.validate()?;
+        self.config = config;
+        self.generate_outputs();
+        sim.register_input_edge(handle, 0, true, false);
+        Ok(())
+    }
+}
+
+//ip ComponentBuilder for Fifo
+impl<V: SimCopyValue> ComponentBuilder for Fifo<V> {
+    type Build = Self;
+    fn instantiate<S: SimRegister>(_sim: &mut S, _name: SimNsName) -> Self {
+        Self::default()
+    }
+}
+
+//a AsyncFifo
+//fi gray
+/// Convert a binary value to a gray code
+fn gray(n: usize) -> usize {
+    n ^ (n >> 1)
+}
+
+//fi ungray
+/// Convert a gray code to a binary value
+fn ungray(mut g: usize) -> usize {
+    let mut n = 0;
+    while g != 0 {
+        n ^= g;
+        g >>= 1;
+    }
+    n
+}
+
+//ci ASYNC_STATE_INFO
+const ASYNC_STATE_INFO: &[SimStateInfo] = &[
+    SimStateInfo::clk("wr_clk", 0),
+    SimStateInfo::clk("rd_clk", 1),
+    SimStateInfo::input("push", 0),
+    SimStateInfo::input("data_in", 1),
+    SimStateInfo::input("pop", 2),
+    SimStateInfo::output("data_out", 0),
+    SimStateInfo::output("full", 1),
+    SimStateInfo::output("almost_full", 2),
+    SimStateInfo::output("empty", 3),
+    SimStateInfo::output("almost_empty", 4),
+    SimStateInfo::output("overflow", 5),
+    SimStateInfo::output("underflow", 6),
+];
+
+//tp AsyncFifo
+/// A dual-clock FIFO, with write clock input 0 and read clock input
+/// 1
+///
+/// The pointers count modulo twice the depth, so that full and empty
+/// can be distinguished
+#[derive(Debug, Default)]
+pub struct AsyncFifo<V: SimCopyValue> {
+    pub inputs: Inputs<V>,
+    pub outputs: Outputs<V>,
+    config: FifoConfig,
+    data: Vec<V>,
+    /// Write pointer, in the write domain
+    wr_ptr: usize,
+    /// Read pointer, in the read domain
+    rd_ptr: usize,
+    /// Synchronizer of the gray code of the read pointer, in the
+    /// write domain; the last is the synchronized value
+    rd_ptr_sync: Vec<usize>,
+    /// Synchronizer of the gray code of the write pointer, in the
+    /// read domain; the last is the synchronized value
+    wr_ptr_sync: Vec<usize>,
+}
+
+//ip AsyncFifo
+impl<V: SimCopyValue> AsyncFifo<V> {
+    //ap write_count
+    /// The number of entries as seen by the write side
+    pub fn write_count(&self) -> usize {
+        let rd_ptr = ungray(*self.rd_ptr_sync.last().unwrap());
+        self.wr_ptr.wrapping_sub(rd_ptr) % (2 * self.config.depth)
+    }
+
+    //ap read_count
+    /// The number of entries as seen by the read side
+    pub fn read_count(&self) -> usize {
+        let wr_ptr = ungray(*self.wr_ptr_sync.last().unwrap());
+        wr_ptr.wrapping_sub(self.rd_ptr) % (2 * self.config.depth)
+    }
+
+    //mi generate_write_outputs
+    fn generate_write_outputs(&mut self) {
+        let count = self.write_count();
+        self.outputs.set_write_status(&self.config, count);
+    }
+
+    //mi generate_read_outputs
+    fn generate_read_outputs(&mut self) {
+        let count = self.read_count();
+        let head = (count > 0).then(|| self.data[self.rd_ptr % self.config.depth]);
+        self.outputs.set_read_status(&self.config, count, head);
+    }
+
+    //mi clock_write
+    /// Clock the write domain, given the gray code of the read pointer
+    /// before the clock edge
+    fn clock_write(&mut self, rd_ptr_gray: usize) {
+        let full = self.outputs.full.is_true();
+        let push = self.inputs.push.is_true();
+        if push && !full {
+            self.data[self.wr_ptr % self.config.depth] = self.inputs.data_in;
+            self.wr_ptr = (self.wr_ptr + 1) % (2 * self.config.depth);
+        }
+        self.outputs.overflow = (push && full).into();
+        self.rd_ptr_sync.rotate_right(1);
+        self.rd_ptr_sync[0] = rd_ptr_gray;
+        self.generate_write_outputs();
+    }
+
+    //mi clock_read
+    /// Clock the read domain, given the gray code of the write
+    /// pointer before the clock edge
+    fn clock_read(&mut self, wr_ptr_gray: usize) {
+        let empty = self.outputs.empty.is_true();
+        let pop = self.inputs.pop.is_true();
+        if pop && !empty {
+            self.rd_ptr = (self.rd_ptr + 1) % (2 * self.config.depth);
+        }
+        self.outputs.underflow = (pop && empty).into();
+        self.wr_ptr_sync.rotate_right(1);
+        self.wr_ptr_sync[0] = wr_ptr_gray;
+        self.generate_read_outputs();
+    }
+}
+
+//ip Simulatable for AsyncFifo
+impl<V: SimCopyValue> Simulatable for AsyncFifo<V> {
+    //mp as_any
+    /// Return a reference as an Any so it can be downcast
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    //mp as_mut_any
+    /// Return a mutable reference as an Any so it can be downcast
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    //mp reset
+    /// Reset the FIFO to empty
+    fn reset(&mut self, _reason: SimReset) {
+        self.wr_ptr = 0;
+        self.rd_ptr = 0;
+        self.rd_ptr_sync.fill(0);
+        self.wr_ptr_sync.fill(0);
+        self.outputs.overflow = false.into();
+        self.outputs.underflow = false.into();
+        self.generate_write_outputs();
+        self.generate_read_outputs();
+    }
+
+    //mp clock
+    /// Clock the FIFO on posedges of its write and/or read clocks
+    ///
+    /// If both clocks have edges at once, each domain uses the value
+    /// of the other's pointer from before the edges
+    fn clock(&mut self, mask: SimEdgeMask) {
+        let wr_ptr_gray = gray(self.wr_ptr);
+        let rd_ptr_gray = gray(self.rd_ptr);
+        if mask.is_posedge(0) {
+            self.clock_write(rd_ptr_gray);
+        }
+        if mask.is_posedge(1) {
+            self.clock_read(wr_ptr_gray);
+        }
+    }
+
+    fn propagate(&mut self, _stage: usize) {}
+    fn state_info(&self, index: SimStateIndex) -> Option<SimStateInfo> {
+        ASYNC_STATE_INFO.get(index.as_usize()).copied()
+    }
+    fn try_state_data(&self, index: SimStateIndex) -> Option<SimValueRef> {
+        match index.as_usize() {
+            2 => Some(SimValueRef::of(&self.inputs.push)),
+            3 => Some(SimValueRef::of(&self.inputs.data_in)),
+            4 => Some(SimValueRef::of(&self.inputs.pop)),
+            5 => Some(SimValueRef::of(&self.outputs.data_out)),
+            6 => Some(SimValueRef::of(&self.outputs.full)),
+            7 => Some(SimValueRef::of(&self.outputs.almost_full)),
+            8 => Some(SimValueRef::of(&self.outputs.empty)),
+            9 => Some(SimValueRef::of(&self.outputs.almost_empty)),
+            10 => Some(SimValueRef::of(&self.outputs.overflow)),
+            11 => Some(SimValueRef::of(&self.outputs.underflow)),
+            _ => None,
+        }
+    }
+    fn try_state_data_mut(&mut self, index: SimStateIndex) -> Option<SimValueRefMut> {
+        match index.as_usize() {
+            2 => Some(SimValueRefMut::of(&mut self.inputs.push)),
+            3 => Some(SimValueRefMut::of(&mut self.inputs.data_in)),
+            4 => Some(SimValueRefMut::of(&mut self.inputs.pop)),
+            5 => Some(SimValueRefMut::of(&mut self.outputs.data_out)),
+            6 => Some(SimValueRefMut::of(&mut self.outputs.full)),
+            7 => Some(SimValueRefMut::of(&mut self.outputs.almost_full)),
+            8 => Some(SimValueRefMut::of(&mut self.outputs.empty)),
+            9 => Some(SimValueRefMut::of(&mut self.outputs.almost_empty)),
+            10 => Some(SimValueRefMut::of(&mut self.outputs.overflow)),
+            11 => Some(SimValueRefMut::of(&mut self.outputs.underflow)),
+            _ => None,
+        }
+    }
+}
+
+//ip Component for AsyncFifo
+impl<V: SimCopyValue> Component for AsyncFifo<V> {
+    type Config = FifoConfig;
+    type InputsMut<'a> = &'a mut Inputs<V>;
+    type Inputs<'a> = &'a Inputs<V>;
+    type Outputs<'a> = &'a Outputs<V>;
+    fn inputs(&self) -> &Inputs<V> {
+        &self.inputs
+    }
+    fn outputs(&self) -> &Outputs<V> {
+        &self.outputs
+    }
+    fn inputs_mut(&mut self) -> &mut Inputs<V> {
+        &mut self.inputs
+    }
+    fn configure<S: SimRegister>(
+        &mut self,
+        sim: &mut S,
+        handle: S::Handle,
+        config: FifoConfig,
+    ) -> Result<(), String> {
+        config.validate()?;
+        if !config.depth.is_power_of_two() {
+            return Err(format!(
+                "Asynchronous FIFO depth {} must be a power of two",
+                config.depth
+            ));
+        }
+        if config.sync_stages == 0 {
+            return Err("Asynchronous FIFO must have at least one synchronizer stage".into());
+        }
+        self.config = config;
+        self.data = vec![V::default(); config.depth];
+        self.rd_ptr_sync = vec![0; config.sync_stages];
+        self.wr_ptr_sync = vec![0; config.sync_stages];
+        self.generate_write_outputs();
+        self.generate_read_outputs();
+        sim.register_input_edge(handle, 0, true, false);
+        sim.register_input_edge(handle, 1, true, false);
+        Ok(())
+    }
+}
+
+//ip ComponentBuilder for AsyncFifo
+impl<V: SimCopyValue> ComponentBuilder for AsyncFifo<V> {
+    type Build = Self;
+    fn instantiate<S: SimRegister>(_sim: &mut S, _name: SimNsName) -> Self {
+        Self::default()
+    }
+}
@@ -0,0 +1,893 @@
+//a Documentation
+//! Co-simulation with a model in another process
+//!
+//! A [ForeignComponent] is a simulation component whose behaviour is
+//! provided by a child process - such as a Verilator-built model, or
+//! a reference model written in Python. The component spawns the
+//! process when it is configured, learns the ports of the model from
+//! it, and then proxies reset, clock, propagate and stop to it; the
+//! values of the ports are held in the component, so they may be
+//! accessed as simulation state while the simulation is paused.
+//!
+//! The process is connected either through its standard input and
+//! output (`ForeignTransport::Pipes`), or - on Unix targets - through
+//! a Unix socket (`ForeignTransport::UnixSocket`) whose path is given
+//! to the process in the environment variable `FOREIGN_SOCKET_ENV`;
+//! the latter leaves standard output free for the process to use.
+//!
+//! A peer written in Rust implements [ForeignModel], and calls
+//! [serve_foreign] (which uses whichever transport it was started
+//! with); the `hgl_foreign_peer` binary is such a peer, modelling a
+//! counter, and is used to test co-simulation.
+//!
+//! # Protocol
+//!
+//! The protocol is binary; all integers are little-endian, and each
+//! message starts with a one-byte tag. Every port value is sent as a
+//! u64 (so ports are at most 64 bits wide), and values are always
+//! sent as complete lists: the *inputs* are the values of the clock
+//! and input ports (0 for a clock), and the *outputs* are the values
+//! of the output ports followed by the internal state, each in the
+//! order in which the ports were declared.
+//!
+//! The host starts with a hello, to which the peer replies with its
+//! ports:
+//!
+//! * `'H'` u32 version (currently 1)
+//!
+//! * reply `'H'` u32 version, u16 number of ports, and for each port:
+//!   u8 kind (0 clock, 1 input, 2 output, 3 internal), u8 width
+//!   (1 to 64, ignored for clocks), u8 edges (for a clock, bit 0 for
+//!   the rising edge and bit 1 for the falling edge), u16 name
+//!   length, and the UTF-8 name
+//!
+//! The host then sends any number of:
+//!
+//! * `'R'` inputs - reset the model
+//!
+//! * `'C'` inputs, then a u8 per clock port of the edges that have
+//!   occurred (as in the hello) - clock the model
+//!
+//! * `'P'` inputs - propagate the inputs through combinational paths
+//!
+//! to each of which the peer replies `'V'` outputs. Finally the host
+//! sends `'S'`, to which the peer replies `'S'` and exits.
+//!
+//! The peer may reply to any message with `'E'`, a u16 length, and a
+//! UTF-8 message, if it cannot handle it; the co-simulation cannot
+//! continue after this. The [ForeignComponent] records the first
+//! such error (or failure of the connection, if the peer stops
+//! responding), which is returned by [ForeignComponent::error]; the
+//! process is then killed, and the ports of the model keep their
+//! last values.
+
+//a Imports
+use std::io::{BufReader, BufWriter, Read, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+#[cfg(unix)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(unix)]
+use std::time::{Duration, Instant};
+
+use hgl_sim::prelude::component::*;
+
+//a Constants
+/// The version of the protocol
+pub const FOREIGN_PROTOCOL_VERSION: u32 = 1;
+
+/// The environment variable that holds the path of the Unix socket
+/// for a peer started with [ForeignTransport::UnixSocket]
+#[cfg(unix)]
+pub const FOREIGN_SOCKET_ENV: &str = "HGL_FOREIGN_SOCKET";
+
+/// How long to wait for a peer to connect to the Unix socket
+#[cfg(unix)]
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Count of sockets created, to make socket paths unique
+#[cfg(unix)]
+static SOCKET_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+//a ForeignPort
+//tp ForeignPortKind
+/// The kind of a port of a foreign model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForeignPortKind {
+    Clock,
+    Input,
+    Output,
+    Internal,
+}
+
+//tp ForeignPort
+/// A port of a foreign model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignPort {
+    pub name: String,
+    pub kind: ForeignPortKind,
+    /// Width in bits, from 1 to 64
+    pub width: u8,
+    /// For a clock, bit 0 if it is sensitive to the rising edge, and
+    /// bit 1 for the falling edge
+    pub edges: u8,
+}
+
+//ip ForeignPort
+impl ForeignPort {
+    //cp clock
+    /// Create a clock port, sensitive to the rising and/or falling edge
+    pub fn clock(name: &str, posedge: bool, negedge: bool) -> Self {
+        Self {
+            name: name.into(),
+            kind: ForeignPortKind::Clock,
+            width: 1,
+            edges: (posedge as u8) | ((negedge as u8) << 1),
+        }
+    }
+
+    //cp input
+    /// Create an input port
+    pub fn input(name: &str, width: u8) -> Self {
+        Self::value(name, ForeignPortKind::Input, width)
+    }
+
+    //cp output
+    /// Create an output port
+    pub fn output(name: &str, width: u8) -> Self {
+        Self::value(name, ForeignPortKind::Output, width)
+    }
+
+    //cp internal
+    /// Create a piece of internal state
+    pub fn internal(name: &str, width: u8) -> Self {
+        Self::value(name, ForeignPortKind::Internal, width)
+    }
+
+    //ci value
+    fn value(name: &str, kind: ForeignPortKind, width: u8) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            width,
+            edges: 0,
+        }
+    }
+
+    //mp mask
+    /// Mask a value to the width of the port
+    pub fn mask(&self, value: u64) -> u64 {
+        if self.width >= 64 {
+            value
+        } else {
+            value & ((1 << self.width) - 1)
+        }
+    }
+
+    //mi write_to
+    fn write_to<W: Write>(&self, w: &mut W) -> std::io::Result<()> {
+        let kind = match self.kind {
+            ForeignPortKind::Clock => 0_u8,
+            ForeignPortKind::Input => 1,
+            ForeignPortKind::Output => 2,
+            ForeignPortKind::Internal => 3,
+        };
+        w.write_all(&[kind, self.width, self.edges])?;
+        write_string(w, &self.name)
+    }
+
+    //ci read_from
+    fn read_from<R: Read>(r: &mut R) -> Result<Self, String> {
+        let [kind, width, edges] = read_array(r)?;
+        let kind = match kind {
+            0 => ForeignPortKind::Clock,
+            1 => ForeignPortKind::Input,
+            2 => ForeignPortKind::Output,
+            3 => ForeignPortKind::Internal,
+            _ => return Err(format!("Bad foreign port kind {kind}")),
+        };
+        let name = read_string(r)?;
+        if kind != ForeignPortKind::Clock && !(1..=64).contains(&width) {
+            return Err(format!("Foreign port {name} has bad width {width}"));
+        }
+        Ok(Self {
+            name,
+            kind,
+            width,
+            edges,
+        })
+    }
+}
+
+//a Protocol support
+//fi io_error
+fn io_error(e: std::io::Error) -> String {
+    format!("Foreign model connection failed: {e}")
+}
+
+//fi read_array
+fn read_array<R: Read, const N: usize>(r: &mut R) -> Result<[u8; N], String> {
+    let mut data = [0; N];
+    r.read_exact(&mut data).map_err(io_error)?;
+    Ok(data)
+}
+
+//fi read_string
+fn read_string<R: Read>(r: &mut R) -> Result<String, String> {
+    let n = u16::from_le_bytes(read_array(r)?) as usize;
+    let mut data = vec![0; n];
+    r.read_exact(&mut data).map_err(io_error)?;
+    String::from_utf8(data).map_err(|_| "Foreign model sent a bad string".into())
+}
+
+//fi write_string
+fn write_string<W: Write>(w: &mut W, s: &str) -> std::io::Result<()> {
+    let n = s.len().min(u16::MAX as usize);
+    w.write_all(&(n as u16).to_le_bytes())?;
+    w.write_all(&s.as_bytes()[..n])
+}
+
+//fi read_values
+fn read_values<R: Read>(r: &mut R, values: &mut [u64]) -> Result<(), String> {
+    for v in values.iter_mut() {
+        *v = u64::from_le_bytes(read_array(r)?);
+    }
+    Ok(())
+}
+
+//fi write_values
+fn write_values<W: Write>(w: &mut W, values: &[u64]) -> std::io::Result<()> {
+    for v in values {
+        w.write_all(&v.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+//a ForeignTransport, ForeignConfig
+//tp ForeignTransport
+/// How a [ForeignComponent] is connected to its process
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ForeignTransport {
+    /// The standard input and output of the process
+    #[default]
+    Pipes,
+    /// A Unix socket, whose path is given to the process in the
+    /// environment variable [FOREIGN_SOCKET_ENV]
+    #[cfg(unix)]
+    UnixSocket,
+}
+
+//tp ForeignConfig
+/// The configuration of a [ForeignComponent]: the process to run,
+/// and how to connect to it
+#[derive(Debug, Default, Clone)]
+pub struct ForeignConfig {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub transport: ForeignTransport,
+}
+
+//ip ForeignConfig
+impl ForeignConfig {
+    //cp new
+    /// Create a configuration to run a program, connected by pipes
+    pub fn new<P: Into<PathBuf>>(program: P) -> Self {
+        Self {
+            program: program.into(),
+            ..Default::default()
+        }
+    }
+
+    //cp arg
+    /// Add an argument for the program
+    #[must_use]
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    //cp transport
+    /// Set the transport used to connect to the program
+    #[must_use]
+    pub fn transport(mut self, transport: ForeignTransport) -> Self {
+        self.transport = transport;
+        self
+    }
+}
+
+//a Connection
+//ti Connection
+/// The connection to the process of a [ForeignComponent]
+struct Connection {
+    child: Child,
+    reader: BufReader<Box<dyn Read>>,
+    writer: BufWriter<Box<dyn Write>>,
+}
+
+//ii Connection
+impl Connection {
+    //ci spawn
+    /// Start the process, and connect to it
+    fn spawn(config: &ForeignConfig) -> Result<Self, String> {
+        let mut command = Command::new(&config.program);
+        command.args(&config.args);
+        let spawn_error =
+            |e: std::io::Error| format!("Failed to run {}: {e}", config.program.display());
+        match config.transport {
+            ForeignTransport::Pipes => {
+                command.stdin(Stdio::piped()).stdout(Stdio::piped());
+                let mut child = command.spawn().map_err(spawn_error)?;
+                let writer: Box<dyn Write> = Box::new(child.stdin.take().unwrap());
+                let reader: Box<dyn Read> = Box::new(child.stdout.take().unwrap());
+                Ok(Self::new(child, reader, writer))
+            }
+            #[cfg(unix)]
+            ForeignTransport::UnixSocket => {
+                let path = std::env::temp_dir().join(format!(
+                    "hgl_foreign_{}_{}.sock",
+                    std::process::id(),
+                    SOCKET_COUNT.fetch_add(1, Ordering::Relaxed)
+                ));
+                let _ = std::fs::remove_file(&path);
+                let listener = UnixListener::bind(&path)
+                    .map_err(|e| format!("Failed to listen on {}: {e}", path.display()))?;
+                command.env(FOREIGN_SOCKET_ENV, &path);
+                let child = command.spawn().map_err(spawn_error);
+                let stream = child.and_then(|mut child| {
+                    let stream = Self::accept(&listener, &mut child);
+                    if stream.is_err() {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                    }
+                    stream.map(|stream| (child, stream))
+                });
+                let _ = std::fs::remove_file(&path);
+                let (child, stream) = stream?;
+                let reader: Box<dyn Read> = Box::new(stream.try_clone().map_err(io_error)?);
+                let writer: Box<dyn Write> = Box::new(stream);
+                Ok(Self::new(child, reader, writer))
+            }
+        }
+    }
+
+    //ci new
+    fn new(child: Child, reader: Box<dyn Read>, writer: Box<dyn Write>) -> Self {
+        Self {
+            child,
+            reader: BufReader::new(reader),
+            writer: BufWriter::new(writer),
+        }
+    }
+
+    //ci accept
+    /// Wait for the child to connect to the listener (unless it exits)
+    #[cfg(unix)]
+    fn accept(listener: &UnixListener, child: &mut Child) -> Result<UnixStream, String> {
+        listener.set_nonblocking(true).map_err(io_error)?;
+        let start = Instant::now();
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false).map_err(io_error)?;
+                    return Ok(stream);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => (),
+                Err(e) => return Err(io_error(e)),
+            }
+            if let Ok(Some(status)) = child.try_wait() {
+                return Err(format!("Foreign model exited ({status}) before connecting"));
+            }
+            if start.elapsed() > CONNECT_TIMEOUT {
+                return Err("Timed out waiting for foreign model to connect".into());
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    //mi hello
+    /// Exchange hellos, returning the ports of the model
+    fn hello(&mut self) -> Result<Vec<ForeignPort>, String> {
+        self.writer.write_all(b"H").map_err(io_error)?;
+        self.writer
+            .write_all(&FOREIGN_PROTOCOL_VERSION.to_le_bytes())
+            .map_err(io_error)?;
+        self.writer.flush().map_err(io_error)?;
+        self.expect(b'H')?;
+        let version = u32::from_le_bytes(read_array(&mut self.reader)?);
+        if version != FOREIGN_PROTOCOL_VERSION {
+            return Err(format!(
+                "Foreign model uses protocol version {version}, not {FOREIGN_PROTOCOL_VERSION}"
+            ));
+        }
+        let n = u16::from_le_bytes(read_array(&mut self.reader)?);
+        (0..n)
+            .map(|_| ForeignPort::read_from(&mut self.reader))
+            .collect()
+    }
+
+    //mi expect
+    /// Read the tag of a reply, which must be the expected tag (or an
+    /// error)
+    fn expect(&mut self, tag: u8) -> Result<(), String> {
+        let [reply] = read_array(&mut self.reader)?;
+        match reply {
+            b'E' => Err(format!(
+                "Foreign model error: {}",
+                read_string(&mut self.reader)?
+            )),
+            _ if reply == tag => Ok(()),
+            _ => Err(format!(
+                "Foreign model replied with {reply:#04x} instead of {tag:#04x}"
+            )),
+        }
+    }
+
+    //mi request
+    /// Send a message of the tag, inputs and edges, and read the
+    /// outputs in reply
+    fn request(
+        &mut self,
+        tag: u8,
+        inputs: &[u64],
+        edges: &[u8],
+        outputs: &mut [u64],
+    ) -> Result<(), String> {
+        self.writer.write_all(&[tag]).map_err(io_error)?;
+        write_values(&mut self.writer, inputs).map_err(io_error)?;
+        self.writer.write_all(edges).map_err(io_error)?;
+        self.writer.flush().map_err(io_error)?;
+        self.expect(b'V')?;
+        read_values(&mut self.reader, outputs)
+    }
+
+    //mi kill
+    /// Kill the process, and wait for it to exit
+    fn kill(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    //mi stop
+    /// Stop the process, and wait for it to exit
+    fn stop(mut self) -> Result<(), String> {
+        let stopped = self
+            .writer
+            .write_all(b"S")
+            .and_then(|_| self.writer.flush())
+            .map_err(io_error)
+            .and_then(|_| self.expect(b'S'));
+        if stopped.is_err() {
+            let _ = self.child.kill();
+        }
+        let status = self.child.wait().map_err(io_error)?;
+        stopped?;
+        if !status.success() {
+            return Err(format!("Foreign model exited with {status}"));
+        }
+        Ok(())
+    }
+}
+
+//a ForeignComponent
+//tp ForeignComponent
+/// A component whose behaviour is provided by another process
+///
+/// The state of the component is its ports, in the order declared by
+/// the model; clocks and inputs share the input numbering (as with
+/// other components), and the outputs and internal state are numbered
+/// separately.
+#[derive(Default)]
+pub struct ForeignComponent {
+    ports: Vec<ForeignPort>,
+    /// Index of each port within the values of its kind
+    kind_index: Vec<usize>,
+    /// Values of the inputs; clocks have a value of 0
+    inputs: Vec<u64>,
+    /// Values of the outputs, followed by the internal state
+    outputs: Vec<u64>,
+    num_outputs: usize,
+    /// For each clock port, its input number
+    clocks: Vec<usize>,
+    connection: Option<Connection>,
+    /// The first error from the model, after which it is not used
+    error: Option<String>,
+}
+
+//ip Debug for ForeignComponent
+impl std::fmt::Debug for ForeignComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ForeignComponent")
+            .field("ports", &self.ports)
+            .field("inputs", &self.inputs)
+            .field("outputs", &self.outputs)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+//ip ForeignComponent
+impl ForeignComponent {
+    //ap ports
+    /// The ports of the model
+    pub fn ports(&self) -> &[ForeignPort] {
+        &self.ports
+    }
+
+    //ap error
+    /// The first error from the model (or its connection), if any;
+    /// no requests are sent to the model after an error
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    //mi find
+    fn find(&self, name: &str, kinds: &[ForeignPortKind]) -> Option<usize> {
+        self.ports
+            .iter()
+            .position(|p| p.name == name && kinds.contains(&p.kind))
+    }
+
+    //mp set_input
+    /// Set the value of an input
+    pub fn set_input(&mut self, name: &str, value: u64) -> Result<(), String> {
+        let Some(n) = self.find(name, &[ForeignPortKind::Input]) else {
+            return Err(format!("Foreign model has no input {name}"));
+        };
+        self.inputs[self.kind_index[n]] = self.ports[n].mask(value);
+        Ok(())
+    }
+
+    //ap input
+    /// The value of an input
+    pub fn input(&self, name: &str) -> Option<u64> {
+        let n = self.find(name, &[ForeignPortKind::Input])?;
+        Some(self.inputs[self.kind_index[n]])
+    }
+
+    //ap output
+    /// The value of an output or internal state
+    pub fn output(&self, name: &str) -> Option<u64> {
+        let n = self.find(name, &[ForeignPortKind::Output, ForeignPortKind::Internal])?;
+        Some(self.outputs[self.output_index(n)])
+    }
+
+    //mi output_index
+    /// The index into the outputs of an output or internal port
+    fn output_index(&self, n: usize) -> usize {
+        match self.ports[n].kind {
+            ForeignPortKind::Internal => self.num_outputs + self.kind_index[n],
+            _ => self.kind_index[n],
+        }
+    }
+
+    //mi request
+    /// Send a request to the model; if it fails the error is
+    /// recorded, and the process is killed
+    fn request(&mut self, tag: u8, edges: &[u8]) {
+        let Some(connection) = &mut self.connection else {
+            return;
+        };
+        if let Err(e) = connection.request(tag, &self.inputs, edges, &mut self.outputs) {
+            self.error = Some(e);
+            if let Some(connection) = self.connection.take() {
+                connection.kill();
+            }
+            return;
+        }
+        for n in 0..self.ports.len() {
+            if matches!(
+                self.ports[n].kind,
+                ForeignPortKind::Output | ForeignPortKind::Internal
+            ) {
+                let i = self.output_index(n);
+                self.outputs[i] = self.ports[n].mask(self.outputs[i]);
+            }
+        }
+    }
+
+    //mi set_ports
+    /// Set the ports of the model, and size the values to match
+    fn set_ports(&mut self, ports: Vec<ForeignPort>) {
+        let mut counts = [0; 3];
+        for p in &ports {
+            let count = match p.kind {
+                ForeignPortKind::Clock => {
+                    self.clocks.push(counts[0]);
+                    &mut counts[0]
+                }
+                ForeignPortKind::Input => &mut counts[0],
+                ForeignPortKind::Output => &mut counts[1],
+                ForeignPortKind::Internal => &mut counts[2],
+            };
+            self.kind_index.push(*count);
+            *count += 1;
+        }
+        self.inputs = vec![0; counts[0]];
+        self.outputs = vec![0; counts[1] + counts[2]];
+        self.num_outputs = counts[1];
+        self.ports = ports;
+    }
+}
+
+//ip Simulatable for ForeignComponent
+impl Simulatable for ForeignComponent {
+    //mp as_any
+    /// Return a reference as an Any so it can be downcast
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    //mp as_mut_any
+    /// Return a mutable reference as an Any so it can be downcast
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    //mp reset
+    /// Reset the model
+    fn reset(&mut self, _reason: SimReset) {
+        self.request(b'R', &[]);
+    }
+
+    //mp stop
+    /// Stop the model process, recording any error in doing so
+    fn stop(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            if let Err(e) = connection.stop() {
+                self.error.get_or_insert(e);
+            }
+        }
+    }
+
+    //mp clock
+    /// Clock the model with the edges of its clocks that have occurred
+    fn clock(&mut self, mask: SimEdgeMask) {
+        let edges: Vec<u8> = self
+            .clocks
+            .iter()
+            .map(|i| (mask.is_posedge(*i) as u8) | ((mask.is_negedge(*i) as u8) << 1))
+            .collect();
+        self.request(b'C', &edges);
+    }
+
+    //mp propagate
+    /// Propagate the inputs through the combinational paths of the model
+    fn propagate(&mut self, _stage: usize) {
+        self.request(b'P', &[]);
+    }
+
+    //mp state_info
+    fn state_info(&self, index: SimStateIndex) -> Option<SimStateInfo> {
+        let n = index.as_usize();
+        let p = self.ports.get(n)?;
+        let k = self.kind_index[n];
+        Some(match p.kind {
+            ForeignPortKind::Clock => SimStateInfo::clk(&p.name, k),
+            ForeignPortKind::Input => SimStateInfo::input(&p.name, k),
+            ForeignPortKind::Output => SimStateInfo::output(&p.name, k),
+            ForeignPortKind::Internal => SimStateInfo::internal(&p.name, k),
+        })
+    }
+
+    //mp try_state_data
+    fn try_state_data(&self, index: SimStateIndex) -> Option<SimValueRef> {
+        let n = index.as_usize();
+        match self.ports.get(n)?.kind {
+            ForeignPortKind::Clock => None,
+            ForeignPortKind::Input => Some(SimValueRef::of(&self.inputs[self.kind_index[n]])),
+            _ => Some(SimValueRef::of(&self.outputs[self.output_index(n)])),
+        }
+    }
+
+    //mp try_state_data_mut
+    fn try_state_data_mut(&mut self, index: SimStateIndex) -> Option<SimValueRefMut> {
+        let n = index.as_usize();
+        match self.ports.get(n)?.kind {
+            ForeignPortKind::Clock => None,
+            ForeignPortKind::Input => {
+                let k = self.kind_index[n];
+                Some(SimValueRefMut::of(&mut self.inputs[k]))
+            }
+            _ => {
+                let i = self.output_index(n);
+                Some(SimValueRefMut::of(&mut self.outputs[i]))
+            }
+        }
+    }
+}
+
+//ip Component for ForeignComponent
+impl Component for ForeignComponent {
+    type Config = ForeignConfig;
+    type InputsMut<'a> = &'a mut [u64];
+    type Inputs<'a> = &'a [u64];
+    type Outputs<'a> = &'a [u64];
+    fn inputs(&self) -> &[u64] {
+        &self.inputs
+    }
+    fn outputs(&self) -> &[u64] {
+        &self.outputs
+    }
+    fn inputs_mut(&mut self) -> &mut [u64] {
+        &mut self.inputs
+    }
+
+    //mp configure
+    /// Start the process, and register the edges of the clocks of its
+    /// model
+    fn configure<S: SimRegister>(
+        &mut self,
+        sim: &mut S,
+        handle: S::Handle,
+        config: ForeignConfig,
+    ) -> Result<(), String> {
+        let mut connection = Connection::spawn(&config)?;
+        let ports = match connection.hello() {
+            Ok(ports) => ports,
+            Err(e) => {
+                let _ = connection.child.kill();
+                let _ = connection.child.wait();
+                return Err(e);
+            }
+        };
+        self.set_ports(ports);
+        self.connection = Some(connection);
+        for (n, p) in self.ports.iter().enumerate() {
+            if p.kind == ForeignPortKind::Clock {
+                let posedge = (p.edges & 1) != 0;
+                let negedge = (p.edges & 2) != 0;
+                sim.register_input_edge(handle, self.kind_index[n], posedge, negedge);
+            }
+        }
+        Ok(())
+    }
+}
+
+//ip ComponentBuilder for ForeignComponent
+impl ComponentBuilder for ForeignComponent {
+    type Build = Self;
+    fn instantiate<S: SimRegister>(_sim: &mut S, _name: SimNsName) -> Self {
+        Self::default()
+    }
+}
+
+//ip Drop for ForeignComponent
+impl Drop for ForeignComponent {
+    /// Kill the process if the simulation was not stopped
+    fn drop(&mut self) {
+        if let Some(mut connection) = self.connection.take() {
+            let _ = connection.child.kill();
+            let _ = connection.child.wait();
+        }
+    }
+}
+
+//a ForeignModel, serve_foreign
+//tt ForeignModel
+/// A model that is served to a [ForeignComponent] from another
+/// process, with [serve_foreign]
+///
+/// The values passed to the methods are those of all the ports, in
+/// the order of [ForeignModel::ports]; the inputs have been set, and
+/// the model sets the outputs and internal state (which are otherwise
+/// held from the last call). Clock ports have a value of 0.
+pub trait ForeignModel {
+    /// The ports of the model
+    fn ports(&self) -> Vec<ForeignPort>;
+
+    /// Reset the model
+    fn reset(&mut self, values: &mut [u64]);
+
+    /// Clock the model; edges has an entry for each clock port, with
+    /// bit 0 set for a rising edge and bit 1 for a falling edge
+    fn clock(&mut self, edges: &[u8], values: &mut [u64]);
+
+    /// Propagate the inputs through combinational paths
+    fn propagate(&mut self, _values: &mut [u64]) {}
+}
+
+//fp serve_foreign
+/// Serve a model to the [ForeignComponent] that started this
+/// process, until it is stopped
+///
+/// On Unix targets this connects to the Unix socket given by
+/// `FOREIGN_SOCKET_ENV` if it is set; otherwise it uses the standard
+/// input and output
+pub fn serve_foreign<M: ForeignModel>(model: &mut M) -> Result<(), String> {
+    #[cfg(unix)]
+    if let Some(path) = std::env::var_os(FOREIGN_SOCKET_ENV) {
+        let stream = UnixStream::connect(&path).map_err(io_error)?;
+        let reader = stream.try_clone().map_err(io_error)?;
+        return serve_foreign_on(model, reader, stream);
+    }
+    serve_foreign_on(model, std::io::stdin().lock(), std::io::stdout().lock())
+}
+
+//fp serve_foreign_on
+/// Serve a model on a connection, until it is stopped
+pub fn serve_foreign_on<M: ForeignModel, R: Read, W: Write>(
+    model: &mut M,
+    reader: R,
+    writer: W,
+) -> Result<(), String> {
+    let mut reader = BufReader::new(reader);
+    let mut writer = BufWriter::new(writer);
+    let ports = model.ports();
+    let mut values = vec![0_u64; ports.len()];
+    let with_kind = |kind| -> Vec<usize> {
+        (0..ports.len())
+            .filter(|n| ports[*n].kind == kind)
+            .collect()
+    };
+    let inputs = with_kind(ForeignPortKind::Input);
+    let clocks = with_kind(ForeignPortKind::Clock);
+    let mut outputs = with_kind(ForeignPortKind::Output);
+    outputs.extend(with_kind(ForeignPortKind::Internal));
+    let num_inputs = inputs.len() + clocks.len();
+
+    loop {
+        let [tag] = read_array(&mut reader)?;
+        match tag {
+            b'H' => {
+                let _version: [u8; 4] = read_array(&mut reader)?;
+                writer.write_all(b"H").map_err(io_error)?;
+                writer
+                    .write_all(&FOREIGN_PROTOCOL_VERSION.to_le_bytes())
+                    .map_err(io_error)?;
+                writer
+                    .write_all(&(ports.len() as u16).to_le_bytes())
+                    .map_err(io_error)?;
+                for p in &ports {
+                    p.write_to(&mut writer).map_err(io_error)?;
+                }
+            }
+            b'R' | b'C' | b'P' => {
+                // The inputs are sent in input numbering, which
+                // includes the clocks
+                let mut input_values = vec![0; num_inputs];
+                read_values(&mut reader, &mut input_values)?;
+                let mut k = 0;
+                for (n, p) in ports.iter().enumerate() {
+                    match p.kind {
+                        ForeignPortKind::Clock => k += 1,
+                        ForeignPortKind::Input => {
+                            values[n] = p.mask(input_values[k]);
+                            k += 1;
+                        }
+                        _ => (),
+                    }
+                }
+                match tag {
+                    b'R' => model.reset(&mut values),
+                    b'C' => {
+                        let mut edges = vec![0; clocks.len()];
+                        reader.read_exact(&mut edges).map_err(io_error)?;
+                        model.clock(&edges, &mut values);
+                    }
+                    _ => model.propagate(&mut values),
+                }
+                writer.write_all(b"V").map_err(io_error)?;
+                for n in &outputs {
+                    writer
+                        .write_all(&ports[*n].mask(values[*n]).to_le_bytes())
+                        .map_err(io_error)?;
+                }
+            }
+            b'S' => {
+                writer.write_all(b"S").map_err(io_error)?;
+                writer.flush().map_err(io_error)?;
+                return Ok(());
+            }
+            _ => {
+                writer.write_all(b"E").map_err(io_error)?;
+                write_string(&mut writer, &format!("Unknown message {tag:#04x}"))
+                    .map_err(io_error)?;
+                writer.flush().map_err(io_error)?;
+                return Err(format!("Unknown message {tag:#04x}"));
+            }
+        }
+        writer.flush().map_err(io_error)?;
+    }
+}
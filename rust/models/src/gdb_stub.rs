@@ -0,0 +1,512 @@
+//a Documentation
+//! A GDB remote serial protocol stub for an [Rv32i]
+//!
+//! A [GdbStub] drives a prepared (and started) [Simulation] that
+//! contains an [Rv32i] core, so that firmware can be debugged with
+//! stock GDB using `target remote` to a local TCP port. It supports:
+//!
+//! * reading and writing the registers (`g`, `G`, `p`, `P`), which are
+//!   x0 to x31 followed by the pc, as GDB expects for RISC-V
+//!
+//! * reading and writing memory (`m`, `M`) through the [Backdoor] of
+//!   memories added with [GdbStub::add_memory]
+//!
+//! * software and hardware breakpoints (`Z0`, `Z1`, `z0`, `z1`), which
+//!   are held by the stub (rather than written into memory) and hit
+//!   when the core is about to execute the instruction at the address
+//!
+//! * single step (`s`), which fires clock edges until one instruction
+//!   retires, and continue (`c`), which does so repeatedly until a
+//!   breakpoint is hit, the core halts, or GDB interrupts (Ctrl-C)
+//!
+//! When the core halts with an [Rv32iTrap] the stop is reported to
+//! GDB as a signal: SIGTRAP for EBREAK and ECALL, SIGILL for an
+//! illegal instruction, SIGBUS for a misaligned fetch or access, and
+//! SIGSEGV for an access fault.
+//!
+//! For example, with the simulation on the stub thread:
+//!
+//! ```ignore
+//! let listener = std::net::TcpListener::bind("127.0.0.1:3333")?;
+//! let mut stub = GdbStub::new(&sim, cpu);
+//! stub.add_memory::<Memory<Bv<32>, Bv<16>>>(0, imem);
+//! stub.serve_tcp(&listener)?;
+//! ```
+//!
+//! and then `gdb firmware.elf -ex "target remote :3333"`.
+
+//a Imports
+use std::collections::BTreeSet;
+use std::io::{BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use hgl_sim::prelude::component::*;
+
+use crate::loader::hex_bytes;
+use crate::{Backdoor, Rv32i, Rv32iTrap};
+
+//a Constants
+/// The GDB signal numbers used for stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGBUS: u8 = 10;
+const SIGSEGV: u8 = 11;
+
+/// The number of registers: x0 to x31 and the pc
+const NUM_REGISTERS: usize = 33;
+
+/// The number of instructions executed by a continue between checks
+/// for an interrupt from GDB
+const INTERRUPT_CHECK_INTERVAL: usize = 64;
+
+/// The maximum number of clock edges to wait for an instruction to
+/// retire when stepping
+const MAX_EDGES_PER_INSTRUCTION: usize = 10_000;
+
+//a GdbMemory
+//ti ReadMemory, WriteMemory
+/// Functions to access a memory instance through its backdoor
+type ReadMemory = fn(&Simulation, InstanceHandle, u64, usize) -> Result<Vec<u8>, String>;
+type WriteMemory = fn(&Simulation, InstanceHandle, u64, &[u8]) -> Result<(), String>;
+
+//ti GdbMemory
+/// A memory accessible to GDB, with functions that access an
+/// instance (of a type known when it was added) through its backdoor
+struct GdbMemory {
+    base: u64,
+    handle: InstanceHandle,
+    read: ReadMemory,
+    write: WriteMemory,
+}
+
+//fi read_memory
+fn read_memory<M: Component + Backdoor>(
+    sim: &Simulation,
+    handle: InstanceHandle,
+    address: u64,
+    length: usize,
+) -> Result<Vec<u8>, String> {
+    sim.instances()
+        .inst::<M>(handle)
+        .read_bytes(address, length)
+}
+
+//fi write_memory
+fn write_memory<M: Component + Backdoor>(
+    sim: &Simulation,
+    handle: InstanceHandle,
+    address: u64,
+    data: &[u8],
+) -> Result<(), String> {
+    sim.instances()
+        .inst_mut::<M>(handle)
+        .write_bytes(address, data)
+}
+
+//a Connection
+//ti Connection
+/// A connection to GDB, handling the framing, checksums and
+/// acknowledgements of packets
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    no_ack: bool,
+}
+
+//ii Connection
+impl Connection {
+    //mi read_byte
+    /// Read a byte, or None if the connection is closed
+    fn read_byte(&mut self) -> std::io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.reader.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    //mi read_packet
+    /// Read a packet, returning its (unescaped) data, or None if the
+    /// connection is closed; an interrupt outside a packet is
+    /// returned as a packet of just 0x03
+    fn read_packet(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(0x03) => return Ok(Some(vec![0x03])),
+                Some(b'$') => (),
+                Some(_) => continue,
+            }
+            let mut data = vec![];
+            let mut checksum = 0_u8;
+            let mut escape = false;
+            loop {
+                let Some(byte) = self.read_byte()? else {
+                    return Ok(None);
+                };
+                if byte == b'#' {
+                    break;
+                }
+                checksum = checksum.wrapping_add(byte);
+                match byte {
+                    b'}' => escape = true,
+                    _ if escape => {
+                        data.push(byte ^ 0x20);
+                        escape = false;
+                    }
+                    _ => data.push(byte),
+                }
+            }
+            let mut sum = [0; 2];
+            self.reader.read_exact(&mut sum)?;
+            let valid = std::str::from_utf8(&sum)
+                .ok()
+                .and_then(|s| u8::from_str_radix(s, 16).ok())
+                == Some(checksum);
+            if !self.no_ack {
+                self.writer.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid || self.no_ack {
+                return Ok(Some(data));
+            }
+        }
+    }
+
+    //mi write_packet
+    /// Write a packet, waiting for it to be acknowledged
+    fn write_packet(&mut self, data: &str) -> std::io::Result<()> {
+        let checksum = data.bytes().fold(0_u8, |s, b| s.wrapping_add(b));
+        loop {
+            self.writer
+                .write_all(format!("${data}#{checksum:02x}").as_bytes())?;
+            if self.no_ack {
+                return Ok(());
+            }
+            loop {
+                match self.read_byte()? {
+                    Some(b'+') | None => return Ok(()),
+                    Some(b'-') => break,
+                    Some(_) => (),
+                }
+            }
+        }
+    }
+
+    //mi interrupted
+    /// Return true if GDB has sent an interrupt (or closed the
+    /// connection), without blocking
+    fn interrupted(&mut self) -> std::io::Result<bool> {
+        if !self.reader.buffer().is_empty() {
+            return Ok(self.read_byte()? == Some(0x03));
+        }
+        self.writer.set_nonblocking(true)?;
+        let mut byte = [0];
+        let result = self.reader.get_mut().read(&mut byte);
+        self.writer.set_nonblocking(false)?;
+        match result {
+            Ok(0) => Ok(true),
+            Ok(_) => Ok(byte[0] == 0x03),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+//a GdbStub
+//tp GdbStub
+/// A GDB remote serial protocol stub for an [Rv32i] in a [Simulation]
+///
+/// The simulation is not thread-safe, so the stub runs on the thread
+/// that owns the simulation
+pub struct GdbStub<'a, 's> {
+    sim: &'a Simulation<'s>,
+    cpu: InstanceHandle,
+    memories: Vec<GdbMemory>,
+    breakpoints: BTreeSet<u32>,
+}
+
+//ip GdbStub
+impl<'a, 's> GdbStub<'a, 's> {
+    //cp new
+    /// Create a new stub for a core in a simulation, which must have
+    /// been started
+    pub fn new(sim: &'a Simulation<'s>, cpu: InstanceHandle) -> Self {
+        Self {
+            sim,
+            cpu,
+            memories: vec![],
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    //mp add_memory
+    /// Make a memory instance (of type M) accessible to GDB at a base
+    /// address
+    ///
+    /// An access is made to the memory with the highest base at or
+    /// below its address
+    pub fn add_memory<M: Component + Backdoor>(&mut self, base: u64, handle: InstanceHandle) {
+        self.memories.push(GdbMemory {
+            base,
+            handle,
+            read: read_memory::<M>,
+            write: write_memory::<M>,
+        });
+        self.memories.sort_by_key(|m| m.base);
+    }
+
+    //ap breakpoints
+    /// The addresses of the breakpoints
+    pub fn breakpoints(&self) -> impl Iterator<Item = u32> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    //mp serve_tcp
+    /// Accept a connection from GDB on a TCP listener, and handle it
+    /// until GDB detaches, kills the target, or closes the connection
+    pub fn serve_tcp(&mut self, listener: &TcpListener) -> std::io::Result<()> {
+        let (stream, _) = listener.accept()?;
+        self.serve(stream)
+    }
+
+    //mp serve
+    /// Handle packets from GDB on a stream until it detaches, kills
+    /// the target, or closes the connection
+    pub fn serve(&mut self, stream: TcpStream) -> std::io::Result<()> {
+        stream.set_nodelay(true)?;
+        let mut connection = Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            no_ack: false,
+        };
+        while let Some(packet) = connection.read_packet()? {
+            let packet = String::from_utf8_lossy(&packet).into_owned();
+            let Some(reply) = self.handle_packet(&mut connection, &packet)? else {
+                break;
+            };
+            connection.write_packet(&reply)?;
+            match packet.as_str() {
+                "QStartNoAckMode" => connection.no_ack = true,
+                "D" => break,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    //mi handle_packet
+    /// Handle a packet, returning the reply (empty if the packet is
+    /// not supported), or None if the connection should be closed
+    /// without a reply
+    fn handle_packet(
+        &mut self,
+        connection: &mut Connection,
+        packet: &str,
+    ) -> std::io::Result<Option<String>> {
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "\x03" => format!("S{SIGINT:02x}"),
+            "?" => format!("S{:02x}", self.stop_signal()),
+            "g" => (0..NUM_REGISTERS).map(|r| hex_u32(self.reg(r))).collect(),
+            "G" => match hex_bytes(args).ok() {
+                Some(data) if data.len() == 4 * NUM_REGISTERS => {
+                    for (r, value) in data.chunks(4).enumerate() {
+                        self.set_reg(r, u32::from_le_bytes(value.try_into().unwrap()));
+                    }
+                    "OK".into()
+                }
+                _ => "E01".into(),
+            },
+            "p" => match parse_hex(args) {
+                Some(r) if (r as usize) < NUM_REGISTERS => hex_u32(self.reg(r as usize)),
+                _ => "E01".into(),
+            },
+            "P" => {
+                let (r, value) = args.split_once('=').unwrap_or_default();
+                let value = hex_bytes(value)
+                    .ok()
+                    .and_then(|v| <[u8; 4]>::try_from(v).ok());
+                match (parse_hex(r), value) {
+                    (Some(r), Some(value)) if (r as usize) < NUM_REGISTERS => {
+                        self.set_reg(r as usize, u32::from_le_bytes(value));
+                        "OK".into()
+                    }
+                    _ => "E01".into(),
+                }
+            }
+            "m" => {
+                let (address, length) = args.split_once(',').unwrap_or_default();
+                match (parse_hex(address), parse_hex(length)) {
+                    (Some(address), Some(length)) => self
+                        .read_memory(address, length as usize)
+                        .map(|data| data.iter().map(|b| format!("{b:02x}")).collect())
+                        .unwrap_or_else(|_| "E01".into()),
+                    _ => "E01".into(),
+                }
+            }
+            "M" => {
+                let (range, data) = args.split_once(':').unwrap_or_default();
+                let (address, length) = range.split_once(',').unwrap_or_default();
+                match (parse_hex(address), parse_hex(length), hex_bytes(data).ok()) {
+                    (Some(address), Some(length), Some(data)) if data.len() == length as usize => {
+                        match self.write_memory(address, &data) {
+                            Ok(()) => "OK".into(),
+                            Err(_) => "E01".into(),
+                        }
+                    }
+                    _ => "E01".into(),
+                }
+            }
+            "c" | "s" => {
+                if let Some(address) = parse_hex(args) {
+                    self.set_reg(32, address as u32);
+                }
+                let signal = self.resume(connection, command == "s")?;
+                format!("S{signal:02x}")
+            }
+            "Z" | "z" => {
+                let mut fields = args.split(',');
+                let kind = fields.next();
+                match (kind, fields.next().and_then(parse_hex)) {
+                    (Some("0" | "1"), Some(address)) => {
+                        if command == "Z" {
+                            self.breakpoints.insert(address as u32);
+                        } else {
+                            self.breakpoints.remove(&(address as u32));
+                        }
+                        "OK".into()
+                    }
+                    (Some("0" | "1"), None) => "E01".into(),
+                    _ => String::new(),
+                }
+            }
+            "H" | "T" => "OK".into(),
+            "D" => "OK".into(),
+            "k" => return Ok(None),
+            _ => match packet {
+                "QStartNoAckMode" => "OK".into(),
+                "qAttached" => "1".into(),
+                "qfThreadInfo" => "m1".into(),
+                "qsThreadInfo" => "l".into(),
+                "qC" => "QC1".into(),
+                _ if packet.starts_with("qSupported") => "PacketSize=1000;QStartNoAckMode+".into(),
+                _ => String::new(),
+            },
+        };
+        Ok(Some(reply))
+    }
+
+    //mi reg
+    /// Read a register, with register 32 being the pc
+    fn reg(&self, r: usize) -> u32 {
+        let instances = self.sim.instances();
+        let cpu = instances.inst::<Rv32i>(self.cpu);
+        match r {
+            32 => cpu.pc(),
+            _ => cpu.reg(r),
+        }
+    }
+
+    //mi set_reg
+    /// Write a register, with register 32 being the pc
+    fn set_reg(&self, r: usize, value: u32) {
+        let instances = self.sim.instances();
+        let mut cpu = instances.inst_mut::<Rv32i>(self.cpu);
+        match r {
+            32 => cpu.set_pc(value),
+            _ => cpu.set_reg(r, value),
+        }
+    }
+
+    //mi memory
+    /// Find the memory for an address, and the address within it
+    fn memory(&self, address: u64) -> Result<(&GdbMemory, u64), String> {
+        self.memories
+            .iter()
+            .rev()
+            .find(|m| m.base <= address)
+            .map(|m| (m, address - m.base))
+            .ok_or_else(|| format!("No memory at {address:#x}"))
+    }
+
+    //mp read_memory
+    /// Read memory as GDB would, through the backdoor of the memories
+    pub fn read_memory(&self, address: u64, length: usize) -> Result<Vec<u8>, String> {
+        let (m, offset) = self.memory(address)?;
+        (m.read)(self.sim, m.handle, offset, length)
+    }
+
+    //mp write_memory
+    /// Write memory as GDB would, through the backdoor of the memories
+    pub fn write_memory(&self, address: u64, data: &[u8]) -> Result<(), String> {
+        let (m, offset) = self.memory(address)?;
+        (m.write)(self.sim, m.handle, offset, data)
+    }
+
+    //mi stop_signal
+    /// The signal to report for the state of the core
+    fn stop_signal(&self) -> u8 {
+        match self.sim.instances().inst::<Rv32i>(self.cpu).trap() {
+            None | Some(Rv32iTrap::Ebreak { .. } | Rv32iTrap::Ecall { .. }) => SIGTRAP,
+            Some(Rv32iTrap::IllegalInstruction { .. }) => SIGILL,
+            Some(Rv32iTrap::MisalignedFetch { .. } | Rv32iTrap::MisalignedAccess { .. }) => SIGBUS,
+            Some(Rv32iTrap::AccessFault { .. }) => SIGSEGV,
+        }
+    }
+
+    //mp step_instruction
+    /// Fire clock edges until the core retires an instruction or
+    /// halts (or a limit on the number of edges is reached); return
+    /// true if it retired an instruction
+    pub fn step_instruction(&self) -> bool {
+        let instances = self.sim.instances();
+        let retired = instances.inst::<Rv32i>(self.cpu).retired();
+        for _ in 0..MAX_EDGES_PER_INSTRUCTION {
+            self.sim.fire_next_edges();
+            let cpu = instances.inst::<Rv32i>(self.cpu);
+            if cpu.is_halted() {
+                return false;
+            }
+            if cpu.retired() != retired {
+                return true;
+            }
+        }
+        false
+    }
+
+    //mi resume
+    /// Step one instruction, or continue until a breakpoint is hit,
+    /// the core halts or GDB interrupts; return the stop signal
+    fn resume(&self, connection: &mut Connection, step: bool) -> std::io::Result<u8> {
+        let mut executed = 0;
+        loop {
+            if !self.step_instruction() {
+                return Ok(self.stop_signal());
+            }
+            if step || self.breakpoints.contains(&self.reg(32)) {
+                return Ok(SIGTRAP);
+            }
+            executed += 1;
+            if executed % INTERRUPT_CHECK_INTERVAL == 0 && connection.interrupted()? {
+                return Ok(SIGINT);
+            }
+        }
+    }
+}
+
+//a Hex
+//fi parse_hex
+fn parse_hex(text: &str) -> Option<u64> {
+    u64::from_str_radix(text, 16).ok()
+}
+
+//fi hex_u32
+/// Format a register value as GDB expects, as little-endian bytes
+fn hex_u32(value: u32) -> String {
+    value
+        .to_le_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
@@ -0,0 +1,305 @@
+//a Documentation
+//! An APB interrupt controller
+//!
+//! The [InterruptController] has up to 32 level-sensitive interrupt
+//! sources, which are the bits of its `sources` input; they may be
+//! connected to interrupt outputs of other instances (such as the
+//! `irq` of a [crate::Timer] or the `gpio_input_event` of a GPIO)
+//! with [InterruptController::connect].
+//!
+//! A source that is asserted at a clock edge becomes pending (and
+//! stays pending until it is acknowledged, even if the source is
+//! deasserted). The `irq` output is asserted while any enabled source
+//! is pending. The active interrupt is the enabled pending source with
+//! the highest priority; of sources with equal priority, that with
+//! the lowest number.
+//!
+//! Its registers (selected by the low bits of paddr, as for the other
+//! APB targets here) are:
+//!
+//! * 0 PENDING: a bit per source; writing a 1 to a bit acknowledges
+//!   the source
+//!
+//! * 1 ENABLE: a bit per source
+//!
+//! * 2 ACTIVE: the number of the active interrupt, or 0xffffffff if
+//!   there is none
+//!
+//! * 3 ACKNOWLEDGE: writing a source number acknowledges that source
+//!
+//! * 32 to 63 PRIORITY: the priority (0 to 255) of each source
+//!
+//! The registers may also be accessed at transaction level, as a
+//! [TlmTarget] of [crate::ApbPayload]s.
+
+//a Imports
+use hgl_sim::prelude::component::*;
+
+use crate::apb_monitor::find_typed;
+use crate::{t_apb_request, t_apb_response, ApbPayload};
+
+//a InterruptController
+//ci STATE_INFO
+const STATE_INFO: &[SimStateInfo] = &[
+    SimStateInfo::clk("clk", 0),
+    SimStateInfo::input("sources", 0),
+    SimStateInfo::input("apb_request", 1),
+    SimStateInfo::output("apb_response", 0),
+    SimStateInfo::output("irq", 1),
+];
+
+//ci NO_INTERRUPT
+/// The value of the ACTIVE register if no interrupt is active
+pub const NO_INTERRUPT: u32 = 0xffff_ffff;
+
+//tp InterruptControllerInputs
+#[derive(Debug, Default)]
+pub struct InterruptControllerInputs {
+    pub sources: Bv<32>,
+    pub apb_request: t_apb_request,
+}
+
+//tp InterruptControllerOutputs
+#[derive(Debug, Default)]
+pub struct InterruptControllerOutputs {
+    pub apb_response: t_apb_response,
+    pub irq: Bit,
+}
+
+//tp InterruptController
+/// An interrupt controller with an APB register interface
+///
+/// Its configuration is the number of interrupt sources
+#[derive(Debug, Default)]
+pub struct InterruptController {
+    pub inputs: InterruptControllerInputs,
+    pub outputs: InterruptControllerOutputs,
+    num_sources: usize,
+    pending: u32,
+    enable: u32,
+    priority: [u8; 32],
+}
+
+//ip InterruptController
+impl InterruptController {
+    //ap num_sources
+    /// The number of interrupt sources
+    pub fn num_sources(&self) -> usize {
+        self.num_sources
+    }
+
+    //ap pending
+    /// The pending sources, one bit per source
+    pub fn pending(&self) -> u32 {
+        self.pending
+    }
+
+    //ap active
+    /// The active interrupt, if any
+    pub fn active(&self) -> Option<usize> {
+        let requests = self.pending & self.enable;
+        (0..self.num_sources)
+            .filter(|i| requests & (1 << i) != 0)
+            .min_by_key(|i| std::cmp::Reverse(self.priority[*i]))
+    }
+
+    //mi source_mask
+    /// The mask of bits of the sources
+    fn source_mask(&self) -> u32 {
+        ((1u64 << self.num_sources) - 1) as u32
+    }
+
+    //mi apb_access
+    /// Perform an APB access, returning the read data
+    fn apb_access(&mut self, request: &t_apb_request) -> u32 {
+        let data = request.pwdata.try_as_u64().unwrap_or(0) as u32;
+        let write = request.pwrite.is_true();
+        match request.paddr.try_as_u64().unwrap_or(0) & 63 {
+            0 if write => {
+                self.pending &= !data;
+                0
+            }
+            0 => self.pending,
+            1 if write => {
+                self.enable = data & self.source_mask();
+                0
+            }
+            1 => self.enable,
+            2 => self.active().map(|i| i as u32).unwrap_or(NO_INTERRUPT),
+            3 if write => {
+                if (data as usize) < self.num_sources {
+                    self.pending &= !(1 << data);
+                }
+                0
+            }
+            n @ 32.. if write => {
+                self.priority[n as usize - 32] = data as u8;
+                0
+            }
+            n @ 32.. => self.priority[n as usize - 32] as u32,
+            _ => 0,
+        }
+    }
+
+    //fp connect
+    /// Connect interrupt sources of an instance of the controller to
+    /// single bit states, given by path (such as "timer.irq"), in
+    /// order of source number
+    ///
+    /// This can only be used after prepare_simulation
+    pub fn connect(sim: &Simulation, intc: InstanceHandle, sources: &[&str]) -> Result<(), String> {
+        let num_sources = {
+            let instances = sim.instances();
+            let s = instances.instance(intc).borrow_sim();
+            let Some(i) = s
+                .as_ref()
+                .and_then(|s| s.as_any().downcast_ref::<InterruptController>())
+            else {
+                return Err("Instance to connect is not an InterruptController".into());
+            };
+            i.num_sources
+        };
+        if sources.len() > num_sources {
+            return Err(format!(
+                "Interrupt controller has {num_sources} sources but {} were given",
+                sources.len()
+            ));
+        }
+        let sources = sources
+            .iter()
+            .map(|path| find_typed::<Bit>(sim, path))
+            .collect::<Result<Vec<_>, _>>()?;
+        sim.add_combinational(move |sim| {
+            let mut value = 0;
+            for (i, (h, s, e)) in sources.iter().enumerate() {
+                let asserted = sim
+                    .with_value(*h, *s, e, |v| v.as_any().downcast_ref::<Bit>().copied())
+                    .flatten()
+                    .is_some_and(|b| b.is_true());
+                value |= (asserted as u64) << i;
+            }
+            let instances = sim.instances();
+            let mut i = instances.inst_mut::<InterruptController>(intc);
+            i.inputs.sources.set_u64(value);
+        });
+        Ok(())
+    }
+}
+
+//ip TlmTarget<ApbPayload> for InterruptController
+impl TlmTarget<ApbPayload> for InterruptController {
+    //mp b_transport
+    /// Access a register, with no delay
+    fn b_transport(&mut self, payload: &mut ApbPayload, _delay: &mut usize) {
+        let rdata = self.apb_access(&payload.request());
+        payload.complete(rdata, false);
+    }
+}
+
+//ip Simulatable for InterruptController
+impl Simulatable for InterruptController {
+    //mp as_any
+    /// Return a reference as an Any so it can be downcast
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    //mp as_mut_any
+    /// Return a mutable reference as an Any so it can be downcast
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    //mp reset
+    /// Reset the controller, with no sources pending or enabled, and
+    /// all priorities zero
+    fn reset(&mut self, _reason: SimReset) {
+        self.pending = 0;
+        self.enable = 0;
+        self.priority = [0; 32];
+        self.outputs = InterruptControllerOutputs::default();
+    }
+
+    //mp clock
+    /// Clock the controller on the posedge of its clock
+    ///
+    /// A register access takes effect before the sources are sampled,
+    /// so acknowledging a source that is still asserted leaves it
+    /// pending
+    fn clock(&mut self, _mask: SimEdgeMask) {
+        let request = self.inputs.apb_request;
+        let mut response = t_apb_response {
+            pready: true.into(),
+            ..Default::default()
+        };
+        if request.psel.is_true() && request.penable.is_true() {
+            response.prdata = (self.apb_access(&request) as u64).into();
+        }
+        self.outputs.apb_response = response;
+        self.pending |= self.inputs.sources.try_as_u64().unwrap_or(0) as u32 & self.source_mask();
+        self.outputs.irq = (self.pending & self.enable != 0).into();
+    }
+
+    fn propagate(&mut self, _stage: usize) {}
+    fn state_info(&self, index: SimStateIndex) -> Option<SimStateInfo> {
+        STATE_INFO.get(index.as_usize()).copied()
+    }
+    fn try_state_data(&self, index: SimStateIndex) -> Option<SimValueRef> {
+        match index.as_usize() {
+            1 => Some(SimValueRef::of(&self.inputs.sources)),
+            2 => Some(SimValueRef::of(&self.inputs.apb_request)),
+            3 => Some(SimValueRef::of(&self.outputs.apb_response)),
+            4 => Some(SimValueRef::of(&self.outputs.irq)),
+            _ => None,
+        }
+    }
+    fn try_state_data_mut(&mut self, index: SimStateIndex) -> Option<SimValueRefMut> {
+        match index.as_usize() {
+            1 => Some(SimValueRefMut::of(&mut self.inputs.sources)),
+            2 => Some(SimValueRefMut::of(&mut self.inputs.apb_request)),
+            3 => Some(SimValueRefMut::of(&mut self.outputs.apb_response)),
+            4 => Some(SimValueRefMut::of(&mut self.outputs.irq)),
+            _ => None,
+        }
+    }
+}
+
+//ip Component for InterruptController
+impl Component for InterruptController {
+    type Config = usize;
+    type InputsMut<'a> = &'a mut InterruptControllerInputs;
+    type Inputs<'a> = &'a InterruptControllerInputs;
+    type Outputs<'a> = &'a InterruptControllerOutputs;
+    fn inputs(&self) -> &InterruptControllerInputs {
+        &self.inputs
+    }
+    fn outputs(&self) -> &InterruptControllerOutputs {
+        &self.outputs
+    }
+    fn inputs_mut(&mut self) -> &mut InterruptControllerInputs {
+        &mut self.inputs
+    }
+    fn configure<S: SimRegister>(
+        &mut self,
+        sim: &mut S,
+        handle: S::Handle,
+        num_sources: usize,
+    ) -> Result<(), String> {
+        if !(1..=32).contains(&num_sources) {
+            return Err(format!(
+                "Interrupt controller must have 1 to 32 sources, not {num_sources}"
+            ));
+        }
+        self.num_sources = num_sources;
+        sim.register_input_edge(handle, 0, true, false);
+        Ok(())
+    }
+}
+
+//ip ComponentBuilder for InterruptController
+impl ComponentBuilder for InterruptController {
+    type Build = Self;
+    fn instantiate<S: SimRegister>(_sim: &mut S, _name: SimNsName) -> Self {
+        Self::default()
+    }
+}
@@ -13,6 +13,58 @@ pub mod apb_target_gpio;
 pub mod apb;
 pub use apb::*;
 
+pub mod apb_decoder;
+pub use apb_decoder::{ApbAddressMap, ApbDecoder, ApbRegion};
+
+pub mod apb_master;
+pub use apb_master::{ApbMaster, ApbMasterResult, ApbOp};
+
+pub mod apb_monitor;
+pub use apb_monitor::{
+    ApbMismatch, ApbMonitor, ApbReference, ApbRegister, ApbRegisterMap, ApbScoreboard,
+    ApbTransaction,
+};
+
+pub mod apb_tlm;
+pub use apb_tlm::{ApbPayload, ApbTlmBridge, ApbTlmTarget};
+
+pub mod axi;
+pub use axi::*;
+
+pub mod axi_master;
+pub use axi_master::{AxiMaster, AxiMasterConfig, AxiMasterResult, AxiOp};
+
+pub mod axi_slave;
+pub use axi_slave::{AxiMemory, AxiSlave, AxiSlaveConfig, AxiStorage, SparseMemory};
+
+pub mod fifo;
+pub use fifo::{AsyncFifo, Fifo, FifoConfig};
+
+pub mod foreign;
+pub use foreign::{serve_foreign, serve_foreign_on, ForeignComponent, ForeignConfig};
+pub use foreign::{ForeignModel, ForeignPort, ForeignPortKind, ForeignTransport};
+
+pub mod gdb_stub;
+pub use gdb_stub::GdbStub;
+
+pub mod interrupt_controller;
+pub use interrupt_controller::InterruptController;
+
+pub mod loader;
+pub use loader::{Backdoor, Program, ProgramSegment};
+
+pub mod rv32i;
+pub use rv32i::{Rv32i, Rv32iConfig, Rv32iPort, Rv32iRetired, Rv32iTrap};
+
+pub mod timer;
+pub use timer::Timer;
+
+pub mod uart;
+#[cfg(unix)]
+pub use uart::UnixSocketStream;
+pub use uart::{Uart, UartConfig, UartError, UartParity, UartStream, UartTerminal};
+pub use uart::{UartRx, UartTx};
+
 //a Export components
 pub use counter::Counter;
 pub use memories::Memory;
@@ -0,0 +1,546 @@
+//a Documentation
+//! Program loading into memory models
+//!
+//! A [Program] is a set of segments of bytes at addresses, with an
+//! optional entry point and a symbol table; it may be parsed from an
+//! ELF file (32-bit or 64-bit, of either endianness, using its
+//! loadable program headers and its symbol table), an Intel HEX file
+//! or a Motorola S-record file.
+//!
+//! The segments are written into memory models through the
+//! [Backdoor] trait, which is implemented for [crate::Memory] (of
+//! values whose width is a whole number of bytes, with words stored
+//! little-endian) and [crate::SparseMemory].
+//!
+//! A testbench for a processor might then load firmware with:
+//!
+//! ```ignore
+//! let program = Program::load_file("firmware.elf")?;
+//! program.write_to(&mut *instances.inst_mut::<Memory<Bv<32>, Bv<16>>>(imem), 0)?;
+//! let exit = program.symbol("_exit").unwrap();
+//! ```
+
+//a Imports
+use std::collections::HashMap;
+use std::path::Path;
+
+use hgl_sim::prelude::component::*;
+
+use crate::{Memory, SparseMemory};
+
+//a Backdoor
+//tt Backdoor
+/// Byte-addressed access to a memory model, without clocking
+pub trait Backdoor {
+    /// Write bytes starting at a byte address of the memory
+    fn write_bytes(&mut self, address: u64, data: &[u8]) -> Result<(), String>;
+
+    /// Read bytes starting at a byte address of the memory
+    fn read_bytes(&self, address: u64, length: usize) -> Result<Vec<u8>, String>;
+}
+
+//fi word_bytes
+/// The number of bytes in a word of a memory of a value type, which
+/// must be a whole number of bytes
+fn word_bytes<V: SimBv>() -> Result<usize, String> {
+    let bits = V::default().num_bits();
+    if !bits.is_multiple_of(8) {
+        return Err(format!(
+            "Memory words of {bits} bits are not a whole number of bytes"
+        ));
+    }
+    Ok(bits / 8)
+}
+
+//ip Backdoor for Memory
+impl<V: SimBv, I: SimBv> Backdoor for Memory<V, I> {
+    fn write_bytes(&mut self, address: u64, data: &[u8]) -> Result<(), String> {
+        let n = word_bytes::<V>()? as u64;
+        for (i, byte) in data.iter().enumerate() {
+            let a = address + i as u64;
+            let word_address = (a / n) as usize;
+            let Some(mut word) = self.peek(word_address) else {
+                return Err(format!("Backdoor write to {a:#x} is beyond the memory"));
+            };
+            word.as_u8s_mut()[(a % n) as usize] = *byte;
+            self.poke(word_address, word)?;
+        }
+        Ok(())
+    }
+    fn read_bytes(&self, address: u64, length: usize) -> Result<Vec<u8>, String> {
+        let n = word_bytes::<V>()? as u64;
+        (address..address + length as u64)
+            .map(|a| {
+                self.peek((a / n) as usize)
+                    .map(|word| word.as_u8s()[(a % n) as usize])
+                    .ok_or_else(|| format!("Backdoor read of {a:#x} is beyond the memory"))
+            })
+            .collect()
+    }
+}
+
+//ip Backdoor for SparseMemory
+impl Backdoor for SparseMemory {
+    fn write_bytes(&mut self, address: u64, data: &[u8]) -> Result<(), String> {
+        for (i, byte) in data.iter().enumerate() {
+            let a = address + i as u64;
+            let shift = 8 * (a & 3);
+            let word = u32::try_from(a)
+                .ok()
+                .and_then(|a| self.peek(a).map(|w| (a, w)))
+                .map(|(a, w)| (a, (w & !(0xff << shift)) | ((*byte as u32) << shift)));
+            if !word.is_some_and(|(a, w)| self.poke(a, w)) {
+                return Err(format!("Backdoor write to {a:#x} is beyond the memory"));
+            }
+        }
+        Ok(())
+    }
+    fn read_bytes(&self, address: u64, length: usize) -> Result<Vec<u8>, String> {
+        (address..address + length as u64)
+            .map(|a| {
+                u32::try_from(a)
+                    .ok()
+                    .and_then(|a| self.peek(a))
+                    .map(|w| (w >> (8 * (a & 3))) as u8)
+                    .ok_or_else(|| format!("Backdoor read of {a:#x} is beyond the memory"))
+            })
+            .collect()
+    }
+}
+
+//a ProgramSegment, Program
+//tp ProgramSegment
+/// A contiguous run of bytes of a program
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProgramSegment {
+    /// The (physical) address of the first byte
+    pub address: u64,
+    /// The bytes
+    pub data: Vec<u8>,
+}
+
+//tp Program
+/// A program to be loaded into memory
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    /// The segments, in order of address
+    pub segments: Vec<ProgramSegment>,
+    /// The entry point, if the file provides one
+    pub entry: Option<u64>,
+    /// The symbol table (ELF only), mapping names to values
+    pub symbols: HashMap<String, u64>,
+}
+
+//ip Program
+impl Program {
+    //cp load_file
+    /// Load a program from a file, which may be ELF, Intel HEX or
+    /// S-record (detected from its contents)
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let data =
+            std::fs::read(path).map_err(|e| format!("Failed to read '{}': {e}", path.display()))?;
+        Self::parse(&data).map_err(|e| format!("'{}': {e}", path.display()))
+    }
+
+    //cp parse
+    /// Parse a program that may be ELF, Intel HEX or S-record
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.starts_with(b"\x7fELF") {
+            return Self::parse_elf(data);
+        }
+        let text = std::str::from_utf8(data).map_err(|_| "Program is not ELF or text")?;
+        match text.trim_start().as_bytes().first() {
+            Some(b':') => Self::parse_ihex(text),
+            Some(b'S') => Self::parse_srec(text),
+            _ => Err("Program is not ELF, Intel HEX or S-record".into()),
+        }
+    }
+
+    //cp parse_elf
+    /// Parse an ELF file, using the loadable segments of its program
+    /// headers (at their physical addresses) and its symbol table
+    pub fn parse_elf(data: &[u8]) -> Result<Self, String> {
+        let elf = Elf::new(data)?;
+        let mut program = Program {
+            entry: Some(elf.entry()?),
+            ..Default::default()
+        };
+        for ph in 0..elf.u16(elf.offset(0x2c, 0x38))? as u64 {
+            let (segment, load) = elf.program_header(ph)?;
+            if load {
+                program.add(segment.address, &segment.data);
+            }
+        }
+        program.symbols = elf.symbols()?;
+        Ok(program)
+    }
+
+    //cp parse_ihex
+    /// Parse an Intel HEX file
+    pub fn parse_ihex(text: &str) -> Result<Self, String> {
+        let mut program = Program::default();
+        let mut base = 0;
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |e: &str| format!("Intel HEX line {}: {e}", n + 1);
+            let Some(record) = line.strip_prefix(':') else {
+                return Err(error("missing ':'"));
+            };
+            let bytes = hex_bytes(record).map_err(|e| error(&e))?;
+            if bytes.len() < 5 || bytes.len() != 5 + bytes[0] as usize {
+                return Err(error("bad record length"));
+            }
+            if bytes.iter().fold(0_u8, |s, b| s.wrapping_add(*b)) != 0 {
+                return Err(error("bad checksum"));
+            }
+            let address = u16::from_be_bytes([bytes[1], bytes[2]]) as u64;
+            let data = &bytes[4..bytes.len() - 1];
+            let value = data.iter().fold(0_u64, |v, b| (v << 8) | *b as u64);
+            match bytes[3] {
+                0 => program.add(base + address, data),
+                1 => break,
+                2 => base = value << 4,
+                3 => program.entry = Some(((value >> 16) << 4) + (value & 0xffff)),
+                4 => base = value << 16,
+                5 => program.entry = Some(value),
+                t => return Err(error(&format!("unknown record type {t}"))),
+            }
+        }
+        Ok(program)
+    }
+
+    //cp parse_srec
+    /// Parse a Motorola S-record file
+    pub fn parse_srec(text: &str) -> Result<Self, String> {
+        let mut program = Program::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |e: &str| format!("S-record line {}: {e}", n + 1);
+            let (Some('S'), Some(t)) = (line.chars().next(), line.chars().nth(1)) else {
+                return Err(error("missing 'S' and record type"));
+            };
+            let address_bytes = match t {
+                '0' | '1' | '5' | '9' => 2,
+                '2' | '6' | '8' => 3,
+                '3' | '7' => 4,
+                _ => return Err(error(&format!("unknown record type S{t}"))),
+            };
+            // The record type is ASCII, so the record starts at byte 2
+            let bytes = hex_bytes(&line[2..]).map_err(|e| error(&e))?;
+            if bytes.is_empty() || bytes.len() != 1 + bytes[0] as usize {
+                return Err(error("bad record length"));
+            }
+            if bytes.iter().fold(0_u8, |s, b| s.wrapping_add(*b)) != 0xff {
+                return Err(error("bad checksum"));
+            }
+            if bytes.len() < 2 + address_bytes {
+                return Err(error("bad record length"));
+            }
+            let address = bytes[1..1 + address_bytes]
+                .iter()
+                .fold(0_u64, |v, b| (v << 8) | *b as u64);
+            let data = &bytes[1 + address_bytes..bytes.len() - 1];
+            match t {
+                '1' | '2' | '3' => program.add(address, data),
+                '7' | '8' | '9' => program.entry = Some(address),
+                _ => (),
+            }
+        }
+        Ok(program)
+    }
+
+    //mp add
+    /// Add bytes at an address, merging with a segment they follow
+    pub fn add(&mut self, address: u64, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        let i = self.segments.partition_point(|s| s.address <= address);
+        if i > 0 {
+            let s = &mut self.segments[i - 1];
+            if s.address + s.data.len() as u64 == address {
+                s.data.extend_from_slice(data);
+                return;
+            }
+        }
+        self.segments.insert(
+            i,
+            ProgramSegment {
+                address,
+                data: data.to_vec(),
+            },
+        );
+    }
+
+    //ap symbol
+    /// The value of a symbol, if the program has it
+    pub fn symbol(&self, name: &str) -> Option<u64> {
+        self.symbols.get(name).copied()
+    }
+
+    //mp write_to
+    /// Write the segments into a memory by backdoor, with the memory
+    /// at a base address (so a segment at `base` is written to the
+    /// start of the memory)
+    pub fn write_to<B: Backdoor + ?Sized>(&self, memory: &mut B, base: u64) -> Result<(), String> {
+        for s in &self.segments {
+            let Some(address) = s.address.checked_sub(base) else {
+                return Err(format!(
+                    "Segment at {:#x} is below the memory base {base:#x}",
+                    s.address
+                ));
+            };
+            memory.write_bytes(address, &s.data)?;
+        }
+        Ok(())
+    }
+}
+
+//fi hex_bytes
+/// Convert a string of hex digit pairs to bytes
+pub(crate) fn hex_bytes(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return Err("odd number of hex digits".into());
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&text[i..i + 2], 16)
+                .map_err(|_| format!("bad hex '{}'", &text[i..i + 2]))
+        })
+        .collect()
+}
+
+//a Elf
+//ti Elf
+/// An ELF file being parsed
+struct Elf<'a> {
+    data: &'a [u8],
+    is_64: bool,
+    big_endian: bool,
+}
+
+//ii Elf
+impl<'a> Elf<'a> {
+    /// The largest (zero filled) segment that is loaded
+    const MAX_SEGMENT_SIZE: u64 = 1 << 26;
+
+    //ci new
+    fn new(data: &'a [u8]) -> Result<Self, String> {
+        if data.len() < 0x34 || !data.starts_with(b"\x7fELF") {
+            return Err("Not an ELF file".into());
+        }
+        let is_64 = match data[4] {
+            1 => false,
+            2 => true,
+            c => return Err(format!("Unknown ELF class {c}")),
+        };
+        let big_endian = match data[5] {
+            1 => false,
+            2 => true,
+            e => return Err(format!("Unknown ELF data encoding {e}")),
+        };
+        Ok(Self {
+            data,
+            is_64,
+            big_endian,
+        })
+    }
+
+    //mi bytes
+    fn bytes<const N: usize>(&self, offset: u64) -> Result<[u8; N], String> {
+        let start = offset as usize;
+        start
+            .checked_add(N)
+            .and_then(|end| self.data.get(start..end))
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| format!("ELF file truncated at {offset:#x}"))
+    }
+
+    //mi u16
+    fn u16(&self, offset: u64) -> Result<u16, String> {
+        let b = self.bytes(offset)?;
+        Ok(if self.big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    }
+
+    //mi u32
+    fn u32(&self, offset: u64) -> Result<u32, String> {
+        let b = self.bytes(offset)?;
+        Ok(if self.big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    }
+
+    //mi word
+    /// Read an address-sized value
+    fn word(&self, offset: u64) -> Result<u64, String> {
+        if self.is_64 {
+            let b = self.bytes(offset)?;
+            Ok(if self.big_endian {
+                u64::from_be_bytes(b)
+            } else {
+                u64::from_le_bytes(b)
+            })
+        } else {
+            self.u32(offset).map(|v| v as u64)
+        }
+    }
+
+    //mi offset
+    /// Select the offset of a field for the class
+    fn offset(&self, offset_32: u64, offset_64: u64) -> u64 {
+        if self.is_64 {
+            offset_64
+        } else {
+            offset_32
+        }
+    }
+
+    //mi at
+    /// The offset of a field of entry n of a table, if it does not
+    /// overflow
+    fn at(&self, table: u64, n: u64, entsize: u64, field: u64) -> Result<u64, String> {
+        n.checked_mul(entsize)
+            .and_then(|o| o.checked_add(table))
+            .and_then(|o| o.checked_add(field))
+            .ok_or_else(|| format!("ELF file truncated at entry {n} of table at {table:#x}"))
+    }
+
+    //mi slice
+    fn slice(&self, offset: u64, size: u64) -> Result<&'a [u8], String> {
+        let start = offset as usize;
+        start
+            .checked_add(size as usize)
+            .and_then(|end| self.data.get(start..end))
+            .ok_or_else(|| format!("ELF file truncated at {offset:#x}"))
+    }
+
+    //mi entry
+    fn entry(&self) -> Result<u64, String> {
+        self.word(0x18)
+    }
+
+    //mi program_header
+    /// Get a program header as a segment (with the bytes in memory,
+    /// zero filled) and whether it is loadable
+    fn program_header(&self, n: u64) -> Result<(ProgramSegment, bool), String> {
+        let phoff = self.word(self.offset(0x1c, 0x20))?;
+        let phentsize = self.u16(self.offset(0x2a, 0x36))? as u64;
+        let ph = |field| self.at(phoff, n, phentsize, field);
+        let p_type = self.u32(ph(0)?)?;
+        let (offset, paddr, filesz, memsz) = if self.is_64 {
+            (
+                self.word(ph(0x08)?)?,
+                self.word(ph(0x18)?)?,
+                self.word(ph(0x20)?)?,
+                self.word(ph(0x28)?)?,
+            )
+        } else {
+            (
+                self.word(ph(0x04)?)?,
+                self.word(ph(0x0c)?)?,
+                self.word(ph(0x10)?)?,
+                self.word(ph(0x14)?)?,
+            )
+        };
+        if p_type != 1 {
+            return Ok((ProgramSegment::default(), false));
+        }
+        if filesz > memsz {
+            return Err(format!(
+                "ELF program header {n} has file size above memory size"
+            ));
+        }
+        if memsz > Self::MAX_SEGMENT_SIZE {
+            return Err(format!(
+                "ELF program header {n} has memory size {memsz:#x} above the limit of {:#x}",
+                Self::MAX_SEGMENT_SIZE
+            ));
+        }
+        let mut data = self.slice(offset, filesz)?.to_vec();
+        data.resize(memsz as usize, 0);
+        Ok((
+            ProgramSegment {
+                address: paddr,
+                data,
+            },
+            true,
+        ))
+    }
+
+    //mi section
+    /// Get the type, offset, size and link of a section header
+    fn section(&self, n: u64) -> Result<(u32, u64, u64, u32), String> {
+        let shoff = self.word(self.offset(0x20, 0x28))?;
+        let shentsize = self.u16(self.offset(0x2e, 0x3a))? as u64;
+        let sh = |field| self.at(shoff, n, shentsize, field);
+        Ok((
+            self.u32(sh(4)?)?,
+            self.word(sh(self.offset(0x10, 0x18))?)?,
+            self.word(sh(self.offset(0x14, 0x20))?)?,
+            self.u32(sh(self.offset(0x18, 0x28))?)?,
+        ))
+    }
+
+    //mi symbols
+    /// Get the defined symbols of the symbol tables, other than those
+    /// for sections and files
+    fn symbols(&self) -> Result<HashMap<String, u64>, String> {
+        let mut symbols = HashMap::new();
+        let shnum = self.u16(self.offset(0x30, 0x3c))? as u64;
+        for n in 0..shnum {
+            let (sh_type, offset, size, link) = self.section(n)?;
+            if sh_type != 2 {
+                continue;
+            }
+            let (_, str_offset, str_size, _) = self.section(link as u64)?;
+            let strings = self.slice(str_offset, str_size)?;
+            let entsize = self.offset(16, 24);
+            // The table must lie within the file, so the fields of its
+            // entries do not overflow
+            self.slice(offset, size)?;
+            for sym in (offset..offset + size).step_by(entsize as usize) {
+                let name = self.u32(sym)? as usize;
+                let (info, shndx, value) = if self.is_64 {
+                    (
+                        self.bytes::<1>(sym + 4)?[0],
+                        self.u16(sym + 6)?,
+                        self.word(sym + 8)?,
+                    )
+                } else {
+                    (
+                        self.bytes::<1>(sym + 12)?[0],
+                        self.u16(sym + 14)?,
+                        self.word(sym + 4)?,
+                    )
+                };
+                if shndx == 0 || matches!(info & 0xf, 3 | 4) {
+                    continue;
+                }
+                let Some(name) = strings
+                    .get(name..)
+                    .and_then(|s| s.split(|b| *b == 0).next())
+                else {
+                    return Err(format!("ELF symbol name offset {name:#x} out of range"));
+                };
+                if !name.is_empty() {
+                    symbols.insert(String::from_utf8_lossy(name).into_owned(), value);
+                }
+            }
+        }
+        Ok(symbols)
+    }
+}
@@ -145,6 +145,49 @@ where
             ..Default::default()
         }
     }
+
+    //ap size
+    /// The number of words in the memory
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    //mp peek
+    /// Read a word of the memory through the backdoor, without
+    /// clocking; words never written are default values
+    pub fn peek(&self, address: usize) -> Option<V> {
+        if address >= self.size {
+            None
+        } else {
+            Some(self.data.get(address).copied().unwrap_or_default())
+        }
+    }
+
+    //mp poke
+    /// Write a word of the memory through the backdoor, without
+    /// clocking
+    pub fn poke(&mut self, address: usize, value: V) -> Result<(), String> {
+        self.load(address, &[value])
+    }
+
+    //mp load
+    /// Write consecutive words of the memory through the backdoor,
+    /// starting at an address
+    pub fn load(&mut self, address: usize, values: &[V]) -> Result<(), String> {
+        let end = address + values.len();
+        if end > self.size {
+            return Err(format!(
+                "Backdoor write of {} words at {address:#x} exceeds the memory size {:#x}",
+                values.len(),
+                self.size
+            ));
+        }
+        if end > self.data.len() {
+            self.data.resize(end, V::default());
+        }
+        self.data[address..end].copy_from_slice(values);
+        Ok(())
+    }
 }
 
 impl<V, I> ComponentBuilder for Memory<V, I>
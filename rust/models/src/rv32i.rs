@@ -0,0 +1,927 @@
+//a Documentation
+//! An RV32I processor core
+//!
+//! The [Rv32i] is a simple multi-cycle implementation of the RV32I
+//! base integer instruction set; each instruction is fetched and then
+//! executed, taking two cycles for most instructions (plus the time
+//! for any data access).
+//!
+//! It has three bus ports:
+//!
+//! * the instruction port (`imem_*`), which is compatible with the
+//!   ports of a [crate::Memory] of `Bv<32>` (a word-addressed memory
+//!   that provides read data one cycle after a read)
+//!
+//! * the data port (`dmem_*`), also compatible with a [crate::Memory],
+//!   used for data accesses to addresses below the configured APB
+//!   base; byte and half-word stores are performed as a read of the
+//!   word followed by a write
+//!
+//! * the APB port (`apb_request`, `apb_response`), used for data
+//!   accesses to addresses at and above the APB base; paddr is the word
+//!   index of the access relative to the APB base (as the APB targets
+//!   here use the low bits of paddr as a register index). Byte and
+//!   half-word stores drive the data shifted to its position in the
+//!   word, as APB has no byte strobes. As for an [crate::ApbMaster],
+//!   the APB port is stepped after each clock edge (so that it sees
+//!   the response of the target to the access phase in that edge),
+//!   and hence the port must be connected with [Rv32i::connect_apb]
+//!
+//! The memory ports are connected to memories with
+//! [Rv32i::connect_memory].
+//!
+//! The core has no privileged architecture: an ECALL, EBREAK, illegal
+//! instruction, misaligned access or APB error halts the core with an
+//! [Rv32iTrap], and the `halted` output is asserted. FENCE is executed
+//! as a no-op.
+//!
+//! The program counter and the registers are exposed as internal
+//! state (`pc` and `x0` to `x31`), and if tracing is enabled the
+//! retired instructions are recorded as [Rv32iRetired] for the
+//! testbench to take.
+
+//a Imports
+use hgl_sim::prelude::component::*;
+
+use crate::apb_monitor::find_typed;
+use crate::{t_apb_request, t_apb_response};
+
+//a Rv32iConfig, Rv32iTrap, Rv32iRetired
+//tp Rv32iConfig
+/// The configuration of an [Rv32i]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rv32iConfig {
+    /// The program counter after reset
+    pub reset_pc: u32,
+    /// The first address of the APB port; data accesses below this
+    /// use the data memory port
+    pub apb_base: u32,
+    /// Record the retired instructions
+    pub trace: bool,
+}
+
+//ip Default for Rv32iConfig
+impl Default for Rv32iConfig {
+    fn default() -> Self {
+        Self {
+            reset_pc: 0,
+            apb_base: 0x8000_0000,
+            trace: false,
+        }
+    }
+}
+
+//tp Rv32iTrap
+/// The reason an [Rv32i] halted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rv32iTrap {
+    /// An instruction that is not RV32I
+    IllegalInstruction { pc: u32, instruction: u32 },
+    /// A jump or branch to an address that is not word aligned
+    MisalignedFetch { pc: u32, target: u32 },
+    /// A load or store that is not naturally aligned
+    MisalignedAccess { pc: u32, address: u32 },
+    /// An APB access that completed with an error
+    AccessFault { pc: u32, address: u32 },
+    /// An ECALL instruction
+    Ecall { pc: u32 },
+    /// An EBREAK instruction
+    Ebreak { pc: u32 },
+}
+
+//tp Rv32iRetired
+/// A record of a retired instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rv32iRetired {
+    /// The cycle in which the instruction retired
+    pub cycle: usize,
+    /// The address of the instruction
+    pub pc: u32,
+    /// The instruction
+    pub instruction: u32,
+    /// The register written by the instruction, and its new value
+    pub write: Option<(usize, u32)>,
+}
+
+//ip Display for Rv32iRetired
+impl std::fmt::Display for Rv32iRetired {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{:8} {:08x} {:08x} {:24}",
+            self.cycle,
+            self.pc,
+            self.instruction,
+            disassemble(self.instruction)
+        )?;
+        if let Some((rd, value)) = self.write {
+            write!(f, " x{rd}={value:08x}")?;
+        }
+        Ok(())
+    }
+}
+
+//tp Rv32iPort
+/// A memory port of an [Rv32i]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rv32iPort {
+    Instruction,
+    Data,
+}
+
+//a Instruction decode
+//fi field
+/// Extract a field of an instruction
+fn field(instruction: u32, lsb: usize, width: usize) -> u32 {
+    (instruction >> lsb) & ((1 << width) - 1)
+}
+
+//fi imm_i
+fn imm_i(i: u32) -> u32 {
+    ((i as i32) >> 20) as u32
+}
+
+//fi imm_s
+fn imm_s(i: u32) -> u32 {
+    (((i as i32) >> 25) << 5) as u32 | field(i, 7, 5)
+}
+
+//fi imm_b
+fn imm_b(i: u32) -> u32 {
+    (((i as i32) >> 31) << 12) as u32
+        | field(i, 7, 1) << 11
+        | field(i, 25, 6) << 5
+        | field(i, 8, 4) << 1
+}
+
+//fi imm_u
+fn imm_u(i: u32) -> u32 {
+    i & 0xffff_f000
+}
+
+//fi imm_j
+fn imm_j(i: u32) -> u32 {
+    (((i as i32) >> 31) << 20) as u32
+        | (i & 0x000f_f000)
+        | field(i, 20, 1) << 11
+        | field(i, 21, 10) << 1
+}
+
+//fi alu
+/// Perform an ALU operation, for OP (with funct7 bit 5 selecting SUB
+/// and SRA) and OP-IMM (with it selecting only SRAI)
+fn alu(funct3: u32, alt: bool, a: u32, b: u32) -> u32 {
+    match funct3 {
+        0 if alt => a.wrapping_sub(b),
+        0 => a.wrapping_add(b),
+        1 => a << (b & 31),
+        2 => ((a as i32) < (b as i32)) as u32,
+        3 => (a < b) as u32,
+        4 => a ^ b,
+        5 if alt => ((a as i32) >> (b & 31)) as u32,
+        5 => a >> (b & 31),
+        6 => a | b,
+        _ => a & b,
+    }
+}
+
+//fp disassemble
+/// Disassemble an RV32I instruction
+pub fn disassemble(i: u32) -> String {
+    let rd = field(i, 7, 5);
+    let rs1 = field(i, 15, 5);
+    let rs2 = field(i, 20, 5);
+    let funct3 = field(i, 12, 3);
+    let alt = field(i, 30, 1) != 0;
+    match field(i, 0, 7) {
+        0x37 => format!("lui x{rd}, 0x{:x}", i >> 12),
+        0x17 => format!("auipc x{rd}, 0x{:x}", i >> 12),
+        0x6f => format!("jal x{rd}, {}", imm_j(i) as i32),
+        0x67 => format!("jalr x{rd}, {}(x{rs1})", imm_i(i) as i32),
+        0x63 => {
+            let op = ["beq", "bne", "?", "?", "blt", "bge", "bltu", "bgeu"][funct3 as usize];
+            format!("{op} x{rs1}, x{rs2}, {}", imm_b(i) as i32)
+        }
+        0x03 => {
+            let op = ["lb", "lh", "lw", "?", "lbu", "lhu", "?", "?"][funct3 as usize];
+            format!("{op} x{rd}, {}(x{rs1})", imm_i(i) as i32)
+        }
+        0x23 => {
+            let op = ["sb", "sh", "sw", "?", "?", "?", "?", "?"][funct3 as usize];
+            format!("{op} x{rs2}, {}(x{rs1})", imm_s(i) as i32)
+        }
+        0x13 => {
+            let op = match funct3 {
+                5 if alt => "srai",
+                _ => [
+                    "addi", "slli", "slti", "sltiu", "xori", "srli", "ori", "andi",
+                ][funct3 as usize],
+            };
+            let imm = match funct3 {
+                1 | 5 => rs2 as i32,
+                _ => imm_i(i) as i32,
+            };
+            format!("{op} x{rd}, x{rs1}, {imm}")
+        }
+        0x33 => {
+            let op = match funct3 {
+                0 if alt => "sub",
+                5 if alt => "sra",
+                _ => ["add", "sll", "slt", "sltu", "xor", "srl", "or", "and"][funct3 as usize],
+            };
+            format!("{op} x{rd}, x{rs1}, x{rs2}")
+        }
+        0x0f => "fence".into(),
+        0x73 if i == 0x0000_0073 => "ecall".into(),
+        0x73 if i == 0x0010_0073 => "ebreak".into(),
+        _ => format!(".word 0x{i:08x}"),
+    }
+}
+
+//a Rv32i
+//ci REGISTER_NAMES
+const REGISTER_NAMES: [&str; 32] = [
+    "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13", "x14",
+    "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27",
+    "x28", "x29", "x30", "x31",
+];
+
+//ci STATE_INFO
+/// The state other than the registers, which follow it
+const STATE_INFO: &[SimStateInfo] = &[
+    SimStateInfo::clk("clk", 0),
+    SimStateInfo::input("imem_read_valid", 0),
+    SimStateInfo::input("imem_read_data", 1),
+    SimStateInfo::input("dmem_read_valid", 2),
+    SimStateInfo::input("dmem_read_data", 3),
+    SimStateInfo::input("apb_response", 4),
+    SimStateInfo::output("imem_read_enable", 0),
+    SimStateInfo::output("imem_address", 1),
+    SimStateInfo::output("dmem_read_enable", 2),
+    SimStateInfo::output("dmem_write_enable", 3),
+    SimStateInfo::output("dmem_address", 4),
+    SimStateInfo::output("dmem_write_data", 5),
+    SimStateInfo::output("apb_request", 6),
+    SimStateInfo::output("halted", 7),
+    SimStateInfo::internal("pc", 0),
+];
+
+//tp Rv32iInputs
+#[derive(Debug, Default)]
+pub struct Rv32iInputs {
+    pub imem_read_valid: Bit,
+    pub imem_read_data: Bv<32>,
+    pub dmem_read_valid: Bit,
+    pub dmem_read_data: Bv<32>,
+    pub apb_response: t_apb_response,
+}
+
+//tp Rv32iOutputs
+#[derive(Debug, Default)]
+pub struct Rv32iOutputs {
+    pub imem_read_enable: Bit,
+    pub imem_address: Bv<32>,
+    pub dmem_read_enable: Bit,
+    pub dmem_write_enable: Bit,
+    pub dmem_address: Bv<32>,
+    pub dmem_write_data: Bv<32>,
+    pub apb_request: t_apb_request,
+    pub halted: Bit,
+}
+
+//ti Access
+/// A data access in progress
+#[derive(Debug, Clone, Copy)]
+struct Access {
+    /// Byte address of the access
+    address: u32,
+    /// funct3 of the load or store, giving its size
+    funct3: u32,
+    /// Destination register of a load
+    rd: usize,
+    /// Data of a store
+    store: Option<u32>,
+}
+
+//ti Phase
+#[derive(Debug, Default, Clone, Copy)]
+enum Phase {
+    /// The next instruction is to be fetched
+    #[default]
+    Fetch,
+    /// Awaiting the instruction from the instruction port
+    FetchWait,
+    /// Awaiting read data from the data port
+    DataWait(Access),
+    /// An APB access is to be started
+    ApbStart(Access),
+    /// Driving the setup phase of an APB access
+    ApbSetup(Access),
+    /// Driving the access phase of an APB access
+    ApbAccess(Access),
+    /// Halted on a trap
+    Halted,
+}
+
+//tp Rv32i
+/// An RV32I processor core
+#[derive(Debug, Default)]
+pub struct Rv32i {
+    pub inputs: Rv32iInputs,
+    pub outputs: Rv32iOutputs,
+    config: Rv32iConfig,
+    pc: Bv<32>,
+    regs: [Bv<32>; 32],
+    phase: Phase,
+    /// The instruction being executed
+    instruction: u32,
+    cycle: usize,
+    retired: usize,
+    trap: Option<Rv32iTrap>,
+    trace: Vec<Rv32iRetired>,
+}
+
+//ip Rv32i
+impl Rv32i {
+    //ap config
+    /// The configuration of the core
+    pub fn config(&self) -> &Rv32iConfig {
+        &self.config
+    }
+
+    //ap pc
+    /// The program counter
+    pub fn pc(&self) -> u32 {
+        self.pc.try_as_u64().unwrap() as u32
+    }
+
+    //ap reg
+    /// The value of a register
+    pub fn reg(&self, r: usize) -> u32 {
+        match r {
+            0 => 0,
+            _ => self.regs[r].try_as_u64().unwrap() as u32,
+        }
+    }
+
+    //mp set_reg
+    /// Set the value of a register; writes to x0 are ignored
+    pub fn set_reg(&mut self, r: usize, value: u32) {
+        if r != 0 {
+            self.regs[r] = (value as u64).into();
+        }
+    }
+
+    //mp set_pc
+    /// Set the program counter
+    ///
+    /// This is for use at an instruction boundary (after reset, or
+    /// after the clock edge at which an instruction retires), when the
+    /// fetch of the next instruction has been requested but not yet
+    /// sampled by the memory
+    pub fn set_pc(&mut self, pc: u32) {
+        self.pc = (pc as u64).into();
+        if matches!(self.phase, Phase::FetchWait) {
+            self.outputs.imem_address = ((pc >> 2) as u64).into();
+        }
+    }
+
+    //ap is_halted
+    /// Return true if the core has halted
+    pub fn is_halted(&self) -> bool {
+        self.trap.is_some()
+    }
+
+    //ap trap
+    /// The trap that halted the core, if it has halted
+    pub fn trap(&self) -> Option<Rv32iTrap> {
+        self.trap
+    }
+
+    //ap retired
+    /// The number of instructions retired since reset
+    pub fn retired(&self) -> usize {
+        self.retired
+    }
+
+    //mp take_trace
+    /// Take the retired instructions recorded since the last call
+    pub fn take_trace(&mut self) -> Vec<Rv32iRetired> {
+        std::mem::take(&mut self.trace)
+    }
+
+    //mi issue_fetch
+    /// Request the instruction at the program counter
+    fn issue_fetch(&mut self) {
+        self.outputs.imem_read_enable = true.into();
+        self.outputs.imem_address = ((self.pc() >> 2) as u64).into();
+        self.phase = Phase::FetchWait;
+    }
+
+    //mi halt
+    fn halt(&mut self, trap: Rv32iTrap) {
+        self.trap = Some(trap);
+        self.outputs.halted = true.into();
+        self.phase = Phase::Halted;
+    }
+
+    //mi retire
+    /// Retire the current instruction, writing back a register, and
+    /// fetch the next instruction
+    ///
+    /// A misaligned next program counter traps before the instruction
+    /// updates any state, so it is not retired
+    fn retire(&mut self, write: Option<(usize, u32)>, next_pc: u32) {
+        if next_pc & 3 != 0 {
+            let pc = self.pc();
+            self.halt(Rv32iTrap::MisalignedFetch {
+                pc,
+                target: next_pc,
+            });
+            return;
+        }
+        let write = write.filter(|(rd, _)| *rd != 0);
+        if let Some((rd, value)) = write {
+            self.set_reg(rd, value);
+        }
+        if self.config.trace {
+            self.trace.push(Rv32iRetired {
+                cycle: self.cycle,
+                pc: self.pc(),
+                instruction: self.instruction,
+                write,
+            });
+        }
+        self.retired += 1;
+        self.pc = (next_pc as u64).into();
+        self.issue_fetch();
+    }
+
+    //mi execute
+    /// Execute a fetched instruction
+    fn execute(&mut self, i: u32) {
+        self.instruction = i;
+        let pc = self.pc();
+        let next_pc = pc.wrapping_add(4);
+        let rd = field(i, 7, 5) as usize;
+        let rs1 = self.reg(field(i, 15, 5) as usize);
+        let rs2 = self.reg(field(i, 20, 5) as usize);
+        let funct3 = field(i, 12, 3);
+        let funct7 = field(i, 25, 7);
+        let illegal = Rv32iTrap::IllegalInstruction { pc, instruction: i };
+        match field(i, 0, 7) {
+            0x37 => self.retire(Some((rd, imm_u(i))), next_pc),
+            0x17 => self.retire(Some((rd, pc.wrapping_add(imm_u(i)))), next_pc),
+            0x6f => self.retire(Some((rd, next_pc)), pc.wrapping_add(imm_j(i))),
+            0x67 if funct3 == 0 => {
+                let target = rs1.wrapping_add(imm_i(i)) & !1;
+                self.retire(Some((rd, next_pc)), target);
+            }
+            0x63 => {
+                let taken = match funct3 {
+                    0 => rs1 == rs2,
+                    1 => rs1 != rs2,
+                    4 => (rs1 as i32) < (rs2 as i32),
+                    5 => (rs1 as i32) >= (rs2 as i32),
+                    6 => rs1 < rs2,
+                    7 => rs1 >= rs2,
+                    _ => return self.halt(illegal),
+                };
+                let target = if taken {
+                    pc.wrapping_add(imm_b(i))
+                } else {
+                    next_pc
+                };
+                self.retire(None, target);
+            }
+            0x03 if matches!(funct3, 0 | 1 | 2 | 4 | 5) => {
+                let address = rs1.wrapping_add(imm_i(i));
+                self.start_access(Access {
+                    address,
+                    funct3,
+                    rd,
+                    store: None,
+                });
+            }
+            0x23 if funct3 <= 2 => {
+                let address = rs1.wrapping_add(imm_s(i));
+                self.start_access(Access {
+                    address,
+                    funct3,
+                    rd: 0,
+                    store: Some(rs2),
+                });
+            }
+            0x13 => {
+                let shift = funct3 == 1 || funct3 == 5;
+                if shift && (funct7 & !0x20 != 0 || (funct3 == 1 && funct7 != 0)) {
+                    return self.halt(illegal);
+                }
+                let alt = funct3 == 5 && funct7 == 0x20;
+                let value = alu(funct3, alt, rs1, imm_i(i));
+                self.retire(Some((rd, value)), next_pc);
+            }
+            0x33 => {
+                let alt = funct7 == 0x20;
+                if (funct7 & !0x20 != 0) || (alt && funct3 != 0 && funct3 != 5) {
+                    return self.halt(illegal);
+                }
+                self.retire(Some((rd, alu(funct3, alt, rs1, rs2))), next_pc);
+            }
+            0x0f => self.retire(None, next_pc),
+            0x73 if i == 0x0000_0073 => self.halt(Rv32iTrap::Ecall { pc }),
+            0x73 if i == 0x0010_0073 => self.halt(Rv32iTrap::Ebreak { pc }),
+            _ => self.halt(illegal),
+        }
+    }
+
+    //mi start_access
+    /// Start a data access for a load or store
+    fn start_access(&mut self, access: Access) {
+        let size = 1 << (access.funct3 & 3);
+        if access.address & (size - 1) != 0 {
+            let pc = self.pc();
+            return self.halt(Rv32iTrap::MisalignedAccess {
+                pc,
+                address: access.address,
+            });
+        }
+        if access.address >= self.config.apb_base {
+            self.phase = Phase::ApbStart(access);
+            return;
+        }
+        self.outputs.dmem_address = ((access.address >> 2) as u64).into();
+        match access.store {
+            Some(data) if size == 4 => {
+                self.outputs.dmem_write_enable = true.into();
+                self.outputs.dmem_write_data = (data as u64).into();
+                let next_pc = self.pc().wrapping_add(4);
+                self.retire(None, next_pc);
+            }
+            _ => {
+                self.outputs.dmem_read_enable = true.into();
+                self.phase = Phase::DataWait(access);
+            }
+        }
+    }
+
+    //mi complete_access
+    /// Complete a data access given the word read (or the word
+    /// containing the data to be replaced for a byte or half-word
+    /// store to memory)
+    fn complete_access(&mut self, access: Access, word: u32) {
+        let next_pc = self.pc().wrapping_add(4);
+        let shift = 8 * (access.address & 3);
+        let mask = match access.funct3 & 3 {
+            0 => 0xff,
+            1 => 0xffff,
+            _ => 0xffff_ffff,
+        };
+        if let Some(data) = access.store {
+            let word = (word & !(mask << shift)) | ((data & mask) << shift);
+            self.outputs.dmem_write_enable = true.into();
+            self.outputs.dmem_write_data = (word as u64).into();
+            return self.retire(None, next_pc);
+        }
+        let value = (word >> shift) & mask;
+        let value = match access.funct3 {
+            0 => value as u8 as i8 as u32,
+            1 => value as u16 as i16 as u32,
+            _ => value,
+        };
+        self.retire(Some((access.rd, value)), next_pc);
+    }
+
+    //mp step
+    /// Step the core by one clock cycle
+    fn step(&mut self) {
+        self.cycle += 1;
+        self.outputs.imem_read_enable = false.into();
+        self.outputs.dmem_read_enable = false.into();
+        self.outputs.dmem_write_enable = false.into();
+        match self.phase {
+            Phase::Fetch => self.issue_fetch(),
+            Phase::FetchWait if self.inputs.imem_read_valid.is_true() => {
+                let instruction = self.inputs.imem_read_data.try_as_u64().unwrap() as u32;
+                self.execute(instruction);
+            }
+            Phase::DataWait(access) if self.inputs.dmem_read_valid.is_true() => {
+                let word = self.inputs.dmem_read_data.try_as_u64().unwrap() as u32;
+                self.complete_access(access, word);
+            }
+            _ => (),
+        }
+    }
+
+    //mp apb_step
+    /// Step the APB port after a clock edge
+    ///
+    /// The response input must be the response of the target to the
+    /// request output during the cycle just completed
+    fn apb_step(&mut self) {
+        match self.phase {
+            Phase::ApbStart(access) => {
+                let offset = access.address - self.config.apb_base;
+                let (write, data) = match access.store {
+                    Some(data) => (true, data << (8 * (access.address & 3))),
+                    None => (false, 0),
+                };
+                self.outputs.apb_request = t_apb_request {
+                    paddr: ((offset >> 2) as u64).into(),
+                    penable: false.into(),
+                    psel: true.into(),
+                    pwrite: write.into(),
+                    pwdata: (data as u64).into(),
+                };
+                self.phase = Phase::ApbSetup(access);
+            }
+            Phase::ApbSetup(access) => {
+                self.outputs.apb_request.penable = true.into();
+                self.phase = Phase::ApbAccess(access);
+            }
+            Phase::ApbAccess(access) => {
+                let response = self.inputs.apb_response;
+                if response.pready.is_false() {
+                    return;
+                }
+                self.outputs.apb_request = t_apb_request::default();
+                if response.perr.is_true() {
+                    let pc = self.pc();
+                    return self.halt(Rv32iTrap::AccessFault {
+                        pc,
+                        address: access.address,
+                    });
+                }
+                let next_pc = self.pc().wrapping_add(4);
+                if access.store.is_some() {
+                    self.retire(None, next_pc);
+                } else {
+                    let word = response.prdata.try_as_u64().unwrap_or(0) as u32;
+                    self.complete_access(access, word);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    //fp connect_memory
+    /// Connect a port of an instance of the core to a memory instance
+    /// (such as "imem"), which must have the states of a
+    /// [crate::Memory] with 32-bit data
+    ///
+    /// This can only be used after prepare_simulation
+    pub fn connect_memory(
+        sim: &Simulation,
+        cpu: InstanceHandle,
+        port: Rv32iPort,
+        memory: &str,
+    ) -> Result<(), String> {
+        let is_cpu = sim
+            .instances()
+            .instance(cpu)
+            .borrow_sim()
+            .is_some_and(|s| s.as_any().is::<Rv32i>());
+        if !is_cpu {
+            return Err("Instance to connect is not an Rv32i".into());
+        }
+        let find = |name: &str| {
+            let path = format!("{memory}.{name}");
+            match sim.find_value(&path) {
+                Some((h, s, e)) if e.is_empty() => Ok((h, s)),
+                _ => Err(format!("Failed to find '{path}' in the simulation")),
+            }
+        };
+        let memory_states = [
+            find("read_enable")?,
+            find("write_enable")?,
+            find("address")?,
+            find("write_data")?,
+            find("read_valid")?,
+            find("read_data")?,
+        ];
+        sim.add_combinational(move |sim| {
+            let instances = sim.instances();
+            let requests = {
+                let c = instances.inst::<Rv32i>(cpu);
+                let o = &c.outputs;
+                match port {
+                    Rv32iPort::Instruction => [
+                        o.imem_read_enable.is_true() as u64,
+                        0,
+                        o.imem_address.try_as_u64().unwrap(),
+                        0,
+                    ],
+                    Rv32iPort::Data => [
+                        o.dmem_read_enable.is_true() as u64,
+                        o.dmem_write_enable.is_true() as u64,
+                        o.dmem_address.try_as_u64().unwrap(),
+                        o.dmem_write_data.try_as_u64().unwrap(),
+                    ],
+                }
+            };
+            for ((h, s), value) in memory_states.iter().zip(requests) {
+                sim.with_state_mut(*h, *s, |mut v| v.set_u64(value));
+            }
+            let [read_valid, read_data] = [memory_states[4], memory_states[5]]
+                .map(|(h, s)| sim.with_state(h, s, |v| v.get_u64()).flatten().unwrap_or(0));
+            let mut c = instances.inst_mut::<Rv32i>(cpu);
+            let i = &mut c.inputs;
+            match port {
+                Rv32iPort::Instruction => {
+                    i.imem_read_valid = (read_valid != 0).into();
+                    i.imem_read_data = read_data.into();
+                }
+                Rv32iPort::Data => {
+                    i.dmem_read_valid = (read_valid != 0).into();
+                    i.dmem_read_data = read_data.into();
+                }
+            }
+        });
+        Ok(())
+    }
+
+    //fp connect_apb
+    /// Connect the APB port of an instance of the core to the request
+    /// and response of a target (or decoder), given by path (such as
+    /// "bus.apb_request" and "bus.apb_response"), stepping the port
+    /// after every posedge of the clock of the core
+    ///
+    /// The target request must be the whole of a state of the target.
+    /// This can only be used after prepare_simulation
+    pub fn connect_apb(
+        sim: &Simulation,
+        clock: ClockIndex,
+        cpu: InstanceHandle,
+        request: &str,
+        response: &str,
+    ) -> Result<(), String> {
+        let (req_h, req_s, req_e) = find_typed::<t_apb_request>(sim, request)?;
+        if !req_e.is_empty() {
+            return Err(format!(
+                "APB request '{request}' must be a state of the target, not part of one"
+            ));
+        }
+        let (resp_h, resp_s, resp_e) = find_typed::<t_apb_response>(sim, response)?;
+        let is_cpu = sim
+            .instances()
+            .instance(cpu)
+            .borrow_sim()
+            .is_some_and(|s| s.as_any().is::<Rv32i>());
+        if !is_cpu {
+            return Err("Instance to connect is not an Rv32i".into());
+        }
+        sim.add_driver(clock, move |sim| {
+            let response = sim
+                .with_value(resp_h, resp_s, &resp_e, |v| {
+                    v.as_any().downcast_ref::<t_apb_response>().copied()
+                })
+                .flatten()
+                .unwrap_or_default();
+            let instances = sim.instances();
+            let request = {
+                let mut c = instances.inst_mut::<Rv32i>(cpu);
+                c.inputs.apb_response = response;
+                c.apb_step();
+                c.outputs.apb_request
+            };
+            sim.with_state_mut(req_h, req_s, |mut v| {
+                v.try_copy_from(&SimValueRef::of(&request))
+            });
+        });
+        Ok(())
+    }
+}
+
+//ip Simulatable for Rv32i
+impl Simulatable for Rv32i {
+    //mp as_any
+    /// Return a reference as an Any so it can be downcast
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    //mp as_mut_any
+    /// Return a mutable reference as an Any so it can be downcast
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    //mp reset
+    /// Reset the core to fetch from the reset program counter, with
+    /// the registers zero
+    fn reset(&mut self, _reason: SimReset) {
+        self.pc = (self.config.reset_pc as u64).into();
+        self.regs = Default::default();
+        self.phase = Phase::Fetch;
+        self.cycle = 0;
+        self.retired = 0;
+        self.trap = None;
+        self.trace.clear();
+        self.outputs = Rv32iOutputs::default();
+    }
+
+    //mp clock
+    /// Clock the core on the posedge of its clock
+    fn clock(&mut self, _mask: SimEdgeMask) {
+        self.step();
+    }
+
+    fn propagate(&mut self, _stage: usize) {}
+    fn state_info(&self, index: SimStateIndex) -> Option<SimStateInfo> {
+        let index = index.as_usize();
+        STATE_INFO.get(index).copied().or_else(|| {
+            let r = index - STATE_INFO.len();
+            REGISTER_NAMES
+                .get(r)
+                .map(|name| SimStateInfo::internal(name, r + 1))
+        })
+    }
+    fn try_state_data(&self, index: SimStateIndex) -> Option<SimValueRef> {
+        match index.as_usize() {
+            1 => Some(SimValueRef::of(&self.inputs.imem_read_valid)),
+            2 => Some(SimValueRef::of(&self.inputs.imem_read_data)),
+            3 => Some(SimValueRef::of(&self.inputs.dmem_read_valid)),
+            4 => Some(SimValueRef::of(&self.inputs.dmem_read_data)),
+            5 => Some(SimValueRef::of(&self.inputs.apb_response)),
+            6 => Some(SimValueRef::of(&self.outputs.imem_read_enable)),
+            7 => Some(SimValueRef::of(&self.outputs.imem_address)),
+            8 => Some(SimValueRef::of(&self.outputs.dmem_read_enable)),
+            9 => Some(SimValueRef::of(&self.outputs.dmem_write_enable)),
+            10 => Some(SimValueRef::of(&self.outputs.dmem_address)),
+            11 => Some(SimValueRef::of(&self.outputs.dmem_write_data)),
+            12 => Some(SimValueRef::of(&self.outputs.apb_request)),
+            13 => Some(SimValueRef::of(&self.outputs.halted)),
+            14 => Some(SimValueRef::of(&self.pc)),
+            n => self.regs.get(n - 15).map(|r| SimValueRef::of(r)),
+        }
+    }
+    fn try_state_data_mut(&mut self, index: SimStateIndex) -> Option<SimValueRefMut> {
+        match index.as_usize() {
+            1 => Some(SimValueRefMut::of(&mut self.inputs.imem_read_valid)),
+            2 => Some(SimValueRefMut::of(&mut self.inputs.imem_read_data)),
+            3 => Some(SimValueRefMut::of(&mut self.inputs.dmem_read_valid)),
+            4 => Some(SimValueRefMut::of(&mut self.inputs.dmem_read_data)),
+            5 => Some(SimValueRefMut::of(&mut self.inputs.apb_response)),
+            6 => Some(SimValueRefMut::of(&mut self.outputs.imem_read_enable)),
+            7 => Some(SimValueRefMut::of(&mut self.outputs.imem_address)),
+            8 => Some(SimValueRefMut::of(&mut self.outputs.dmem_read_enable)),
+            9 => Some(SimValueRefMut::of(&mut self.outputs.dmem_write_enable)),
+            10 => Some(SimValueRefMut::of(&mut self.outputs.dmem_address)),
+            11 => Some(SimValueRefMut::of(&mut self.outputs.dmem_write_data)),
+            12 => Some(SimValueRefMut::of(&mut self.outputs.apb_request)),
+            13 => Some(SimValueRefMut::of(&mut self.outputs.halted)),
+            14 => Some(SimValueRefMut::of(&mut self.pc)),
+            // x0 is not writable
+            15 => None,
+            n => self.regs.get_mut(n - 15).map(|r| SimValueRefMut::of(r)),
+        }
+    }
+}
+
+//ip Component for Rv32i
+impl Component for Rv32i {
+    type Config = Rv32iConfig;
+    type InputsMut<'a> = &'a mut Rv32iInputs;
+    type Inputs<'a> = &'a Rv32iInputs;
+    type Outputs<'a> = &'a Rv32iOutputs;
+    fn inputs(&self) -> &Rv32iInputs {
+        &self.inputs
+    }
+    fn outputs(&self) -> &Rv32iOutputs {
+        &self.outputs
+    }
+    fn inputs_mut(&mut self) -> &mut Rv32iInputs {
+        &mut self.inputs
+    }
+    fn configure<S: SimRegister>(
+        &mut self,
+        sim: &mut S,
+        handle: S::Handle,
+        config: Rv32iConfig,
+    ) -> Result<(), String> {
+        if config.reset_pc & 3 != 0 {
+            return Err(format!(
+                "Reset PC {:#x} of an RV32I core must be word aligned",
+                config.reset_pc
+            ));
+        }
+        if config.apb_base & 3 != 0 {
+            return Err(format!(
+                "APB base {:#x} of an RV32I core must be word aligned",
+                config.apb_base
+            ));
+        }
+        self.config = config;
+        self.pc = (config.reset_pc as u64).into();
+        sim.register_input_edge(handle, 0, true, false);
+        Ok(())
+    }
+}
+
+//ip ComponentBuilder for Rv32i
+impl ComponentBuilder for Rv32i {
+    type Build = Self;
+    fn instantiate<S: SimRegister>(_sim: &mut S, _name: SimNsName) -> Self {
+        Self::default()
+    }
+}
@@ -1,45 +1,12 @@
 //a Imports
-use std::sync::{Arc, Condvar, Mutex, MutexGuard};
-use std::thread::{spawn, JoinHandle};
-
 use cpu_timer::AccTimer;
 
 use hgl_sim::prelude::component::*;
 
-//a State and Action
-//tp State
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
-enum State {
-    #[default]
-    Idle,
-    Running,
-    Paused,
-    Stopped,
-}
-impl State {
-    fn is_stopped(&self) -> bool {
-        matches!(self, State::Stopped)
-    }
-}
-
-//tp Action
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
-enum Action {
-    #[default]
-    Idle,
-    Resume,
-    Pause,
-    Stop,
-}
-
-impl Action {
-    fn is_idle(&self) -> bool {
-        matches!(self, Action::Idle)
-    }
-}
-
 //a MCInner
 //tp MCInner
+/// The threaded model; it accumulates the data presented to it on
+/// each clock edge, and times how long it has been running
 #[derive(Default, Debug)]
 struct MCInner {
     input_data: u64,
@@ -49,6 +16,8 @@ struct MCInner {
 
 //ip ThreadedModel for MCInner
 impl ThreadedModel for MCInner {
+    type Request = u64;
+    type Response = u64;
     fn start(&mut self) {
         self.timer.clear();
     }
@@ -58,206 +27,13 @@ impl ThreadedModel for MCInner {
     fn resume(&mut self) {
         self.timer.start()
     }
-    fn stop(&mut self) {}
-}
-
-//a ModelControl
-//tt ThreadedModel
-/// Trait for a threaded model
-///
-/// All methods are invoked from within a thread separate from the
-/// simulation thread
-trait ThreadedModel: Send + 'static {
-    fn start(&mut self) {}
-    fn pause(&mut self) {}
-    fn resume(&mut self) {}
-    fn stop(&mut self) {}
-}
-
-//ti ModelControl
-#[derive(Default, Debug)]
-struct ModelControl {
-    state: State,
-    action: Action,
-    thread_ready: bool,
-    thread: Option<JoinHandle<()>>,
-}
-
-//tp Model
-struct Model<T>
-where
-    T: ThreadedModel,
-{
-    inner: Arc<(Mutex<(ModelControl, T)>, Condvar, Condvar)>,
-}
-
-impl<T> std::clone::Clone for Model<T>
-where
-    T: ThreadedModel,
-{
-    fn clone(&self) -> Self {
-        Self {
-            inner: self.inner.clone(),
-        }
+    fn edge(&mut self, _mask: SimEdgeMask, data: u64) -> u64 {
+        self.input_data = data;
+        self.result_data = self.result_data.wrapping_add(data);
+        self.result_data
     }
 }
 
-//ip Model
-impl<T> Model<T>
-where
-    T: ThreadedModel,
-{
-    //cp new
-    fn new(inner: T) -> Self {
-        let control = ModelControl::default();
-        let inner = Arc::new((Mutex::new((control, inner)), Condvar::new(), Condvar::new()));
-        Self { inner }
-    }
-
-    //ap get_state
-    fn get_state(&self) -> State {
-        self.inner.0.lock().unwrap().0.state
-    }
-
-    //ap get
-    fn get<R, F: FnOnce(&T) -> R>(&self, f: F) -> R {
-        f(&self.inner.0.lock().unwrap().1)
-    }
-
-    //mp start
-    fn start(&mut self, running: bool) {
-        let s = self.clone();
-        eprintln!("start inner running:{running}");
-        let mut mg = self.inner.0.lock().unwrap();
-        assert!(mg.0.state == State::Idle);
-        mg.0.action = Action::Idle;
-        mg.0.state = State::Paused;
-        mg.0.thread_ready = false;
-        mg.0.thread = Some(spawn(move || s.thread_run()));
-        drop(mg);
-        if running {
-            self.update_state(Action::Resume);
-        };
-    }
-
-    //ap wait_for_thread_ready - invoked from simulation thread
-    fn wait_for_thread_ready(&self) {
-        let (m, t, f) = &*self.inner;
-        let mut mg = m.lock().unwrap();
-        while !mg.0.thread_ready {
-            let (new_mg, f) = f.wait_timeout(mg, std::time::Duration::new(1, 0)).unwrap();
-            if f.timed_out() {
-                eprintln!("timed out waiting for sim thread, retrying");
-            }
-            mg = new_mg;
-        }
-    }
-
-    //ap update_state - invoked from simulation thread
-    fn update_state(&self, action: Action) {
-        self.wait_for_thread_ready();
-        let (m, t, f) = &*self.inner;
-        let mut mg = m.lock().unwrap();
-        if !mg.0.action.is_idle() {
-            panic!("Action should have been cleared if thread is ready!");
-        }
-        match (mg.0.state, action) {
-            (State::Idle, Action::Resume) => (),
-            (State::Running, Action::Pause) => (),
-            (State::Paused, Action::Resume) => (),
-            (State::Idle, Action::Stop) => (),
-            (State::Running, Action::Stop) => (),
-            (State::Paused, Action::Stop) => (),
-            _ => {
-                return;
-            }
-        }
-        mg.0.action = action;
-        mg.0.thread_ready = false;
-        t.notify_all();
-    }
-
-    //mp thread_run - running in the model thread
-    fn thread_run(&self) {
-        {
-            let (m, _t, f) = &*self.inner;
-            let mut mg = m.lock().unwrap();
-            mg.1.start();
-            mg.0.thread_ready = true;
-            f.notify_all();
-        }
-        eprintln!("thread_run: start");
-        loop {
-            eprintln!("thread_run: loop start");
-            let mg = self.inner.0.lock().unwrap();
-            if mg.0.state.is_stopped() {
-                break;
-            };
-            drop(mg);
-            self.wait_for_state_change();
-        }
-        eprintln!("thread_run: finished");
-    }
-
-    //ap handle_state_change - running in the model thread
-    //
-    // Cannot be in Idle; this is only invoked from within the thread,
-    // so it must be either Running or Paused
-    fn handle_state_change(&self, mci: &mut (ModelControl, T)) {
-        match (mci.0.state, mci.0.action) {
-            (State::Running, Action::Pause) => {
-                mci.1.pause();
-                mci.0.state = State::Paused;
-            }
-            (State::Running, Action::Stop) => {
-                mci.1.pause();
-                mci.1.stop();
-                mci.0.state = State::Stopped;
-            }
-            (State::Paused, Action::Resume) => {
-                mci.1.resume();
-                mci.0.state = State::Running;
-            }
-            (State::Paused, Action::Stop) => {
-                mci.1.stop();
-                mci.0.state = State::Stopped;
-            }
-            _ => {
-                panic!(
-                    "Unexpected state/action {:?}, {:?}",
-                    mci.0.state, mci.0.action
-                );
-            }
-        }
-    }
-
-    //ap wait_for_state_change - in the model thread
-    fn wait_for_state_change(&self) -> bool {
-        eprintln!("thread: wait_for_state_change");
-        let (m, t, f) = &*self.inner;
-        let mut mg = m.lock().unwrap();
-        while mg.0.thread_ready {
-            let (new_mg, t) = t.wait_timeout(mg, std::time::Duration::new(1, 0)).unwrap();
-            mg = new_mg;
-            eprintln!("thread: wait_for_state_change: condvar returned {t:?}");
-            // secs, ns
-            if t.timed_out() {
-                eprintln!("model thread timed out waiting for main thread");
-            }
-        }
-        if !mg.0.action.is_idle() {
-            self.handle_state_change(&mut *mg);
-            mg.0.action = Action::Idle;
-            mg.0.thread_ready = true;
-            f.notify_all();
-            true
-        } else {
-            false
-        }
-    }
-    //zz All done
-}
-
 //a STATE_INFO, Inputs, Outputs
 //ci STATE_INFO
 const STATE_INFO: &[SimStateInfo] = &[
@@ -267,6 +43,7 @@ const STATE_INFO: &[SimStateInfo] = &[
     SimStateInfo::input("stop", 3),
     SimStateInfo::input("data", 4),
     SimStateInfo::output("q", 0),
+    SimStateInfo::output("sum", 1),
 ];
 
 //tp Inputs
@@ -284,6 +61,7 @@ pub struct Inputs {
 #[derive(Debug, Default)]
 pub struct Outputs {
     pub q: u64,
+    pub sum: u64,
 }
 
 //a Threaded
@@ -346,7 +124,7 @@ impl Simulatable for Threaded {
     fn resume(&mut self) {}
 
     fn stop(&mut self) {
-        self.model.update_state(Action::Stop);
+        self.model.stop();
     }
 
     //mp Clock
@@ -359,10 +137,12 @@ impl Simulatable for Threaded {
                 // self.model.
             } else {
                 if self.inputs.start {
-                    self.model.update_state(Action::Resume);
+                    self.model.resume();
                 } else if self.inputs.stop {
-                    self.model.update_state(Action::Pause);
-                    self.model.wait_for_thread_ready();
+                    self.model.pause();
+                }
+                if let Some(sum) = self.model.edge(mask, self.inputs.data) {
+                    self.outputs.sum = sum;
                 }
             }
             self.generate_outputs();
@@ -392,6 +172,7 @@ impl Simulatable for Threaded {
             3 => Some(SimValueRef::of(&self.inputs.stop)),
             4 => Some(SimValueRef::of(&self.inputs.data)),
             5 => Some(SimValueRef::of(&self.outputs.q)),
+            6 => Some(SimValueRef::of(&self.outputs.sum)),
             _ => None,
         }
     }
@@ -402,6 +183,7 @@ impl Simulatable for Threaded {
             3 => Some(SimValueRefMut::of(&mut self.inputs.stop)),
             4 => Some(SimValueRefMut::of(&mut self.inputs.data)),
             5 => Some(SimValueRefMut::of(&mut self.outputs.q)),
+            6 => Some(SimValueRefMut::of(&mut self.outputs.sum)),
             _ => None,
         }
     }
@@ -0,0 +1,262 @@
+//a Documentation
+//! An APB timer
+//!
+//! The [Timer] has a counter that increments once every (PRESCALER+1)
+//! clock cycles while it is enabled; when the counter reaches the
+//! COMPARE value the match flag is set, and the counter either
+//! reloads with the RELOAD value (if reload is enabled) or the timer
+//! stops. Its `irq` output is asserted while the match flag is set
+//! and interrupts are enabled.
+//!
+//! Its registers (selected by the low bits of paddr, as for the other
+//! APB targets here) are:
+//!
+//! * 0 CONTROL: bit 0 enables counting, bit 1 enables reloading, and
+//!   bit 2 enables the interrupt
+//!
+//! * 1 STATUS: bit 0 is the match flag; writing a 1 to it clears it
+//!
+//! * 2 PRESCALER: the number of clock cycles between counter
+//!   increments, less one
+//!
+//! * 3 COUNTER: the counter value
+//!
+//! * 4 COMPARE: the value at which the counter matches
+//!
+//! * 5 RELOAD: the value the counter is reloaded with on a match
+//!
+//! The registers may also be accessed at transaction level, as a
+//! [TlmTarget] of [crate::ApbPayload]s.
+
+//a Imports
+use hgl_sim::prelude::component::*;
+
+use crate::{t_apb_request, t_apb_response, ApbPayload};
+
+//a Timer
+//ci STATE_INFO
+const STATE_INFO: &[SimStateInfo] = &[
+    SimStateInfo::clk("clk", 0),
+    SimStateInfo::input("apb_request", 0),
+    SimStateInfo::output("apb_response", 0),
+    SimStateInfo::output("irq", 1),
+];
+
+//tp TimerInputs
+#[derive(Debug, Default)]
+pub struct TimerInputs {
+    pub apb_request: t_apb_request,
+}
+
+//tp TimerOutputs
+#[derive(Debug, Default)]
+pub struct TimerOutputs {
+    pub apb_response: t_apb_response,
+    pub irq: Bit,
+}
+
+//tp Timer
+/// A timer with an APB register interface and an interrupt output
+#[derive(Debug, Default)]
+pub struct Timer {
+    pub inputs: TimerInputs,
+    pub outputs: TimerOutputs,
+    enable: bool,
+    reload_enable: bool,
+    irq_enable: bool,
+    matched: bool,
+    prescaler: u32,
+    prescale_count: u32,
+    counter: u32,
+    compare: u32,
+    reload: u32,
+}
+
+//ip Timer
+impl Timer {
+    //ap counter
+    /// The counter value
+    pub fn counter(&self) -> u32 {
+        self.counter
+    }
+
+    //mi control
+    /// The value of the CONTROL register
+    fn control(&self) -> u32 {
+        (self.enable as u32) | (self.reload_enable as u32) << 1 | (self.irq_enable as u32) << 2
+    }
+
+    //mi apb_access
+    /// Perform an APB access, returning the read data
+    fn apb_access(&mut self, request: &t_apb_request) -> u32 {
+        let data = request.pwdata.try_as_u64().unwrap_or(0) as u32;
+        let write = request.pwrite.is_true();
+        match request.paddr.try_as_u64().unwrap_or(0) & 7 {
+            0 if write => {
+                self.enable = data & 1 != 0;
+                self.reload_enable = data & 2 != 0;
+                self.irq_enable = data & 4 != 0;
+                self.prescale_count = 0;
+                0
+            }
+            0 => self.control(),
+            1 if write => {
+                self.matched &= data & 1 == 0;
+                0
+            }
+            1 => self.matched as u32,
+            2 if write => {
+                self.prescaler = data;
+                self.prescale_count = 0;
+                0
+            }
+            2 => self.prescaler,
+            3 if write => {
+                self.counter = data;
+                0
+            }
+            3 => self.counter,
+            4 if write => {
+                self.compare = data;
+                0
+            }
+            4 => self.compare,
+            5 if write => {
+                self.reload = data;
+                0
+            }
+            5 => self.reload,
+            _ => 0,
+        }
+    }
+
+    //mi tick
+    /// Advance the prescaler and counter by a clock cycle
+    fn tick(&mut self) {
+        if !self.enable {
+            return;
+        }
+        if self.prescale_count < self.prescaler {
+            self.prescale_count += 1;
+            return;
+        }
+        self.prescale_count = 0;
+        self.counter = self.counter.wrapping_add(1);
+        if self.counter == self.compare {
+            self.matched = true;
+            if self.reload_enable {
+                self.counter = self.reload;
+            } else {
+                self.enable = false;
+            }
+        }
+    }
+}
+
+//ip TlmTarget<ApbPayload> for Timer
+impl TlmTarget<ApbPayload> for Timer {
+    //mp b_transport
+    /// Access a register, with no delay
+    fn b_transport(&mut self, payload: &mut ApbPayload, _delay: &mut usize) {
+        let rdata = self.apb_access(&payload.request());
+        payload.complete(rdata, false);
+    }
+}
+
+//ip Simulatable for Timer
+impl Simulatable for Timer {
+    //mp as_any
+    /// Return a reference as an Any so it can be downcast
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    //mp as_mut_any
+    /// Return a mutable reference as an Any so it can be downcast
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    //mp reset
+    /// Reset the timer to disabled, with all registers zero
+    fn reset(&mut self, _reason: SimReset) {
+        let inputs = std::mem::take(&mut self.inputs);
+        *self = Self {
+            inputs,
+            ..Default::default()
+        };
+    }
+
+    //mp clock
+    /// Clock the timer on the posedge of its clock
+    ///
+    /// A register access takes effect before the counter is advanced
+    fn clock(&mut self, _mask: SimEdgeMask) {
+        let request = self.inputs.apb_request;
+        let mut response = t_apb_response {
+            pready: true.into(),
+            ..Default::default()
+        };
+        if request.psel.is_true() && request.penable.is_true() {
+            response.prdata = (self.apb_access(&request) as u64).into();
+        }
+        self.outputs.apb_response = response;
+        self.tick();
+        self.outputs.irq = (self.matched && self.irq_enable).into();
+    }
+
+    fn propagate(&mut self, _stage: usize) {}
+    fn state_info(&self, index: SimStateIndex) -> Option<SimStateInfo> {
+        STATE_INFO.get(index.as_usize()).copied()
+    }
+    fn try_state_data(&self, index: SimStateIndex) -> Option<SimValueRef> {
+        match index.as_usize() {
+            1 => Some(SimValueRef::of(&self.inputs.apb_request)),
+            2 => Some(SimValueRef::of(&self.outputs.apb_response)),
+            3 => Some(SimValueRef::of(&self.outputs.irq)),
+            _ => None,
+        }
+    }
+    fn try_state_data_mut(&mut self, index: SimStateIndex) -> Option<SimValueRefMut> {
+        match index.as_usize() {
+            1 => Some(SimValueRefMut::of(&mut self.inputs.apb_request)),
+            2 => Some(SimValueRefMut::of(&mut self.outputs.apb_response)),
+            3 => Some(SimValueRefMut::of(&mut self.outputs.irq)),
+            _ => None,
+        }
+    }
+}
+
+//ip Component for Timer
+impl Component for Timer {
+    type Config = ();
+    type InputsMut<'a> = &'a mut TimerInputs;
+    type Inputs<'a> = &'a TimerInputs;
+    type Outputs<'a> = &'a TimerOutputs;
+    fn inputs(&self) -> &TimerInputs {
+        &self.inputs
+    }
+    fn outputs(&self) -> &TimerOutputs {
+        &self.outputs
+    }
+    fn inputs_mut(&mut self) -> &mut TimerInputs {
+        &mut self.inputs
+    }
+    fn configure<S: SimRegister>(
+        &mut self,
+        sim: &mut S,
+        handle: S::Handle,
+        _config: (),
+    ) -> Result<(), String> {
+        sim.register_input_edge(handle, 0, true, false);
+        Ok(())
+    }
+}
+
+//ip ComponentBuilder for Timer
+impl ComponentBuilder for Timer {
+    type Build = Self;
+    fn instantiate<S: SimRegister>(_sim: &mut S, _name: SimNsName) -> Self {
+        Self::default()
+    }
+}
@@ -0,0 +1,745 @@
+//a Documentation
+//! A UART, and a terminal to connect to it
+//!
+//! The [Uart] is an APB target with `tx` and `rx` pins; it has a
+//! configurable baud divisor (clock cycles per bit), parity and
+//! number of stop bits, and always uses eight data bits. Its
+//! registers (selected by the low bits of paddr, as for the other APB
+//! targets here) are:
+//!
+//! * 0 DATA: writing transmits a byte; reading returns the oldest
+//!   received byte (or 0 if there is none) and removes it
+//!
+//! * 1 STATUS: bit 0 is set if a received byte is available, bit 1
+//!   if the transmit FIFO is full, bit 2 if the transmitter is idle,
+//!   bit 3 on a parity error, bit 4 on a framing error and bit 5 if a
+//!   received byte was lost as the receive FIFO was full; writing a 1
+//!   to bits 3 to 5 clears them
+//!
+//! * 2 DIVISOR: the baud divisor
+//!
+//! The [UartTerminal] is the host side of a serial line: it has `tx`
+//! and `rx` pins, which are connected (crossed over) to the pins of a
+//! UART with [UartTerminal::connect]. A testbench may send bytes with
+//! it and take the bytes it has received; it may also be given a
+//! [UartStream] - such as a `UnixSocketStream` on Unix targets, to
+//! which a terminal program may connect - which is polled for bytes
+//! to send and is given all the bytes received.
+
+//a Imports
+use std::collections::VecDeque;
+#[cfg(unix)]
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+
+use hgl_sim::prelude::component::*;
+
+use crate::apb_monitor::find_typed;
+use crate::{t_apb_request, t_apb_response};
+
+//a UartConfig, UartTx, UartRx
+//tp UartParity
+/// The parity of a UART frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UartParity {
+    #[default]
+    None,
+    Even,
+    Odd,
+}
+
+//tp UartConfig
+/// The configuration of the serial line of a [Uart] or [UartTerminal]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UartConfig {
+    /// Number of clock cycles per bit
+    pub divisor: u32,
+    pub parity: UartParity,
+    /// Number of stop bits (1 or 2)
+    pub stop_bits: usize,
+}
+
+//ip Default for UartConfig
+impl Default for UartConfig {
+    fn default() -> Self {
+        Self {
+            divisor: 16,
+            parity: UartParity::None,
+            stop_bits: 1,
+        }
+    }
+}
+
+//ip UartConfig
+impl UartConfig {
+    //mp validate
+    /// Check the configuration is valid
+    pub fn validate(&self) -> Result<(), String> {
+        if self.divisor < 2 {
+            return Err(format!("UART divisor {} must be at least 2", self.divisor));
+        }
+        if !(1..=2).contains(&self.stop_bits) {
+            return Err(format!(
+                "UART must have 1 or 2 stop bits, not {}",
+                self.stop_bits
+            ));
+        }
+        Ok(())
+    }
+
+    //mi parity_bit
+    /// The parity bit for a byte, if there is one
+    fn parity_bit(&self, byte: u8) -> Option<bool> {
+        let odd = byte.count_ones() & 1 != 0;
+        match self.parity {
+            UartParity::None => None,
+            UartParity::Even => Some(odd),
+            UartParity::Odd => Some(!odd),
+        }
+    }
+
+    //mp frame
+    /// The bits of the frame for a byte, in order, starting with the
+    /// start bit
+    pub fn frame(&self, byte: u8) -> Vec<bool> {
+        let mut bits = vec![false];
+        bits.extend((0..8).map(|i| (byte >> i) & 1 != 0));
+        bits.extend(self.parity_bit(byte));
+        bits.extend(std::iter::repeat_n(true, self.stop_bits));
+        bits
+    }
+}
+
+//tp UartError
+/// An error in a received UART frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UartError {
+    /// The parity bit was wrong for the byte
+    Parity(u8),
+    /// A stop bit was low
+    Framing,
+}
+
+//tp UartTx
+/// A UART transmitter, serializing bytes onto a line
+#[derive(Debug, Default)]
+pub struct UartTx {
+    bits: VecDeque<bool>,
+    count: u32,
+}
+
+//ip UartTx
+impl UartTx {
+    //ap is_idle
+    /// Return true if no frame is being transmitted
+    pub fn is_idle(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    //mp start
+    /// Start transmitting a byte; the transmitter must be idle
+    pub fn start(&mut self, config: &UartConfig, byte: u8) {
+        self.bits = config.frame(byte).into();
+        self.count = 0;
+    }
+
+    //mp tick
+    /// Advance the transmitter by a clock cycle, returning the line
+    /// value for the next cycle (high when idle)
+    pub fn tick(&mut self, config: &UartConfig) -> bool {
+        if !self.bits.is_empty() {
+            self.count += 1;
+            if self.count >= config.divisor {
+                self.count = 0;
+                self.bits.pop_front();
+            }
+        }
+        self.bits.front().copied().unwrap_or(true)
+    }
+
+    //mp reset
+    /// Abandon any frame
+    pub fn reset(&mut self) {
+        self.bits.clear();
+        self.count = 0;
+    }
+}
+
+//tp UartRx
+/// A UART receiver, sampling a line in the middle of each bit
+#[derive(Debug, Default)]
+pub struct UartRx {
+    /// True once the line has been seen idle (high)
+    line_idle: bool,
+    /// Bits received of the current frame, if one has started
+    bits: Option<Vec<bool>>,
+    count: u32,
+}
+
+//ip UartRx
+impl UartRx {
+    //mp tick
+    /// Sample the line for a clock cycle, returning a byte (or error)
+    /// when a frame completes
+    pub fn tick(&mut self, config: &UartConfig, line: bool) -> Option<Result<u8, UartError>> {
+        let Some(bits) = &mut self.bits else {
+            if line {
+                self.line_idle = true;
+            } else if self.line_idle {
+                // Start bit; sample in the middle of each bit
+                self.bits = Some(vec![]);
+                self.count = config.divisor / 2;
+            }
+            return None;
+        };
+        self.count -= 1;
+        if self.count > 0 {
+            return None;
+        }
+        self.count = config.divisor;
+        if bits.is_empty() && line {
+            // Glitch rather than a start bit
+            self.bits = None;
+            return None;
+        }
+        bits.push(line);
+        let frame_len = 1 + 8 + config.parity_bit(0).map_or(0, |_| 1) + config.stop_bits;
+        if bits.len() < frame_len {
+            return None;
+        }
+        let bits = self.bits.take().unwrap();
+        self.line_idle = line;
+        let byte = (0..8).fold(0_u8, |b, i| b | ((bits[1 + i] as u8) << i));
+        if bits[frame_len - config.stop_bits..].iter().any(|b| !b) {
+            Some(Err(UartError::Framing))
+        } else if config.parity_bit(byte).is_some_and(|p| p != bits[9]) {
+            Some(Err(UartError::Parity(byte)))
+        } else {
+            Some(Ok(byte))
+        }
+    }
+
+    //mp reset
+    /// Abandon any frame
+    pub fn reset(&mut self) {
+        self.line_idle = false;
+        self.bits = None;
+    }
+}
+
+//a Uart
+//ci FIFO_DEPTH
+/// Depth of the transmit and receive FIFOs of a [Uart]
+const FIFO_DEPTH: usize = 16;
+
+//ci UART_STATE_INFO
+const UART_STATE_INFO: &[SimStateInfo] = &[
+    SimStateInfo::clk("clk", 0),
+    SimStateInfo::input("rx", 0),
+    SimStateInfo::input("apb_request", 1),
+    SimStateInfo::output("tx", 0),
+    SimStateInfo::output("apb_response", 1),
+];
+
+//tp UartInputs
+#[derive(Debug, Default)]
+pub struct UartInputs {
+    pub rx: Bit,
+    pub apb_request: t_apb_request,
+}
+
+//tp UartOutputs
+#[derive(Debug, Default)]
+pub struct UartOutputs {
+    pub tx: Bit,
+    pub apb_response: t_apb_response,
+}
+
+//tp Uart
+/// A UART with an APB register interface
+#[derive(Debug, Default)]
+pub struct Uart {
+    pub inputs: UartInputs,
+    pub outputs: UartOutputs,
+    config: UartConfig,
+    tx: UartTx,
+    rx: UartRx,
+    tx_fifo: VecDeque<u8>,
+    rx_fifo: VecDeque<u8>,
+    parity_error: bool,
+    framing_error: bool,
+    overrun: bool,
+}
+
+//ip Uart
+impl Uart {
+    //ap config
+    /// The current configuration of the serial line
+    pub fn config(&self) -> &UartConfig {
+        &self.config
+    }
+
+    //mi status
+    /// The value of the STATUS register
+    fn status(&self) -> u32 {
+        (!self.rx_fifo.is_empty() as u32)
+            | ((self.tx_fifo.len() >= FIFO_DEPTH) as u32) << 1
+            | ((self.tx_fifo.is_empty() && self.tx.is_idle()) as u32) << 2
+            | (self.parity_error as u32) << 3
+            | (self.framing_error as u32) << 4
+            | (self.overrun as u32) << 5
+    }
+
+    //mi apb_access
+    /// Perform an APB access, returning the read data
+    fn apb_access(&mut self, request: &t_apb_request) -> u32 {
+        let data = request.pwdata.try_as_u64().unwrap_or(0) as u32;
+        let write = request.pwrite.is_true();
+        match request.paddr.try_as_u64().unwrap_or(0) & 3 {
+            0 if write => {
+                if self.tx_fifo.len() < FIFO_DEPTH {
+                    self.tx_fifo.push_back(data as u8);
+                }
+                0
+            }
+            0 => self.rx_fifo.pop_front().unwrap_or(0) as u32,
+            1 if write => {
+                self.parity_error &= data & (1 << 3) == 0;
+                self.framing_error &= data & (1 << 4) == 0;
+                self.overrun &= data & (1 << 5) == 0;
+                0
+            }
+            1 => self.status(),
+            2 if write => {
+                self.config.divisor = data.max(2);
+                0
+            }
+            2 => self.config.divisor,
+            _ => 0,
+        }
+    }
+}
+
+//ip Simulatable for Uart
+impl Simulatable for Uart {
+    //mp as_any
+    /// Return a reference as an Any so it can be downcast
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    //mp as_mut_any
+    /// Return a mutable reference as an Any so it can be downcast
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    //mp reset
+    /// Reset the UART, emptying the FIFOs and driving the line idle
+    fn reset(&mut self, _reason: SimReset) {
+        self.tx.reset();
+        self.rx.reset();
+        self.tx_fifo.clear();
+        self.rx_fifo.clear();
+        self.parity_error = false;
+        self.framing_error = false;
+        self.overrun = false;
+        self.outputs.tx = true.into();
+        self.outputs.apb_response = t_apb_response::default();
+    }
+
+    //mp clock
+    /// Clock the UART on the posedge of its clock
+    fn clock(&mut self, _mask: SimEdgeMask) {
+        let request = self.inputs.apb_request;
+        let mut response = t_apb_response {
+            pready: true.into(),
+            ..Default::default()
+        };
+        if request.psel.is_true() && request.penable.is_true() {
+            response.prdata = (self.apb_access(&request) as u64).into();
+        }
+        self.outputs.apb_response = response;
+
+        match self.rx.tick(&self.config, self.inputs.rx.is_true()) {
+            Some(Ok(byte)) => {
+                if self.rx_fifo.len() < FIFO_DEPTH {
+                    self.rx_fifo.push_back(byte);
+                } else {
+                    self.overrun = true;
+                }
+            }
+            Some(Err(UartError::Parity(_))) => self.parity_error = true,
+            Some(Err(UartError::Framing)) => self.framing_error = true,
+            None => (),
+        }
+
+        if self.tx.is_idle() {
+            if let Some(byte) = self.tx_fifo.pop_front() {
+                self.tx.start(&self.config, byte);
+                self.outputs.tx = false.into();
+                return;
+            }
+        }
+        self.outputs.tx = self.tx.tick(&self.config).into();
+    }
+
+    fn propagate(&mut self, _stage: usize) {}
+    fn state_info(&self, index: SimStateIndex) -> Option<SimStateInfo> {
+        UART_STATE_INFO.get(index.as_usize()).copied()
+    }
+    fn try_state_data(&self, index: SimStateIndex) -> Option<SimValueRef> {
+        match index.as_usize() {
+            1 => Some(SimValueRef::of(&self.inputs.rx)),
+            2 => Some(SimValueRef::of(&self.inputs.apb_request)),
+            3 => Some(SimValueRef::of(&self.outputs.tx)),
+            4 => Some(SimValueRef::of(&self.outputs.apb_response)),
+            _ => None,
+        }
+    }
+    fn try_state_data_mut(&mut self, index: SimStateIndex) -> Option<SimValueRefMut> {
+        match index.as_usize() {
+            1 => Some(SimValueRefMut::of(&mut self.inputs.rx)),
+            2 => Some(SimValueRefMut::of(&mut self.inputs.apb_request)),
+            3 => Some(SimValueRefMut::of(&mut self.outputs.tx)),
+            4 => Some(SimValueRefMut::of(&mut self.outputs.apb_response)),
+            _ => None,
+        }
+    }
+}
+
+//ip Component for Uart
+impl Component for Uart {
+    type Config = UartConfig;
+    type InputsMut<'a> = &'a mut UartInputs;
+    type Inputs<'a> = &'a UartInputs;
+    type Outputs<'a> = &'a UartOutputs;
+    fn inputs(&self) -> &UartInputs {
+        &self.inputs
+    }
+    fn outputs(&self) -> &UartOutputs {
+        &self.outputs
+    }
+    fn inputs_mut(&mut self) -> &mut UartInputs {
+        &mut self.inputs
+    }
+    fn configure<S: SimRegister>(
+        &mut self,
+        sim: &mut S,
+        handle: S::Handle,
+        config: UartConfig,
+    ) -> Result<(), String> {
+        config.validate()?;
+        self.config = config;
+        self.outputs.tx = true.into();
+        sim.register_input_edge(handle, 0, true, false);
+        Ok(())
+    }
+}
+
+//ip ComponentBuilder for Uart
+impl ComponentBuilder for Uart {
+    type Build = Self;
+    fn instantiate<S: SimRegister>(_sim: &mut S, _name: SimNsName) -> Self {
+        Self::default()
+    }
+}
+
+//a UartStream, UnixSocketStream
+//tt UartStream
+/// A host-side byte stream for a [UartTerminal]
+pub trait UartStream: std::fmt::Debug {
+    /// Return any bytes available to send, without blocking
+    fn poll(&mut self) -> Vec<u8>;
+
+    /// Deliver bytes that have been received
+    fn deliver(&mut self, bytes: &[u8]);
+}
+
+//tp UnixSocketStream
+/// A [UartStream] that listens on a Unix socket, and uses the most
+/// recent connection to it (for example from `socat - UNIX-CONNECT:<path>`)
+///
+/// Bytes received while nothing is connected are discarded
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct UnixSocketStream {
+    listener: UnixListener,
+    stream: Option<UnixStream>,
+}
+
+//ip UnixSocketStream
+#[cfg(unix)]
+impl UnixSocketStream {
+    //cp bind
+    /// Listen on a Unix socket at a path
+    pub fn bind<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let listener = UnixListener::bind(path)
+            .map_err(|e| format!("Failed to listen on {}: {e}", path.display()))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to make {} nonblocking: {e}", path.display()))?;
+        Ok(Self {
+            listener,
+            stream: None,
+        })
+    }
+}
+
+//ip UartStream for UnixSocketStream
+#[cfg(unix)]
+impl UartStream for UnixSocketStream {
+    fn poll(&mut self) -> Vec<u8> {
+        if let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.stream = Some(stream);
+            }
+        }
+        let mut bytes = vec![];
+        let Some(stream) = &mut self.stream else {
+            return bytes;
+        };
+        let mut buffer = [0; 256];
+        loop {
+            match stream.read(&mut buffer) {
+                Ok(0) => {
+                    self.stream = None;
+                    break;
+                }
+                Ok(n) => bytes.extend_from_slice(&buffer[..n]),
+                Err(_) => break,
+            }
+        }
+        bytes
+    }
+    fn deliver(&mut self, bytes: &[u8]) {
+        if let Some(stream) = &mut self.stream {
+            if stream.write_all(bytes).is_err() {
+                self.stream = None;
+            }
+        }
+    }
+}
+
+//a UartTerminal
+//ci TERMINAL_STATE_INFO
+const TERMINAL_STATE_INFO: &[SimStateInfo] = &[
+    SimStateInfo::clk("clk", 0),
+    SimStateInfo::input("rx", 0),
+    SimStateInfo::output("tx", 0),
+];
+
+//tp TerminalInputs
+#[derive(Debug, Default)]
+pub struct TerminalInputs {
+    pub rx: Bit,
+}
+
+//tp TerminalOutputs
+#[derive(Debug, Default)]
+pub struct TerminalOutputs {
+    pub tx: Bit,
+}
+
+//tp UartTerminal
+/// The host side of a serial line
+#[derive(Debug, Default)]
+pub struct UartTerminal {
+    pub inputs: TerminalInputs,
+    pub outputs: TerminalOutputs,
+    config: UartConfig,
+    tx: UartTx,
+    rx: UartRx,
+    to_send: VecDeque<u8>,
+    received: Vec<u8>,
+    errors: Vec<UartError>,
+    stream: Option<Box<dyn UartStream>>,
+}
+
+//ip UartTerminal
+impl UartTerminal {
+    //mp send
+    /// Queue bytes to send
+    pub fn send(&mut self, bytes: &[u8]) {
+        self.to_send.extend(bytes);
+    }
+
+    //ap is_idle
+    /// Return true if there is nothing left to send
+    pub fn is_idle(&self) -> bool {
+        self.to_send.is_empty() && self.tx.is_idle()
+    }
+
+    //mp take_received
+    /// Take the bytes received (that have not been delivered to a
+    /// stream)
+    pub fn take_received(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.received)
+    }
+
+    //ap errors
+    /// Errors in the frames received
+    pub fn errors(&self) -> &[UartError] {
+        &self.errors
+    }
+
+    //mp set_stream
+    /// Set a host-side stream, which is polled for bytes to send, and
+    /// to which received bytes are delivered
+    pub fn set_stream(&mut self, stream: Box<dyn UartStream>) {
+        self.stream = Some(stream);
+    }
+
+    //fp connect
+    /// Connect an instance of the terminal to the pins of a UART
+    /// (such as "uart.tx" and "uart.rx"), so that the terminal
+    /// receives what the UART transmits and vice versa
+    ///
+    /// The UART pins must be whole states; this can only be used after
+    /// prepare_simulation
+    pub fn connect(
+        sim: &Simulation,
+        terminal: InstanceHandle,
+        tx: &str,
+        rx: &str,
+    ) -> Result<(), String> {
+        let (tx_h, tx_s, tx_e) = find_typed::<Bit>(sim, tx)?;
+        let (rx_h, rx_s, rx_e) = find_typed::<Bit>(sim, rx)?;
+        if !tx_e.is_empty() || !rx_e.is_empty() {
+            return Err("UART pins must be states of the UART, not parts of them".into());
+        }
+        let is_terminal = sim
+            .instances()
+            .instance(terminal)
+            .borrow_sim()
+            .is_some_and(|s| s.as_any().is::<UartTerminal>());
+        if !is_terminal {
+            return Err("Instance to connect is not a UartTerminal".into());
+        }
+        sim.add_combinational(move |sim| {
+            let line = sim
+                .with_state(tx_h, tx_s, |v| v.get_u64())
+                .flatten()
+                .is_none_or(|v| v != 0);
+            let instances = sim.instances();
+            let tx = {
+                let mut t = instances.inst_mut::<UartTerminal>(terminal);
+                t.inputs.rx = line.into();
+                t.outputs.tx
+            };
+            sim.with_state_mut(rx_h, rx_s, |mut v| v.try_copy_from(&SimValueRef::of(&tx)));
+        });
+        Ok(())
+    }
+}
+
+//ip Simulatable for UartTerminal
+impl Simulatable for UartTerminal {
+    //mp as_any
+    /// Return a reference as an Any so it can be downcast
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    //mp as_mut_any
+    /// Return a mutable reference as an Any so it can be downcast
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    //mp reset
+    /// Reset the terminal, abandoning the frames in progress
+    fn reset(&mut self, _reason: SimReset) {
+        self.tx.reset();
+        self.rx.reset();
+        self.outputs.tx = true.into();
+    }
+
+    //mp clock
+    /// Clock the terminal on the posedge of its clock
+    fn clock(&mut self, _mask: SimEdgeMask) {
+        let mut delivered = vec![];
+        match self.rx.tick(&self.config, self.inputs.rx.is_true()) {
+            Some(Ok(byte)) => delivered.push(byte),
+            Some(Err(e)) => self.errors.push(e),
+            None => (),
+        }
+        if let Some(stream) = &mut self.stream {
+            self.to_send.extend(stream.poll());
+            if !delivered.is_empty() {
+                stream.deliver(&delivered);
+            }
+        } else {
+            self.received.extend(delivered);
+        }
+
+        if self.tx.is_idle() {
+            if let Some(byte) = self.to_send.pop_front() {
+                self.tx.start(&self.config, byte);
+                self.outputs.tx = false.into();
+                return;
+            }
+        }
+        self.outputs.tx = self.tx.tick(&self.config).into();
+    }
+
+    fn propagate(&mut self, _stage: usize) {}
+    fn state_info(&self, index: SimStateIndex) -> Option<SimStateInfo> {
+        TERMINAL_STATE_INFO.get(index.as_usize()).copied()
+    }
+    fn try_state_data(&self, index: SimStateIndex) -> Option<SimValueRef> {
+        match index.as_usize() {
+            1 => Some(SimValueRef::of(&self.inputs.rx)),
+            2 => Some(SimValueRef::of(&self.outputs.tx)),
+            _ => None,
+        }
+    }
+    fn try_state_data_mut(&mut self, index: SimStateIndex) -> Option<SimValueRefMut> {
+        match index.as_usize() {
+            1 => Some(SimValueRefMut::of(&mut self.inputs.rx)),
+            2 => Some(SimValueRefMut::of(&mut self.outputs.tx)),
+            _ => None,
+        }
+    }
+}
+
+//ip Component for UartTerminal
+impl Component for UartTerminal {
+    type Config = UartConfig;
+    type InputsMut<'a> = &'a mut TerminalInputs;
+    type Inputs<'a> = &'a TerminalInputs;
+    type Outputs<'a> = &'a TerminalOutputs;
+    fn inputs(&self) -> &TerminalInputs {
+        &self.inputs
+    }
+    fn outputs(&self) -> &TerminalOutputs {
+        &self.outputs
+    }
+    fn inputs_mut(&mut self) -> &mut TerminalInputs {
+        &mut self.inputs
+    }
+    fn configure<S: SimRegister>(
+        &mut self,
+        sim: &mut S,
+        handle: S::Handle,
+        config: UartConfig,
+    ) -> Result<(), String> {
+        config.validate()?;
+        self.config = config;
+        self.outputs.tx = true.into();
+        sim.register_input_edge(handle, 0, true, false);
+        Ok(())
+    }
+}
+
+//ip ComponentBuilder for UartTerminal
+impl ComponentBuilder for UartTerminal {
+    type Build = Self;
+    fn instantiate<S: SimRegister>(_sim: &mut S, _name: SimNsName) -> Self {
+        Self::default()
+    }
+}
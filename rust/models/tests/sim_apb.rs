@@ -1,7 +1,50 @@
 use hgl_models::apb_target_gpio::apb_target_gpio;
-use hgl_models::t_apb_request;
+use hgl_models::{add_apb_assertions, apb_covergroup, t_apb_request};
+use hgl_models::{ApbMonitor, ApbRegisterMap, ApbScoreboard};
 use hgl_sim::prelude::sim::*;
 
+//fi gpio_register_map
+/// The expected register map of the GPIO target
+///
+/// The input status register depends on the GPIO inputs, so its
+/// read data is not compared; the input type registers cannot be
+/// read back
+fn gpio_register_map() -> ApbRegisterMap {
+    ApbRegisterMap::new(false)
+        .register("output", 0, 0, 0xffff_ffff, 0xffff_ffff)
+        .register("input_status", 1, 0, 0, 0)
+        .register("input_reg_0", 2, 0, 0x7777_7777, 0)
+        .register("input_reg_1", 3, 0, 0x7777_7777, 0)
+}
+
+//fi gpio_access
+/// Perform an APB access to the GPIO target, with the setup phase,
+/// the access phase, and an idle cycle
+async fn gpio_access(
+    sim: &Simulation<'_>,
+    clk: ClockIndex,
+    dut: InstanceHandle,
+    req: t_apb_request,
+) {
+    let instances = sim.instances();
+    instances
+        .inst_mut::<apb_target_gpio>(dut)
+        .inputs
+        .apb_request = req;
+    sim.posedge(clk).await;
+    instances
+        .inst_mut::<apb_target_gpio>(dut)
+        .inputs
+        .apb_request
+        .penable = true.into();
+    sim.posedge(clk).await;
+    instances
+        .inst_mut::<apb_target_gpio>(dut)
+        .inputs
+        .apb_request = t_apb_request::default();
+    sim.posedge(clk).await;
+}
+
 #[test]
 fn sim() -> Result<(), String> {
     let mut sim = Simulation::new();
@@ -11,42 +54,308 @@ fn sim() -> Result<(), String> {
     sim.connect_clock(clk, cntr, 0); // cntr_clk);
 
     sim.prepare_simulation();
+    add_apb_assertions(&sim, clk, "dut.apb_request", "dut.apb_response", 4)?;
+    let monitor = ApbMonitor::add_to_simulation(&sim, clk, "dut.apb_request", "dut.apb_response")?;
+    let mut scoreboard = ApbScoreboard::new(gpio_register_map());
     let instances = sim.instances();
     sim.start(true)?;
+    instances.inst_mut::<apb_target_gpio>(cntr).inputs.reset_n = true.into();
 
-    fn f<'a>(m: &'a mut RefMutInstance<'_, apb_target_gpio>) -> &'a mut t_apb_request {
-        &mut m.inputs.apb_request
-    }
+    let mut testbench = Testbench::new();
+    testbench.spawn(async {
+        for (w, a, d) in [
+            (true, 0, 0),
+            (true, 0, 1),
+            (false, 0, 0),
+            (true, 0, 4),
+            (true, 1, 3),
+            (false, 0, 0),
+            (true, 2, 0x1234),
+            (false, 1, 0),
+        ] {
+            let req = t_apb_request {
+                psel: true.into(),
+                penable: false.into(),
+                pwrite: w.into(),
+                paddr: a.into(),
+                pwdata: d.into(),
+            };
+            gpio_access(&sim, clk, cntr, req).await;
+            sim.cycles(clk, 2).await;
+
+            // Bits 2i and 2i+1 of the output register are the value and
+            // enable of output i
+            scoreboard.check_monitor(&monitor);
+            let expected = scoreboard.reference().value("output").unwrap();
+            let inst = instances.inst::<apb_target_gpio>(cntr);
+            for i in 0..16 {
+                assert_eq!(
+                    inst.outputs.gpio_output.bit(i),
+                    (expected >> (2 * i)) & 1 != 0,
+                    "gpio_output[{i}] at {}",
+                    sim.time()
+                );
+                assert_eq!(
+                    inst.outputs.gpio_output_enable.bit(i),
+                    (expected >> (2 * i + 1)) & 1 != 0,
+                    "gpio_output_enable[{i}] at {}",
+                    sim.time()
+                );
+            }
+        }
+        sim.cycles(clk, 1_000).await;
+    });
+    testbench.run(&sim, 2_000)?;
+    drop(testbench);
+
+    sim.stop()?;
+    assert_eq!(sim.assertion_failures(), vec![]);
+    assert_eq!(monitor.borrow().protocol_errors(), &[]);
+    assert_eq!(scoreboard.num_checked(), 8);
+    scoreboard.result()?;
+
+    Ok(())
+}
+
+#[test]
+fn scoreboard_mismatches() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    let dut = sim.instantiate::<apb_target_gpio, _, _>("dut", || ())?;
+    sim.connect_clock(clk, dut, 0);
+    sim.prepare_simulation();
+    assert!(ApbMonitor::new(&sim, "dut.apb_response", "dut.apb_response").is_err());
+    assert!(ApbMonitor::new(&sim, "dut.apb_request", "dut.bus").is_err());
+    let monitor = ApbMonitor::add_to_simulation(&sim, clk, "dut.apb_request", "dut.apb_response")?;
+
+    // A reference model which believes only bits 0 to 7 of the output
+    // register are writable, and that unmapped addresses error
+    let reference = ApbRegisterMap::new(true).register("output", 0, 0, 0xff, 0xffff_ffff);
+    let mut scoreboard = ApbScoreboard::new(reference);
+
+    let instances = sim.instances();
+    sim.start(true)?;
+    instances.inst_mut::<apb_target_gpio>(dut).inputs.reset_n = true.into();
+    let mut testbench = Testbench::new();
+    testbench.spawn(async {
+        for (w, a, d) in [(true, 0, 0x3_0303), (false, 0, 0), (false, 1, 0)] {
+            let req = t_apb_request {
+                psel: true.into(),
+                pwrite: w.into(),
+                paddr: a.into(),
+                pwdata: d.into(),
+                ..Default::default()
+            };
+            gpio_access(&sim, clk, dut, req).await;
+        }
+    });
+    testbench.run(&sim, 9)?;
+    // penable without psel is a protocol error
+    instances
+        .inst_mut::<apb_target_gpio>(dut)
+        .inputs
+        .apb_request
+        .penable = true.into();
+    sim.fire_next_edges();
+    sim.stop()?;
+
+    let transactions = monitor.borrow().transactions().to_vec();
+    assert_eq!(transactions.len(), 3);
+    assert!(transactions[0].write);
+    assert_eq!(transactions[0].wdata, 0x3_0303);
+    assert_eq!(transactions[0].wait_states, 0);
+    assert_eq!(transactions[0].end, transactions[0].start + 1);
+    assert!(!transactions[1].write);
+    assert_eq!(transactions[1].rdata, 0x3_0303);
+    assert_eq!(
+        transactions[1].to_string(),
+        "read 0x0 -> 0x30303 (0 wait states)"
+    );
+    assert_eq!(
+        monitor.borrow().protocol_errors(),
+        &[(sim.time(), "penable without psel".to_string())]
+    );
+
+    scoreboard.check_monitor(&monitor);
+    assert_eq!(scoreboard.num_checked(), 3);
+    let mismatches = scoreboard.mismatches();
+    assert_eq!(mismatches.len(), 2, "{mismatches:?}");
+    assert_eq!(mismatches[0].time, transactions[1].end);
+    assert!(mismatches[0]
+        .message
+        .contains("read of register 'output' expected 0x3"));
+    assert_eq!(mismatches[1].transaction.address, 1);
+    assert!(mismatches[1]
+        .to_string()
+        .contains("expected error for unmapped address 0x1"));
+    assert!(scoreboard.result().is_err());
+    Ok(())
+}
+
+#[test]
+fn protocol_violations() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    let dut = sim.instantiate::<apb_target_gpio, _, _>("dut", || ())?;
+    sim.connect_clock(clk, dut, 0);
+    sim.prepare_simulation();
+    add_apb_assertions(&sim, clk, "dut.apb_request", "dut.apb_response", 4)?;
+    assert!(sim
+        .add_assertion("bad", clk, Property::always("dut.apb_request.pwdata")?)
+        .is_ok());
+    assert!(sim
+        .add_assertion("missing", clk, Property::always("dut.apb_request.nothing")?)
+        .is_err());
+    assert!(sim
+        .add_assertion("too_wide", clk, Property::always("dut.apb_request")?)
+        .is_err());
+    sim.take_assertion_failures();
+
+    let instances = sim.instances();
+    sim.start(true)?;
+    // Drop the 'bad' assertion failures from the idle cycles
+    sim.fire_next_edges();
+    sim.take_assertion_failures();
+
+    // penable without psel
+    instances
+        .inst_mut::<apb_target_gpio>(dut)
+        .inputs
+        .apb_request
+        .penable = true.into();
+    sim.fire_next_edges();
+    let time = sim.time();
+    let failures: Vec<_> = sim
+        .take_assertion_failures()
+        .into_iter()
+        .filter(|f| f.name != "bad")
+        .collect();
+    assert_eq!(failures.len(), 1, "{failures:?}");
+    assert_eq!(failures[0].name, "dut.apb_request.penable_needs_psel");
+    assert_eq!(failures[0].time, time);
+    assert_eq!(failures[0].instance, "dut");
+    assert!(failures[0]
+        .values
+        .contains(&("dut.apb_request.penable".to_string(), "1b1".to_string())));
 
-    for (a, d) in [(0, 0), (0, 1), (0, 4), (1, 3)] {
-        let req = t_apb_request {
-            psel: true.into(),
-            penable: false.into(),
-            pwrite: true.into(),
-            paddr: a.into(),
-            pwdata: d.into(),
-        };
-
-        *(f(&mut instances.inst_mut::<apb_target_gpio>(cntr))) = req;
-        sim.fire_next_edges();
-        f(&mut instances.inst_mut::<apb_target_gpio>(cntr)).penable = true.into();
-        sim.fire_next_edges();
-        f(&mut instances.inst_mut::<apb_target_gpio>(cntr)).psel = false.into();
-        sim.fire_next_edges();
-
-        sim.fire_next_edges();
-        sim.fire_next_edges();
+    // Setup phase not followed by an access phase
+    {
+        let mut dut = instances.inst_mut::<apb_target_gpio>(dut);
+        dut.inputs.apb_request.penable = false.into();
+        dut.inputs.apb_request.psel = true.into();
     }
-    for _ in 0..1_000 {
-        sim.fire_next_edges();
+    sim.fire_next_edges();
+    {
+        let mut dut = instances.inst_mut::<apb_target_gpio>(dut);
+        dut.inputs.apb_request.psel = false.into();
     }
+    sim.fire_next_edges();
+    let failures: Vec<_> = sim
+        .take_assertion_failures()
+        .into_iter()
+        .filter(|f| f.name != "bad")
+        .collect();
+    assert_eq!(failures.len(), 1, "{failures:?}");
+    assert_eq!(failures[0].name, "dut.apb_request.setup_then_access");
+    assert!(failures[0].to_string().contains("failed in 'dut'"));
+    sim.stop()?;
+    Ok(())
+}
 
-    // *simm.inst_mut::<Counter<T>>(cntr).inputs.increment = false.into();
+fn gpio_coverage_run(write: bool, prefix: &std::path::Path) -> Result<CoverageReport, String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    let dut = sim.instantiate::<apb_target_gpio, _, _>("dut", || ())?;
+    sim.connect_clock(clk, dut, 0);
+    sim.prepare_simulation();
+    let registers = [
+        ("output", 0),
+        ("input_status", 1),
+        ("input_reg_0", 2),
+        ("input_reg_1", 3),
+    ];
+    sim.add_covergroup(apb_covergroup(
+        "apb",
+        clk,
+        "dut.apb_request",
+        "dut.apb_response",
+        &registers,
+    ))?;
+    sim.add_covergroup(
+        CoverGroup::new("gpio", clk).point(CoverPoint::new("output", "dut.gpio_output").toggle()),
+    )?;
+    sim.set_coverage_output(Some(prefix));
 
+    let instances = sim.instances();
+    sim.start(true)?;
+    instances.inst_mut::<apb_target_gpio>(dut).inputs.reset_n = true.into();
+    // Reading the input type registers is not supported by the model
+    let accesses: &[(u64, u64)] = {
+        if write {
+            &[(0, 0x3), (1, 0), (2, 0), (3, 0), (0, 0x2)]
+        } else {
+            &[(0, 0), (1, 0), (0, 0), (1, 0), (0, 0)]
+        }
+    };
+    let mut testbench = Testbench::new();
+    testbench.spawn(async {
+        for (a, d) in accesses.iter().copied() {
+            let req = t_apb_request {
+                psel: true.into(),
+                penable: false.into(),
+                pwrite: write.into(),
+                paddr: a.into(),
+                pwdata: d.into(),
+            };
+            gpio_access(&sim, clk, dut, req).await;
+        }
+    });
+    testbench.run(&sim, 3 * accesses.len())?;
     sim.stop()?;
-    dbg!(&sim);
+    let json = std::fs::read_to_string(prefix.with_extension("json"))
+        .map_err(|e| format!("Failed to read report: {e}"))?;
+    let report = CoverageReport::from_json(&json)?;
+    assert_eq!(report, sim.coverage_report());
+    Ok(report)
+}
+
+#[test]
+fn coverage() -> Result<(), String> {
+    let dir = std::env::temp_dir().join(format!("hgl_apb_coverage_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let writes = gpio_coverage_run(true, &dir.join("writes"))?;
+    let reads = gpio_coverage_run(false, &dir.join("reads"))?;
+    let text = std::fs::read_to_string(dir.join("writes.txt")).map_err(|e| e.to_string())?;
+    let _ = std::fs::remove_dir_all(&dir);
+
+    assert!(text.starts_with("covergroup apb: "), "{text}");
+    assert!(text.contains("  cross direction_x_register: 50.0% (4/8 bins)"));
+    assert!(text.contains("   *read,output: 0"));
+
+    let apb = writes.group("apb").unwrap();
+    assert_eq!(apb.samples, 5);
+    assert_eq!(apb.points[0].bins[1].hits, 5, "All transactions are writes");
 
-    // assert!(false);
+    let gpio = writes.group("gpio").unwrap();
+    let output = &gpio.points[0];
+    assert_eq!(output.bins[0].name, "0.rise");
+    assert_eq!(output.bins[0].hits, 1, "Bit 0 of the output is set once");
+    assert_eq!(
+        output.bins[1].hits, 1,
+        "Bit 0 of the output is cleared once"
+    );
+    assert_eq!(reads.group("gpio").unwrap().num_covered(), 0);
 
+    let mut merged = writes.clone();
+    merged.merge(&reads);
+    let apb = merged.group("apb").unwrap();
+    assert_eq!(apb.samples, 10);
+    assert_eq!(apb.crosses[0].num_covered(), 6);
+    assert_eq!(
+        apb.points[1].num_covered(),
+        1,
+        "The GPIO target never responds with an error"
+    );
+    assert!(merged.percent() > writes.percent());
     Ok(())
 }
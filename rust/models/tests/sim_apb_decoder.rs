@@ -0,0 +1,169 @@
+use hgl_models::apb_target_gpio::apb_target_gpio;
+use hgl_models::{add_apb_assertions, t_apb_request};
+use hgl_models::{ApbAddressMap, ApbDecoder, ApbMaster, ApbMonitor, ApbOp};
+use hgl_sim::prelude::sim::*;
+
+#[test]
+fn address_map() {
+    let map = ApbAddressMap::default()
+        .target("gpio0", 0, 0x100)
+        .target("gpio1", 0x1000, 0x100);
+    assert_eq!(map.validate(), Ok(()));
+    assert_eq!(map.decode(0xff), Some(0));
+    assert_eq!(map.decode(0x100), None);
+    assert_eq!(map.decode(0x1010), Some(1));
+
+    for bad in [
+        ApbAddressMap::default().target("a", 0, 0),
+        ApbAddressMap::default().target("a", 0xffff_ff00, 0x200),
+        ApbAddressMap::default()
+            .target("a", 0, 0x100)
+            .target("b", 0x80, 0x100),
+        ApbAddressMap::default()
+            .target("a", 0x80, 0x100)
+            .target("b", 0, 0x100),
+        ApbAddressMap::default()
+            .target("a", 0, 0x100)
+            .target("a", 0x100, 0x100),
+        ApbAddressMap::default().target("apb", 0, 0x100),
+    ] {
+        assert!(bad.validate().is_err(), "{bad:?} should be invalid");
+    }
+
+    let mut sim = Simulation::new();
+    assert!(sim
+        .instantiate::<ApbDecoder, _, _>("bus", || ApbAddressMap::default().target("a", 0, 0))
+        .is_err());
+}
+
+#[test]
+fn decoder_gpios() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    let gpio0 = sim.instantiate::<apb_target_gpio, _, _>("gpio0", || ())?;
+    let gpio1 = sim.instantiate::<apb_target_gpio, _, _>("gpio1", || ())?;
+    let bus = sim.instantiate::<ApbDecoder, _, _>("bus", || {
+        ApbAddressMap::default()
+            .target("gpio0", 0, 0x100)
+            .target("gpio1", 0x100, 0x100)
+    })?;
+    let master = sim.instantiate::<ApbMaster, _, _>("master", || ())?;
+    sim.connect_clock(clk, gpio0, 0);
+    sim.connect_clock(clk, gpio1, 0);
+    sim.connect_clock(clk, master, 0);
+    sim.prepare_simulation();
+
+    assert!(ApbDecoder::connect(&sim, master, &[]).is_err());
+    assert!(
+        ApbDecoder::connect(&sim, bus, &[("gpio0.apb_request", "gpio0.apb_response")]).is_err()
+    );
+    assert!(sim.find_value("bus.gpio1_request.psel").is_some());
+    ApbDecoder::connect(
+        &sim,
+        bus,
+        &[
+            ("gpio0.apb_request", "gpio0.apb_response"),
+            ("gpio1.apb_request", "gpio1.apb_response"),
+        ],
+    )?;
+    for path in ["bus.apb", "gpio0.apb", "gpio1.apb"] {
+        add_apb_assertions(
+            &sim,
+            clk,
+            &format!("{path}_request"),
+            &format!("{path}_response"),
+            4,
+        )?;
+    }
+    let monitor = ApbMonitor::add_to_simulation(&sim, clk, "bus.apb_request", "bus.apb_response")?;
+    // The decoder connects only its targets; the master is attached
+    // to the master side of the decoder
+    ApbMaster::attach(&sim, clk, master, "bus.apb_request", "bus.apb_response")?;
+
+    let instances = sim.instances();
+    sim.start(true)?;
+    instances.inst_mut::<apb_target_gpio>(gpio0).inputs.reset_n = true.into();
+    instances.inst_mut::<apb_target_gpio>(gpio1).inputs.reset_n = true.into();
+    {
+        let mut m = instances.inst_mut::<ApbMaster>(master);
+        m.write(0x000, 0x5);
+        m.write(0x100, 0xa);
+        m.read(0x000);
+        m.read(0x100);
+        m.read(0x200);
+        m.write(0x300, 0x1);
+    }
+    let mut cycles = 0;
+    let mut decode_errors = 0;
+    while !instances.inst::<ApbMaster>(master).is_idle() {
+        sim.fire_next_edges();
+        if instances
+            .inst::<ApbDecoder>(bus)
+            .outputs
+            .decode_error
+            .is_true()
+        {
+            decode_errors += 1;
+        }
+        cycles += 1;
+        assert!(cycles < 100, "Master should complete its operations");
+    }
+    sim.fire_next_edges();
+    sim.stop()?;
+
+    let results = instances.inst_mut::<ApbMaster>(master).take_completed();
+    assert_eq!(results.len(), 6);
+    assert_eq!(results[2].rdata, 0x5);
+    assert_eq!(results[3].rdata, 0xa);
+    for r in &results[0..4] {
+        assert!(!r.error, "{r:?} should succeed");
+    }
+    assert!(results[4].error, "Read of unmapped address");
+    assert!(results[5].error, "Write of unmapped address");
+    assert_eq!(
+        results[5].op,
+        ApbOp::Write {
+            address: 0x300,
+            data: 1
+        }
+    );
+    // Setup and access phases of each unmapped access
+    assert_eq!(decode_errors, 4);
+
+    // Each GPIO sees only its own writes
+    assert_eq!(
+        instances
+            .inst::<apb_target_gpio>(gpio0)
+            .outputs
+            .gpio_output
+            .try_as_u64(),
+        Some(0b11)
+    );
+    assert_eq!(
+        instances
+            .inst::<apb_target_gpio>(gpio1)
+            .outputs
+            .gpio_output
+            .try_as_u64(),
+        Some(0b00)
+    );
+    assert_eq!(
+        instances
+            .inst::<apb_target_gpio>(gpio1)
+            .outputs
+            .gpio_output_enable
+            .try_as_u64(),
+        Some(0b11)
+    );
+    // Unselected targets see an idle bus
+    assert_eq!(
+        instances.inst::<apb_target_gpio>(gpio1).inputs.apb_request,
+        t_apb_request::default()
+    );
+
+    let transactions = monitor.borrow().transactions().to_vec();
+    assert_eq!(transactions.len(), 6);
+    assert_eq!(transactions.iter().filter(|t| t.error).count(), 2);
+    assert_eq!(sim.assertion_failures(), vec![]);
+    Ok(())
+}
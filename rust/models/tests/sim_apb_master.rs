@@ -0,0 +1,142 @@
+use hgl_models::apb_target_gpio::apb_target_gpio;
+use hgl_models::{add_apb_assertions, t_apb_response};
+use hgl_models::{ApbMaster, ApbMonitor, ApbOp, ApbRegisterMap, ApbScoreboard};
+use hgl_sim::prelude::sim::*;
+
+#[test]
+fn master_gpio() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    let dut = sim.instantiate::<apb_target_gpio, _, _>("dut", || ())?;
+    let master = sim.instantiate::<ApbMaster, _, _>("master", || ())?;
+    sim.connect_clock(clk, dut, 0);
+    sim.connect_clock(clk, master, 0);
+    sim.prepare_simulation();
+    assert!(ApbMaster::attach(&sim, clk, dut, "dut.apb_request", "dut.apb_response").is_err());
+    assert!(ApbMaster::attach(
+        &sim,
+        clk,
+        master,
+        "dut.apb_request.psel",
+        "dut.apb_response"
+    )
+    .is_err());
+    ApbMaster::attach(&sim, clk, master, "dut.apb_request", "dut.apb_response")?;
+    add_apb_assertions(&sim, clk, "dut.apb_request", "dut.apb_response", 4)?;
+    let monitor = ApbMonitor::add_to_simulation(&sim, clk, "dut.apb_request", "dut.apb_response")?;
+    let mut scoreboard = ApbScoreboard::new(ApbRegisterMap::new(false).register(
+        "output",
+        0,
+        0,
+        0xffff_ffff,
+        0xffff_ffff,
+    ));
+
+    let instances = sim.instances();
+    sim.start(true)?;
+    instances.inst_mut::<apb_target_gpio>(dut).inputs.reset_n = true.into();
+    {
+        let mut m = instances.inst_mut::<ApbMaster>(master);
+        m.write(0, 0x5);
+        m.read(0);
+        m.write(0, 0xa);
+        m.read(0);
+    }
+    let mut cycles = 0;
+    while !instances.inst::<ApbMaster>(master).is_idle() {
+        sim.fire_next_edges();
+        cycles += 1;
+        assert!(cycles < 100, "Master should complete its operations");
+    }
+    sim.fire_next_edges();
+    sim.stop()?;
+
+    let results = instances.inst_mut::<ApbMaster>(master).take_completed();
+    assert_eq!(results.len(), 4);
+    assert_eq!(
+        results[0].op,
+        ApbOp::Write {
+            address: 0,
+            data: 0x5
+        }
+    );
+    assert_eq!(results[1].rdata, 0x5);
+    assert_eq!(results[3].rdata, 0xa);
+    for (i, r) in results.iter().enumerate() {
+        assert!(!r.error);
+        assert_eq!(r.wait_states, 0);
+        assert_eq!(r.completed - r.started, 2, "Setup and access phases");
+        // Back-to-back transfers, each queued at cycle 0
+        assert_eq!(r.latency(), 2 * (i + 1) + 1);
+    }
+
+    scoreboard.check_monitor(&monitor);
+    assert_eq!(scoreboard.num_checked(), 4);
+    scoreboard.result()?;
+    assert_eq!(monitor.borrow().protocol_errors(), &[]);
+    assert_eq!(sim.assertion_failures(), vec![]);
+    Ok(())
+}
+
+#[test]
+fn master_wait_states() {
+    let mut m = ApbMaster::default();
+    m.read(0x10);
+    m.write(0x14, 0x1234);
+
+    let not_ready = t_apb_response::default();
+    let ready = t_apb_response {
+        pready: true.into(),
+        prdata: 0x55.into(),
+        ..Default::default()
+    };
+    let error = t_apb_response {
+        pready: true.into(),
+        perr: true.into(),
+        ..Default::default()
+    };
+
+    // The cycle in which the operations are queued, with the bus idle
+    m.step();
+
+    // Setup phase, access phase, 3 wait states, then ready
+    m.evaluate();
+    assert!(m.outputs.apb_request.psel.is_true());
+    assert!(m.outputs.apb_request.penable.is_false());
+    assert_eq!(m.outputs.apb_request.paddr.try_as_u64(), Some(0x10));
+    m.step();
+    m.evaluate();
+    assert!(m.outputs.apb_request.penable.is_true());
+    for _ in 0..3 {
+        m.step();
+        m.inputs.apb_response = not_ready;
+        m.evaluate();
+        assert!(m.outputs.apb_request.penable.is_true(), "Access phase held");
+    }
+    m.step();
+    m.inputs.apb_response = ready;
+    m.evaluate();
+    // Next transfer's setup phase immediately
+    assert!(m.outputs.apb_request.penable.is_false());
+    assert!(m.outputs.apb_request.pwrite.is_true());
+    assert_eq!(m.outputs.apb_request.pwdata.try_as_u64(), Some(0x1234));
+    m.step();
+    m.inputs.apb_response = not_ready;
+    m.evaluate();
+    m.step();
+    m.inputs.apb_response = error;
+    m.evaluate();
+    assert!(m.outputs.apb_request.psel.is_false());
+    m.step();
+    assert!(m.is_idle());
+
+    let results = m.take_completed();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].rdata, 0x55);
+    assert_eq!(results[0].wait_states, 3);
+    assert_eq!(results[0].latency(), 6);
+    assert!(!results[0].error);
+    assert_eq!(results[1].wait_states, 0);
+    assert!(results[1].error);
+    assert_eq!(results[1].latency(), 8);
+}
@@ -0,0 +1,101 @@
+use hgl_models::Counter;
+use hgl_sim::prelude::sim::*;
+
+#[test]
+fn parse_expressions() -> Result<(), String> {
+    assert_eq!(
+        Expr::parse("!a.b || c == 0x3")?,
+        Expr::Or(
+            Box::new(Expr::Not(Box::new(Expr::Signal("a.b".into())))),
+            Box::new(Expr::Cmp(
+                CmpOp::Eq,
+                Box::new(Expr::Signal("c".into())),
+                Box::new(Expr::Const(3))
+            ))
+        )
+    );
+    assert_eq!(
+        Expr::parse("(a || b) && c")?,
+        Expr::And(
+            Box::new(Expr::Or(
+                Box::new(Expr::Signal("a".into())),
+                Box::new(Expr::Signal("b".into()))
+            )),
+            Box::new(Expr::Signal("c".into()))
+        ),
+        "Parenthesised expressions bind first"
+    );
+    for s in ["", "a &&", "(a", "a == 0xzz", "a b", "a = 1"] {
+        assert!(Expr::parse(s).is_err(), "Parsing '{s}' should fail");
+    }
+    Ok(())
+}
+
+#[test]
+fn counter_assertions() -> Result<(), String> {
+    type T = Bv<8>;
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    let cntr = sim.instantiate::<Counter<T>, _, _>("counter", || Some(T::of_u64(0)))?;
+    sim.connect_clock(clk, cntr, 0);
+    sim.prepare_simulation();
+
+    sim.add_assertion(
+        "below_ten",
+        clk,
+        Property::always("!counter.reset_n || counter.q < 10")?,
+    )?;
+    sim.add_assertion(
+        "three_then_five",
+        clk,
+        Property::implies_within("counter.q == 3", "counter.q == 5", 2)?,
+    )?;
+    sim.add_assertion(
+        "three_then_big",
+        clk,
+        Property::implies("counter.q == 3", "counter.q >= 200", 1, 2)?,
+    )?;
+    assert!(sim
+        .add_assertion("clock", clk, Property::always("counter.clk")?)
+        .is_err());
+
+    let instances = sim.instances();
+    sim.start(true)?;
+    {
+        let mut inst = instances.inst_mut::<Counter<T>>(cntr);
+        *inst.inputs.reset_n = true;
+        *inst.inputs.increment = true;
+    }
+    let mut three_time = None;
+    for _ in 0..12 {
+        sim.fire_next_edges();
+        if instances.inst::<Counter<T>>(cntr).outputs.data.try_as_u64() == Some(3) {
+            three_time = Some(sim.time());
+        }
+    }
+    sim.stop()?;
+
+    let three_time = three_time.expect("Counter should have reached 3");
+    let failures = sim.take_assertion_failures();
+    assert!(sim.assertion_failures().is_empty());
+
+    let big: Vec<_> = failures
+        .iter()
+        .filter(|f| f.name == "three_then_big")
+        .collect();
+    assert_eq!(big.len(), 1, "{failures:?}");
+    assert_eq!(big[0].instance, "counter");
+    assert!(big[0].time > three_time);
+    assert!(failures.iter().all(|f| f.name != "three_then_five"));
+
+    let below: Vec<_> = failures.iter().filter(|f| f.name == "below_ten").collect();
+    assert!(!below.is_empty(), "{failures:?}");
+    assert_eq!(
+        below[0].values,
+        vec![
+            ("counter.reset_n".to_string(), "1b1".to_string()),
+            ("counter.q".to_string(), "8h0a".to_string())
+        ]
+    );
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use hgl_models::{AxiBurst, AxiMaster, AxiMasterConfig, AxiMemory, AxiOp, AxiResp};
+use hgl_models::{AxiMasterResult, AxiSlaveConfig, SparseMemory};
+use hgl_sim::prelude::sim::*;
+
+#[test]
+fn bursts() {
+    assert_eq!(
+        AxiBurst::Fixed.addresses(0x10, 3),
+        Ok(vec![0x10, 0x10, 0x10])
+    );
+    assert_eq!(
+        AxiBurst::Incr.addresses(0x10, 3),
+        Ok(vec![0x10, 0x14, 0x18])
+    );
+    assert_eq!(
+        AxiBurst::Wrap.addresses(0x18, 4),
+        Ok(vec![0x18, 0x1c, 0x10, 0x14])
+    );
+    // Bursts at the top of the address space
+    assert_eq!(
+        AxiBurst::Wrap.addresses(0xffff_fff8, 4),
+        Ok(vec![0xffff_fff8, 0xffff_fffc, 0xffff_fff0, 0xffff_fff4])
+    );
+    assert_eq!(
+        AxiBurst::Incr.addresses(0xffff_fff8, 4),
+        Ok(vec![0xffff_fff8, 0xffff_fffc, 0, 4])
+    );
+    assert!(AxiBurst::Wrap.check(3).is_err());
+    assert!(AxiBurst::Wrap.addresses(0x10, 3).is_err());
+    assert!(AxiBurst::Incr.addresses(0x10, 0).is_err());
+    assert!(AxiBurst::Fixed.check(17).is_err());
+    assert!(AxiBurst::Incr.check(256).is_ok());
+    assert_eq!(AxiResp::of_encoding(2), AxiResp::SlvErr);
+    assert!(AxiResp::DecErr.is_error());
+}
+
+//ti RunResult
+type RunResult = (Vec<AxiMasterResult>, SparseMemory, (usize, usize));
+
+//fi run
+/// Run a master and memory for batches of operations, each until the
+/// master is idle (as reads and writes are not ordered with respect
+/// to each other), returning the completed operations, the memory and
+/// its maximum outstanding writes and reads
+fn run(
+    master_config: AxiMasterConfig,
+    slave_config: AxiSlaveConfig<SparseMemory>,
+    batches: Vec<Vec<AxiOp>>,
+) -> Result<RunResult, String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    let mem = sim.instantiate::<AxiMemory, _, _>("mem", || slave_config)?;
+    let master = sim.instantiate::<AxiMaster, _, _>("master", || master_config)?;
+    sim.connect_clock(clk, mem, 0);
+    sim.prepare_simulation();
+    assert!(AxiMaster::attach(&sim, clk, mem, "mem.axi_request", "mem.axi_response").is_err());
+    AxiMaster::attach(&sim, clk, master, "mem.axi_request", "mem.axi_response")?;
+
+    let instances = sim.instances();
+    sim.start(true)?;
+    for ops in batches {
+        for op in ops {
+            instances.inst_mut::<AxiMaster>(master).queue(op)?;
+        }
+        let mut cycles = 0;
+        while !instances.inst::<AxiMaster>(master).is_idle() {
+            sim.fire_next_edges();
+            cycles += 1;
+            assert!(cycles < 1000, "Master should complete its operations");
+        }
+    }
+    sim.stop()?;
+
+    let mut m = instances.inst_mut::<AxiMaster>(master);
+    assert_eq!(m.protocol_errors(), &[] as &[String]);
+    let s = instances.inst::<AxiMemory>(mem);
+    assert_eq!(s.protocol_errors(), &[] as &[String]);
+    Ok((m.take_completed(), s.storage().clone(), s.max_outstanding()))
+}
+
+#[test]
+fn memory_bursts() -> Result<(), String> {
+    let data: Vec<u32> = (0..8).map(|i| 0x1000 + i).collect();
+    for (ready_percent, latency) in [(100, 0), (50, 3)] {
+        let master_config = AxiMasterConfig {
+            max_outstanding: 2,
+            ready_percent,
+            seed: 1,
+            ..Default::default()
+        };
+        let slave_config = AxiSlaveConfig {
+            max_outstanding: 2,
+            latency,
+            ready_percent,
+            seed: 2,
+            ..AxiSlaveConfig::new(SparseMemory::new(0x1000))
+        };
+        let writes = vec![
+            AxiOp::write(1, 0x100, &data),
+            AxiOp::write(2, 0x208, &data[0..4]).with_burst(AxiBurst::Wrap),
+            AxiOp::write(3, 0x300, &[1, 2]).with_burst(AxiBurst::Fixed),
+            AxiOp::write(4, 0x2000, &[5]),
+        ];
+        let reads = vec![
+            AxiOp::read(5, 0x100, 8),
+            AxiOp::read(6, 0x200, 4),
+            AxiOp::read(7, 0x300, 1),
+            AxiOp::read(8, 0x2000, 2),
+        ];
+        let (results, memory, (max_writes, max_reads)) =
+            run(master_config, slave_config, vec![writes, reads])?;
+        assert_eq!(results.len(), 8);
+        let result = |id| results.iter().find(|r| r.op.id() == id).unwrap();
+
+        for id in 1..=3 {
+            assert_eq!(result(id).resp, AxiResp::Okay);
+        }
+        assert_eq!(result(4).resp, AxiResp::SlvErr, "Write beyond memory");
+        assert_eq!(result(5).rdata, data);
+        assert_eq!(result(6).rdata, vec![0x1002, 0x1003, 0x1000, 0x1001]);
+        assert_eq!(result(7).rdata, vec![2], "Fixed burst keeps last beat");
+        assert_eq!(result(8).resp, AxiResp::SlvErr, "Read beyond memory");
+        assert_eq!(result(8).rdata.len(), 2);
+        assert_eq!(memory.peek(0x208), Some(0x1000));
+        assert_eq!(memory.peek(0x2000), None);
+
+        assert!(max_writes <= 2 && max_reads <= 2);
+        assert!(
+            max_writes == 2 && max_reads == 2,
+            "Outstanding transactions"
+        );
+        for r in &results {
+            assert!(r.completed > r.started && r.started >= r.queued);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn lite() -> Result<(), String> {
+    let master_config = AxiMasterConfig {
+        lite: true,
+        ..Default::default()
+    };
+    let mut sim = Simulation::new();
+    let master = sim.instantiate::<AxiMaster, _, _>("master", || master_config)?;
+    assert!(sim
+        .instantiate::<AxiMaster, _, _>("bad", || AxiMasterConfig {
+            max_outstanding: 0,
+            ..Default::default()
+        })
+        .is_err());
+    sim.prepare_simulation();
+    {
+        let instances = sim.instances();
+        let mut m = instances.inst_mut::<AxiMaster>(master);
+        assert!(m.read(0, 0x100, 2).is_err(), "Lite bursts are single beats");
+        assert!(m.read(1, 0x100, 1).is_err(), "Lite IDs are zero");
+        assert!(m.read(0, 0x102, 1).is_err(), "Addresses are word aligned");
+        assert!(m.read(0, 0x100, 1).is_ok());
+    }
+
+    let writes = vec![
+        AxiOp::write(0, 0x10, &[0x1234]),
+        AxiOp::write(0, 0x14, &[0x5678]),
+    ];
+    let reads = vec![AxiOp::read(0, 0x14, 1), AxiOp::read(0, 0x10, 1)];
+    let slave_config = AxiSlaveConfig::new(SparseMemory::new(0x100));
+    let (results, _, _) = run(master_config, slave_config, vec![writes, reads])?;
+    let reads: Vec<_> = results
+        .iter()
+        .filter(|r| matches!(r.op, AxiOp::Read { .. }))
+        .map(|r| r.rdata[0])
+        .collect();
+    assert_eq!(reads, vec![0x5678, 0x1234]);
+
+    let mut m = AxiMaster::default();
+    assert!(m.read(0, 0xff8, 4).is_err(), "Bursts may not cross 4kB");
+    Ok(())
+}
@@ -1,6 +1,56 @@
 use hgl_indexed_vec::Idx;
+use hgl_models::Counter;
 use hgl_sim::prelude::sim::*;
 
+/// Clocks as (delay, period, negedge_offset)
+type ClockSet<'a> = &'a [(usize, usize, usize)];
+
+//fi expected_edges
+/// Determine the edges of a set of clocks at a time, from their
+/// definitions
+fn expected_edges(clocks: ClockSet, time: usize) -> SimEdgeMask {
+    let mut edges = SimEdgeMask::none();
+    for (i, &(delay, period, offset)) in clocks.iter().enumerate() {
+        if time < delay {
+            continue;
+        }
+        if time % period == delay % period {
+            edges.set_posedge(i);
+        }
+        if offset > 0 && (time + period - offset) % period == delay % period {
+            edges.set_negedge(i);
+        }
+    }
+    edges
+}
+
+//fi check_schedule
+/// Check the edges of a simulation of a set of clocks up to a time,
+/// returning the number of steps in its compiled schedule
+fn check_schedule(clocks: ClockSet, end: usize) -> Result<Option<usize>, String> {
+    let mut sim = Simulation::new();
+    for (i, &(delay, period, offset)) in clocks.iter().enumerate() {
+        sim.add_clock(&format!("clk{i}"), delay, period, offset)?;
+    }
+    sim.prepare_simulation();
+    sim.start(true)?;
+    let mut time = 0;
+    loop {
+        while expected_edges(clocks, time).is_none() {
+            time += 1;
+        }
+        if time > end {
+            break;
+        }
+        let edges = sim.fire_next_edges();
+        assert_eq!(sim.time(), time);
+        assert_eq!(edges, expected_edges(clocks, time), "Edges at time {time}");
+        time += 1;
+    }
+    sim.stop()?;
+    Ok(sim.schedule_steps())
+}
+
 #[test]
 fn sim_clocks() -> Result<(), String> {
     let mut sim = Simulation::new();
@@ -18,6 +68,124 @@ fn sim_clocks() -> Result<(), String> {
     Ok(())
 }
 
+#[test]
+fn sim_schedule() -> Result<(), String> {
+    // Hyperperiod of 210, once the clocks are all enabled at 17
+    assert!(check_schedule(&[(0, 21, 0), (17, 5, 3), (3, 14, 0)], 1000)?.is_some());
+    assert!(check_schedule(&[(0, 2, 1)], 100)?.is_some());
+    assert!(check_schedule(&[(5, 3, 1), (0, 4, 2), (2, 6, 3), (1, 1, 0)], 500)?.is_some());
+
+    // Too long a hyperperiod to compile, so the clocks are stepped
+    // directly
+    let steps = check_schedule(&[(0, 1009, 500), (3, 1013, 0), (1, 7, 3)], 5000)?;
+    assert_eq!(steps, None);
+    Ok(())
+}
+
+#[test]
+fn sim_schedule_connect() -> Result<(), String> {
+    type T = Bv<16>;
+    let mut sim = Simulation::new();
+    let fast = sim.add_clock("fast", 0, 2, 1)?;
+    let slow = sim.add_clock("slow", 1, 6, 3)?;
+    let a = sim.instantiate::<Counter<T>, _, _>("a", || None)?;
+    let b = sim.instantiate::<Counter<T>, _, _>("b", || None)?;
+    sim.connect_clock(fast, a, 0);
+    sim.prepare_simulation();
+    let instances = sim.instances();
+    sim.start(true)?;
+    for c in [a, b] {
+        let mut inst = instances.inst_mut::<Counter<T>>(c);
+        *inst.inputs.reset_n = true;
+        *inst.inputs.increment = true;
+    }
+    let count = |c| {
+        instances
+            .inst::<Counter<T>>(c)
+            .outputs
+            .data
+            .try_as_u64()
+            .unwrap()
+    };
+
+    // Clocks may be connected once the schedule is compiled
+    while sim.time() < 12 {
+        sim.fire_next_edges();
+    }
+    assert!(sim.schedule_steps().is_some());
+    assert_eq!((count(a), count(b)), (7, 0));
+    sim.connect_clock(slow, b, 0);
+    while sim.time() < 60 {
+        sim.fire_next_edges();
+    }
+    assert_eq!((count(a), count(b)), (31, 8));
+    sim.stop()?;
+    Ok(())
+}
+
+#[test]
+fn sim_many_clocks() -> Result<(), String> {
+    // Clocks beyond the 32nd use the upper words of the edge masks
+    let clocks: Vec<_> = (0..40).map(|i| (i % 7, 2 + i % 5, 1)).collect();
+    assert!(check_schedule(&clocks, 500)?.is_some());
+
+    type T = Bv<16>;
+    let mut sim = Simulation::new();
+    let mut clks = vec![];
+    for i in 0..40 {
+        clks.push(sim.add_clock(&format!("clk{i}"), 0, 4 + i, 1)?);
+    }
+    let cntr = sim.instantiate::<Counter<T>, _, _>("counter", || None)?;
+    sim.connect_clock(clks[38], cntr, 0);
+    sim.prepare_simulation();
+    let instances = sim.instances();
+    sim.start(true)?;
+    {
+        let mut inst = instances.inst_mut::<Counter<T>>(cntr);
+        *inst.inputs.reset_n = true;
+        *inst.inputs.increment = true;
+    }
+    while sim.time() < 420 {
+        let edges = sim.fire_next_edges();
+        assert_eq!(edges.is_posedge(38), sim.time().is_multiple_of(42));
+    }
+    let data = instances.inst::<Counter<T>>(cntr).outputs.data;
+    assert_eq!(data.try_as_u64(), Some(11));
+    assert_eq!(sim.edge_count(clks[38], true), 11);
+    assert_eq!(sim.edge_count(clks[1], false), 84);
+    sim.stop()?;
+    Ok(())
+}
+
+#[test]
+fn sim_too_many_clocks() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    for i in 0..SimEdgeMask::MAX_INPUTS {
+        sim.add_clock(&format!("clk{i}"), 0, 2, 1)?;
+    }
+    assert!(sim.add_clock("one_too_many", 0, 2, 1).is_err());
+
+    assert!(SimEdgeMask::check_input(SimEdgeMask::MAX_INPUTS - 1).is_ok());
+    assert!(SimEdgeMask::check_input(SimEdgeMask::MAX_INPUTS).is_err());
+    let edges = SimEdgeMask::none().add_negedge(SimEdgeMask::MAX_INPUTS - 1);
+    assert!(edges.is_negedge(SimEdgeMask::MAX_INPUTS - 1));
+    assert!(!edges.is_posedge(SimEdgeMask::MAX_INPUTS - 1));
+    assert!(!edges.is_negedge(SimEdgeMask::MAX_INPUTS));
+    let edges = edges.add_posedge(0).add_posedge(33);
+    let all: Vec<_> = edges.iter_edges().collect();
+    assert_eq!(
+        all,
+        vec![(0, true), (33, true), (SimEdgeMask::MAX_INPUTS - 1, false)]
+    );
+    Ok(())
+}
+
+#[test]
+#[should_panic]
+fn sim_bad_edge() {
+    let _ = SimEdgeMask::none().add_posedge(SimEdgeMask::MAX_INPUTS);
+}
+
 #[test]
 #[should_panic]
 fn sim_bad_period() {
@@ -0,0 +1,84 @@
+use hgl_models::Counter;
+use hgl_sim::prelude::sim::*;
+
+#[test]
+fn counter_coverage() -> Result<(), String> {
+    type T = Bv<4>;
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 2, 1)?;
+    let cntr = sim.instantiate::<Counter<T>, _, _>("counter", || Some(T::of_u64(0)))?;
+    sim.connect_clock(clk, cntr, 0);
+    sim.prepare_simulation();
+
+    sim.add_covergroup(
+        CoverGroup::new("counter", clk)
+            .when("counter.reset_n")
+            .point(
+                CoverPoint::new("q", "counter.q")
+                    .range("low", 0, 3)
+                    .value("eight", 8)
+                    .range("high", 12, 15),
+            )
+            .point(CoverPoint::new("q_auto", "counter.q").auto(4))
+            .point(CoverPoint::new("increment", "counter.increment").variants(&[("on", 1)]))
+            .cross("q_x_increment", &["q", "increment"]),
+    )?;
+    sim.add_covergroup(
+        CoverGroup::new("negedge", clk)
+            .on_negedge()
+            .point(CoverPoint::new("q", "counter.q").toggle()),
+    )?;
+    for group in [
+        CoverGroup::new("missing", clk).point(CoverPoint::new("x", "counter.x").auto(2)),
+        CoverGroup::new("no_bins", clk).point(CoverPoint::new("q", "counter.q")),
+        CoverGroup::new("cross", clk)
+            .point(CoverPoint::new("q", "counter.q").auto(2))
+            .cross("bad", &["q", "r"]),
+        CoverGroup::new("condition", clk)
+            .when("counter.q &&")
+            .point(CoverPoint::new("q", "counter.q").auto(2)),
+    ] {
+        assert!(sim.add_covergroup(group).is_err());
+    }
+
+    let instances = sim.instances();
+    sim.start(true)?;
+    // Not sampled while in reset
+    sim.fire_next_edges();
+    sim.fire_next_edges();
+    {
+        let mut inst = instances.inst_mut::<Counter<T>>(cntr);
+        *inst.inputs.reset_n = true;
+        *inst.inputs.increment = true;
+    }
+    // 10 posedges with q of 1 to 10
+    for _ in 0..20 {
+        sim.fire_next_edges();
+    }
+    sim.stop()?;
+
+    let report = sim.coverage_report();
+    let group = report.group("counter").unwrap();
+    assert_eq!(group.samples, 10);
+    let hits = |p: &CoverPointReport| p.bins.iter().map(|b| b.hits).collect::<Vec<_>>();
+    assert_eq!(hits(&group.points[0]), vec![3, 1, 0]);
+    assert_eq!(hits(&group.points[1]), vec![3, 4, 3, 0]);
+    assert_eq!(group.points[1].bins[3].name, "auto[3]");
+    assert_eq!(hits(&group.crosses[0]), vec![3, 1, 0]);
+    assert_eq!(group.crosses[0].bins[1].name, "eight,on");
+
+    let negedge = report.group("negedge").unwrap();
+    assert_eq!(negedge.points[0].bins.len(), 8);
+    assert_eq!(
+        negedge.points[0].bins[0].hits, 5,
+        "Bit 0 rises every other count"
+    );
+    assert_eq!(negedge.points[0].bins[7].name, "3.fall");
+    assert_eq!(negedge.points[0].bins[7].hits, 0);
+
+    let mut merged = report.clone();
+    merged.merge(&report);
+    assert_eq!(merged.group("counter").unwrap().samples, 20);
+    assert_eq!(merged.percent(), report.percent());
+    Ok(())
+}
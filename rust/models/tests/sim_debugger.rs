@@ -0,0 +1,61 @@
+use hgl_models::Counter;
+use hgl_sim::prelude::sim::*;
+
+#[test]
+fn sim_debugger() -> Result<(), String> {
+    type T = Bv<16>;
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 2, 1)?;
+    let cntr = sim.instantiate::<Counter<T>, _, _>("counter", || Some(T::of_u64(0)))?;
+    sim.connect_clock(clk, cntr, 0);
+    sim.prepare_simulation();
+    sim.start(true)?;
+
+    let script = "deposit counter.reset_n 1
+deposit counter.increment 1
+break counter.q == 0x5
+run
+print counter.q
+breaks
+delete 0
+deposit counter.d 0b1000_0000
+run 40
+print counter.q bin
+time
+print counter
+list
+print counter.nonexistent
+frobnicate
+quit
+print counter.q
+";
+    let mut output = vec![];
+    Debugger::new(&sim)
+        .run_shell(script.as_bytes(), &mut output)
+        .map_err(|e| e.to_string())?;
+    let output = String::from_utf8(output).unwrap();
+
+    assert!(
+        output.contains("breakpoint 0 hit at time 8: counter.q = 0x5"),
+        "{output}"
+    );
+    assert!(output.contains("counter.q = 16h0005"), "{output}");
+    assert!(output.contains("0: counter.q == 0x5"), "{output}");
+    // 16 posedges from time 10 to time 40
+    assert!(
+        output.contains("counter.q = 16b0000000010010000"),
+        "{output}"
+    );
+    assert!(output.contains("hgl> 40\n"), "{output}");
+    assert!(output.contains("counter.increment = 1b1"), "{output}");
+    assert!(output.contains("hgl> counter\n  clk"), "{output}");
+    assert!(output.contains("error: Failed to find state or instance"));
+    assert!(output.contains("error: Bad command 'frobnicate'"));
+    assert_eq!(
+        output.matches("counter.q = ").count(),
+        4,
+        "Commands after quit must not be executed: {output}"
+    );
+    sim.stop()?;
+    Ok(())
+}
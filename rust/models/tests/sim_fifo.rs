@@ -0,0 +1,178 @@
+use hgl_indexed_vec::Idx;
+use hgl_models::{AsyncFifo, Fifo, FifoConfig};
+use hgl_sim::prelude::sim::*;
+
+type Fifo8 = Fifo<Bv<8>>;
+type AsyncFifo8 = AsyncFifo<Bv<8>>;
+
+#[test]
+fn configs() {
+    assert!(FifoConfig::new(0).validate().is_err());
+    assert!(FifoConfig {
+        almost_full: 5,
+        ..FifoConfig::new(4)
+    }
+    .validate()
+    .is_err());
+    assert!(FifoConfig::new(3).validate().is_ok());
+
+    let mut sim = Simulation::new();
+    assert!(sim
+        .instantiate::<AsyncFifo8, _, _>("npot", || FifoConfig::new(3))
+        .is_err());
+    assert!(sim
+        .instantiate::<AsyncFifo8, _, _>("unsynchronized", || FifoConfig {
+            sync_stages: 0,
+            ..FifoConfig::new(4)
+        })
+        .is_err());
+}
+
+#[test]
+fn sync_fifo() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    let fifo = sim.instantiate::<Fifo8, _, _>("fifo", || FifoConfig {
+        almost_empty: 1,
+        almost_full: 2,
+        ..FifoConfig::new(3)
+    })?;
+    sim.connect_clock(clk, fifo, 0);
+    sim.prepare_simulation();
+    let instances = sim.instances();
+    sim.start(true)?;
+
+    let step = |push: Option<u64>, pop: bool| {
+        {
+            let mut f = instances.inst_mut::<Fifo8>(fifo);
+            let inputs = f.inputs_mut();
+            inputs.push = push.is_some().into();
+            inputs.data_in.set_u64(push.unwrap_or_default());
+            inputs.pop = pop.into();
+        }
+        sim.fire_next_edges();
+        let f = instances.inst::<Fifo8>(fifo);
+        let o = f.outputs();
+        (
+            f.len(),
+            o.data_out.try_as_u64().unwrap(),
+            [o.full, o.almost_full, o.empty, o.almost_empty].map(|b| b.is_true()),
+            [o.overflow, o.underflow].map(|b| b.is_true()),
+        )
+    };
+
+    let f = instances.inst::<Fifo8>(fifo);
+    assert!(f.outputs().empty.is_true() && f.outputs().almost_empty.is_true());
+    drop(f);
+
+    assert_eq!(
+        step(None, true),
+        (0, 0, [false, false, true, true], [false, true])
+    );
+    assert_eq!(
+        step(Some(1), false),
+        (1, 1, [false, false, false, true], [false; 2])
+    );
+    assert_eq!(
+        step(Some(2), false),
+        (2, 1, [false, true, false, false], [false; 2])
+    );
+    assert_eq!(
+        step(Some(3), false),
+        (3, 1, [true, true, false, false], [false; 2])
+    );
+    assert_eq!(
+        step(Some(4), false),
+        (3, 1, [true, true, false, false], [true, false])
+    );
+    assert_eq!(
+        step(Some(5), true),
+        (2, 2, [false, true, false, false], [true, false]),
+        "Push ignored when full"
+    );
+    assert_eq!(
+        step(Some(6), true),
+        (2, 3, [false, true, false, false], [false; 2])
+    );
+    assert_eq!(
+        step(None, true),
+        (1, 6, [false, false, false, true], [false; 2])
+    );
+    assert_eq!(
+        step(None, true),
+        (0, 0, [false, false, true, true], [false; 2])
+    );
+    Ok(())
+}
+
+#[test]
+fn async_fifo() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    let wr_clk = sim.add_clock("wr_clk", 0, 2, 1)?;
+    let rd_clk = sim.add_clock("rd_clk", 1, 5, 2)?;
+    let fifo = sim.instantiate::<AsyncFifo8, _, _>("fifo", || FifoConfig::new(4))?;
+    sim.connect_clock(wr_clk, fifo, 0);
+    sim.connect_clock(rd_clk, fifo, 1);
+    sim.prepare_simulation();
+    let instances = sim.instances();
+    sim.start(true)?;
+
+    // Push one entry, and count the read clock edges until it is seen
+    {
+        let mut f = instances.inst_mut::<AsyncFifo8>(fifo);
+        f.inputs_mut().push = true.into();
+        f.inputs_mut().data_in.set_u64(0x5a);
+    }
+    while !sim.fire_next_edges().is_posedge(wr_clk.index()) {}
+    instances.inst_mut::<AsyncFifo8>(fifo).inputs_mut().push = false.into();
+    let mut rd_edges = 0;
+    while instances.inst::<AsyncFifo8>(fifo).outputs().empty.is_true() {
+        if sim.fire_next_edges().is_posedge(rd_clk.index()) {
+            rd_edges += 1;
+        }
+        assert!(rd_edges <= 2, "Synchronizer latency of two read clocks");
+    }
+    assert_eq!(rd_edges, 2, "Synchronizer latency of two read clocks");
+    let f = instances.inst::<AsyncFifo8>(fifo);
+    assert_eq!(f.outputs().data_out.try_as_u64(), Some(0x5a));
+    assert_eq!((f.write_count(), f.read_count()), (1, 1));
+    drop(f);
+
+    // Stream data through, pushing whenever not full and popping
+    // whenever not empty
+    let mut pushed: Vec<u64> = vec![0x5a];
+    let mut popped = vec![];
+    let mut popping = None;
+    let mut was_full = false;
+    while popped.len() < 20 {
+        let edges = sim.fire_next_edges();
+        let mut f = instances.inst_mut::<AsyncFifo8>(fifo);
+        assert!(f.outputs().overflow.is_false() && f.outputs().underflow.is_false());
+        if edges.is_posedge(rd_clk.index()) {
+            if let Some(data) = popping.take() {
+                popped.push(data);
+            }
+            if f.outputs().empty.is_false() {
+                popping = f.outputs().data_out.try_as_u64();
+            }
+            f.inputs_mut().pop = popping.is_some().into();
+        }
+        if edges.is_posedge(wr_clk.index()) {
+            if f.inputs().push.is_true() {
+                pushed.push(f.inputs().data_in.try_as_u64().unwrap());
+            }
+            let full = f.outputs().full.is_true();
+            was_full |= full;
+            f.inputs_mut().push = (!full && pushed.len() < 30).into();
+            f.inputs_mut().data_in.set_u64(pushed.len() as u64);
+        }
+    }
+    assert!(was_full, "Faster writer should fill the FIFO");
+    assert_eq!(
+        popped,
+        (0..20)
+            .map(|i| if i == 0 { 0x5a } else { i })
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
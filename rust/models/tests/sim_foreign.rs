@@ -0,0 +1,122 @@
+use hgl_models::{ForeignComponent, ForeignConfig, ForeignPortKind, ForeignTransport};
+use hgl_sim::prelude::sim::*;
+
+/// The stand-in peer, a counter
+const PEER: &str = env!("CARGO_BIN_EXE_hgl_foreign_peer");
+
+//fi counter
+/// Run the peer counter with a transport and step, returning its
+/// count, sum and number of edges after enabling it for 10 cycles
+fn counter(transport: ForeignTransport, step: u64) -> Result<(u64, u64, u64), String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 2, 1)?;
+    let config = ForeignConfig::new(PEER)
+        .arg(&step.to_string())
+        .transport(transport);
+    let peer = sim.instantiate::<ForeignComponent, _, _>("peer", || config)?;
+    sim.connect_clock(clk, peer, 0);
+    sim.prepare_simulation();
+    sim.start(true)?;
+
+    let instances = sim.instances();
+    {
+        let mut p = instances.inst_mut::<ForeignComponent>(peer);
+        assert_eq!(p.ports().len(), 7);
+        assert_eq!(p.ports()[6].kind, ForeignPortKind::Internal);
+        assert!(p.set_input("count", 1).is_err(), "count is an output");
+        p.set_input("reset_n", 1)?;
+        p.set_input("enable", 1)?;
+        // The input is masked to its width
+        p.set_input("data", 0x105)?;
+        assert_eq!(p.input("data"), Some(5));
+    }
+    // Rising and falling edges fire; the counter only uses the rising
+    for _ in 0..20 {
+        sim.fire_next_edges();
+    }
+    instances
+        .inst_mut::<ForeignComponent>(peer)
+        .set_input("enable", 0)?;
+    sim.fire_next_edges();
+    sim.fire_next_edges();
+
+    // The ports are simulation state
+    let (h, s) = sim.find_state("peer.edges").unwrap();
+    let edges = sim.with_state(h, s, |v| v.get_u64()).flatten().unwrap();
+    let (h, s) = sim.find_state("peer.data").unwrap();
+    sim.with_state_mut(h, s, |mut v| v.set_u64(1));
+    instances.inst_mut::<ForeignComponent>(peer).propagate(0);
+
+    let p = instances.inst::<ForeignComponent>(peer);
+    let result = (p.output("count").unwrap(), p.output("sum").unwrap(), edges);
+    drop(p);
+    sim.stop()?;
+    Ok(result)
+}
+
+#[test]
+fn foreign_pipes() -> Result<(), String> {
+    assert_eq!(counter(ForeignTransport::Pipes, 1)?, (10, 11, 11));
+    assert_eq!(counter(ForeignTransport::Pipes, 7)?, (70, 71, 11));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn foreign_unix_socket() -> Result<(), String> {
+    assert_eq!(counter(ForeignTransport::UnixSocket, 3)?, (30, 31, 11));
+    Ok(())
+}
+
+#[test]
+fn foreign_errors() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    let missing = ForeignConfig::new("/nonexistent/hgl_foreign_peer");
+    let e = sim
+        .instantiate::<ForeignComponent, _, _>("missing", || missing)
+        .unwrap_err();
+    assert!(e.contains("Failed to run"), "{e}");
+
+    // The peer exits with an error on a bad step, before connecting
+    #[cfg(unix)]
+    {
+        let bad = ForeignConfig::new(PEER)
+            .arg("many")
+            .transport(ForeignTransport::UnixSocket);
+        let e = sim
+            .instantiate::<ForeignComponent, _, _>("bad", || bad)
+            .unwrap_err();
+        assert!(e.contains("exited"), "{e}");
+    }
+    Ok(())
+}
+
+#[test]
+fn foreign_crash() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 2, 1)?;
+    let config = ForeignConfig::new(PEER).arg("1").arg("3");
+    let peer = sim.instantiate::<ForeignComponent, _, _>("peer", || config)?;
+    sim.connect_clock(clk, peer, 0);
+    sim.prepare_simulation();
+    sim.start(true)?;
+
+    let instances = sim.instances();
+    instances
+        .inst_mut::<ForeignComponent>(peer)
+        .set_input("reset_n", 1)?;
+    // The peer exits at its third rising edge; the simulation
+    // continues, with the error recorded and the ports unchanged
+    for _ in 0..20 {
+        sim.fire_next_edges();
+    }
+    {
+        let p = instances.inst::<ForeignComponent>(peer);
+        assert!(p.error().is_some());
+        assert_eq!(p.output("edges"), Some(2));
+    }
+    sim.stop()?;
+    let p = instances.inst::<ForeignComponent>(peer);
+    assert!(p.error().is_some_and(|e| !e.is_empty()), "{:?}", p.error());
+    Ok(())
+}
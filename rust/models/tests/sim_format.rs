@@ -0,0 +1,97 @@
+use hgl_models::apb::{t_apb_request, t_apb_response};
+use hgl_models::Register;
+use hgl_sim::prelude::sim::*;
+
+//a An array that does not support bit-copying
+#[derive(Debug)]
+struct Samples(Vec<Bv<4>>);
+
+impl SimValueObject for Samples {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn num_subelements(&self) -> usize {
+        self.0.len()
+    }
+    fn get_subelement(&self, n: usize) -> Option<(&str, &dyn SimValueObject)> {
+        self.0.get(n).map(|v| ("", v as &dyn SimValueObject))
+    }
+}
+
+#[test]
+fn format_structured() -> Result<(), String> {
+    let req = t_apb_request {
+        paddr: 0x10.into(),
+        penable: Bit::T,
+        psel: Bit::T,
+        pwrite: Bit::F,
+        pwdata: 0x1234.into(),
+    };
+    assert_eq!(
+        SimFormatValue::value_string(&req, fmt::AS_HEX | fmt::HDR),
+        "{paddr: 32h00000010, penable: 1, psel: 1, pwrite: 0, pwdata: 32h00001234}"
+    );
+    assert_eq!(
+        SimFormatObject::new(&req, fmt::AS_DEC).to_string(),
+        "{paddr: 16, penable: 1, psel: 1, pwrite: 0, pwdata: 4660}"
+    );
+    assert_eq!(<t_apb_request as SimCopyValue>::BIT_WIDTH, 67);
+    assert_eq!(<t_apb_response as SimCopyValue>::NUM_SUBELEMENTS, 3);
+
+    let samples = Samples(vec![1.into(), 2.into(), 15.into()]);
+    assert_eq!(
+        SimFormatObject::new(&samples, fmt::AS_HEX | fmt::HDR).to_string(),
+        "[4h1, 4h2, 4hf]"
+    );
+    assert_eq!(
+        SimFormatObject::new(&samples, fmt::AS_SIGNED).to_string(),
+        "[1, 2, -1]"
+    );
+    Ok(())
+}
+
+#[test]
+fn format_structured_state() -> Result<(), String> {
+    type T = t_apb_request;
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    let reg = sim.instantiate::<Register<T>, _, _>("reg", || None)?;
+    sim.connect_clock(clk, reg, 0);
+    sim.prepare_simulation();
+    let instances = sim.instances();
+    sim.start(true)?;
+
+    let mut vcd = Vcd::new(vec![], &sim).map_err(|e| e.to_string())?;
+    {
+        let mut r = instances.inst_mut::<Register<T>>(reg);
+        r.inputs.reset_n = Bit::T;
+        r.inputs.enable = Bit::T;
+        r.inputs.data.paddr = 0x20.into();
+        r.inputs.data.psel = Bit::T;
+    }
+    sim.fire_next_edges();
+    vcd.sample(&sim).map_err(|e| e.to_string())?;
+
+    let (handle, state_index) = sim.find_state("reg.q").unwrap();
+    let s = sim
+        .with_state(handle, state_index, |v| {
+            SimFormatObject::new(v.sim_value(), fmt::AS_HEX | fmt::HDR).to_string()
+        })
+        .unwrap();
+    assert_eq!(
+        s,
+        "{paddr: 32h00000020, penable: 0, psel: 1, pwrite: 0, pwdata: 32h00000000}"
+    );
+    let full = format!("{:?}", sim);
+    assert!(full.contains("psel: 1"), "{full}");
+
+    let vcd = String::from_utf8(vcd.into_inner()).unwrap();
+    assert!(vcd.contains(" q.paddr $end"), "{vcd}");
+    assert!(
+        vcd.contains("$var wire 1 ") && vcd.contains(" q.psel $end"),
+        "{vcd}"
+    );
+    assert!(vcd.contains("b00000000000000000000000000100000 "), "{vcd}");
+    sim.stop()?;
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use hgl_models::{GdbStub, Memory, Rv32i, Rv32iConfig, Rv32iPort};
+use hgl_sim::prelude::sim::*;
+
+type Mem = Memory<Bv<32>, Bv<16>>;
+
+/// The address at which the data memory is presented to GDB
+const DMEM: u64 = 0x1000_0000;
+
+const PROGRAM: [u32; 5] = [
+    0x0050_0093, // 00: addi x1, x0, 5
+    0x0070_0113, // 04: addi x2, x0, 7
+    0x0020_81b3, // 08: add x3, x1, x2
+    0x1030_2023, // 0c: sw x3, 0x100(x0)
+    0x0000_006f, // 10: jal x0, 0
+];
+
+//a Client
+//tp Client
+/// A scripted GDB client
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    ack: bool,
+}
+
+//ip Client
+impl Client {
+    fn connect(addr: std::net::SocketAddr) -> Self {
+        let writer = TcpStream::connect(addr).unwrap();
+        writer.set_nodelay(true).unwrap();
+        Self {
+            reader: BufReader::new(writer.try_clone().unwrap()),
+            writer,
+            ack: true,
+        }
+    }
+
+    fn byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.reader.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    fn send_raw(&mut self, data: &str, checksum: u8) {
+        write!(self.writer, "${data}#{checksum:02x}").unwrap();
+        self.writer.flush().unwrap();
+    }
+
+    fn send(&mut self, data: &str) {
+        self.send_raw(data, data.bytes().fold(0_u8, |s, b| s.wrapping_add(b)));
+        if self.ack {
+            assert_eq!(self.byte(), b'+');
+        }
+    }
+
+    fn receive(&mut self) -> String {
+        while self.byte() != b'$' {}
+        let mut data = vec![];
+        self.reader.read_until(b'#', &mut data).unwrap();
+        data.pop();
+        let mut checksum = [0; 2];
+        self.reader.read_exact(&mut checksum).unwrap();
+        let checksum = u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap();
+        assert_eq!(data.iter().fold(0_u8, |s, b| s.wrapping_add(*b)), checksum);
+        if self.ack {
+            self.writer.write_all(b"+").unwrap();
+        }
+        String::from_utf8(data).unwrap()
+    }
+
+    fn packet(&mut self, data: &str) -> String {
+        self.send(data);
+        self.receive()
+    }
+}
+
+//a Server
+//fi serve
+/// Build a core with instruction and data memories, and serve GDB on
+/// the listener, returning the number of instructions retired
+fn serve(listener: TcpListener) -> Result<usize, String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    let cpu = sim.instantiate::<Rv32i, _, _>("cpu", Rv32iConfig::default)?;
+    let imem = sim.instantiate::<Mem, _, _>("imem", || 1024)?;
+    let dmem = sim.instantiate::<Mem, _, _>("dmem", || 1024)?;
+    for i in [cpu, imem, dmem] {
+        sim.connect_clock(clk, i, 0);
+    }
+    sim.prepare_simulation();
+    Rv32i::connect_memory(&sim, cpu, Rv32iPort::Instruction, "imem")?;
+    Rv32i::connect_memory(&sim, cpu, Rv32iPort::Data, "dmem")?;
+    sim.instances().inst_mut::<Mem>(imem).load(
+        0,
+        &PROGRAM
+            .iter()
+            .map(|w| (*w as u64).into())
+            .collect::<Vec<_>>(),
+    )?;
+    sim.start(true)?;
+
+    let mut stub = GdbStub::new(&sim, cpu);
+    stub.add_memory::<Mem>(0, imem);
+    stub.add_memory::<Mem>(DMEM, dmem);
+    stub.serve_tcp(&listener).map_err(|e| e.to_string())?;
+    assert_eq!(stub.breakpoints().count(), 0);
+    let retired = sim.instances().inst::<Rv32i>(cpu).retired();
+    Ok(retired)
+}
+
+#[test]
+fn gdb_stub() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    // The simulation is not Send, so it is built on the server thread
+    let server = std::thread::spawn(move || serve(listener));
+    let mut gdb = Client::connect(addr);
+
+    assert!(gdb.packet("qSupported:swbreak+").contains("PacketSize"));
+    assert_eq!(gdb.packet("vMustReplyEmpty"), "");
+    assert_eq!(
+        gdb.packet("\u{e9}"),
+        "",
+        "Unknown non-ASCII commands are unsupported"
+    );
+    assert_eq!(gdb.packet("?"), "S05");
+    let registers = gdb.packet("g");
+    assert_eq!(registers.len(), 33 * 8);
+    assert!(registers.bytes().all(|b| b == b'0'));
+
+    // A packet with a bad checksum is rejected, and may be resent
+    gdb.send_raw("g", 0);
+    assert_eq!(gdb.byte(), b'-');
+
+    // Run to a breakpoint, and step
+    assert_eq!(gdb.packet("Z0,8,4"), "OK");
+    assert_eq!(gdb.packet("c"), "S05");
+    assert_eq!(gdb.packet("p20"), "08000000");
+    assert_eq!(gdb.packet("p1"), "05000000");
+    assert_eq!(gdb.packet("s"), "S05");
+    assert_eq!(gdb.packet("p20"), "0c000000");
+    assert_eq!(gdb.packet("p3"), "0c000000");
+    assert_eq!(gdb.packet("z0,8,4"), "OK");
+    assert_eq!(gdb.packet("p21"), "E01");
+
+    // Change the register to be stored, and run to the loop
+    assert_eq!(gdb.packet("P3=44332211"), "OK");
+    assert_eq!(gdb.packet("Z1,10,4"), "OK");
+    assert_eq!(gdb.packet("c"), "S05");
+    assert_eq!(gdb.packet("p20"), "10000000");
+    // The store is posted to the memory, and completes as the loop
+    // executes
+    assert_eq!(gdb.packet("s"), "S05");
+    assert_eq!(gdb.packet("m10000100,4"), "44332211");
+    assert_eq!(gdb.packet("m8,8"), "b3812000 23203010".replace(' ', ""));
+    assert_eq!(gdb.packet("M10000200,4:deadbeef"), "OK");
+    assert_eq!(gdb.packet("m10000200,4"), "deadbeef");
+    assert_eq!(gdb.packet("m10001000,4"), "E01");
+    assert_eq!(gdb.packet("M10000200,4:dead"), "E01");
+    assert_eq!(gdb.packet("z1,10,4"), "OK");
+
+    // Continue in the loop until interrupted
+    gdb.send("c");
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    gdb.writer.write_all(&[3]).unwrap();
+    assert_eq!(gdb.receive(), "S02");
+    assert_eq!(gdb.packet("p20"), "10000000");
+
+    // Without acknowledgements, jump to an EBREAK written by GDB
+    assert_eq!(gdb.packet("QStartNoAckMode"), "OK");
+    gdb.ack = false;
+    assert_eq!(gdb.packet("M14,4:73001000"), "OK");
+    assert_eq!(gdb.packet("P20=14000000"), "OK");
+    assert_eq!(gdb.packet("c"), "S05");
+    assert_eq!(gdb.packet("p20"), "14000000");
+    assert_eq!(gdb.packet("s"), "S05", "The core stays halted");
+    assert_eq!(gdb.packet("D"), "OK");
+
+    assert!(server.join().unwrap().unwrap() > 6);
+}
@@ -0,0 +1,191 @@
+use hgl_models::interrupt_controller::NO_INTERRUPT;
+use hgl_models::{add_apb_assertions, ApbAddressMap, ApbDecoder, ApbMaster, ApbOp};
+use hgl_models::{InterruptController, Timer};
+use hgl_sim::prelude::sim::*;
+
+const TIMER0: u32 = 0x00;
+const TIMER1: u32 = 0x40;
+const INTC: u32 = 0x100;
+
+//tp Bench
+/// Two timers and an interrupt controller behind an APB decoder, with
+/// the timer interrupts as sources 0 and 1 of the controller
+struct Bench<'a> {
+    sim: Simulation<'a>,
+    master: InstanceHandle,
+    timer0: InstanceHandle,
+    intc: InstanceHandle,
+}
+
+//ip Bench
+impl Bench<'_> {
+    //cp new
+    fn new() -> Result<Self, String> {
+        let mut sim = Simulation::new();
+        let clk = sim.add_clock("clk", 0, 1, 0)?;
+        let timer0 = sim.instantiate::<Timer, _, _>("timer0", || ())?;
+        let timer1 = sim.instantiate::<Timer, _, _>("timer1", || ())?;
+        let intc = sim.instantiate::<InterruptController, _, _>("intc", || 2)?;
+        assert!(sim
+            .instantiate::<InterruptController, _, _>("bad", || 33)
+            .is_err());
+        let bus = sim.instantiate::<ApbDecoder, _, _>("bus", || {
+            ApbAddressMap::default()
+                .target("timer0", TIMER0, 0x40)
+                .target("timer1", TIMER1, 0x40)
+                .target("intc", INTC, 0x100)
+        })?;
+        let master = sim.instantiate::<ApbMaster, _, _>("master", || ())?;
+        for i in [timer0, timer1, intc, master] {
+            sim.connect_clock(clk, i, 0);
+        }
+        sim.prepare_simulation();
+        ApbDecoder::connect(
+            &sim,
+            bus,
+            &[
+                ("timer0.apb_request", "timer0.apb_response"),
+                ("timer1.apb_request", "timer1.apb_response"),
+                ("intc.apb_request", "intc.apb_response"),
+            ],
+        )?;
+        assert!(InterruptController::connect(&sim, intc, &["a", "b", "c"]).is_err());
+        assert!(InterruptController::connect(&sim, intc, &["timer0.apb_response"]).is_err());
+        InterruptController::connect(&sim, intc, &["timer0.irq", "timer1.irq"])?;
+        add_apb_assertions(&sim, clk, "bus.apb_request", "bus.apb_response", 1)?;
+        ApbMaster::attach(&sim, clk, master, "bus.apb_request", "bus.apb_response")?;
+        sim.start(true)?;
+        Ok(Self {
+            sim,
+            master,
+            timer0,
+            intc,
+        })
+    }
+
+    //mp apb
+    /// Perform APB operations, returning the read data
+    fn apb(&self, ops: &[ApbOp]) -> Vec<u32> {
+        let instances = self.sim.instances();
+        for op in ops {
+            instances.inst_mut::<ApbMaster>(self.master).queue(*op);
+        }
+        while !instances.inst::<ApbMaster>(self.master).is_idle() {
+            self.sim.fire_next_edges();
+        }
+        let results = instances
+            .inst_mut::<ApbMaster>(self.master)
+            .take_completed();
+        results.iter().map(|r| r.rdata).collect()
+    }
+
+    //mp irq
+    fn irq(&self) -> bool {
+        let instances = self.sim.instances();
+        let irq = instances.inst::<InterruptController>(self.intc).outputs.irq;
+        irq.is_true()
+    }
+}
+
+fn write(address: u32, data: u32) -> ApbOp {
+    ApbOp::Write { address, data }
+}
+
+fn read(address: u32) -> ApbOp {
+    ApbOp::Read { address }
+}
+
+#[test]
+fn timer() -> Result<(), String> {
+    let bench = Bench::new()?;
+    // Count every 4 cycles to 3, reloading with 1, without interrupts
+    bench.apb(&[
+        write(TIMER0 + 2, 3),
+        write(TIMER0 + 4, 3),
+        write(TIMER0 + 5, 1),
+        write(TIMER0, 0b011),
+    ]);
+    assert_eq!(
+        bench.apb(&[
+            read(TIMER0),
+            read(TIMER0 + 2),
+            read(TIMER0 + 4),
+            read(TIMER0 + 5)
+        ]),
+        vec![0b011, 3, 3, 1]
+    );
+    let counter = || bench.sim.instances().inst::<Timer>(bench.timer0).counter();
+    let mut counts = vec![];
+    for _ in 0..16 {
+        bench.sim.fire_next_edges();
+        counts.push(counter());
+    }
+    counts.dedup();
+    assert!(counts.len() >= 4, "Counter changes every 4 cycles");
+    assert!(
+        counts.iter().all(|c| [1, 2].contains(c)),
+        "Counter reloads on compare"
+    );
+    assert_eq!(bench.apb(&[read(TIMER0 + 1)]), vec![1], "Matched");
+    assert!(!bench.irq(), "Timer interrupt not enabled");
+
+    // One-shot: the timer stops on a match
+    bench.apb(&[
+        write(TIMER0, 0),
+        write(TIMER0 + 1, 1),
+        write(TIMER0 + 2, 0),
+        write(TIMER0 + 3, 0),
+        write(TIMER0, 0b001),
+    ]);
+    for _ in 0..10 {
+        bench.sim.fire_next_edges();
+    }
+    assert_eq!(
+        bench.apb(&[read(TIMER0), read(TIMER0 + 1), read(TIMER0 + 3)]),
+        vec![0, 1, 3]
+    );
+    Ok(())
+}
+
+#[test]
+fn interrupts() -> Result<(), String> {
+    let bench = Bench::new()?;
+    bench.apb(&[
+        write(INTC + 1, 0b11),
+        write(INTC + 32 + 1, 5),
+        write(TIMER0 + 4, 2),
+        write(TIMER0, 0b101),
+        write(TIMER1 + 4, 4),
+        write(TIMER1, 0b101),
+    ]);
+    for _ in 0..10 {
+        bench.sim.fire_next_edges();
+    }
+    assert!(bench.irq());
+    assert_eq!(
+        bench.apb(&[read(INTC), read(INTC + 2)]),
+        vec![0b11, 1],
+        "Timer 1 has the higher priority"
+    );
+
+    // Acknowledging while the timer interrupt is asserted leaves it
+    // pending
+    bench.apb(&[write(INTC + 3, 1)]);
+    assert_eq!(bench.apb(&[read(INTC)]), vec![0b11]);
+    bench.apb(&[write(TIMER1 + 1, 1), write(INTC + 3, 1)]);
+    assert_eq!(bench.apb(&[read(INTC), read(INTC + 2)]), vec![0b01, 0]);
+
+    // Disabled sources stay pending but are not active
+    bench.apb(&[write(INTC + 1, 0b10)]);
+    assert_eq!(bench.apb(&[read(INTC + 2)]), vec![NO_INTERRUPT]);
+    assert!(!bench.irq());
+    bench.apb(&[write(TIMER0 + 1, 1), write(INTC, 0b11)]);
+    assert_eq!(bench.apb(&[read(INTC)]), vec![0]);
+    assert_eq!(
+        bench.apb(&[read(INTC + 32), read(INTC + 33)]),
+        vec![0, 5],
+        "Priorities"
+    );
+    assert_eq!(bench.sim.assertion_failures(), vec![]);
+    Ok(())
+}
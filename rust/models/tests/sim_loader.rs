@@ -0,0 +1,242 @@
+use hgl_models::{Backdoor, Program, ProgramSegment, SparseMemory};
+use hgl_models::{Memory, Rv32i, Rv32iConfig, Rv32iPort};
+use hgl_sim::prelude::sim::*;
+
+type Mem = Memory<Bv<32>, Bv<16>>;
+
+//a ELF
+//fi elf32
+/// Build a little-endian ELF32 file with one loadable segment (with
+/// some zero fill) at an address, and a symbol table
+fn elf32(address: u32, entry: u32, code: &[u32], symbols: &[(&str, u32)]) -> Vec<u8> {
+    let mut text: Vec<u8> = code.iter().flat_map(|w| w.to_le_bytes()).collect();
+    let filesz = text.len() as u32;
+    let mut strtab = vec![0_u8];
+    let mut symtab = vec![0_u8; 16];
+    for (name, value) in symbols {
+        symtab.extend((strtab.len() as u32).to_le_bytes());
+        symtab.extend(value.to_le_bytes());
+        symtab.extend([0; 4]);
+        symtab.extend([0x10, 0, 1, 0]);
+        strtab.extend(name.as_bytes());
+        strtab.push(0);
+    }
+    let text_offset = 52 + 32;
+    let strtab_offset = text_offset + text.len();
+    let symtab_offset = strtab_offset + strtab.len();
+    let shoff = symtab_offset + symtab.len();
+
+    let mut elf = b"\x7fELF\x01\x01\x01".to_vec();
+    elf.resize(16, 0);
+    let half = |v: u16| v.to_le_bytes();
+    let word = |v: u32| v.to_le_bytes();
+    elf.extend(half(2)); // executable
+    elf.extend(half(0xf3)); // RISC-V
+    elf.extend(word(1));
+    elf.extend(word(entry));
+    elf.extend(word(52));
+    elf.extend(word(shoff as u32));
+    elf.extend(word(0));
+    elf.extend(half(52));
+    elf.extend(half(32));
+    elf.extend(half(1));
+    elf.extend(half(40));
+    elf.extend(half(3));
+    elf.extend(half(0));
+    for v in [
+        1,
+        text_offset as u32,
+        address,
+        address,
+        filesz,
+        filesz + 8,
+        5,
+        4,
+    ] {
+        elf.extend(word(v));
+    }
+    elf.append(&mut text);
+    elf.extend(&strtab);
+    elf.extend(&symtab);
+    elf.extend([0; 40]);
+    for (sh_type, offset, size, link, entsize) in [
+        (2, symtab_offset, symtab.len(), 2, 16),
+        (3, strtab_offset, strtab.len(), 0, 0),
+    ] {
+        for v in [
+            0,
+            sh_type,
+            0,
+            0,
+            offset as u32,
+            size as u32,
+            link,
+            0,
+            4,
+            entsize,
+        ] {
+            elf.extend(word(v));
+        }
+    }
+    elf
+}
+
+#[test]
+fn elf() -> Result<(), String> {
+    let elf = elf32(
+        0x100,
+        0x104,
+        &[1, 2, 3],
+        &[("start", 0x104), ("_exit", 0x108)],
+    );
+    let program = Program::parse(&elf)?;
+    assert_eq!(program.entry, Some(0x104));
+    assert_eq!(program.symbol("_exit"), Some(0x108));
+    assert_eq!(program.symbol("start"), Some(0x104));
+    assert_eq!(program.symbol("main"), None);
+    assert_eq!(program.segments.len(), 1);
+    assert_eq!(program.segments[0].address, 0x100);
+    assert_eq!(program.segments[0].data.len(), 20, "Zero filled to memsz");
+    assert_eq!(
+        &program.segments[0].data[8..],
+        &[3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+    );
+
+    assert!(Program::parse(&elf[..40]).is_err());
+    assert!(Program::parse(&elf[..100]).is_err());
+    assert!(Program::parse(b"junk").is_err());
+
+    // A segment too large to load
+    let mut huge = elf.clone();
+    huge[72..76].copy_from_slice(&0x8000_0000_u32.to_le_bytes());
+    assert!(Program::parse(&huge).is_err());
+
+    // ELF64 headers whose offsets overflow
+    let mut elf64 = b"\x7fELF\x02\x01\x01".to_vec();
+    elf64.resize(0x40, 0);
+    elf64[0x20..0x28].copy_from_slice(&u64::MAX.to_le_bytes());
+    elf64[0x36..0x38].copy_from_slice(&56_u16.to_le_bytes());
+    elf64[0x38..0x3a].copy_from_slice(&1_u16.to_le_bytes());
+    assert!(Program::parse(&elf64).is_err());
+    elf64[0x20..0x28].copy_from_slice(&0x40_u64.to_le_bytes());
+    elf64[0x38..0x3a].copy_from_slice(&0_u16.to_le_bytes());
+    elf64[0x28..0x30].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+    elf64[0x3a..0x3c].copy_from_slice(&64_u16.to_le_bytes());
+    elf64[0x3c..0x3e].copy_from_slice(&2_u16.to_le_bytes());
+    assert!(Program::parse(&elf64).is_err());
+    Ok(())
+}
+
+#[test]
+fn hex_and_srec() -> Result<(), String> {
+    let ihex = ":0400000012345678E8\n\
+                :020000040001F9\n\
+                :02000200ABCD84\n\
+                :0400000500010004F2\n\
+                :00000001FF\n";
+    let program = Program::parse(ihex.as_bytes())?;
+    assert_eq!(program.entry, Some(0x10004));
+    assert_eq!(
+        program.segments,
+        vec![
+            ProgramSegment {
+                address: 0,
+                data: vec![0x12, 0x34, 0x56, 0x78]
+            },
+            ProgramSegment {
+                address: 0x10002,
+                data: vec![0xab, 0xcd]
+            },
+        ]
+    );
+    assert!(Program::parse_ihex(":0400000012345678E9").is_err());
+    assert!(Program::parse_ihex(":0400000012345678").is_err());
+
+    let srec = "S00600004844521B\n\
+                S107001001020304DE\n\
+                S107001405060708CA\n\
+                S9030014E8\n";
+    let program = Program::parse(srec.as_bytes())?;
+    assert_eq!(program.entry, Some(0x14));
+    assert_eq!(
+        program.segments,
+        vec![ProgramSegment {
+            address: 0x10,
+            data: vec![1, 2, 3, 4, 5, 6, 7, 8]
+        }]
+    );
+    assert!(Program::parse_srec("S107001001020304DF").is_err());
+    assert!(Program::parse_srec("S4030014E8").is_err());
+    assert!(Program::parse("S\u{e9}00\n".as_bytes()).is_err());
+    assert!(Program::parse_srec("S").is_err());
+
+    let mut sparse = SparseMemory::new(0x100);
+    program.write_to(&mut sparse, 0)?;
+    assert_eq!(sparse.peek(0x10), Some(0x0403_0201));
+    assert_eq!(sparse.peek(0x14), Some(0x0807_0605));
+    assert_eq!(sparse.read_bytes(0x12, 4)?, vec![3, 4, 5, 6]);
+    assert!(program.write_to(&mut sparse, 0x20).is_err());
+    assert!(SparseMemory::new(0x14).write_bytes(0x10, &[0; 8]).is_err());
+    Ok(())
+}
+
+#[test]
+fn memory_backdoor() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    let mem = sim.instantiate::<Memory<Bv<16>, Bv<8>>, _, _>("mem", || 16)?;
+    sim.prepare_simulation();
+    let instances = sim.instances();
+    let mut m = instances.inst_mut::<Memory<Bv<16>, Bv<8>>>(mem);
+    m.write_bytes(3, &[0x12, 0x34, 0x56])?;
+    assert_eq!(m.peek(1).unwrap().try_as_u64(), Some(0x1200));
+    assert_eq!(m.peek(2).unwrap().try_as_u64(), Some(0x5634));
+    assert_eq!(m.read_bytes(2, 4)?, vec![0, 0x12, 0x34, 0x56]);
+    assert!(m.write_bytes(31, &[1, 2]).is_err());
+    Ok(())
+}
+
+#[test]
+fn firmware() -> Result<(), String> {
+    let code = [
+        0x0050_0093, // 00: addi x1, x0, 5
+        0x0070_0113, // 04: addi x2, x0, 7
+        0x0020_81b3, // 08: add x3, x1, x2
+        0x1030_2023, // 0c: sw x3, 0x100(x0)
+        0x0000_006f, // 10: _exit: jal x0, 0
+    ];
+    let program = Program::parse(&elf32(0, 0, &code, &[("_exit", 0x10)]))?;
+    let exit = program.symbol("_exit").unwrap() as u32;
+
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    let cpu = sim.instantiate::<Rv32i, _, _>("cpu", || Rv32iConfig {
+        reset_pc: program.entry.unwrap() as u32,
+        ..Default::default()
+    })?;
+    let imem = sim.instantiate::<Mem, _, _>("imem", || 1024)?;
+    let dmem = sim.instantiate::<Mem, _, _>("dmem", || 1024)?;
+    for i in [cpu, imem, dmem] {
+        sim.connect_clock(clk, i, 0);
+    }
+    sim.prepare_simulation();
+    Rv32i::connect_memory(&sim, cpu, Rv32iPort::Instruction, "imem")?;
+    Rv32i::connect_memory(&sim, cpu, Rv32iPort::Data, "dmem")?;
+    program.write_to(&mut *sim.instances().inst_mut::<Mem>(imem), 0)?;
+    sim.start(true)?;
+
+    let instances = sim.instances();
+    let mut cycles = 0;
+    while instances.inst::<Rv32i>(cpu).pc() != exit {
+        sim.fire_next_edges();
+        cycles += 1;
+        assert!(cycles < 100, "Should reach _exit");
+    }
+    // Let the store complete
+    for _ in 0..4 {
+        sim.fire_next_edges();
+    }
+    assert_eq!(instances.inst::<Rv32i>(cpu).pc(), exit);
+    let data = instances.inst::<Mem>(dmem).read_bytes(0x100, 4)?;
+    assert_eq!(data, vec![12, 0, 0, 0]);
+    Ok(())
+}
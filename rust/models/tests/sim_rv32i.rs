@@ -0,0 +1,248 @@
+use hgl_models::rv32i::disassemble;
+use hgl_models::{add_apb_assertions, ApbAddressMap, ApbDecoder, Memory, Timer};
+use hgl_models::{Rv32i, Rv32iConfig, Rv32iPort, Rv32iTrap};
+use hgl_sim::prelude::sim::*;
+
+type Mem = Memory<Bv<32>, Bv<16>>;
+
+//a Assembler
+fn r(f7: u32, rs2: u32, rs1: u32, f3: u32, rd: u32, op: u32) -> u32 {
+    f7 << 25 | rs2 << 20 | rs1 << 15 | f3 << 12 | rd << 7 | op
+}
+fn i(imm: i32, rs1: u32, f3: u32, rd: u32, op: u32) -> u32 {
+    (imm as u32) << 20 | rs1 << 15 | f3 << 12 | rd << 7 | op
+}
+fn s(imm: i32, rs2: u32, rs1: u32, f3: u32) -> u32 {
+    let imm = imm as u32;
+    (imm >> 5) << 25 | rs2 << 20 | rs1 << 15 | f3 << 12 | (imm & 31) << 7 | 0x23
+}
+fn b(imm: i32, rs2: u32, rs1: u32, f3: u32) -> u32 {
+    let imm = imm as u32;
+    (imm >> 12 & 1) << 31
+        | (imm >> 5 & 0x3f) << 25
+        | rs2 << 20
+        | rs1 << 15
+        | f3 << 12
+        | (imm >> 1 & 0xf) << 8
+        | (imm >> 11 & 1) << 7
+        | 0x63
+}
+fn jal(imm: i32, rd: u32) -> u32 {
+    let imm = imm as u32;
+    (imm >> 20 & 1) << 31
+        | (imm >> 1 & 0x3ff) << 21
+        | (imm >> 11 & 1) << 20
+        | (imm >> 12 & 0xff) << 12
+        | rd << 7
+        | 0x6f
+}
+fn lui(imm: u32, rd: u32) -> u32 {
+    imm << 12 | rd << 7 | 0x37
+}
+fn addi(rd: u32, rs1: u32, imm: i32) -> u32 {
+    i(imm, rs1, 0, rd, 0x13)
+}
+const EBREAK: u32 = 0x0010_0073;
+
+//a Bench
+//tp Bench
+/// A core with instruction and data memories, and a timer on APB
+struct Bench<'a> {
+    sim: Simulation<'a>,
+    cpu: InstanceHandle,
+    imem: InstanceHandle,
+    dmem: InstanceHandle,
+}
+
+//ip Bench
+impl Bench<'_> {
+    //cp new
+    fn new(program: &[u32]) -> Result<Self, String> {
+        let mut sim = Simulation::new();
+        let clk = sim.add_clock("clk", 0, 1, 0)?;
+        let cpu = sim.instantiate::<Rv32i, _, _>("cpu", || Rv32iConfig {
+            trace: true,
+            ..Default::default()
+        })?;
+        let imem = sim.instantiate::<Mem, _, _>("imem", || 1024)?;
+        let dmem = sim.instantiate::<Mem, _, _>("dmem", || 1024)?;
+        let timer = sim.instantiate::<Timer, _, _>("timer", || ())?;
+        let bus = sim.instantiate::<ApbDecoder, _, _>("bus", || {
+            ApbAddressMap::default().target("timer", 0, 0x40)
+        })?;
+        for i in [cpu, imem, dmem, timer] {
+            sim.connect_clock(clk, i, 0);
+        }
+        sim.prepare_simulation();
+        assert!(Rv32i::connect_memory(&sim, imem, Rv32iPort::Data, "dmem").is_err());
+        assert!(Rv32i::connect_memory(&sim, cpu, Rv32iPort::Data, "nothing").is_err());
+        Rv32i::connect_memory(&sim, cpu, Rv32iPort::Instruction, "imem")?;
+        Rv32i::connect_memory(&sim, cpu, Rv32iPort::Data, "dmem")?;
+        Rv32i::connect_apb(&sim, clk, cpu, "bus.apb_request", "bus.apb_response")?;
+        ApbDecoder::connect(&sim, bus, &[("timer.apb_request", "timer.apb_response")])?;
+        add_apb_assertions(&sim, clk, "bus.apb_request", "bus.apb_response", 1)?;
+        sim.instances().inst_mut::<Mem>(imem).load(
+            0,
+            &program
+                .iter()
+                .map(|w| (*w as u64).into())
+                .collect::<Vec<_>>(),
+        )?;
+        sim.start(true)?;
+        Ok(Self {
+            sim,
+            cpu,
+            imem,
+            dmem,
+        })
+    }
+
+    //mp run
+    /// Run until the core halts, returning the trap
+    fn run(&self) -> Rv32iTrap {
+        let instances = self.sim.instances();
+        for _ in 0..1000 {
+            if let Some(trap) = instances.inst::<Rv32i>(self.cpu).trap() {
+                return trap;
+            }
+            self.sim.fire_next_edges();
+        }
+        panic!("Core should have halted");
+    }
+
+    //mp reg
+    fn reg(&self, r: usize) -> u32 {
+        self.sim.instances().inst::<Rv32i>(self.cpu).reg(r)
+    }
+
+    //mp dmem
+    fn dmem(&self, address: usize) -> u64 {
+        let instances = self.sim.instances();
+        let m = instances.inst::<Mem>(self.dmem);
+        m.peek(address).unwrap().try_as_u64().unwrap()
+    }
+}
+
+#[test]
+fn disassembly() {
+    assert_eq!(disassemble(addi(1, 2, -3)), "addi x1, x2, -3");
+    assert_eq!(disassemble(s(17, 4, 0, 0)), "sb x4, 17(x0)");
+    assert_eq!(disassemble(b(-8, 0, 3, 1)), "bne x3, x0, -8");
+    assert_eq!(disassemble(jal(8, 9)), "jal x9, 8");
+    assert_eq!(disassemble(r(0x20, 2, 0, 0, 13, 0x33)), "sub x13, x0, x2");
+    assert_eq!(disassemble(EBREAK), "ebreak");
+    assert_eq!(disassemble(0), ".word 0x00000000");
+}
+
+#[test]
+fn firmware() -> Result<(), String> {
+    let program = [
+        lui(0x80000, 1),            // 00: x1 = APB base
+        addi(2, 0, 0),              // 04: x2 = 0
+        addi(3, 0, 10),             // 08: x3 = 10
+        r(0, 3, 2, 0, 2, 0x33),     // 0c: x2 += x3
+        addi(3, 3, -1),             // 10: x3 -= 1
+        b(-8, 0, 3, 1),             // 14: bne x3, x0, 0c
+        s(16, 2, 0, 2),             // 18: sw x2, 16(x0)
+        addi(4, 0, -2),             // 1c: x4 = -2
+        s(17, 4, 0, 0),             // 20: sb x4, 17(x0)
+        i(16, 0, 2, 5, 0x03),       // 24: lw x5, 16(x0)
+        i(17, 0, 0, 6, 0x03),       // 28: lb x6, 17(x0)
+        i(17, 0, 4, 7, 0x03),       // 2c: lbu x7, 17(x0)
+        s(16, 2, 1, 2),             // 30: sw x2, 16(x1) - timer COMPARE
+        i(16, 1, 2, 8, 0x03),       // 34: lw x8, 16(x1)
+        jal(8, 9),                  // 38: jal x9, 40
+        addi(10, 0, 1),             // 3c: skipped
+        i(0, 6, 2, 11, 0x13),       // 40: slti x11, x6, 0
+        i(0x401, 6, 5, 12, 0x13),   // 44: srai x12, x6, 1
+        r(0x20, 2, 0, 0, 13, 0x33), // 48: sub x13, x0, x2
+        EBREAK,                     // 4c
+    ];
+    let bench = Bench::new(&program)?;
+    assert_eq!(bench.run(), Rv32iTrap::Ebreak { pc: 0x4c });
+    assert_eq!(bench.reg(2), 55);
+    assert_eq!(bench.dmem(4), 0xfe37);
+    assert_eq!(bench.reg(5), 0xfe37);
+    assert_eq!(bench.reg(6), -2i32 as u32);
+    assert_eq!(bench.reg(7), 0xfe);
+    assert_eq!(bench.reg(8), 55, "Timer COMPARE written and read over APB");
+    assert_eq!(bench.reg(9), 0x3c);
+    assert_eq!(bench.reg(10), 0);
+    assert_eq!(bench.reg(11), 1);
+    assert_eq!(bench.reg(12), -1i32 as u32);
+    assert_eq!(bench.reg(13), -55i32 as u32);
+
+    let pc = bench.sim.find_value("cpu.pc").expect("pc is state");
+    let x2 = bench.sim.find_value("cpu.x2").expect("x2 is state");
+    assert_eq!(
+        bench.sim.with_state(pc.0, pc.1, |v| v.get_u64()).flatten(),
+        Some(0x4c)
+    );
+    assert_eq!(
+        bench.sim.with_state(x2.0, x2.1, |v| v.get_u64()).flatten(),
+        Some(55)
+    );
+
+    let instances = bench.sim.instances();
+    let mut cpu = instances.inst_mut::<Rv32i>(bench.cpu);
+    let trace = cpu.take_trace();
+    assert_eq!(trace.len(), cpu.retired());
+    assert_eq!(trace.len(), 3 + 3 * 10 + 12);
+    assert_eq!(trace[3].write, Some((2, 10)));
+    assert!(trace[3].to_string().contains("add x2, x2, x3"));
+    assert!(trace.windows(2).all(|w| w[0].cycle < w[1].cycle));
+    assert!(cpu.outputs.halted.is_true());
+    drop(cpu);
+    assert_eq!(bench.sim.assertion_failures(), vec![]);
+    Ok(())
+}
+
+#[test]
+fn traps() -> Result<(), String> {
+    for (program, trap) in [
+        (
+            vec![0xffff_ffff],
+            Rv32iTrap::IllegalInstruction {
+                pc: 0,
+                instruction: 0xffff_ffff,
+            },
+        ),
+        (vec![addi(0, 0, 0), 0x0000_0073], Rv32iTrap::Ecall { pc: 4 }),
+        (
+            vec![jal(6, 0)],
+            Rv32iTrap::MisalignedFetch { pc: 0, target: 6 },
+        ),
+        (
+            vec![i(2, 0, 2, 1, 0x03)],
+            Rv32iTrap::MisalignedAccess { pc: 0, address: 2 },
+        ),
+        (
+            vec![lui(0x80001, 1), s(0, 0, 1, 2)],
+            Rv32iTrap::AccessFault {
+                pc: 4,
+                address: 0x8000_1000,
+            },
+        ),
+    ] {
+        let bench = Bench::new(&program)?;
+        assert_eq!(bench.run(), trap);
+        let instances = bench.sim.instances();
+        assert_eq!(instances.inst::<Mem>(bench.imem).peek(2048), None);
+    }
+    Ok(())
+}
+
+#[test]
+fn misaligned_jumps() -> Result<(), String> {
+    for (jump, target) in [(jal(6, 2), 10), (i(2, 1, 0, 2, 0x67), 7 & !1)] {
+        let program = [addi(1, 0, 5), jump];
+        let bench = Bench::new(&program)?;
+        assert_eq!(bench.run(), Rv32iTrap::MisalignedFetch { pc: 4, target });
+        assert_eq!(bench.reg(2), 0, "Link register unchanged by the trap");
+        let instances = bench.sim.instances();
+        let mut cpu = instances.inst_mut::<Rv32i>(bench.cpu);
+        assert_eq!(cpu.retired(), 1);
+        assert_eq!(cpu.take_trace().len(), 1);
+    }
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use hgl_models::Counter;
+use hgl_sim::prelude::sim::*;
+use serde_json::json;
+
+type T = Bv<16>;
+
+fn build_sim() -> Result<Simulation<'static>, String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 2, 1)?;
+    let cntr = sim.instantiate::<Counter<T>, _, _>("counter", || Some(T::of_u64(4)))?;
+    sim.connect_clock(clk, cntr, 0);
+    sim.prepare_simulation();
+    Ok(sim)
+}
+
+fn drive<R: std::io::BufRead, W: std::io::Write>(
+    client: &mut RpcClient<R, W>,
+) -> Result<(), String> {
+    assert!(client.call("step", json!(null)).is_err(), "Not started");
+    client.call("start", json!({"running": true}))?;
+    assert_eq!(
+        client.call("lookup", json!({"name": "counter"}))?,
+        "instance"
+    );
+    assert_eq!(
+        client.call("lookup", json!({"name": "counter.q"}))?,
+        "state"
+    );
+    assert_eq!(client.call("lookup", json!({"name": "clk"}))?, "clock");
+    assert_eq!(
+        client.call("lookup", json!({"name": "nothing"}))?,
+        json!(null)
+    );
+    let states = client.call("states", json!(null))?;
+    assert!(states.as_array().unwrap().contains(&json!("counter.q")));
+
+    client.call("write", json!({"path": "counter.reset_n", "value": 1}))?;
+    client.call("write", json!({"path": "counter.increment", "value": 1}))?;
+    // Posedges at 0, 2, 4, 6
+    assert_eq!(client.call("step", json!({"edges": 7}))?, 6);
+    assert_eq!(
+        client.call("read", json!({"path": "counter.q"}))?,
+        json!({"text": "16h0008", "value": 8})
+    );
+    assert_eq!(client.call("run", json!({"time": 20}))?, 20);
+    assert_eq!(client.call("time", json!(null))?, 20);
+    assert_eq!(
+        client.call("read", json!({"path": "counter.q", "format": "bin"}))?["text"],
+        "16b0000000000001111"
+    );
+    client.call("pause", json!(null))?;
+    assert!(client.call("step", json!(null)).is_err(), "Paused");
+    assert!(client.call("run", json!({"time": 30})).is_err(), "Paused");
+    client.call("resume", json!(null))?;
+
+    assert!(client.call("read", json!({"path": "counter.x"})).is_err());
+    assert!(client.call("write", json!({"path": "counter.q"})).is_err());
+    assert!(client.call("frobnicate", json!(null)).is_err());
+    client.call("stop", json!(null))?;
+    assert!(client.call("resume", json!(null)).is_err());
+    client.call("shutdown", json!(null))?;
+    Ok(())
+}
+
+#[test]
+fn sim_server_tcp() -> Result<(), String> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").map_err(|e| e.to_string())?;
+    let addr = listener.local_addr().map_err(|e| e.to_string())?;
+    // The simulation is not Send, so it is built on the server thread
+    let server = std::thread::spawn(move || -> Result<usize, String> {
+        let sim = build_sim()?;
+        RpcServer::new(&sim)
+            .serve_tcp(&listener)
+            .map_err(|e| e.to_string())?;
+        Ok(sim.time())
+    });
+    // A client sending invalid UTF-8 fails only its own connection
+    let mut bad = std::net::TcpStream::connect(addr).map_err(|e| e.to_string())?;
+    std::io::Write::write_all(&mut bad, b"\xff\xfe\n").map_err(|e| e.to_string())?;
+    drop(bad);
+    let mut client = RpcClient::connect_tcp(addr).map_err(|e| e.to_string())?;
+    drive(&mut client)?;
+    assert_eq!(server.join().unwrap()?, 20);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn sim_server_unix() -> Result<(), String> {
+    let path = std::env::temp_dir().join(format!("hgl_sim_server_{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = std::os::unix::net::UnixListener::bind(&path).map_err(|e| e.to_string())?;
+    let server = std::thread::spawn(move || -> Result<(), String> {
+        let sim = build_sim()?;
+        RpcServer::new(&sim)
+            .serve_unix(&listener)
+            .map_err(|e| e.to_string())
+    });
+    let mut client = RpcClient::connect_unix(&path).map_err(|e| e.to_string())?;
+    drive(&mut client)?;
+    server.join().unwrap()?;
+    std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[test]
+fn sim_server_requests() -> Result<(), String> {
+    let sim = build_sim()?;
+    let mut server = RpcServer::new(&sim);
+    let response: serde_json::Value =
+        serde_json::from_str(&server.handle_request("{not json").unwrap()).unwrap();
+    assert_eq!(response["error"]["code"], -32700);
+    let response: serde_json::Value =
+        serde_json::from_str(&server.handle_request(r#"{"id": 3}"#).unwrap()).unwrap();
+    assert_eq!(response["error"]["code"], -32600);
+    assert_eq!(response["id"], 3);
+    // Notifications (without an id) are handled but get no response
+    assert!(server
+        .handle_request(r#"{"jsonrpc": "2.0", "method": "start"}"#)
+        .is_none());
+    let requests = r#"{"jsonrpc": "2.0", "id": "a", "method": "step"}
+{"jsonrpc": "2.0", "id": "b", "method": "shutdown"}
+{"jsonrpc": "2.0", "id": "c", "method": "step"}
+"#;
+    let mut output = vec![];
+    server
+        .serve(requests.as_bytes(), &mut output)
+        .map_err(|e| e.to_string())?;
+    assert!(server.is_shutdown());
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(
+        output,
+        "{\"id\":\"a\",\"jsonrpc\":\"2.0\",\"result\":0}\n{\"id\":\"b\",\"jsonrpc\":\"2.0\",\"result\":null}\n"
+    );
+    sim.stop()?;
+
+    // Without clocks time cannot advance
+    let mut sim = Simulation::new();
+    sim.prepare_simulation();
+    sim.start(true)?;
+    let mut server = RpcServer::new(&sim);
+    for request in [
+        r#"{"jsonrpc": "2.0", "id": 1, "method": "run", "params": {"time": 10}}"#,
+        r#"{"jsonrpc": "2.0", "id": 2, "method": "step"}"#,
+    ] {
+        let response: serde_json::Value =
+            serde_json::from_str(&server.handle_request(request).unwrap()).unwrap();
+        assert_eq!(response["error"]["code"], -32000);
+    }
+    Ok(())
+}
@@ -18,36 +18,43 @@ fn sim_threaded() -> Result<(), String> {
     let instances = sim.instances();
     sim.start(true)?;
 
-    instances.inst_mut::<Threaded>(m).inputs.reset_n = true;
-    sim.fire_next_edges();
+    let mut testbench = Testbench::new();
+    testbench.spawn(async {
+        instances.inst_mut::<Threaded>(m).inputs.reset_n = true;
+        sim.posedge(clk).await;
 
-    instances.inst_mut::<Threaded>(m).inputs.start = true;
-    sim.fire_next_edges();
-    instances.inst_mut::<Threaded>(m).inputs.start = false;
+        instances.inst_mut::<Threaded>(m).inputs.start = true;
+        sim.posedge(clk).await;
+        instances.inst_mut::<Threaded>(m).inputs.start = false;
 
-    for _ in 0..10_000 {
-        sim.fire_next_edges();
-    }
+        // The model thread accumulates the data on every clock edge
+        instances.inst_mut::<Threaded>(m).inputs.data = 3;
+        sim.cycles(clk, 10_000).await;
+        let sum = instances.inst::<Threaded>(m).outputs.sum;
+        assert_eq!(sum, 30_000);
 
-    instances.inst_mut::<Threaded>(m).inputs.stop = true;
-    sim.fire_next_edges();
-    instances.inst_mut::<Threaded>(m).inputs.stop = false;
+        instances.inst_mut::<Threaded>(m).inputs.stop = true;
+        sim.posedge(clk).await;
+        instances.inst_mut::<Threaded>(m).inputs.stop = false;
+        sim.posedge(clk).await;
 
-    sim.fire_next_edges();
+        eprintln!(
+            "Time after while running edges {}",
+            instances.inst_mut::<Threaded>(m).outputs.q
+        );
+    });
+    assert_eq!(testbench.run(&sim, 10_010)?, 10_004);
 
-    eprintln!(
-        "Time after while running edges {}",
-        instances.inst_mut::<Threaded>(m).outputs.q
-    );
-
-    for _ in 0..10_000 {
-        sim.fire_next_edges();
-    }
+    // Once stopped, the clock can run on without the model
+    testbench.spawn(sim.cycles(clk, 10_000));
+    assert_eq!(testbench.run(&sim, 10_000)?, 10_000);
 
     eprintln!(
         "Time after edges stopped {}",
         instances.inst_mut::<Threaded>(m).outputs.q
     );
+    let sum = instances.inst::<Threaded>(m).outputs.sum;
+    assert_eq!(sum, 30_000, "The model does not see edges once stopped");
 
     dbg!(&sim);
 
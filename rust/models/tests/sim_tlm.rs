@@ -0,0 +1,203 @@
+use hgl_models::apb_target_gpio::apb_target_gpio;
+use hgl_models::{add_apb_assertions, ApbMaster, ApbPayload, ApbTlmBridge, ApbTlmTarget, Timer};
+use hgl_sim::prelude::sim::*;
+
+/// The timer COMPARE register
+const COMPARE: u32 = 4;
+
+//tp Delayed
+/// A socket target that adds a delay to the transactions of another
+struct Delayed {
+    socket: TlmSocket<'static, ApbPayload>,
+    delay: usize,
+}
+
+//ip TlmTransport for Delayed
+impl TlmTransport<ApbPayload> for Delayed {
+    fn b_transport(&self, sim: &Simulation, payload: &mut ApbPayload, delay: &mut usize) {
+        self.socket.b_transport(sim, payload, delay);
+        *delay += self.delay;
+    }
+    fn nb_transport_fw(
+        &self,
+        sim: &Simulation,
+        payload: &mut ApbPayload,
+        phase: &mut TlmPhase,
+        delay: &mut usize,
+    ) -> TlmSync {
+        self.socket.nb_transport_fw(sim, payload, phase, delay)
+    }
+    fn nb_transport_bw(&self, sim: &Simulation) -> Option<(ApbPayload, TlmPhase, usize)> {
+        self.socket.nb_transport_bw(sim)
+    }
+}
+
+//fi compare
+/// An initiator that writes and reads back the timer COMPARE register
+fn compare(sim: &Simulation, socket: &TlmSocket<ApbPayload>, value: u32) -> ApbPayload {
+    let mut delay = 0;
+    let mut write = ApbPayload::write(COMPARE, value);
+    socket.b_transport(sim, &mut write, &mut delay);
+    assert!(!write.error);
+    let mut read = ApbPayload::read(COMPARE);
+    socket.b_transport(sim, &mut read, &mut delay);
+    read
+}
+
+#[test]
+fn tlm_target() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    let timer = sim.instantiate::<Timer, _, _>("timer", || ())?;
+    sim.connect_clock(clk, timer, 0);
+    sim.prepare_simulation();
+    sim.start(true)?;
+    let socket = TlmSocket::instance::<Timer>(timer);
+
+    assert_eq!(compare(&sim, &socket, 55).data, 55);
+    assert_eq!(sim.time(), 0, "Transaction-level accesses take no time");
+
+    let mut delay = 3;
+    let mut read = ApbPayload::read(COMPARE);
+    socket.b_transport(&sim, &mut read, &mut delay);
+    assert_eq!((read.data, delay), (55, 3));
+
+    let mut phase = TlmPhase::BeginReq;
+    let mut write = ApbPayload::write(COMPARE, 7);
+    assert_eq!(
+        socket.nb_transport_fw(&sim, &mut write, &mut phase, &mut delay),
+        TlmSync::Completed
+    );
+    assert_eq!(phase, TlmPhase::BeginResp);
+    assert!(socket.nb_transport_bw(&sim).is_none());
+    assert_eq!(compare(&sim, &socket.clone(), 9).data, 9);
+    Ok(())
+}
+
+#[test]
+fn tlm_to_pins() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 2, 1)?;
+    let gpio = sim.instantiate::<apb_target_gpio, _, _>("gpio", || ())?;
+    let master = sim.instantiate::<ApbMaster, _, _>("master", || ())?;
+    sim.connect_clock(clk, gpio, 0);
+    sim.connect_clock(clk, master, 0);
+    sim.prepare_simulation();
+    ApbMaster::attach(&sim, clk, master, "gpio.apb_request", "gpio.apb_response")?;
+    add_apb_assertions(&sim, clk, "gpio.apb_request", "gpio.apb_response", 1)?;
+    assert!(ApbTlmBridge::new(&sim, gpio).is_err());
+    let socket = TlmSocket::new(ApbTlmBridge::new(&sim, master)?);
+    sim.start(true)?;
+    let instances = sim.instances();
+    instances.inst_mut::<apb_target_gpio>(gpio).inputs.reset_n = true.into();
+
+    // Output 1 driven high, and output 2 enabled; bits 2i and 2i+1 of
+    // the output register are the value and enable of output i
+    let mut delay = 10;
+    let mut write = ApbPayload::write(0, 0b10_1100);
+    socket.b_transport(&sim, &mut write, &mut delay);
+    assert_eq!(delay, 0, "The bridge consumes the delay");
+    assert!(sim.time() >= 10);
+    // The GPIO outputs are registered after the register write
+    for _ in 0..4 {
+        sim.fire_next_edges();
+    }
+    let outputs = instances.inst::<apb_target_gpio>(gpio).outputs;
+    assert_eq!(outputs.gpio_output.try_as_u64(), Some(0b010));
+    assert_eq!(outputs.gpio_output_enable.try_as_u64(), Some(0b110));
+
+    let mut read = ApbPayload::read(0);
+    socket.b_transport(&sim, &mut read, &mut delay);
+    assert_eq!(read.data, 0b10_1100);
+
+    // Non-blocking accesses are accepted, and complete in order as the
+    // simulation runs
+    let mut phase = TlmPhase::BeginReq;
+    for mut payload in [ApbPayload::write(0, 0b11), ApbPayload::read(0)] {
+        assert_eq!(
+            socket.nb_transport_fw(&sim, &mut payload, &mut phase, &mut delay),
+            TlmSync::Accepted
+        );
+    }
+    assert!(socket.nb_transport_bw(&sim).is_none());
+    let mut responses = vec![];
+    while responses.len() < 2 {
+        sim.fire_next_edges();
+        while let Some((payload, phase, _)) = socket.nb_transport_bw(&sim) {
+            assert_eq!(phase, TlmPhase::BeginResp);
+            responses.push(payload);
+        }
+    }
+    assert_eq!(
+        responses,
+        vec![
+            ApbPayload::write(0, 0b11),
+            ApbPayload {
+                data: 0b11,
+                ..ApbPayload::read(0)
+            }
+        ]
+    );
+    assert_eq!(sim.assertion_failures(), vec![]);
+    Ok(())
+}
+
+#[test]
+fn pins_to_tlm() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    let timer = sim.instantiate::<Timer, _, _>("timer", || ())?;
+    let fast = sim.instantiate::<ApbTlmTarget, _, _>("fast", || ())?;
+    let slow = sim.instantiate::<ApbTlmTarget, _, _>("slow", || ())?;
+    let master = sim.instantiate::<ApbMaster, _, _>("master", || ())?;
+    let slow_master = sim.instantiate::<ApbMaster, _, _>("slow_master", || ())?;
+    for i in [timer, fast, slow, master, slow_master] {
+        sim.connect_clock(clk, i, 0);
+    }
+    sim.prepare_simulation();
+    assert!(ApbTlmTarget::connect(&sim, timer, TlmSocket::instance::<Timer>(timer)).is_err());
+    ApbTlmTarget::connect(&sim, fast, TlmSocket::instance::<Timer>(timer))?;
+    let delayed = Delayed {
+        socket: TlmSocket::instance::<Timer>(timer),
+        delay: 3,
+    };
+    ApbTlmTarget::connect(&sim, slow, TlmSocket::new(delayed))?;
+    ApbMaster::attach(&sim, clk, master, "fast.apb_request", "fast.apb_response")?;
+    ApbMaster::attach(
+        &sim,
+        clk,
+        slow_master,
+        "slow.apb_request",
+        "slow.apb_response",
+    )?;
+    add_apb_assertions(&sim, clk, "fast.apb_request", "fast.apb_response", 1)?;
+    add_apb_assertions(&sim, clk, "slow.apb_request", "slow.apb_response", 4)?;
+    sim.start(true)?;
+
+    // The same initiator, through a bridge to the pins, and back to
+    // transaction level
+    let bridge = TlmSocket::new(ApbTlmBridge::new(&sim, master)?);
+    assert_eq!(compare(&sim, &bridge, 21).data, 21);
+
+    let instances = sim.instances();
+    let results = {
+        let mut m = instances.inst_mut::<ApbMaster>(slow_master);
+        m.write(COMPARE, 99);
+        m.read(COMPARE);
+        m.read(COMPARE);
+        drop(m);
+        while !instances.inst::<ApbMaster>(slow_master).is_idle() {
+            sim.fire_next_edges();
+        }
+        instances
+            .inst_mut::<ApbMaster>(slow_master)
+            .take_completed()
+    };
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[1].rdata, 99);
+    assert!(results.iter().all(|r| r.wait_states == 3));
+    assert_eq!(instances.inst::<ApbTlmTarget>(slow).accesses(), 3);
+    assert_eq!(instances.inst::<ApbTlmTarget>(fast).accesses(), 2);
+    assert_eq!(sim.assertion_failures(), vec![]);
+    Ok(())
+}
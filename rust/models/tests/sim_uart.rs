@@ -0,0 +1,224 @@
+#[cfg(unix)]
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+#[cfg(unix)]
+use hgl_models::UnixSocketStream;
+use hgl_models::{add_apb_assertions, ApbMaster, ApbOp};
+use hgl_models::{Uart, UartConfig, UartError, UartParity, UartTerminal};
+use hgl_sim::prelude::sim::*;
+
+#[test]
+fn frames() {
+    let config = UartConfig {
+        divisor: 4,
+        parity: UartParity::Even,
+        stop_bits: 2,
+    };
+    assert_eq!(
+        config.frame(0x83),
+        vec![false, true, true, false, false, false, false, false, true, true, true, true]
+    );
+    assert!(UartConfig {
+        divisor: 1,
+        ..Default::default()
+    }
+    .validate()
+    .is_err());
+    assert!(UartConfig {
+        stop_bits: 3,
+        ..Default::default()
+    }
+    .validate()
+    .is_err());
+}
+
+//tp Bench
+/// A UART with an APB master and a terminal connected to it
+struct Bench<'a> {
+    sim: Simulation<'a>,
+    uart: InstanceHandle,
+    master: InstanceHandle,
+    terminal: InstanceHandle,
+}
+
+//ip Bench
+impl Bench<'_> {
+    //cp new
+    fn new(uart_config: UartConfig, terminal_config: UartConfig) -> Result<Self, String> {
+        let mut sim = Simulation::new();
+        let clk = sim.add_clock("clk", 0, 1, 0)?;
+        let uart = sim.instantiate::<Uart, _, _>("uart", || uart_config)?;
+        let terminal = sim.instantiate::<UartTerminal, _, _>("terminal", || terminal_config)?;
+        let master = sim.instantiate::<ApbMaster, _, _>("master", || ())?;
+        sim.connect_clock(clk, uart, 0);
+        sim.connect_clock(clk, terminal, 0);
+        sim.connect_clock(clk, master, 0);
+        sim.prepare_simulation();
+        assert!(UartTerminal::connect(&sim, uart, "uart.tx", "uart.rx").is_err());
+        UartTerminal::connect(&sim, terminal, "uart.tx", "uart.rx")?;
+        add_apb_assertions(&sim, clk, "uart.apb_request", "uart.apb_response", 1)?;
+        ApbMaster::attach(&sim, clk, master, "uart.apb_request", "uart.apb_response")?;
+        sim.start(true)?;
+        Ok(Self {
+            sim,
+            uart,
+            master,
+            terminal,
+        })
+    }
+
+    //mp apb
+    /// Perform APB operations, returning the read data
+    fn apb(&self, ops: &[ApbOp]) -> Vec<u32> {
+        let instances = self.sim.instances();
+        for op in ops {
+            instances.inst_mut::<ApbMaster>(self.master).queue(*op);
+        }
+        while !instances.inst::<ApbMaster>(self.master).is_idle() {
+            self.sim.fire_next_edges();
+        }
+        let results = instances
+            .inst_mut::<ApbMaster>(self.master)
+            .take_completed();
+        results.iter().map(|r| r.rdata).collect()
+    }
+
+    //mp run
+    /// Run for a number of cycles
+    fn run(&self, cycles: usize) {
+        for _ in 0..cycles {
+            self.sim.fire_next_edges();
+        }
+    }
+}
+
+#[test]
+fn uart_to_terminal() -> Result<(), String> {
+    let config = UartConfig {
+        divisor: 4,
+        parity: UartParity::Odd,
+        stop_bits: 2,
+    };
+    let bench = Bench::new(config, config)?;
+    let write = |data| ApbOp::Write { address: 0, data };
+    let status = ApbOp::Read { address: 1 };
+
+    assert_eq!(bench.apb(&[status]), vec![0b100], "Transmitter idle");
+    bench.apb(&[write(b'H' as u32), write(b'i' as u32), write(0xff)]);
+    assert_eq!(bench.apb(&[status])[0] & 0b100, 0, "Transmitter busy");
+    // Each frame is 12 bits of 4 cycles
+    bench.run(3 * 12 * 4);
+    assert_eq!(bench.apb(&[status]), vec![0b100], "Transmitter idle");
+
+    let instances = bench.sim.instances();
+    let mut t = instances.inst_mut::<UartTerminal>(bench.terminal);
+    assert_eq!(t.take_received(), b"Hi\xff".to_vec());
+    assert_eq!(t.errors(), &[]);
+    drop(t);
+    assert_eq!(bench.sim.assertion_failures(), vec![]);
+    Ok(())
+}
+
+#[test]
+fn terminal_to_uart() -> Result<(), String> {
+    let config = UartConfig {
+        divisor: 5,
+        ..Default::default()
+    };
+    let bench = Bench::new(config, config)?;
+    let instances = bench.sim.instances();
+    instances
+        .inst_mut::<UartTerminal>(bench.terminal)
+        .send(b"ok\n");
+    while !instances.inst::<UartTerminal>(bench.terminal).is_idle() {
+        bench.sim.fire_next_edges();
+    }
+    bench.run(10);
+
+    let data = ApbOp::Read { address: 0 };
+    let status = ApbOp::Read { address: 1 };
+    assert_eq!(bench.apb(&[status])[0] & 1, 1, "Byte available");
+    assert_eq!(
+        bench.apb(&[data, data, data, status, data]),
+        vec![b'o' as u32, b'k' as u32, b'\n' as u32, 0b100, 0]
+    );
+    assert_eq!(bench.apb(&[ApbOp::Read { address: 2 }]), vec![5], "Divisor");
+    bench.apb(&[ApbOp::Write {
+        address: 2,
+        data: 8,
+    }]);
+    assert_eq!(instances.inst::<Uart>(bench.uart).config().divisor, 8);
+    Ok(())
+}
+
+#[test]
+fn parity_errors() -> Result<(), String> {
+    let uart_config = UartConfig {
+        divisor: 4,
+        parity: UartParity::Even,
+        stop_bits: 1,
+    };
+    let terminal_config = UartConfig {
+        parity: UartParity::Odd,
+        ..uart_config
+    };
+    let bench = Bench::new(uart_config, terminal_config)?;
+    let instances = bench.sim.instances();
+    instances
+        .inst_mut::<UartTerminal>(bench.terminal)
+        .send(b"a");
+    bench.apb(&[ApbOp::Write {
+        address: 0,
+        data: b'b' as u32,
+    }]);
+    bench.run(4 * 11 * 2);
+
+    let status = ApbOp::Read { address: 1 };
+    assert_eq!(bench.apb(&[status])[0] & 0b1001, 0b1000, "Parity error");
+    assert_eq!(
+        instances.inst::<UartTerminal>(bench.terminal).errors(),
+        &[UartError::Parity(b'b')]
+    );
+    bench.apb(&[ApbOp::Write {
+        address: 1,
+        data: 0b1000,
+    }]);
+    assert_eq!(bench.apb(&[status])[0] & 0b1000, 0, "Parity error cleared");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_socket() -> Result<(), String> {
+    let path = std::env::temp_dir().join(format!("hgl_uart_{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let stream = UnixSocketStream::bind(&path)?;
+    let config = UartConfig {
+        divisor: 2,
+        ..Default::default()
+    };
+    let bench = Bench::new(config, config)?;
+    let instances = bench.sim.instances();
+    instances
+        .inst_mut::<UartTerminal>(bench.terminal)
+        .set_stream(Box::new(stream));
+
+    let mut host = UnixStream::connect(&path).map_err(|e| e.to_string())?;
+    host.write_all(b"xy").map_err(|e| e.to_string())?;
+    bench.run(2 * 10 * 4);
+    let data = ApbOp::Read { address: 0 };
+    assert_eq!(bench.apb(&[data, data]), vec![b'x' as u32, b'y' as u32]);
+
+    bench.apb(&[ApbOp::Write {
+        address: 0,
+        data: b'z' as u32,
+    }]);
+    bench.run(2 * 10 * 2);
+    let mut buffer = [0; 1];
+    host.read_exact(&mut buffer).map_err(|e| e.to_string())?;
+    assert_eq!(&buffer, b"z");
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
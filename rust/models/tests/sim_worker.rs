@@ -0,0 +1,113 @@
+use hgl_models::Counter;
+use hgl_sim::prelude::sim::*;
+
+type T = Bv<16>;
+
+/// Run a counter with two worker threads, one sampling it on rising
+/// edges and one loading it on a falling edge, returning the samples
+fn run() -> Result<(Vec<(usize, u64)>, usize), String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 2, 1)?;
+    let cntr = sim.instantiate::<Counter<T>, _, _>("counter", || None)?;
+    sim.connect_clock(clk, cntr, 0);
+    sim.prepare_simulation();
+    let instances = sim.instances();
+    sim.start(true)?;
+
+    {
+        let mut inst = instances.inst_mut::<Counter<T>>(cntr);
+        *inst.inputs.reset_n = true;
+        *inst.inputs.increment = true;
+    }
+
+    let mut sampler = sim.spawn_worker(SimEdgeMask::of_clock(clk, true, false));
+    let mut loader = sim.spawn_worker(SimEdgeMask::of_clock(clk, false, true));
+    std::thread::scope(|s| {
+        let sampler = s.spawn(move || {
+            let mut samples = vec![];
+            while let Some(edges) = sampler.wait_for_edge() {
+                assert!(edges.is_posedge(0));
+                let sample = sampler.with_sim(move |sim| {
+                    let data = sim.instances().inst::<Counter<T>>(cntr).outputs.data;
+                    (sim.time(), data.try_as_u64().unwrap())
+                });
+                samples.extend(sample);
+            }
+            samples
+        });
+        let loader = s.spawn(move || {
+            let mut negedges = 0;
+            while loader.wait_for_edge().is_some() {
+                negedges += 1;
+                loader.with_sim(move |sim| {
+                    let instances = sim.instances();
+                    let mut inst = instances.inst_mut::<Counter<T>>(cntr);
+                    match sim.time() {
+                        9 => {
+                            *inst.inputs.increment = false;
+                            *inst.inputs.load = true;
+                            inst.inputs.data = T::of_u64(100);
+                        }
+                        11 => {
+                            *inst.inputs.increment = true;
+                            *inst.inputs.load = false;
+                        }
+                        _ => (),
+                    }
+                });
+            }
+            negedges
+        });
+
+        for _ in 0..40 {
+            sim.fire_next_edges();
+        }
+        sim.stop()?;
+
+        // Once the simulation has stopped the workers are finished
+        let samples = sampler.join().unwrap();
+        let negedges = loader.join().unwrap();
+        Ok((samples, negedges))
+    })
+}
+
+#[test]
+fn sim_worker() -> Result<(), String> {
+    let (samples, negedges) = run()?;
+    assert_eq!(negedges, 20);
+    assert_eq!(samples.len(), 20, "One sample per rising edge");
+    for (i, (time, data)) in samples.iter().enumerate() {
+        assert_eq!(*time, 2 * i);
+        if i < 5 {
+            assert_eq!(*data, i as u64 + 1);
+        } else {
+            assert_eq!(*data, i as u64 + 95, "Loaded with 100 at time 10");
+        }
+    }
+    assert_eq!(run()?, (samples, negedges), "Runs are deterministic");
+    Ok(())
+}
+
+#[test]
+fn sim_worker_dropped() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    sim.prepare_simulation();
+    sim.start(true)?;
+
+    // A worker dropped before it waits does not hold up the edges;
+    // nor does one that stops waiting part way through
+    drop(sim.spawn_worker(()));
+    let mut worker = sim.spawn_worker(SimEdgeMask::of_clock(clk, true, false));
+    let handle = std::thread::spawn(move || {
+        for _ in 0..5 {
+            worker.wait_for_edge().unwrap();
+        }
+    });
+    for _ in 0..10 {
+        sim.fire_next_edges();
+    }
+    handle.join().unwrap();
+    sim.stop()?;
+    Ok(())
+}
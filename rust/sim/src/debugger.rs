@@ -0,0 +1,361 @@
+//a Documentation
+//! An interactive debugger for simulations
+//!
+//! A [Debugger] wraps a prepared (and started) [Simulation], and
+//! executes textual commands to step the simulation, run it to a
+//! time, print and deposit state by hierarchical name, and manage
+//! breakpoints on value conditions. It can be driven line-by-line
+//! using [Debugger::execute], or as a read-eval-print loop using
+//! [Debugger::run_shell].
+//!
+//! The commands are:
+//!
+//! * `step [n]` - fire the next n (default 1) clock edges
+//!
+//! * `run [time]` - fire edges until the time is reached (or a
+//!   breakpoint hits); with no time, run until a breakpoint hits
+//!
+//! * `time` - show the current simulation time
+//!
+//! * `print <path> [hex|bin|dec|signed|oct]` - print state such as
+//!   `counter.q`, or all the state of an instance such as `counter`
+//!
+//! * `deposit <path> <value>` - set state to a value, such as `0x12`
+//!   or `16h1234`
+//!
+//! * `break <path> <op> <value>` - stop when the state compares with
+//!   a value (op is one of `==`, `!=`, `<`, `<=`, `>`, `>=`)
+//!
+//! * `breaks` - list the breakpoints; `delete <n>` removes one
+//!
+//! * `list` - show the instance tree and its state
+//!
+//! * `quit`
+
+//a Imports
+use std::io::{BufRead, Write};
+
+use crate::simulation::{InstanceHandle, SimStateIndex, Simulation};
+use crate::values::{fmt, parse_u8s, SimFormatObject};
+
+//a Types
+//tp DebugAction
+/// The result of executing a debugger command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugAction {
+    /// Continue accepting commands
+    Continue,
+    /// The debugger should exit
+    Quit,
+}
+
+//tp CompareOp
+/// A comparison used by a breakpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+//ip CompareOp
+impl CompareOp {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "==" => Some(Self::Eq),
+            "!=" => Some(Self::Ne),
+            "<" => Some(Self::Lt),
+            "<=" => Some(Self::Le),
+            ">" => Some(Self::Gt),
+            ">=" => Some(Self::Ge),
+            _ => None,
+        }
+    }
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+        }
+    }
+    fn compare(&self, a: u64, b: u64) -> bool {
+        match self {
+            Self::Eq => a == b,
+            Self::Ne => a != b,
+            Self::Lt => a < b,
+            Self::Le => a <= b,
+            Self::Gt => a > b,
+            Self::Ge => a >= b,
+        }
+    }
+}
+
+//tp Breakpoint
+/// A breakpoint on the value of some state
+#[derive(Debug, Clone)]
+struct Breakpoint {
+    path: String,
+    handle: InstanceHandle,
+    state_index: SimStateIndex,
+    op: CompareOp,
+    value: u64,
+}
+
+//fi parse_u64
+/// Parse a u64 in any of the forms accepted by [parse_u8s], such as
+/// decimal, `0x1f` or `64h1f`
+fn parse_u64(s: &str) -> Result<u64, String> {
+    let mut data = [0; 8];
+    parse_u8s(s, 64, &mut data)?;
+    Ok(u64::from_le_bytes(data))
+}
+
+//a Debugger
+//tp Debugger
+/// An interactive debugger operating on a [Simulation]
+pub struct Debugger<'a, 's> {
+    sim: &'a Simulation<'s>,
+    breakpoints: Vec<Breakpoint>,
+}
+
+//ip Debugger
+impl<'a, 's> Debugger<'a, 's> {
+    //cp new
+    /// Create a new debugger for a simulation, which must have been
+    /// prepared and started
+    pub fn new(sim: &'a Simulation<'s>) -> Self {
+        Self {
+            sim,
+            breakpoints: vec![],
+        }
+    }
+
+    //mp run_shell
+    /// Run a read-eval-print loop, reading commands from the input
+    /// until the end of the input or a `quit` command
+    pub fn run_shell<R: BufRead, W: Write>(
+        &mut self,
+        input: R,
+        output: &mut W,
+    ) -> std::io::Result<()> {
+        write!(output, "hgl> ")?;
+        output.flush()?;
+        for line in input.lines() {
+            match self.execute(&line?, output) {
+                Ok(DebugAction::Quit) => break,
+                Ok(DebugAction::Continue) => (),
+                Err(e) => writeln!(output, "error: {e}")?,
+            }
+            write!(output, "hgl> ")?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    //mp execute
+    /// Execute a single command, writing any results to the output
+    pub fn execute<W: Write>(&mut self, line: &str, output: &mut W) -> Result<DebugAction, String> {
+        let args: Vec<&str> = line.split_whitespace().collect();
+        let Some(cmd) = args.first() else {
+            return Ok(DebugAction::Continue);
+        };
+        let io = |e: std::io::Error| e.to_string();
+        match (*cmd, args.len()) {
+            ("quit" | "q", 1) => {
+                return Ok(DebugAction::Quit);
+            }
+            ("help" | "h", 1) => {
+                writeln!(
+                    output,
+                    "step [n], run [time], time, print <path> [hex|bin|dec|signed|oct], deposit <path> <value>,"
+                )
+                .map_err(io)?;
+                writeln!(
+                    output,
+                    "break <path> <op> <value>, breaks, delete <n>, list, quit"
+                )
+                .map_err(io)?;
+            }
+            ("time", 1) => {
+                writeln!(output, "{}", self.sim.time()).map_err(io)?;
+            }
+            ("step" | "s", 1 | 2) => {
+                let n = args.get(1).map(|n| parse_u64(n)).transpose()?.unwrap_or(1);
+                for _ in 0..n {
+                    if self.fire_edges(output)? {
+                        break;
+                    }
+                }
+                writeln!(output, "time {}", self.sim.time()).map_err(io)?;
+            }
+            ("run" | "r", 1 | 2) => {
+                let end_time = args.get(1).map(|n| parse_u64(n)).transpose()?;
+                if end_time.is_none() && self.breakpoints.is_empty() {
+                    return Err("run without a time requires a breakpoint".into());
+                }
+                while end_time.is_none_or(|t| (self.sim.time() as u64) < t) {
+                    if self.fire_edges(output)? {
+                        break;
+                    }
+                }
+                writeln!(output, "time {}", self.sim.time()).map_err(io)?;
+            }
+            ("print" | "p", 2 | 3) => {
+                let style = match args.get(2) {
+                    None | Some(&"hex") => fmt::FULL,
+                    Some(&"bin") => fmt::AS_BIN | fmt::HDR,
+                    Some(&"dec") => fmt::AS_DEC,
+                    Some(&"signed") => fmt::AS_SIGNED,
+                    Some(&"oct") => fmt::AS_OCT | fmt::HDR,
+                    Some(s) => {
+                        return Err(format!("Unknown format '{s}'"));
+                    }
+                };
+                self.print(args[1], style, output)?;
+            }
+            ("deposit" | "d", 3) => {
+                let (handle, state_index) = self.find_state(args[1])?;
+                self.sim
+                    .with_state_mut(handle, state_index, |mut v| v.set_from_str(args[2]))
+                    .ok_or_else(|| format!("Failed to deposit into '{}'", args[1]))??;
+            }
+            ("break" | "b", 4) => {
+                let (handle, state_index) = self.find_state(args[1])?;
+                let Some(op) = CompareOp::parse(args[2]) else {
+                    return Err(format!("Unknown comparison '{}'", args[2]));
+                };
+                let value = parse_u64(args[3])?;
+                self.breakpoints.push(Breakpoint {
+                    path: args[1].into(),
+                    handle,
+                    state_index,
+                    op,
+                    value,
+                });
+                writeln!(output, "breakpoint {}", self.breakpoints.len() - 1).map_err(io)?;
+            }
+            ("breaks", 1) => {
+                for (i, b) in self.breakpoints.iter().enumerate() {
+                    writeln!(output, "{i}: {} {} {:#x}", b.path, b.op.as_str(), b.value)
+                        .map_err(io)?;
+                }
+            }
+            ("delete", 2) => {
+                let n = parse_u64(args[1])? as usize;
+                if n >= self.breakpoints.len() {
+                    return Err(format!("No breakpoint {n}"));
+                }
+                self.breakpoints.remove(n);
+            }
+            ("list" | "l", 1) => {
+                self.list(output)?;
+            }
+            _ => {
+                return Err(format!("Bad command '{line}' - try 'help'"));
+            }
+        }
+        Ok(DebugAction::Continue)
+    }
+
+    //mi find_state
+    fn find_state(&self, path: &str) -> Result<(InstanceHandle, SimStateIndex), String> {
+        self.sim
+            .find_state(path)
+            .ok_or_else(|| format!("Failed to find state '{path}'"))
+    }
+
+    //mi fire_edges
+    /// Fire the next edges, and return true if a breakpoint has hit
+    fn fire_edges<W: Write>(&self, output: &mut W) -> Result<bool, String> {
+        self.sim.fire_next_edges();
+        let mut hit = false;
+        for (i, b) in self.breakpoints.iter().enumerate() {
+            let Some(v) = self
+                .sim
+                .with_state(b.handle, b.state_index, |v| v.get_u64())
+                .flatten()
+            else {
+                continue;
+            };
+            if b.op.compare(v, b.value) {
+                writeln!(
+                    output,
+                    "breakpoint {i} hit at time {}: {} = {v:#x}",
+                    self.sim.time(),
+                    b.path
+                )
+                .map_err(|e| e.to_string())?;
+                hit = true;
+            }
+        }
+        Ok(hit)
+    }
+
+    //mi print
+    /// Print a state, or all of the state of an instance
+    fn print<W: Write>(&self, path: &str, style: usize, output: &mut W) -> Result<(), String> {
+        let io = |e: std::io::Error| e.to_string();
+        if let Some((handle, state_index)) = self.sim.find_state(path) {
+            let s = self
+                .sim
+                .with_state(handle, state_index, |v| {
+                    SimFormatObject::new(v.sim_value(), style).to_string()
+                })
+                .ok_or_else(|| format!("State '{path}' has no data"))?;
+            writeln!(output, "{path} = {s}").map_err(io)?;
+        } else if let Some(handle) = self.sim.find_instance(path) {
+            for (p, _, state_index) in self
+                .sim
+                .state_paths()
+                .into_iter()
+                .filter(|(_, h, _)| *h == handle)
+            {
+                if let Some(s) = self.sim.with_state(handle, state_index, |v| {
+                    SimFormatObject::new(v.sim_value(), style).to_string()
+                }) {
+                    writeln!(output, "{p} = {s}").map_err(io)?;
+                }
+            }
+        } else {
+            return Err(format!("Failed to find state or instance '{path}'"));
+        }
+        Ok(())
+    }
+
+    //mi list
+    /// List the instance tree, with the state of each instance
+    fn list<W: Write>(&self, output: &mut W) -> Result<(), String> {
+        let io = |e: std::io::Error| e.to_string();
+        let instances = self.sim.instances();
+        let state_paths = self.sim.state_paths();
+        for handle in instances.iter_handles() {
+            let path = self.sim.ns_name_string(instances.instance(handle).name());
+            let depth = path.matches('.').count();
+            let name = path.rsplit('.').next().unwrap_or(&path);
+            writeln!(output, "{:indent$}{name}", "", indent = depth * 2).map_err(io)?;
+            for (p, _, state_index) in state_paths.iter().filter(|(_, h, _)| *h == handle) {
+                let state_name = p.rsplit('.').next().unwrap_or(p);
+                let value = self
+                    .sim
+                    .with_state(handle, *state_index, |v| {
+                        SimFormatObject::new(v.sim_value(), fmt::FULL).to_string()
+                    })
+                    .unwrap_or_default();
+                writeln!(
+                    output,
+                    "{:indent$}{state_name} {value}",
+                    "",
+                    indent = depth * 2 + 2
+                )
+                .map_err(io)?;
+            }
+        }
+        Ok(())
+    }
+}
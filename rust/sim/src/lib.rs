@@ -164,12 +164,16 @@
 //!
 
 //a Modules
-pub mod component_desc;
 pub(crate) mod data;
+pub(crate) mod debugger;
 pub mod prelude;
+pub(crate) mod server;
 pub(crate) mod simulation;
+pub(crate) mod threaded;
+pub(crate) mod tlm;
 pub(crate) mod traits;
 pub(crate) mod value_types;
 pub(crate) mod values;
+pub(crate) mod waveform;
 
 pub mod sync;
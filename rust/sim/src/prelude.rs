@@ -1,17 +1,29 @@
 pub mod sim {
-    pub use crate::simulation::{Clock, InstanceHandle, RefMutInstance, Simulation};
+    pub use crate::debugger::{DebugAction, Debugger};
+    pub use crate::server::{RpcClient, RpcServer};
+    pub use crate::simulation::{AssertionFailure, CmpOp, Expr, Property};
+    pub use crate::simulation::{Checkpoint, CheckpointState};
+    pub use crate::simulation::{Clock, ClockIndex, InstanceHandle, RefMutInstance, Simulation};
+    pub use crate::simulation::{
+        CoverBinReport, CoverGroup, CoverGroupReport, CoverPoint, CoverPointReport, CoverageReport,
+    };
+    pub use crate::simulation::{Dist, SimRng, SimSeed, Stimulus};
+    pub use crate::simulation::{SimEdgeMask, SimulationWorker, Testbench};
+    pub use crate::tlm::{TlmPhase, TlmSocket, TlmSync, TlmTarget, TlmTransport};
     pub use crate::traits::{Component, Simulatable};
     pub use crate::traits::{
         IsBv, SimArray, SimBit, SimBv, SimCopyValue, SimStruct, SimValueAsU8s, SimValueObject,
     };
     pub use crate::value_types::{Bit, Bv, BvN};
-    pub use crate::values::fmt;
-    pub use crate::values::{SimFormatValue, SimValueRef, SimValueRefMut};
+    pub use crate::values::{fmt, parse_u8s};
+    pub use crate::values::{SimFormatObject, SimFormatValue, SimValueRef, SimValueRefMut};
+    pub use crate::waveform::Vcd;
 }
 
 pub mod component {
     pub use super::sim::*;
     pub use crate::simulation::SimNsName;
     pub use crate::simulation::{SimEdgeMask, SimReset, SimStateIndex, SimStateInfo};
+    pub use crate::threaded::{Model, ModelState, ThreadedModel};
     pub use crate::traits::{ComponentBuilder, SimHandle, SimRegister};
 }
@@ -0,0 +1,415 @@
+//a Documentation
+//! A remote control server for simulations
+//!
+//! An [RpcServer] exposes a [Simulation] using JSON-RPC 2.0, with one
+//! request or response per line, so that it can be driven from other
+//! languages (such as a Python test infrastructure) over a local TCP
+//! or Unix socket. An [RpcClient] is provided for Rust clients (and
+//! for testing).
+//!
+//! The methods supported are:
+//!
+//! * `start` (with optional param `running`, default true), `pause`,
+//!   `resume`, `stop` - control the simulation; result is null
+//!
+//! * `step` (with optional param `edges`, default 1) - fire the next
+//!   clock edges; result is the new time
+//!
+//! * `run` (with param `time`) - fire edges until the time is reached;
+//!   result is the new time
+//!
+//! `step` and `run` are errors if the simulation is not running (it
+//! has not been started, or is paused or stopped), or if it has no
+//! clocks (as time could not advance).
+//!
+//! * `time` - result is the current simulation time
+//!
+//! * `lookup` (with param `name`) - result is "state", "instance",
+//!   "clock", or null, for a hierarchical name
+//!
+//! * `states` - result is an array of the paths of all the state
+//!
+//! * `read` (with param `path`, optional `format` of "hex", "bin",
+//!   "dec", "signed" or "oct") - result is an object with `text` of
+//!   the formatted value and `value` as a number (or null if it does
+//!   not fit in 64 bits)
+//!
+//! * `write` (with params `path` and `value`) - set state to a
+//!   number, or to a string such as "16h1234"; result is null
+//!
+//! * `shutdown` - the server returns after responding
+//!
+//! For example:
+//!
+//! ```text
+//! --> {"jsonrpc": "2.0", "id": 1, "method": "read", "params": {"path": "counter.q"}}
+//! <-- {"jsonrpc":"2.0","id":1,"result":{"text":"32h00000004","value":4}}
+//! ```
+
+//a Imports
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use serde_json::{json, Value};
+
+use crate::simulation::{InstanceHandle, SimStateIndex, Simulation};
+use crate::values::{fmt, SimFormatObject};
+
+//a Error codes
+/// Invalid JSON was received
+const PARSE_ERROR: i64 = -32700;
+/// The JSON is not a valid request object
+const INVALID_REQUEST: i64 = -32600;
+/// The method does not exist
+const METHOD_NOT_FOUND: i64 = -32601;
+/// Invalid method parameters
+const INVALID_PARAMS: i64 = -32602;
+/// The simulation reported an error
+const SIMULATION_ERROR: i64 = -32000;
+
+//a RpcError
+//ti RpcError
+/// An error to be returned in a response
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+//ii RpcError
+impl RpcError {
+    fn params<S: Into<String>>(message: S) -> Self {
+        Self {
+            code: INVALID_PARAMS,
+            message: message.into(),
+        }
+    }
+}
+
+//ii From<String> for RpcError
+impl From<String> for RpcError {
+    fn from(message: String) -> Self {
+        Self {
+            code: SIMULATION_ERROR,
+            message,
+        }
+    }
+}
+
+//a RpcServer
+//tp RpcServer
+/// A JSON-RPC server for a [Simulation]
+///
+/// The simulation is not thread-safe, so the server runs on the
+/// thread that owns the simulation, and handles one connection at a
+/// time
+pub struct RpcServer<'a, 's> {
+    sim: &'a Simulation<'s>,
+    shutdown: bool,
+}
+
+//ip RpcServer
+impl<'a, 's> RpcServer<'a, 's> {
+    //cp new
+    /// Create a new server for a simulation, which must have been
+    /// prepared
+    pub fn new(sim: &'a Simulation<'s>) -> Self {
+        Self {
+            sim,
+            shutdown: false,
+        }
+    }
+
+    //ap is_shutdown
+    /// Return true if a `shutdown` request has been handled
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown
+    }
+
+    //mp serve_tcp
+    /// Accept connections on a TCP listener, handling each in turn,
+    /// until a `shutdown` request is received
+    ///
+    /// An I/O error on a connection (such as the client resetting it)
+    /// ends only that connection; it is reported to stderr
+    pub fn serve_tcp(&mut self, listener: &TcpListener) -> std::io::Result<()> {
+        while !self.shutdown {
+            let (stream, _) = listener.accept()?;
+            let served = stream
+                .try_clone()
+                .and_then(|reader| self.serve(BufReader::new(reader), stream));
+            if let Err(e) = served {
+                eprintln!("hgl_sim: RPC connection failed: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    //mp serve_unix
+    /// Accept connections on a Unix socket listener, handling each in
+    /// turn, until a `shutdown` request is received, as for
+    /// [RpcServer::serve_tcp]
+    #[cfg(unix)]
+    pub fn serve_unix(
+        &mut self,
+        listener: &std::os::unix::net::UnixListener,
+    ) -> std::io::Result<()> {
+        while !self.shutdown {
+            let (stream, _) = listener.accept()?;
+            let served = stream
+                .try_clone()
+                .and_then(|reader| self.serve(BufReader::new(reader), stream));
+            if let Err(e) = served {
+                eprintln!("hgl_sim: RPC connection failed: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    //mp serve
+    /// Handle requests, one per line, from a reader until it closes
+    /// or a `shutdown` request is received, writing the responses to
+    /// the writer
+    pub fn serve<R: BufRead, W: Write>(&mut self, reader: R, mut writer: W) -> std::io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle_request(&line) {
+                writeln!(writer, "{response}")?;
+                writer.flush()?;
+            }
+            if self.shutdown {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    //mp handle_request
+    /// Handle a single JSON-RPC request, returning the response (if
+    /// the request is not a notification)
+    pub fn handle_request(&mut self, request: &str) -> Option<String> {
+        let request: Value = match serde_json::from_str(request) {
+            Ok(r) => r,
+            Err(e) => {
+                return Some(Self::response(
+                    Value::Null,
+                    Err(RpcError {
+                        code: PARSE_ERROR,
+                        message: e.to_string(),
+                    }),
+                ));
+            }
+        };
+        let id = request.get("id").cloned();
+        let result = match request.get("method").and_then(|m| m.as_str()) {
+            Some(method) => {
+                let params = request.get("params").cloned().unwrap_or(Value::Null);
+                self.invoke(method, &params)
+            }
+            None => Err(RpcError {
+                code: INVALID_REQUEST,
+                message: "Request must have a method".into(),
+            }),
+        };
+        id.map(|id| Self::response(id, result))
+    }
+
+    //fi response
+    fn response(id: Value, result: Result<Value, RpcError>) -> String {
+        match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(e) => json!({"jsonrpc": "2.0", "id": id,
+                             "error": {"code": e.code, "message": e.message}}),
+        }
+        .to_string()
+    }
+
+    //mi find_state
+    fn find_state(&self, params: &Value) -> Result<(InstanceHandle, SimStateIndex), RpcError> {
+        let path = params
+            .get("path")
+            .and_then(|p| p.as_str())
+            .ok_or_else(|| RpcError::params("Missing 'path'"))?;
+        self.sim
+            .find_state(path)
+            .ok_or_else(|| RpcError::params(format!("Failed to find state '{path}'")))
+    }
+
+    //mi invoke
+    fn invoke(&mut self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        let sim = self.sim;
+        let u64_param = |name: &str| params.get(name).and_then(|v| v.as_u64());
+        match method {
+            "start" => {
+                let running = params
+                    .get("running")
+                    .and_then(|r| r.as_bool())
+                    .unwrap_or(true);
+                sim.start(running)?;
+                Ok(Value::Null)
+            }
+            "pause" => Ok(sim.pause().map(|_| Value::Null)?),
+            "resume" => Ok(sim.resume().map(|_| Value::Null)?),
+            "stop" => Ok(sim.stop().map(|_| Value::Null)?),
+            "step" | "run" if !sim.is_running() => {
+                Err(format!("Cannot {method} a simulation that is not running").into())
+            }
+            "step" | "run" if sim.num_clocks() == 0 => {
+                Err(format!("Cannot {method} a simulation that has no clocks").into())
+            }
+            "step" => {
+                for _ in 0..u64_param("edges").unwrap_or(1) {
+                    sim.fire_next_edges();
+                }
+                Ok(sim.time().into())
+            }
+            "run" => {
+                let time = u64_param("time").ok_or_else(|| RpcError::params("Missing 'time'"))?;
+                while (sim.time() as u64) < time {
+                    sim.fire_next_edges();
+                }
+                Ok(sim.time().into())
+            }
+            "time" => Ok(sim.time().into()),
+            "lookup" => {
+                let name = params
+                    .get("name")
+                    .and_then(|p| p.as_str())
+                    .ok_or_else(|| RpcError::params("Missing 'name'"))?;
+                if sim.find_state(name).is_some() {
+                    Ok("state".into())
+                } else if sim.find_instance(name).is_some() {
+                    Ok("instance".into())
+                } else if sim
+                    .find_ns_name(name)
+                    .and_then(|n| sim.find_clock(n))
+                    .is_some()
+                {
+                    Ok("clock".into())
+                } else {
+                    Ok(Value::Null)
+                }
+            }
+            "states" => Ok(sim
+                .state_paths()
+                .into_iter()
+                .map(|(p, _, _)| Value::from(p))
+                .collect()),
+            "read" => {
+                let (handle, state_index) = self.find_state(params)?;
+                let style = match params.get("format").and_then(|f| f.as_str()) {
+                    None | Some("hex") => fmt::FULL,
+                    Some("bin") => fmt::AS_BIN | fmt::HDR,
+                    Some("dec") => fmt::AS_DEC,
+                    Some("signed") => fmt::AS_SIGNED,
+                    Some("oct") => fmt::AS_OCT | fmt::HDR,
+                    Some(f) => {
+                        return Err(RpcError::params(format!("Unknown format '{f}'")));
+                    }
+                };
+                sim.with_state(handle, state_index, |v| {
+                    json!({"text": SimFormatObject::new(v.sim_value(), style).to_string(),
+                           "value": v.get_u64()})
+                })
+                .ok_or_else(|| "State is not accessible".to_string().into())
+            }
+            "write" => {
+                let (handle, state_index) = self.find_state(params)?;
+                let value = match params.get("value") {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(Value::Number(n)) if n.is_u64() => n.to_string(),
+                    _ => {
+                        return Err(RpcError::params("Missing or bad 'value'"));
+                    }
+                };
+                sim.with_state_mut(handle, state_index, |mut v| v.set_from_str(&value))
+                    .ok_or_else(|| "State is not accessible".to_string())??;
+                Ok(Value::Null)
+            }
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            _ => Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("Unknown method '{method}'"),
+            }),
+        }
+    }
+}
+
+//a RpcClient
+//tp RpcClient
+/// A client for an [RpcServer]
+pub struct RpcClient<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+    next_id: u64,
+}
+
+//ip RpcClient for TcpStream
+impl RpcClient<BufReader<TcpStream>, TcpStream> {
+    //cp connect_tcp
+    /// Connect to a server on a TCP socket
+    pub fn connect_tcp<A: ToSocketAddrs>(addr: A) -> std::io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        Ok(Self::new(BufReader::new(stream.try_clone()?), stream))
+    }
+}
+
+//ip RpcClient for UnixStream
+#[cfg(unix)]
+impl RpcClient<BufReader<std::os::unix::net::UnixStream>, std::os::unix::net::UnixStream> {
+    //cp connect_unix
+    /// Connect to a server on a Unix socket
+    pub fn connect_unix<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let stream = std::os::unix::net::UnixStream::connect(path)?;
+        Ok(Self::new(BufReader::new(stream.try_clone()?), stream))
+    }
+}
+
+//ip RpcClient
+impl<R: BufRead, W: Write> RpcClient<R, W> {
+    //cp new
+    /// Create a client from a reader and writer connected to a server
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer,
+            next_id: 0,
+        }
+    }
+
+    //mp call
+    /// Invoke a method on the server, and wait for its result
+    pub fn call(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        self.next_id += 1;
+        let request =
+            json!({"jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params});
+        writeln!(self.writer, "{request}").map_err(|e| e.to_string())?;
+        self.writer.flush().map_err(|e| e.to_string())?;
+        let mut line = String::new();
+        if self
+            .reader
+            .read_line(&mut line)
+            .map_err(|e| e.to_string())?
+            == 0
+        {
+            return Err("Server closed the connection".into());
+        }
+        let mut response: Value = serde_json::from_str(&line).map_err(|e| e.to_string())?;
+        if let Some(error) = response.get("error") {
+            return Err(error
+                .get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("Unknown error")
+                .to_string());
+        }
+        Ok(response
+            .get_mut("result")
+            .map(|r| r.take())
+            .unwrap_or_default())
+    }
+}
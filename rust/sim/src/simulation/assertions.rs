@@ -0,0 +1,620 @@
+//a Documentation
+//! Temporal assertions over the named state of a simulation
+//!
+//! A [Property] is an [Expr] (or a pair of them) over named state,
+//! such as `dut.apb_request.psel && !dut.apb_request.penable`. It is
+//! registered with a [Simulation] for a clock using
+//! [Simulation::add_assertion], and is then checked after every
+//! posedge of that clock that the simulation fires. Failures are
+//! recorded as [AssertionFailure]s, which report the time, the
+//! instance path, and the values of the signals involved.
+//!
+//! Expressions use `!`, `&&`, `||`, comparisons (`==`, `!=`, `<`,
+//! `<=`, `>`, `>=`) and parentheses, with signals given by
+//! hierarchical path (including fields of structures) and numbers in
+//! any form accepted by [parse_u8s] (such as `0x10` or `4b1010`). A
+//! signal or number is true if it is non-zero.
+
+//a Imports
+use std::collections::HashMap;
+
+use hgl_indexed_vec::Idx;
+
+use crate::simulation::{ClockIndex, InstanceHandle, SimEdgeMask, SimStateIndex, Simulation};
+use crate::values::{fmt, parse_u8s, SimFormatObject};
+
+//a Expr
+//tp CmpOp
+/// A comparison operator in an [Expr]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+//ip CmpOp
+impl CmpOp {
+    fn compare(&self, a: u64, b: u64) -> bool {
+        match self {
+            Self::Eq => a == b,
+            Self::Ne => a != b,
+            Self::Lt => a < b,
+            Self::Le => a <= b,
+            Self::Gt => a > b,
+            Self::Ge => a >= b,
+        }
+    }
+}
+
+//tp Expr
+/// An expression over named state of a simulation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// The value of a signal, by hierarchical path
+    Signal(String),
+    /// A constant
+    Const(u64),
+    /// True if the expression is false
+    Not(Box<Expr>),
+    /// True if both expressions are true
+    And(Box<Expr>, Box<Expr>),
+    /// True if either expression is true
+    Or(Box<Expr>, Box<Expr>),
+    /// A comparison of the values of two expressions
+    Cmp(CmpOp, Box<Expr>, Box<Expr>),
+}
+
+//ip Expr
+impl Expr {
+    //cp parse
+    /// Parse an expression from text, such as `req.psel && !req.penable`
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+        };
+        let expr = parser.or_expr()?;
+        if parser.pos != tokens.len() {
+            return Err(format!(
+                "Unexpected '{}' in expression '{text}'",
+                tokens[parser.pos]
+            ));
+        }
+        Ok(expr)
+    }
+
+    //mi add_signals
+    /// Add the paths of the signals used in the expression to a Vec,
+    /// if they are not already present
+    pub(crate) fn add_signals(&self, signals: &mut Vec<String>) {
+        match self {
+            Self::Signal(s) => {
+                if !signals.contains(s) {
+                    signals.push(s.clone());
+                }
+            }
+            Self::Const(_) => (),
+            Self::Not(e) => e.add_signals(signals),
+            Self::And(a, b) | Self::Or(a, b) | Self::Cmp(_, a, b) => {
+                a.add_signals(signals);
+                b.add_signals(signals);
+            }
+        }
+    }
+
+    //mi compile
+    pub(crate) fn compile(&self, signals: &HashMap<String, usize>) -> CExpr {
+        match self {
+            Self::Signal(s) => CExpr::Signal(signals[s]),
+            Self::Const(v) => CExpr::Const(*v),
+            Self::Not(e) => CExpr::Not(Box::new(e.compile(signals))),
+            Self::And(a, b) => {
+                CExpr::And(Box::new(a.compile(signals)), Box::new(b.compile(signals)))
+            }
+            Self::Or(a, b) => CExpr::Or(Box::new(a.compile(signals)), Box::new(b.compile(signals))),
+            Self::Cmp(op, a, b) => CExpr::Cmp(
+                *op,
+                Box::new(a.compile(signals)),
+                Box::new(b.compile(signals)),
+            ),
+        }
+    }
+}
+
+//ti CExpr
+/// An expression whose signals are indices into a set of signals
+#[derive(Debug)]
+pub(crate) enum CExpr {
+    Signal(usize),
+    Const(u64),
+    Not(Box<CExpr>),
+    And(Box<CExpr>, Box<CExpr>),
+    Or(Box<CExpr>, Box<CExpr>),
+    Cmp(CmpOp, Box<CExpr>, Box<CExpr>),
+}
+
+//ii CExpr
+impl CExpr {
+    fn value(&self, values: &[u64]) -> u64 {
+        match self {
+            Self::Signal(n) => values[*n],
+            Self::Const(v) => *v,
+            Self::Not(e) => (e.value(values) == 0) as u64,
+            Self::And(a, b) => (a.value(values) != 0 && b.value(values) != 0) as u64,
+            Self::Or(a, b) => (a.value(values) != 0 || b.value(values) != 0) as u64,
+            Self::Cmp(op, a, b) => op.compare(a.value(values), b.value(values)) as u64,
+        }
+    }
+    pub(crate) fn is_true(&self, values: &[u64]) -> bool {
+        self.value(values) != 0
+    }
+}
+
+//a Parsing
+//fi tokenize
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c.is_ascii_alphanumeric() || c == '_' {
+            let mut end = i + c.len_utf8();
+            while let Some((j, c)) = chars.peek() {
+                if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '[' | ']') {
+                    end = j + c.len_utf8();
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(text[i..end].to_string());
+            continue;
+        }
+        let next = chars.peek().map(|(_, c)| *c);
+        let token = match (c, next) {
+            ('&', Some('&')) | ('|', Some('|')) | ('=', Some('=')) => {
+                chars.next();
+                format!("{c}{c}")
+            }
+            ('!' | '<' | '>', Some('=')) => {
+                chars.next();
+                format!("{c}=")
+            }
+            ('!' | '<' | '>' | '(' | ')', _) => c.to_string(),
+            _ => {
+                return Err(format!("Unexpected '{c}' in expression '{text}'"));
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+//fi parse_number
+/// Parse a number of up to 64 bits
+fn parse_number(text: &str) -> Result<u64, String> {
+    let mut width = 64;
+    if !(text.starts_with("0x") || text.starts_with("0b") || text.starts_with("0o")) {
+        if let Some(n) = text.find(|c: char| !c.is_ascii_digit()) {
+            if n > 0 && matches!(text.as_bytes()[n], b'h' | b'b' | b'o' | b'd') {
+                width = text[0..n]
+                    .parse::<usize>()
+                    .map_err(|e| format!("Bad width in '{text}': {e}"))?;
+            }
+        }
+    }
+    if width > 64 {
+        return Err(format!("Value '{text}' is wider than 64 bits"));
+    }
+    let mut data = [0; 8];
+    parse_u8s(text, width, &mut data)?;
+    Ok(u64::from_le_bytes(data))
+}
+
+//ti Parser
+struct Parser<'a> {
+    tokens: &'a [String],
+    pos: usize,
+}
+
+//ii Parser
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|s| s.as_str())
+    }
+    fn next(&mut self) -> Result<&str, String> {
+        let t = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| "Unexpected end of expression".to_string())?;
+        self.pos += 1;
+        Ok(t)
+    }
+    fn or_expr(&mut self) -> Result<Expr, String> {
+        let mut e = self.and_expr()?;
+        while self.peek() == Some("||") {
+            self.pos += 1;
+            e = Expr::Or(Box::new(e), Box::new(self.and_expr()?));
+        }
+        Ok(e)
+    }
+    fn and_expr(&mut self) -> Result<Expr, String> {
+        let mut e = self.not_expr()?;
+        while self.peek() == Some("&&") {
+            self.pos += 1;
+            e = Expr::And(Box::new(e), Box::new(self.not_expr()?));
+        }
+        Ok(e)
+    }
+    fn not_expr(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some("!") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.not_expr()?)));
+        }
+        let e = self.primary()?;
+        let op = match self.peek() {
+            Some("==") => CmpOp::Eq,
+            Some("!=") => CmpOp::Ne,
+            Some("<") => CmpOp::Lt,
+            Some("<=") => CmpOp::Le,
+            Some(">") => CmpOp::Gt,
+            Some(">=") => CmpOp::Ge,
+            _ => {
+                return Ok(e);
+            }
+        };
+        self.pos += 1;
+        Ok(Expr::Cmp(op, Box::new(e), Box::new(self.primary()?)))
+    }
+    fn primary(&mut self) -> Result<Expr, String> {
+        let t = self.next()?;
+        if t == "(" {
+            let e = self.or_expr()?;
+            if self.next()? != ")" {
+                return Err("Expected ')' in expression".into());
+            }
+            Ok(e)
+        } else if t.starts_with(|c: char| c.is_ascii_digit()) {
+            Ok(Expr::Const(parse_number(t)?))
+        } else if t.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            Ok(Expr::Signal(t.to_string()))
+        } else {
+            Err(format!("Unexpected '{t}' in expression"))
+        }
+    }
+}
+
+//a Property
+//tp Property
+/// A property that must hold in a simulation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Property {
+    /// The expression must be true whenever it is checked
+    Always(Expr),
+    /// Whenever the antecedent is true, the consequent must be true
+    /// at some check between `min_cycles` and `max_cycles` (inclusive)
+    /// later; a `min_cycles` of 0 includes the same check
+    Implies {
+        antecedent: Expr,
+        consequent: Expr,
+        min_cycles: usize,
+        max_cycles: usize,
+    },
+}
+
+//ip Property
+impl Property {
+    //cp always
+    /// Create a property that an expression is always true
+    pub fn always(expr: &str) -> Result<Self, String> {
+        Ok(Self::Always(Expr::parse(expr)?))
+    }
+
+    //cp implies_next
+    /// Create a property that whenever the antecedent is true, the
+    /// consequent is true in the next cycle
+    pub fn implies_next(antecedent: &str, consequent: &str) -> Result<Self, String> {
+        Self::implies(antecedent, consequent, 1, 1)
+    }
+
+    //cp implies_within
+    /// Create a property that whenever the antecedent is true, the
+    /// consequent is true in that cycle or within the given number
+    /// of cycles afterwards
+    pub fn implies_within(
+        antecedent: &str,
+        consequent: &str,
+        cycles: usize,
+    ) -> Result<Self, String> {
+        Self::implies(antecedent, consequent, 0, cycles)
+    }
+
+    //cp implies
+    /// Create a property that whenever the antecedent is true, the
+    /// consequent is true at a check between `min_cycles` and
+    /// `max_cycles` later
+    pub fn implies(
+        antecedent: &str,
+        consequent: &str,
+        min_cycles: usize,
+        max_cycles: usize,
+    ) -> Result<Self, String> {
+        if min_cycles > max_cycles {
+            return Err(format!(
+                "Minimum cycles {min_cycles} is more than maximum {max_cycles}"
+            ));
+        }
+        Ok(Self::Implies {
+            antecedent: Expr::parse(antecedent)?,
+            consequent: Expr::parse(consequent)?,
+            min_cycles,
+            max_cycles,
+        })
+    }
+}
+
+//a AssertionFailure
+//tp AssertionFailure
+/// A failure of an assertion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssertionFailure {
+    /// The name the assertion was registered with
+    pub name: String,
+    /// The simulation time of the failure
+    pub time: usize,
+    /// The path of the instance (common to all the signals)
+    pub instance: String,
+    /// Description of the failure
+    pub message: String,
+    /// The path and formatted value of each signal involved, at the
+    /// time of failure
+    pub values: Vec<(String, String)>,
+}
+
+//ip Display for AssertionFailure
+impl std::fmt::Display for AssertionFailure {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(
+            fmt,
+            "{}: assertion '{}' failed in '{}': {} [",
+            self.time, self.name, self.instance, self.message
+        )?;
+        for (i, (path, value)) in self.values.iter().enumerate() {
+            if i > 0 {
+                fmt.write_str(", ")?;
+            }
+            write!(fmt, "{path}={value}")?;
+        }
+        fmt.write_str("]")
+    }
+}
+
+//a Assertion, AssertionSet
+//ti Signal
+struct Signal {
+    path: String,
+    handle: InstanceHandle,
+    state_index: SimStateIndex,
+    subelements: Vec<usize>,
+}
+
+//ti Assertion
+struct Assertion {
+    name: String,
+    clock: ClockIndex,
+    instance: String,
+    signals: Vec<Signal>,
+    /// The antecedent for an implication, or the expression for always
+    antecedent: CExpr,
+    consequent: Option<CExpr>,
+    min_cycles: usize,
+    max_cycles: usize,
+    /// Start time and elapsed cycles of antecedents that are waiting
+    /// for the consequent
+    pending: Vec<(usize, usize)>,
+}
+
+//tp AssertionSet
+/// The assertions registered with a simulation, and their failures
+#[derive(Default)]
+pub struct AssertionSet {
+    assertions: Vec<Assertion>,
+    failures: Vec<AssertionFailure>,
+}
+
+//a Simulation
+//ip Simulation
+impl Simulation<'_> {
+    //mp add_assertion
+    /// Add an assertion that is checked after every posedge of the
+    /// clock
+    ///
+    /// This can only be used after prepare_simulation; every signal
+    /// in the property must be bit-copyable state of at most 64 bits
+    pub fn add_assertion(
+        &self,
+        name: &str,
+        clock: ClockIndex,
+        property: Property,
+    ) -> Result<(), String> {
+        let (antecedent, consequent, min_cycles, max_cycles) = match property {
+            Property::Always(e) => (e, None, 0, 0),
+            Property::Implies {
+                antecedent,
+                consequent,
+                min_cycles,
+                max_cycles,
+            } => (antecedent, Some(consequent), min_cycles, max_cycles),
+        };
+        let mut paths = vec![];
+        antecedent.add_signals(&mut paths);
+        if let Some(c) = &consequent {
+            c.add_signals(&mut paths);
+        }
+
+        let mut signals = vec![];
+        let mut indices = HashMap::new();
+        let mut instance: Option<String> = None;
+        for path in paths {
+            let Some((handle, state_index, subelements)) = self.find_value(&path) else {
+                return Err(format!(
+                    "Failed to find signal '{path}' for assertion '{name}'"
+                ));
+            };
+            let width = self
+                .with_value(handle, state_index, &subelements, |v| {
+                    v.try_as_u8s().map(|_| v.bit_width())
+                })
+                .flatten()
+                .unwrap_or(0);
+            if width == 0 || width > 64 {
+                return Err(format!(
+                    "Signal '{path}' for assertion '{name}' must be a value of 1 to 64 bits"
+                ));
+            }
+            let inst_path = self.ns_name_string(self.instances().instance(handle).name());
+            instance = Some(match instance {
+                None => inst_path,
+                Some(i) => common_path(&i, &inst_path),
+            });
+            indices.insert(path.clone(), signals.len());
+            signals.push(Signal {
+                path,
+                handle,
+                state_index,
+                subelements,
+            });
+        }
+
+        let assertion = Assertion {
+            name: name.into(),
+            clock,
+            instance: instance.unwrap_or_default(),
+            signals,
+            antecedent: antecedent.compile(&indices),
+            consequent: consequent.map(|c| c.compile(&indices)),
+            min_cycles,
+            max_cycles,
+            pending: vec![],
+        };
+        self.assertions.borrow_mut().assertions.push(assertion);
+        Ok(())
+    }
+
+    //mp assertion_failures
+    /// Get the assertion failures that have occurred
+    pub fn assertion_failures(&self) -> Vec<AssertionFailure> {
+        self.assertions.borrow().failures.clone()
+    }
+
+    //mp take_assertion_failures
+    /// Take the assertion failures that have occurred, clearing them
+    pub fn take_assertion_failures(&self) -> Vec<AssertionFailure> {
+        std::mem::take(&mut self.assertions.borrow_mut().failures)
+    }
+
+    //mi check_assertions
+    /// Check the assertions for the clocks that have posedges
+    pub(crate) fn check_assertions(&self, edges: SimEdgeMask) {
+        let mut set = self.assertions.borrow_mut();
+        let AssertionSet {
+            assertions,
+            failures,
+        } = &mut *set;
+        for a in assertions.iter_mut() {
+            if !edges.is_posedge(a.clock.index()) {
+                continue;
+            }
+            let values: Vec<u64> = a
+                .signals
+                .iter()
+                .map(|s| {
+                    self.with_value(s.handle, s.state_index, &s.subelements, |v| {
+                        crate::values::SimValueRef::of(v).get_u64()
+                    })
+                    .flatten()
+                    .unwrap_or(0)
+                })
+                .collect();
+            let mut failed = vec![];
+            if let Some(consequent) = &a.consequent {
+                let consequent_true = consequent.is_true(&values);
+                let (min, max) = (a.min_cycles, a.max_cycles);
+                a.pending.retain_mut(|(start, elapsed)| {
+                    *elapsed += 1;
+                    if *elapsed >= min && consequent_true {
+                        false
+                    } else if *elapsed >= max {
+                        failed.push(*start);
+                        false
+                    } else {
+                        true
+                    }
+                });
+                if a.antecedent.is_true(&values) && !(min == 0 && consequent_true) {
+                    if max == 0 {
+                        failed.push(self.time());
+                    } else {
+                        a.pending.push((self.time(), 0));
+                    }
+                }
+            } else if !a.antecedent.is_true(&values) {
+                failed.push(self.time());
+            }
+            for start in failed {
+                let message = {
+                    if a.consequent.is_none() {
+                        "expression is false".to_string()
+                    } else if a.min_cycles == a.max_cycles {
+                        format!(
+                            "consequent not true {} cycles after antecedent at time {start}",
+                            a.max_cycles
+                        )
+                    } else {
+                        format!(
+                            "consequent not true within {}..={} cycles of antecedent at time {start}",
+                            a.min_cycles, a.max_cycles
+                        )
+                    }
+                };
+                let values = a
+                    .signals
+                    .iter()
+                    .map(|s| {
+                        let v = self
+                            .with_value(s.handle, s.state_index, &s.subelements, |v| {
+                                SimFormatObject::new(v, fmt::FULL).to_string()
+                            })
+                            .unwrap_or_default();
+                        (s.path.clone(), v)
+                    })
+                    .collect();
+                failures.push(AssertionFailure {
+                    name: a.name.clone(),
+                    time: self.time(),
+                    instance: a.instance.clone(),
+                    message,
+                    values,
+                });
+            }
+        }
+    }
+}
+
+//fi common_path
+/// Find the common prefix of two hierarchical paths
+fn common_path(a: &str, b: &str) -> String {
+    a.split('.')
+        .zip(b.split('.'))
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| a)
+        .collect::<Vec<_>>()
+        .join(".")
+}
@@ -0,0 +1,83 @@
+//a Imports
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::Simulation;
+
+//a CheckpointState, Checkpoint
+//tp CheckpointState
+/// The bit-copyable data for one piece of exposed state of an
+/// instance, identified by its hierarchical path
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointState {
+    /// Hierarchical path of the state, such as "dut.counter.q"
+    pub path: String,
+    /// Contents of the state as a slice of u8
+    pub data: Vec<u8>,
+}
+
+//tp Checkpoint
+/// A checkpoint of all of the bit-copyable exposed state of the
+/// instances in a [Simulation]
+///
+/// State that does not support bit-copying (such as a Vec) is not
+/// included
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Simulation time at which the checkpoint was taken
+    pub time: usize,
+    /// The state of the instances
+    pub states: Vec<CheckpointState>,
+}
+
+//ip Simulation
+impl Simulation<'_> {
+    //mp checkpoint
+    /// Take a checkpoint of the exposed state of every instance
+    ///
+    /// This should only be invoked when the simulation is paused (or
+    /// between edges); instances that are mutably borrowed are skipped
+    pub fn checkpoint(&self) -> Checkpoint {
+        let instances = self.instances();
+        let mut states = vec![];
+        for (path, handle, state_index) in self.state_paths() {
+            let Some(s) = instances.instance(handle).borrow_sim() else {
+                continue;
+            };
+            let Some(data) = s
+                .try_state_data(state_index)
+                .and_then(|v| v.sim_value().try_as_u8s().map(|d| d.to_vec()))
+            else {
+                continue;
+            };
+            states.push(CheckpointState { path, data });
+        }
+        Checkpoint {
+            time: self.time(),
+            states,
+        }
+    }
+
+    //mp restore
+    /// Restore the exposed state of the instances from a [Checkpoint]
+    ///
+    /// The simulation time is *not* restored; only the state of the
+    /// instances is updated
+    pub fn restore(&self, checkpoint: &Checkpoint) -> Result<(), String> {
+        let instances = self.instances();
+        for cs in checkpoint.states.iter() {
+            let Some((handle, state_index)) = self.find_state(&cs.path) else {
+                return Err(format!("Failed to find state {} to restore", cs.path));
+            };
+            let Some(mut s) = instances.instance(handle).borrow_sim_mut() else {
+                return Err(format!("Instance for {} is in use", cs.path));
+            };
+            let restored = s
+                .try_state_data_mut(state_index)
+                .is_some_and(|mut v| v.set_u8s(&cs.data));
+            if !restored {
+                return Err(format!("Failed to restore state {}", cs.path));
+            }
+        }
+        Ok(())
+    }
+}
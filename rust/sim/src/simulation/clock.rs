@@ -3,6 +3,7 @@ use std::collections::HashMap;
 
 use hgl_indexed_vec::make_index;
 use hgl_indexed_vec::{Idx, VecWithIndex};
+use hgl_utils::bit_ops::lcm;
 
 use crate::simulation::{InstanceHandle, SimEdgeMask, SimNsName};
 
@@ -168,12 +169,8 @@ impl Schedule {
                 let (time, posedge, negedge) =
                     self.clock_pos[i].next_time_and_edges(&system_clocks[i], self.time);
                 earliest = earliest.min(time);
-                if posedge {
-                    edges.set_posedge(i);
-                }
-                if negedge {
-                    edges.set_negedge(i);
-                }
+                // The number of clocks is limited by add_clock
+                edges.set_edges(i, posedge, negedge);
             }
             self.next_time = earliest;
             if earliest == usize::MAX {
@@ -187,6 +184,165 @@ impl Schedule {
     }
 }
 
+//a ScheduleTable
+//ti ScheduleStep
+/// A step in a [ScheduleTable]: the time since the previous step,
+/// and the index of the system edge mask for the step
+#[derive(Debug, Clone, Copy)]
+struct ScheduleStep {
+    delta: usize,
+    mask: usize,
+}
+
+//tp ScheduleTable
+/// A [ScheduleTable] is a compiled [Schedule] of a set of clocks
+///
+/// Once every clock has passed its initial delay the edges of the
+/// clocks repeat with the hyperperiod of the clocks (the lowest
+/// common multiple of their periods); the table holds the steps
+/// before that point and the steps of one hyperperiod, so that moving
+/// on to the next edges is a lookup. The instance edges for each
+/// distinct system edge mask are kept with the table, and rederived
+/// if the uses of the clocks change.
+///
+/// A table is only compiled if it is not too large; otherwise the
+/// clocks use the [Schedule] directly.
+#[derive(Default, Debug)]
+pub struct ScheduleTable {
+    /// Current time
+    time: usize,
+
+    /// Index of the next step to take
+    next: usize,
+
+    /// Index into `masks` of the current edges
+    current: usize,
+
+    /// Steps before the clocks are periodic, followed by the steps of
+    /// one hyperperiod
+    steps: Vec<ScheduleStep>,
+
+    /// Index of the first step of the hyperperiod
+    repeat_from: usize,
+
+    /// Time from the last step of the hyperperiod to the first step
+    /// of the next
+    wrap_delta: usize,
+
+    /// The distinct system edge masks of the steps
+    masks: Vec<SimEdgeMask>,
+
+    /// The instance edges for each mask, if they have been derived
+    instance_edges: Option<Vec<Vec<(InstanceHandle, SimEdgeMask)>>>,
+}
+
+//ip ScheduleTable
+impl ScheduleTable {
+    /// The maximum span of time (initial delays plus hyperperiod)
+    /// that a table is compiled for
+    const MAX_TIME: usize = 1 << 16;
+
+    //fi compile
+    /// Compile the table for a set of [Clock]s, if it is small enough
+    fn compile(clocks: &[Clock]) -> Option<Self> {
+        let steady = clocks.iter().map(|c| c.delay).max()?;
+        let mut hyperperiod = 1;
+        for c in clocks {
+            if c.period > Self::MAX_TIME {
+                return None;
+            }
+            hyperperiod = lcm(hyperperiod, c.period);
+            if steady + hyperperiod > Self::MAX_TIME {
+                return None;
+            }
+        }
+
+        let mut schedule = Schedule::new(clocks);
+        let mut table = Self::default();
+        let mut mask_index = HashMap::new();
+        let mut last_time = 0;
+        let mut repeat_time = None;
+        loop {
+            let edges = schedule.next_edges(clocks);
+            let time = schedule.time;
+            match repeat_time {
+                Some(t) if time >= t + hyperperiod => {
+                    table.wrap_delta = t + hyperperiod - last_time;
+                    break;
+                }
+                None if time >= steady => {
+                    table.repeat_from = table.steps.len();
+                    repeat_time = Some(time);
+                }
+                _ => (),
+            }
+            let mask = *mask_index.entry(edges).or_insert_with(|| {
+                table.masks.push(edges);
+                table.masks.len() - 1
+            });
+            table.steps.push(ScheduleStep {
+                delta: time - last_time,
+                mask,
+            });
+            last_time = time;
+        }
+        Some(table)
+    }
+
+    //mp next_edges
+    /// Move time on to the next step, returning its system edges
+    fn next_edges(&mut self) -> SimEdgeMask {
+        let (step, delta) = if self.next < self.steps.len() {
+            (self.next, self.steps[self.next].delta)
+        } else {
+            (self.repeat_from, self.wrap_delta)
+        };
+        self.time += delta;
+        self.next = step + 1;
+        self.current = self.steps[step].mask;
+        self.masks[self.current]
+    }
+
+    //mp derive_instance_edges
+    /// Derive the instance edges for every mask, if required
+    fn derive_instance_edges<F>(&mut self, f: F)
+    where
+        F: Fn(&SimEdgeMask) -> Vec<(InstanceHandle, SimEdgeMask)>,
+    {
+        if self.instance_edges.is_none() {
+            self.instance_edges = Some(self.masks.iter().map(f).collect());
+        }
+    }
+
+    //mp invalidate_instance_edges
+    /// Invalidate the instance edges, as the uses of the clocks have
+    /// changed
+    fn invalidate_instance_edges(&mut self) {
+        self.instance_edges = None;
+    }
+
+    //ap current_instance_edges
+    /// The instance edges for the current step, if they have been
+    /// derived and the system edges match
+    fn current_instance_edges(
+        &self,
+        system_edges: &SimEdgeMask,
+    ) -> Option<&[(InstanceHandle, SimEdgeMask)]> {
+        if self.masks.get(self.current) != Some(system_edges) {
+            return None;
+        }
+        self.instance_edges
+            .as_ref()
+            .map(|ie| ie[self.current].as_slice())
+    }
+
+    //ap num_steps
+    /// The number of steps in the table
+    pub fn num_steps(&self) -> usize {
+        self.steps.len()
+    }
+}
+
 //a ClockArray, ClockIndex
 //tp ClockIndex
 make_index!(ClockIndex, usize);
@@ -208,6 +364,10 @@ pub struct ClockArray<'a> {
     /// This should be rebuilt when time is reset
     schedule: Option<Schedule>,
 
+    /// Compiled schedule of the clocks, used instead of the running
+    /// schedule if the clocks permit
+    table: Option<ScheduleTable>,
+
     instance_edges: HashMap<SimEdgeMask, Vec<(InstanceHandle, SimEdgeMask)>>,
     clock_uses: HashMap<(ClockIndex, bool), Vec<ClockUse>>,
 }
@@ -223,6 +383,12 @@ impl ClockArray<'_> {
         period: usize,
         negedge_offset: usize,
     ) -> Result<ClockIndex, String> {
+        if SimEdgeMask::check_input(self.clocks.len()).is_err() {
+            return Err(format!(
+                "Too many clocks; at most {} are supported",
+                SimEdgeMask::MAX_INPUTS
+            ));
+        }
         let clock = Clock::new(name, delay, period, negedge_offset);
         self.clocks
             .insert(name, |_| clock)
@@ -240,6 +406,13 @@ impl ClockArray<'_> {
             return;
         }
         self.schedule = Some(Schedule::new(self.clocks.as_ref()));
+        self.table = ScheduleTable::compile(self.clocks.as_ref());
+    }
+
+    //ap schedule_table
+    /// The compiled schedule of the clocks, if there is one
+    pub fn schedule_table(&self) -> Option<&ScheduleTable> {
+        self.table.as_ref()
     }
 
     //mp edge_used_by
@@ -254,6 +427,10 @@ impl ClockArray<'_> {
             .entry((clock, posedge))
             .or_default()
             .push(ClockUse { instance, input });
+        self.instance_edges.clear();
+        if let Some(table) = &mut self.table {
+            table.invalidate_instance_edges();
+        }
     }
 
     //mp derive_instance_edges_of_masks
@@ -261,30 +438,19 @@ impl ClockArray<'_> {
         if self.instance_edges.contains_key(system_edges) {
             return;
         }
-        let mut blah: HashMap<InstanceHandle, SimEdgeMask> = HashMap::new();
-        for i in 0..self.clocks.len() {
-            if system_edges.is_posedge(i) {
-                if let Some(x) = self.clock_uses.get(&(ClockIndex::from_usize(i), true)) {
-                    for c in x.iter() {
-                        blah.entry(c.instance).or_default().set_posedge(c.input);
-                    }
-                }
-            }
-            if system_edges.is_negedge(i) {
-                if let Some(x) = self.clock_uses.get(&(ClockIndex::from_usize(i), false)) {
-                    for c in x.iter() {
-                        blah.entry(c.instance).or_default().set_negedge(c.input);
-                    }
-                }
-            }
-        }
-        let blah: Vec<(InstanceHandle, SimEdgeMask)> = blah.into_iter().collect();
+        let blah = instance_edges_of_mask(&self.clock_uses, self.clocks.len(), system_edges);
         self.instance_edges.insert(*system_edges, blah);
     }
 
     //mp next_edges
     #[track_caller]
     pub fn next_edges(&mut self) -> SimEdgeMask {
+        if let Some(table) = &mut self.table {
+            let clock_uses = &self.clock_uses;
+            let num_clocks = self.clocks.len();
+            table.derive_instance_edges(|m| instance_edges_of_mask(clock_uses, num_clocks, m));
+            return table.next_edges();
+        }
         let Some(schedule) = &mut self.schedule else {
             panic!("Schedule has not been set up - no call of derive_schedule yet");
         };
@@ -297,6 +463,13 @@ impl ClockArray<'_> {
 
     //mp instance_edges
     pub fn instance_edges(&self, system_edges: &SimEdgeMask) -> &[(InstanceHandle, SimEdgeMask)] {
+        if let Some(ie) = self
+            .table
+            .as_ref()
+            .and_then(|t| t.current_instance_edges(system_edges))
+        {
+            return ie;
+        }
         let Some(ie) = self.instance_edges.get(system_edges) else {
             return &[];
         };
@@ -306,6 +479,9 @@ impl ClockArray<'_> {
     //ap time
     #[track_caller]
     pub fn time(&self) -> usize {
+        if let Some(table) = &self.table {
+            return table.time;
+        }
         let Some(schedule) = &self.schedule else {
             panic!("Schedule has not been set up - no call of derive_schedule yet");
         };
@@ -317,3 +493,32 @@ impl ClockArray<'_> {
         self.clocks.into_iter()
     }
 }
+
+//a Functions
+//fi instance_edges_of_mask
+/// Determine the edges of each instance that are used for a set of
+/// system clock edges
+fn instance_edges_of_mask(
+    clock_uses: &HashMap<(ClockIndex, bool), Vec<ClockUse>>,
+    num_clocks: usize,
+    system_edges: &SimEdgeMask,
+) -> Vec<(InstanceHandle, SimEdgeMask)> {
+    let mut blah: HashMap<InstanceHandle, SimEdgeMask> = HashMap::new();
+    for i in 0..num_clocks {
+        if system_edges.is_posedge(i) {
+            if let Some(x) = clock_uses.get(&(ClockIndex::from_usize(i), true)) {
+                for c in x.iter() {
+                    blah.entry(c.instance).or_default().set_posedge(c.input);
+                }
+            }
+        }
+        if system_edges.is_negedge(i) {
+            if let Some(x) = clock_uses.get(&(ClockIndex::from_usize(i), false)) {
+                for c in x.iter() {
+                    blah.entry(c.instance).or_default().set_negedge(c.input);
+                }
+            }
+        }
+    }
+    blah.into_iter().collect()
+}
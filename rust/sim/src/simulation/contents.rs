@@ -3,7 +3,7 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use hgl_indexed_vec::VecWithIndex;
+use hgl_indexed_vec::{Idx, VecWithIndex};
 
 use crate::simulation::{
     Clock, ClockArray, ClockIndex, Instance, InstanceHandle, Name, NameFmt, Names, NamespaceStack,
@@ -40,6 +40,18 @@ impl SimulationBodyInner<'_> {
         self.instances.contains(name)
     }
 
+    //ap find_instance
+    /// Find the handle of an instance given its full name
+    pub fn find_instance(&self, name: SimNsName) -> Option<InstanceHandle> {
+        self.instances.find_key(&name)
+    }
+
+    //ap iter_handles
+    /// Iterate through the handles of the instances
+    pub fn iter_handles(&self) -> impl std::iter::Iterator<Item = InstanceHandle> {
+        (0..self.instances.len()).map(InstanceHandle::from_usize)
+    }
+
     //mp fire_next_edges
     pub fn fire_next_edges(&self, inst_edges: &[(InstanceHandle, SimEdgeMask)]) {
         for (inst, edge_mask) in inst_edges {
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 
 use crate::simulation::{
     Clock, ClockArray, ClockIndex, InstanceHandle, Name, NameFmt, Names, NamespaceStack, NsNameFmt,
-    SimNsName,
+    SimEdgeMask, SimNsName,
 };
 use crate::traits::SimRegister;
 
@@ -117,6 +117,19 @@ impl SimulationContents<'_> {
             negedge,
         });
     }
+    //mp check_input_uses
+    /// Check that the inputs whose edges an instance uses fit in a
+    /// [SimEdgeMask]
+    pub fn check_input_uses(&self, instance: InstanceHandle) -> Result<(), String> {
+        let Some(edge_uses) = self.edge_uses.get(&instance) else {
+            return Ok(());
+        };
+        for e in edge_uses.iter() {
+            SimEdgeMask::check_input(e.input)?;
+        }
+        Ok(())
+    }
+
     pub fn connect_clock(&mut self, clock: ClockIndex, instance: InstanceHandle, input: usize) {
         let Some(edge_uses) = self.edge_uses.get(&instance) else {
             return;
@@ -0,0 +1,738 @@
+//a Documentation
+//! Functional coverage of the named state of a simulation
+//!
+//! A [CoverGroup] is a set of [CoverPoint]s, and crosses of those
+//! points, that is sampled on an edge of a clock. A cover point is a
+//! value of state given by hierarchical path (including fields of
+//! structures), which must be bit-copyable and of at most 64 bits;
+//! it has bins that count the samples for which the value is in a
+//! range, is a particular value (such as an enumeration variant), or
+//! has a bit that toggled since the previous sample.
+//!
+//! A cover group may have a condition (an [Expr], as used for
+//! assertions) that must be true for it to be sampled, such as
+//! `dut.apb_request.psel && dut.apb_request.penable` to sample only
+//! the access phase of APB transactions.
+//!
+//! A cross of cover points has a bin for every combination of the
+//! bins of the points, and a sample hits each combination of the
+//! bins that the points hit.
+//!
+//! Cover groups are registered with [Simulation::add_covergroup];
+//! the [CoverageReport] of the simulation is available at any time
+//! with [Simulation::coverage_report], and is written out (as text
+//! and JSON) when the simulation is stopped if
+//! [Simulation::set_coverage_output] has been used. Reports from
+//! several runs can be combined with [CoverageReport::merge].
+
+//a Imports
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use hgl_indexed_vec::Idx;
+use serde::{Deserialize, Serialize};
+
+use crate::simulation::{
+    CExpr, ClockIndex, Expr, InstanceHandle, SimEdgeMask, SimStateIndex, Simulation,
+};
+use crate::values::SimValueRef;
+
+//a CoverPoint, CoverGroup
+//ti BinKind
+/// The values (or changes) of a value that hit a bin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinKind {
+    /// The value is within the inclusive range
+    Range(u64, u64),
+    /// The bit changed from 0 to 1
+    Rise(usize),
+    /// The bit changed from 1 to 0
+    Fall(usize),
+}
+
+//ip BinKind
+impl BinKind {
+    fn is_hit(&self, value: u64, last: Option<u64>) -> bool {
+        match self {
+            Self::Range(lo, hi) => (*lo..=*hi).contains(&value),
+            Self::Rise(b) => last.is_some_and(|l| (l >> b) & 1 == 0 && (value >> b) & 1 != 0),
+            Self::Fall(b) => last.is_some_and(|l| (l >> b) & 1 != 0 && (value >> b) & 1 == 0),
+        }
+    }
+}
+
+//tp CoverPoint
+/// A cover point: a value given by path, and the bins for it
+#[derive(Debug, Clone)]
+pub struct CoverPoint {
+    name: String,
+    path: String,
+    bins: Vec<(String, BinKind)>,
+    /// Add bins for every bit rising and falling, once the width is
+    /// known
+    toggle: bool,
+    /// Add this many bins splitting the full range of the value, once
+    /// the width is known
+    auto_bins: usize,
+}
+
+//ip CoverPoint
+impl CoverPoint {
+    //cp new
+    /// Create a new cover point for the value at a path, with no bins
+    pub fn new(name: &str, path: &str) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+            bins: vec![],
+            toggle: false,
+            auto_bins: 0,
+        }
+    }
+
+    //cp value
+    /// Add a bin for a single value
+    #[must_use]
+    pub fn value(self, bin: &str, value: u64) -> Self {
+        self.range(bin, value, value)
+    }
+
+    //cp range
+    /// Add a bin for an inclusive range of values
+    #[must_use]
+    pub fn range(mut self, bin: &str, lo: u64, hi: u64) -> Self {
+        self.bins.push((bin.into(), BinKind::Range(lo, hi)));
+        self
+    }
+
+    //cp variants
+    /// Add a bin for each of the variants of an enumeration, given as
+    /// name and value
+    #[must_use]
+    pub fn variants(mut self, variants: &[(&str, u64)]) -> Self {
+        for (bin, value) in variants {
+            self = self.value(bin, *value);
+        }
+        self
+    }
+
+    //cp toggle
+    /// Add bins for each bit of the value rising and falling (named
+    /// `<bit>.rise` and `<bit>.fall`)
+    #[must_use]
+    pub fn toggle(mut self) -> Self {
+        self.toggle = true;
+        self
+    }
+
+    //cp auto
+    /// Add a number of bins that split the full range of the value
+    /// evenly (named `auto[<n>]`)
+    #[must_use]
+    pub fn auto(mut self, num_bins: usize) -> Self {
+        self.auto_bins = num_bins;
+        self
+    }
+
+    //mi resolve_bins
+    /// Add the toggle and auto bins given the width of the value
+    fn resolve_bins(&mut self, width: usize) {
+        if self.toggle {
+            for b in 0..width {
+                self.bins.push((format!("{b}.rise"), BinKind::Rise(b)));
+                self.bins.push((format!("{b}.fall"), BinKind::Fall(b)));
+            }
+        }
+        if self.auto_bins > 0 {
+            let total = 1_u128 << width;
+            let n = (self.auto_bins as u128).min(total).min(1 << 16);
+            for i in 0..n {
+                let lo = (total * i / n) as u64;
+                let hi = (total * (i + 1) / n - 1) as u64;
+                self.bins
+                    .push((format!("auto[{i}]"), BinKind::Range(lo, hi)));
+            }
+        }
+    }
+}
+
+//tp CoverGroup
+/// A group of cover points and crosses, sampled on an edge of a
+/// clock
+#[derive(Debug, Clone)]
+pub struct CoverGroup {
+    name: String,
+    clock: ClockIndex,
+    negedge: bool,
+    condition: Option<String>,
+    points: Vec<CoverPoint>,
+    crosses: Vec<(String, Vec<String>)>,
+}
+
+//ip CoverGroup
+impl CoverGroup {
+    //cp new
+    /// Create a new cover group sampled on the posedge of a clock
+    pub fn new(name: &str, clock: ClockIndex) -> Self {
+        Self {
+            name: name.into(),
+            clock,
+            negedge: false,
+            condition: None,
+            points: vec![],
+            crosses: vec![],
+        }
+    }
+
+    //cp when
+    /// Only sample the cover group when an expression (over named
+    /// state, as for assertions) is true
+    #[must_use]
+    pub fn when(mut self, condition: &str) -> Self {
+        self.condition = Some(condition.into());
+        self
+    }
+
+    //cp on_negedge
+    /// Sample the cover group on the negedge of the clock instead
+    #[must_use]
+    pub fn on_negedge(mut self) -> Self {
+        self.negedge = true;
+        self
+    }
+
+    //cp point
+    /// Add a cover point to the group
+    #[must_use]
+    pub fn point(mut self, point: CoverPoint) -> Self {
+        self.points.push(point);
+        self
+    }
+
+    //cp cross
+    /// Add a cross of cover points (by name) to the group
+    #[must_use]
+    pub fn cross(mut self, name: &str, points: &[&str]) -> Self {
+        self.crosses
+            .push((name.into(), points.iter().map(|s| s.to_string()).collect()));
+        self
+    }
+}
+
+//a CoverageReport
+//tp CoverBinReport
+/// The number of hits of a bin
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoverBinReport {
+    pub name: String,
+    pub hits: u64,
+}
+
+//tp CoverPointReport
+/// The bins of a cover point or cross
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoverPointReport {
+    pub name: String,
+    pub bins: Vec<CoverBinReport>,
+}
+
+//ip CoverPointReport
+impl CoverPointReport {
+    //ap num_covered
+    /// Number of bins that have been hit
+    pub fn num_covered(&self) -> usize {
+        self.bins.iter().filter(|b| b.hits > 0).count()
+    }
+
+    //ap percent
+    /// Percentage of bins that have been hit
+    pub fn percent(&self) -> f64 {
+        percent(self.num_covered(), self.bins.len())
+    }
+
+    //mp merge
+    /// Merge another report for the same point into this, adding
+    /// the hits of bins with the same name
+    pub fn merge(&mut self, other: &Self) {
+        for b in &other.bins {
+            if let Some(s) = self.bins.iter_mut().find(|s| s.name == b.name) {
+                s.hits += b.hits;
+            } else {
+                self.bins.push(b.clone());
+            }
+        }
+    }
+}
+
+//tp CoverGroupReport
+/// The coverage of a cover group
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoverGroupReport {
+    pub name: String,
+    /// Number of times the group was sampled
+    pub samples: u64,
+    pub points: Vec<CoverPointReport>,
+    pub crosses: Vec<CoverPointReport>,
+}
+
+//ip CoverGroupReport
+impl CoverGroupReport {
+    //ap num_bins
+    /// Total number of bins of the points and crosses
+    pub fn num_bins(&self) -> usize {
+        self.points
+            .iter()
+            .chain(self.crosses.iter())
+            .map(|p| p.bins.len())
+            .sum()
+    }
+
+    //ap num_covered
+    /// Number of bins of the points and crosses that have been hit
+    pub fn num_covered(&self) -> usize {
+        self.points
+            .iter()
+            .chain(self.crosses.iter())
+            .map(|p| p.num_covered())
+            .sum()
+    }
+
+    //ap percent
+    /// Percentage of the bins of the points and crosses that have
+    /// been hit
+    pub fn percent(&self) -> f64 {
+        percent(self.num_covered(), self.num_bins())
+    }
+
+    //mp merge
+    /// Merge another report for the same group into this
+    pub fn merge(&mut self, other: &Self) {
+        self.samples += other.samples;
+        merge_points(&mut self.points, &other.points);
+        merge_points(&mut self.crosses, &other.crosses);
+    }
+}
+
+//tp CoverageReport
+/// The coverage of all the cover groups of a simulation
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoverageReport {
+    pub groups: Vec<CoverGroupReport>,
+}
+
+//ip CoverageReport
+impl CoverageReport {
+    //ap group
+    /// Find the report for a cover group by name
+    pub fn group(&self, name: &str) -> Option<&CoverGroupReport> {
+        self.groups.iter().find(|g| g.name == name)
+    }
+
+    //ap percent
+    /// Percentage of the bins of all the groups that have been hit
+    pub fn percent(&self) -> f64 {
+        percent(
+            self.groups.iter().map(|g| g.num_covered()).sum(),
+            self.groups.iter().map(|g| g.num_bins()).sum(),
+        )
+    }
+
+    //mp merge
+    /// Merge the report of another run into this one
+    ///
+    /// Hits of bins with the same group, point and bin name are
+    /// added; groups, points and bins that are only in the other
+    /// report are added to this
+    pub fn merge(&mut self, other: &Self) {
+        for g in &other.groups {
+            if let Some(s) = self.groups.iter_mut().find(|s| s.name == g.name) {
+                s.merge(g);
+            } else {
+                self.groups.push(g.clone());
+            }
+        }
+    }
+
+    //mp to_json
+    /// Generate a JSON string for the report
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    //cp from_json
+    /// Read a report from a JSON string
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Failed to read coverage report: {e}"))
+    }
+
+    //mp write_files
+    /// Write the report as text to `<prefix>.txt` and as JSON to
+    /// `<prefix>.json`
+    pub fn write_files(&self, prefix: &Path) -> Result<(), String> {
+        let write = |ext: &str, contents: String| {
+            let mut path = prefix.as_os_str().to_owned();
+            path.push(ext);
+            std::fs::write(&path, contents).map_err(|e| {
+                format!(
+                    "Failed to write coverage report '{}': {e}",
+                    Path::new(&path).display()
+                )
+            })
+        };
+        write(".txt", self.to_string())?;
+        write(".json", self.to_json())
+    }
+}
+
+//ip Display for CoverageReport
+impl std::fmt::Display for CoverageReport {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        for g in &self.groups {
+            writeln!(
+                fmt,
+                "covergroup {}: {:.1}% ({}/{} bins), {} samples",
+                g.name,
+                g.percent(),
+                g.num_covered(),
+                g.num_bins(),
+                g.samples
+            )?;
+            for (kind, points) in [("point", &g.points), ("cross", &g.crosses)] {
+                for p in points {
+                    writeln!(
+                        fmt,
+                        "  {kind} {}: {:.1}% ({}/{} bins)",
+                        p.name,
+                        p.percent(),
+                        p.num_covered(),
+                        p.bins.len()
+                    )?;
+                    for b in &p.bins {
+                        let marker = if b.hits == 0 { "*" } else { " " };
+                        writeln!(fmt, "   {marker}{}: {}", b.name, b.hits)?;
+                    }
+                }
+            }
+        }
+        writeln!(fmt, "total: {:.1}%", self.percent())
+    }
+}
+
+//fi percent
+fn percent(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        100.0 * (covered as f64) / (total as f64)
+    }
+}
+
+//fi merge_points
+fn merge_points(points: &mut Vec<CoverPointReport>, others: &[CoverPointReport]) {
+    for p in others {
+        if let Some(s) = points.iter_mut().find(|s| s.name == p.name) {
+            s.merge(p);
+        } else {
+            points.push(p.clone());
+        }
+    }
+}
+
+//a Sampling
+//ti Value
+/// A value within the state of an instance
+struct Value {
+    handle: InstanceHandle,
+    state_index: SimStateIndex,
+    subelements: Vec<usize>,
+}
+
+//ti SampledPoint
+struct SampledPoint {
+    name: String,
+    value: Value,
+    bins: Vec<(String, BinKind)>,
+    hits: Vec<u64>,
+    last: Option<u64>,
+}
+
+//ti SampledCross
+struct SampledCross {
+    name: String,
+    points: Vec<usize>,
+    /// Hits for each combination of bins, with the first point's bin
+    /// varying fastest
+    hits: Vec<u64>,
+}
+
+//ti SampledGroup
+struct SampledGroup {
+    name: String,
+    clock: ClockIndex,
+    negedge: bool,
+    condition: Option<(Vec<Value>, CExpr)>,
+    samples: u64,
+    points: Vec<SampledPoint>,
+    crosses: Vec<SampledCross>,
+}
+
+//ip SampledGroup
+impl SampledGroup {
+    //mi report
+    fn report(&self) -> CoverGroupReport {
+        let points = self
+            .points
+            .iter()
+            .map(|p| CoverPointReport {
+                name: p.name.clone(),
+                bins: p
+                    .bins
+                    .iter()
+                    .zip(p.hits.iter())
+                    .map(|((name, _), hits)| CoverBinReport {
+                        name: name.clone(),
+                        hits: *hits,
+                    })
+                    .collect(),
+            })
+            .collect();
+        let crosses = self
+            .crosses
+            .iter()
+            .map(|c| CoverPointReport {
+                name: c.name.clone(),
+                bins: c
+                    .hits
+                    .iter()
+                    .enumerate()
+                    .map(|(mut n, hits)| {
+                        let mut names = vec![];
+                        for p in &c.points {
+                            let bins = &self.points[*p].bins;
+                            names.push(bins[n % bins.len()].0.as_str());
+                            n /= bins.len();
+                        }
+                        CoverBinReport {
+                            name: names.join(","),
+                            hits: *hits,
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+        CoverGroupReport {
+            name: self.name.clone(),
+            samples: self.samples,
+            points,
+            crosses,
+        }
+    }
+}
+
+//tp CoverageSet
+/// The cover groups registered with a simulation
+#[derive(Default)]
+pub struct CoverageSet {
+    groups: Vec<SampledGroup>,
+    output: Option<PathBuf>,
+}
+
+//a Simulation
+//ip Simulation
+impl Simulation<'_> {
+    //mp add_covergroup
+    /// Add a cover group that is sampled after every (posedge or
+    /// negedge) of its clock
+    ///
+    /// This can only be used after prepare_simulation; every cover
+    /// point must be bit-copyable state of at most 64 bits
+    pub fn add_covergroup(&self, group: CoverGroup) -> Result<(), String> {
+        let mut points: Vec<SampledPoint> = vec![];
+        for mut p in group.points {
+            if points.iter().any(|s| s.name == p.name) {
+                return Err(format!(
+                    "Duplicate cover point '{}' in covergroup '{}'",
+                    p.name, group.name
+                ));
+            }
+            let (value, width) = self.find_coverage_value(&p.path)?;
+            p.resolve_bins(width);
+            if p.bins.is_empty() {
+                return Err(format!("Cover point '{}' has no bins", p.name));
+            }
+            points.push(SampledPoint {
+                name: p.name,
+                value,
+                hits: vec![0; p.bins.len()],
+                bins: p.bins,
+                last: None,
+            });
+        }
+        let mut crosses = vec![];
+        for (name, names) in group.crosses {
+            let mut indices = vec![];
+            let mut size = 1_usize;
+            for n in &names {
+                let Some(i) = points.iter().position(|p| &p.name == n) else {
+                    return Err(format!(
+                        "Cross '{name}' of unknown cover point '{n}' in covergroup '{}'",
+                        group.name
+                    ));
+                };
+                indices.push(i);
+                size = size.saturating_mul(points[i].bins.len());
+            }
+            if size > 1 << 20 {
+                return Err(format!("Cross '{name}' has too many bins ({size})"));
+            }
+            crosses.push(SampledCross {
+                name,
+                points: indices,
+                hits: vec![0; size],
+            });
+        }
+        let condition = {
+            if let Some(condition) = group.condition {
+                let expr = Expr::parse(&condition)?;
+                let mut paths = vec![];
+                expr.add_signals(&mut paths);
+                let mut values = vec![];
+                let mut indices = HashMap::new();
+                for path in paths {
+                    indices.insert(path.clone(), values.len());
+                    values.push(self.find_coverage_value(&path)?.0);
+                }
+                Some((values, expr.compile(&indices)))
+            } else {
+                None
+            }
+        };
+        self.coverage.borrow_mut().groups.push(SampledGroup {
+            name: group.name,
+            clock: group.clock,
+            negedge: group.negedge,
+            condition,
+            samples: 0,
+            points,
+            crosses,
+        });
+        Ok(())
+    }
+
+    //mi find_coverage_value
+    /// Find a value for coverage, which must be bit-copyable and of at
+    /// most 64 bits, and return it with its width
+    fn find_coverage_value(&self, path: &str) -> Result<(Value, usize), String> {
+        let Some((handle, state_index, subelements)) = self.find_value(path) else {
+            return Err(format!("Failed to find '{path}' for coverage"));
+        };
+        let width = self
+            .with_value(handle, state_index, &subelements, |v| {
+                v.try_as_u8s().map(|_| v.bit_width())
+            })
+            .flatten()
+            .unwrap_or(0);
+        if width == 0 || width > 64 {
+            return Err(format!(
+                "Coverage of '{path}' requires a value of 1 to 64 bits"
+            ));
+        }
+        let value = Value {
+            handle,
+            state_index,
+            subelements,
+        };
+        Ok((value, width))
+    }
+
+    //mi coverage_value
+    /// Get the current value of a [Value] as a u64
+    fn coverage_value(&self, value: &Value) -> u64 {
+        self.with_value(value.handle, value.state_index, &value.subelements, |v| {
+            SimValueRef::of(v).get_u64()
+        })
+        .flatten()
+        .unwrap_or(0)
+    }
+
+    //mp set_coverage_output
+    /// Set the prefix of the files to which the coverage report is
+    /// written when the simulation is stopped (`<prefix>.txt` and
+    /// `<prefix>.json`)
+    pub fn set_coverage_output<P: Into<PathBuf>>(&self, prefix: Option<P>) {
+        self.coverage.borrow_mut().output = prefix.map(|p| p.into());
+    }
+
+    //mp coverage_report
+    /// Get the coverage report for the cover groups
+    pub fn coverage_report(&self) -> CoverageReport {
+        CoverageReport {
+            groups: self
+                .coverage
+                .borrow()
+                .groups
+                .iter()
+                .map(|g| g.report())
+                .collect(),
+        }
+    }
+
+    //mi write_coverage
+    /// Write the coverage report to the output files, if set
+    pub(crate) fn write_coverage(&self) -> Result<(), String> {
+        let Some(prefix) = self.coverage.borrow().output.clone() else {
+            return Ok(());
+        };
+        self.coverage_report().write_files(&prefix)
+    }
+
+    //mi sample_coverage
+    /// Sample the cover groups for the clocks that have edges
+    pub(crate) fn sample_coverage(&self, edges: SimEdgeMask) {
+        let mut set = self.coverage.borrow_mut();
+        for g in set.groups.iter_mut() {
+            let c = g.clock.index();
+            if !(if g.negedge {
+                edges.is_negedge(c)
+            } else {
+                edges.is_posedge(c)
+            }) {
+                continue;
+            }
+            if let Some((values, expr)) = &g.condition {
+                let values: Vec<u64> = values.iter().map(|v| self.coverage_value(v)).collect();
+                if !expr.is_true(&values) {
+                    continue;
+                }
+            }
+            g.samples += 1;
+            let mut hit_bins = vec![];
+            for p in g.points.iter_mut() {
+                let value = self.coverage_value(&p.value);
+                let mut hit = vec![];
+                for (i, (_, kind)) in p.bins.iter().enumerate() {
+                    if kind.is_hit(value, p.last) {
+                        p.hits[i] += 1;
+                        hit.push(i);
+                    }
+                }
+                p.last = Some(value);
+                hit_bins.push(hit);
+            }
+            for x in g.crosses.iter_mut() {
+                let mut combinations = vec![0_usize];
+                let mut scale = 1;
+                for p in &x.points {
+                    combinations = combinations
+                        .iter()
+                        .flat_map(|c| hit_bins[*p].iter().map(move |b| c + b * scale))
+                        .collect();
+                    scale *= g.points[*p].bins.len();
+                }
+                for c in combinations {
+                    x.hits[c] += 1;
+                }
+            }
+        }
+    }
+}
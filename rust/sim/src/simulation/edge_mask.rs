@@ -1,36 +1,132 @@
+//a Imports
+use hgl_indexed_vec::Idx;
+
+use crate::simulation::ClockIndex;
+
+//a Constants
+/// Number of 64-bit words in a [SimEdgeMask]
+const WORDS: usize = 4;
+
+/// Number of inputs (or system clocks) whose edges fit in a word
+const INPUTS_PER_WORD: usize = 32;
+
+//a SimEdgeMask
+//tp SimEdgeMask
+/// A mask of the posedges and negedges of a set of inputs (for an
+/// instance) or of the system clocks (for a simulation), with two
+/// bits per input
+///
+/// The mask is a fixed four words, so it remains Copy (and may be
+/// used as a key for the instance edges of a set of system edges);
+/// this limits it to [SimEdgeMask::MAX_INPUTS] (128) inputs. The cost
+/// is that every mask is 32 bytes, and comparing, hashing or testing
+/// a mask for emptiness covers all four words, even for a design
+/// with a single clock.
+///
+/// The limit is reported as an error by the simulation when a clock
+/// is added, or a component registers an input, beyond it; setting
+/// an edge of an input beyond it directly panics.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub struct SimEdgeMask(u64);
+pub struct SimEdgeMask([u64; WORDS]);
 impl SimEdgeMask {
-    pub fn none() -> Self {
-        Self(0)
+    /// The maximum number of inputs (or system clocks) in a mask
+    pub const MAX_INPUTS: usize = WORDS * INPUTS_PER_WORD;
+
+    pub const fn none() -> Self {
+        Self([0; WORDS])
     }
     pub fn is_none(&self) -> bool {
-        self.0 == 0
+        self.0.iter().all(|w| *w == 0)
     }
+
+    //fi check_input
+    /// Return an error if an input is beyond those supported by a mask
+    pub fn check_input(input: usize) -> Result<(), String> {
+        if input < Self::MAX_INPUTS {
+            Ok(())
+        } else {
+            Err(format!(
+                "Input {input} is beyond the {} inputs supported by a SimEdgeMask",
+                Self::MAX_INPUTS
+            ))
+        }
+    }
+
+    //fi word_and_shift
+    /// The word and bit shift of the edges of an input
+    #[track_caller]
+    fn word_and_shift(input: usize) -> (usize, usize) {
+        if let Err(e) = Self::check_input(input) {
+            panic!("{e}");
+        }
+        (input / INPUTS_PER_WORD, (input % INPUTS_PER_WORD) * 2)
+    }
+
     pub const fn is_posedge(&self, input: usize) -> bool {
-        ((self.0 as usize) >> (input * 2)) & 1 != 0
+        input < Self::MAX_INPUTS
+            && (self.0[input / INPUTS_PER_WORD] >> ((input % INPUTS_PER_WORD) * 2)) & 1 != 0
     }
     pub const fn is_negedge(&self, input: usize) -> bool {
-        ((self.0 as usize) >> (input * 2)) & 2 != 0
+        input < Self::MAX_INPUTS
+            && (self.0[input / INPUTS_PER_WORD] >> ((input % INPUTS_PER_WORD) * 2)) & 2 != 0
     }
     #[must_use]
+    #[track_caller]
     pub fn add_posedge(mut self, input: usize) -> Self {
-        self.0 |= (1 << (2 * input)) as u64;
+        self.set_posedge(input);
         self
     }
     #[must_use]
+    #[track_caller]
     pub fn add_negedge(mut self, input: usize) -> Self {
-        self.0 |= (2 << (2 * input)) as u64;
+        self.set_negedge(input);
         self
     }
+    #[track_caller]
     pub fn set_posedge(&mut self, input: usize) {
-        self.0 |= (1 << (2 * input)) as u64;
+        let (word, shift) = Self::word_and_shift(input);
+        self.0[word] |= 1 << shift;
     }
+    #[track_caller]
     pub fn set_negedge(&mut self, input: usize) {
-        self.0 |= (2 << (2 * input)) as u64;
+        let (word, shift) = Self::word_and_shift(input);
+        self.0[word] |= 2 << shift;
+    }
+    //mi set_edges
+    /// Set the edges of an input that is known to be within the mask,
+    /// such as a system clock (whose number is checked when it is
+    /// added to the simulation)
+    pub(crate) fn set_edges(&mut self, input: usize, posedge: bool, negedge: bool) {
+        let bits = (posedge as u64) | ((negedge as u64) << 1);
+        self.0[input / INPUTS_PER_WORD] |= bits << ((input % INPUTS_PER_WORD) * 2);
+    }
+    /// The system edges of a clock: its rising edge, falling edge,
+    /// or both
+    pub fn of_clock(clock: ClockIndex, posedge: bool, negedge: bool) -> Self {
+        let mut mask = Self::none();
+        mask.set_edges(clock.index(), posedge, negedge);
+        mask
+    }
+    /// Iterate over the edges in the mask, as the input and true for a
+    /// posedge (or false for a negedge), visiting only the set bits
+    pub fn iter_edges(&self) -> impl Iterator<Item = (usize, bool)> + '_ {
+        self.0.iter().enumerate().flat_map(|(word, bits)| {
+            let mut bits = *bits;
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let bit = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                Some((word * INPUTS_PER_WORD + bit / 2, bit.is_multiple_of(2)))
+            })
+        })
     }
     /// Return true if self contains all of others
     pub fn contains_all(&self, others: &SimEdgeMask) -> bool {
-        (self.0 & others.0) == others.0
+        self.0
+            .iter()
+            .zip(others.0.iter())
+            .all(|(s, o)| (s & o) == *o)
     }
 }
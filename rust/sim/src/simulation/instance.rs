@@ -140,6 +140,20 @@ impl Instance {
             .map(|sd| sd.state_index())
     }
 
+    //mp state_names
+    /// Return the names and indices of the exposed state of the
+    /// instance, in state index order
+    pub fn state_names(&self) -> Vec<(Name, SimStateIndex)> {
+        let mut names: Vec<_> = self
+            .state_map
+            .borrow()
+            .iter()
+            .map(|(n, sd)| (*n, sd.state_index()))
+            .collect();
+        names.sort_by_key(|(_, s)| *s);
+        names
+    }
+
     //mp fmt_full
     pub fn fmt_full(
         &self,
@@ -156,7 +170,7 @@ impl Instance {
                 if let Ok(s) = self.simulatable.try_borrow() {
                     fmt.write_str("=")?;
                     if let Some(x) = s.try_state_data(p.state_index()) {
-                        x.sim_value().fmt_with(fmt, fmt::FULL)?;
+                        fmt::fmt_structured(x.sim_value(), fmt, fmt::FULL)?;
                     }
                 }
             }
@@ -1,24 +1,42 @@
 //a Modules
+mod assertions;
+mod checkpoint;
 mod clock;
 mod contents;
 mod control;
+mod coverage;
 mod edge_mask;
 mod instance;
 mod instance_ref;
+mod monitor;
 mod names;
 mod port;
 mod simulation;
+mod stimulus;
+mod testbench;
+mod worker;
 
 //a Exports
+pub(crate) use assertions::CExpr;
+pub use assertions::{AssertionFailure, AssertionSet, CmpOp, Expr, Property};
+pub use checkpoint::{Checkpoint, CheckpointState};
 pub use clock::{Clock, ClockArray, ClockIndex};
 pub use contents::{SimulationBody, SimulationBodyInner};
 pub use control::SimulationContents;
+pub use coverage::{
+    CoverBinReport, CoverGroup, CoverGroupReport, CoverPoint, CoverPointReport, CoverageReport,
+    CoverageSet,
+};
 pub use edge_mask::SimEdgeMask;
 pub use instance::{Instance, InstanceHandle};
 pub use instance_ref::{RefInstance, RefMutInstance};
+pub use monitor::MonitorSet;
 pub use names::{Name, NameFmt, Names, NamespaceStack, NsNameFmt, SimNsName};
 pub use port::{SimStateIndex, SimStateInfo, StateDesc};
 pub use simulation::Simulation;
+pub use stimulus::{Dist, SimRng, SimSeed, Stimulus};
+pub use testbench::{EdgeCounts, Testbench};
+pub use worker::{SimulationWorker, WorkerSet};
 
 //a Types
 //tp SimReset
@@ -0,0 +1,131 @@
+//a Documentation
+//! Monitors invoked after clock edges of a simulation
+//!
+//! A monitor is a function that is invoked after every posedge of a
+//! clock has been fired (after assertions are checked and coverage
+//! sampled), with the simulation; it is passive, reading state of
+//! the simulation (for example with [Simulation::with_value]) to
+//! record transactions or check behaviour.
+//!
+//! A driver is similar, but is invoked after all the monitors for the
+//! clock edge; it may change state of the simulation (such as the
+//! inputs of an instance) for the next cycle, without the monitors
+//! seeing those changes as part of the cycle just completed.
+//!
+//! Combinational logic between instances (such as a bus decoder, or
+//! the copying of an output of one instance to the input of another)
+//! may be added as a combinational function; these are evaluated, in
+//! the order they were added, when a simulation is started and once
+//! the instances have been clocked whenever edges are fired (so that
+//! assertions, coverage, monitors and drivers see the results). If a
+//! driver is invoked they are evaluated again after the drivers, so
+//! that the instances see the changes it made when they are next
+//! clocked; changes made by the testbench between edges are not
+//! propagated until the next edges are fired.
+
+//a Imports
+use hgl_indexed_vec::Idx;
+
+use crate::simulation::{ClockIndex, SimEdgeMask, Simulation};
+
+//a MonitorSet
+//ti MonitorFn
+type MonitorFn = Box<dyn FnMut(&Simulation)>;
+
+//tp MonitorSet
+/// The monitors and drivers registered with a simulation
+#[derive(Default)]
+pub struct MonitorSet {
+    monitors: Vec<(ClockIndex, MonitorFn)>,
+    drivers: Vec<(ClockIndex, MonitorFn)>,
+    combinational: Vec<MonitorFn>,
+}
+
+//a Simulation
+//ip Simulation
+impl Simulation<'_> {
+    //mp add_monitor
+    /// Add a monitor that is invoked after every posedge of a clock
+    pub fn add_monitor<F: FnMut(&Simulation) + 'static>(&self, clock: ClockIndex, monitor: F) {
+        self.monitors
+            .borrow_mut()
+            .monitors
+            .push((clock, Box::new(monitor)));
+    }
+
+    //mp add_driver
+    /// Add a driver that is invoked after every posedge of a clock,
+    /// once all the monitors have been invoked
+    pub fn add_driver<F: FnMut(&Simulation) + 'static>(&self, clock: ClockIndex, driver: F) {
+        self.monitors
+            .borrow_mut()
+            .drivers
+            .push((clock, Box::new(driver)));
+    }
+
+    //mp add_combinational
+    /// Add a combinational function that is evaluated when the
+    /// simulation is started, after the instances are clocked
+    /// whenever edges are fired, and after any drivers are invoked
+    pub fn add_combinational<F: FnMut(&Simulation) + 'static>(&self, f: F) {
+        self.monitors.borrow_mut().combinational.push(Box::new(f));
+    }
+
+    //mi evaluate_combinational
+    /// Evaluate the combinational functions, in the order they were
+    /// added
+    pub(crate) fn evaluate_combinational(&self) {
+        if self.monitors.borrow().combinational.is_empty() {
+            return;
+        }
+        let mut combinational = std::mem::take(&mut self.monitors.borrow_mut().combinational);
+        for f in combinational.iter_mut() {
+            f(self);
+        }
+        let mut set = self.monitors.borrow_mut();
+        combinational.append(&mut set.combinational);
+        set.combinational = combinational;
+    }
+
+    //mi invoke_monitors
+    /// Invoke the monitors, and then the drivers, for the clocks that
+    /// have posedges
+    ///
+    /// These are taken out of the simulation while they are invoked,
+    /// so that a monitor may add another monitor (or driver)
+    ///
+    /// Returns true if any driver was invoked
+    pub(crate) fn invoke_monitors(&self, edges: SimEdgeMask) -> bool {
+        {
+            let set = self.monitors.borrow();
+            if set.monitors.is_empty() && set.drivers.is_empty() {
+                return false;
+            }
+        }
+        let (mut monitors, mut drivers) = {
+            let mut set = self.monitors.borrow_mut();
+            (
+                std::mem::take(&mut set.monitors),
+                std::mem::take(&mut set.drivers),
+            )
+        };
+        for (clock, m) in monitors.iter_mut() {
+            if edges.is_posedge(clock.index()) {
+                m(self);
+            }
+        }
+        let mut driven = false;
+        for (clock, d) in drivers.iter_mut() {
+            if edges.is_posedge(clock.index()) {
+                d(self);
+                driven = true;
+            }
+        }
+        let mut set = self.monitors.borrow_mut();
+        monitors.append(&mut set.monitors);
+        drivers.append(&mut set.drivers);
+        set.monitors = monitors;
+        set.drivers = drivers;
+        driven
+    }
+}
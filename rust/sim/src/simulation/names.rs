@@ -165,6 +165,21 @@ impl Names<'_> {
         self.names.find_string(s)
     }
 
+    //mp find_ns_name
+    /// Find a full name given a hierarchical path of names separated
+    /// by '.', starting at the root namespace
+    pub fn find_ns_name(&self, path: &str) -> Option<SimNsName> {
+        let mut namespace = SimNsName::default();
+        let mut ns_name = None;
+        for n in path.split('.') {
+            let name = self.find_name(n)?;
+            let full_name = self.get_full_name((namespace, name).into())?;
+            namespace = full_name;
+            ns_name = Some(full_name);
+        }
+        ns_name
+    }
+
     //mp fmt_name
     pub fn fmt_name(
         &self,
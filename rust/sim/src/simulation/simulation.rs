@@ -6,11 +6,13 @@ use std::sync::Arc;
 use hgl_indexed_vec::VecWithIndex;
 
 use crate::simulation::{
-    Clock, ClockArray, ClockIndex, Instance, InstanceHandle, Name, NameFmt, Names, NamespaceStack,
-    NsNameFmt, RefInstance, RefMutInstance, SimEdgeMask, SimNsName, SimulationBody,
-    SimulationBodyInner, SimulationContents,
+    AssertionSet, Clock, ClockArray, ClockIndex, CoverageSet, EdgeCounts, Instance, InstanceHandle,
+    MonitorSet, Name, NameFmt, Names, NamespaceStack, NsNameFmt, RefInstance, RefMutInstance,
+    SimEdgeMask, SimNsName, SimSeed, SimStateIndex, SimulationBody, SimulationBodyInner,
+    SimulationContents, WorkerSet,
 };
-use crate::traits::{Component, ComponentBuilder, SimHandle, Simulatable};
+use crate::traits::{Component, ComponentBuilder, SimHandle, SimValueObject, Simulatable};
+use crate::values::{SimValueRef, SimValueRefMut};
 
 //a Simulation
 //tp Simulation
@@ -20,6 +22,24 @@ pub struct Simulation<'s> {
 
     build: Option<SimulationBodyInner<'s>>,
     body: SimulationBody<'s>,
+
+    /// Assertions checked after clock edges are fired
+    pub(crate) assertions: RefCell<AssertionSet>,
+
+    /// Cover groups sampled after clock edges are fired
+    pub(crate) coverage: RefCell<CoverageSet>,
+
+    /// Monitors invoked after clock edges are fired
+    pub(crate) monitors: RefCell<MonitorSet>,
+
+    /// Count of the edges of each clock fired, for testbench tasks
+    pub(crate) edge_counts: RefCell<EdgeCounts>,
+
+    /// Worker threads run after clock edges are fired
+    pub(crate) workers: RefCell<WorkerSet>,
+
+    /// Master seed for random streams
+    pub(crate) seed: SimSeed,
 }
 
 //ip Debug for Simulation
@@ -58,10 +78,18 @@ impl Simulation<'_> {
         let control = RefCell::new(SimulationContents::default());
         let build = Some(SimulationBodyInner::new());
         let body = SimulationBody::empty();
+        let assertions = RefCell::new(AssertionSet::default());
+        let coverage = RefCell::new(CoverageSet::default());
         Self {
             control,
             body,
             build,
+            assertions,
+            coverage,
+            monitors: RefCell::new(MonitorSet::default()),
+            edge_counts: RefCell::new(EdgeCounts::default()),
+            workers: RefCell::new(WorkerSet::default()),
+            seed: SimSeed::default(),
         }
     }
 
@@ -73,6 +101,7 @@ impl Simulation<'_> {
         );
         assert!(self.body.is_empty(), "Build should be empty if being built");
         self.control.borrow_mut().clocks.derive_schedule();
+        self.edge_counts.borrow_mut().resize(self.num_clocks());
         self.body = SimulationBody::new(self.build.take().unwrap());
     }
 
@@ -89,6 +118,7 @@ impl Simulation<'_> {
     pub fn start(&self, start_running: bool) -> Result<(), String> {
         if self.control.borrow().is_idle() {
             let _failed = self.map_mut_simulatables(|s| s.start(start_running));
+            self.evaluate_combinational();
             if start_running {
                 self.control.borrow_mut().set_running();
             } else {
@@ -103,6 +133,19 @@ impl Simulation<'_> {
         }
     }
 
+    //ap num_clocks
+    /// The number of clocks in the simulation
+    pub fn num_clocks(&self) -> usize {
+        self.control.borrow().iter_clocks().count()
+    }
+
+    //ap is_running
+    /// Return true if the simulation has been started and is running
+    /// (it is not paused or stopped)
+    pub fn is_running(&self) -> bool {
+        self.control.borrow().is_running()
+    }
+
     //mp pause
     pub fn pause(&self) -> Result<(), String> {
         if self.control.borrow().is_paused() {
@@ -136,11 +179,14 @@ impl Simulation<'_> {
     }
 
     //mp stop
+    /// Stop the simulation, finishing any worker threads, and write
+    /// the coverage report if an output for it has been set
     pub fn stop(&self) -> Result<(), String> {
         if self.control.borrow().is_running() || self.control.borrow().is_paused() {
+            self.finish_workers();
             let _failed = self.map_mut_simulatables(|s| s.stop());
             self.control.borrow_mut().set_stopped();
-            Ok(())
+            self.write_coverage()
         } else {
             Err(format!(
                 "Could not stop; it was already in state {:?}",
@@ -158,11 +204,32 @@ impl Simulation<'_> {
     }
 
     //mp fire_next_edges
-    pub fn fire_next_edges(&self) {
+    /// Move time on to the next *system* clock edges, and clock the
+    /// instances that use those edges, and evaluate the combinational
+    /// functions; then check any assertions for the clocks that had
+    /// posedges, sample any cover groups, invoke any monitors and
+    /// drivers (evaluating the combinational functions again if a
+    /// driver was invoked), and run any worker threads waiting for
+    /// the edges
+    ///
+    /// Returns the system clock edges that fired
+    pub fn fire_next_edges(&self) -> SimEdgeMask {
+        self.sync_workers();
         let ie = self.control.borrow_mut().clocks.next_edges();
-        let c = self.control.borrow();
-        let inst_edges = c.clocks.instance_edges(&ie);
-        self.body.fire_next_edges(inst_edges);
+        self.edge_counts.borrow_mut().record(ie);
+        {
+            let c = self.control.borrow();
+            let inst_edges = c.clocks.instance_edges(&ie);
+            self.body.fire_next_edges(inst_edges);
+        }
+        self.evaluate_combinational();
+        self.check_assertions(ie);
+        self.sample_coverage(ie);
+        if self.invoke_monitors(ie) {
+            self.evaluate_combinational();
+        }
+        self.run_workers(ie);
+        ie
     }
 
     //mp time
@@ -170,6 +237,18 @@ impl Simulation<'_> {
         self.control.borrow().clocks.time()
     }
 
+    //ap schedule_steps
+    /// The number of steps in the compiled schedule of the clocks, if
+    /// the clocks were compiled to one when the simulation was
+    /// prepared
+    pub fn schedule_steps(&self) -> Option<usize> {
+        self.control
+            .borrow()
+            .clocks
+            .schedule_table()
+            .map(|t| t.num_steps())
+    }
+
     //mp add_clock
     /// Add a clock by name, within the current namespace
     ///
@@ -203,10 +282,145 @@ impl Simulation<'_> {
     //mp find_clock
     /// Find a clock by name
     pub fn find_clock(&self, name: SimNsName) -> Option<ClockIndex> {
+        self.control.borrow().clocks.find_clock(name)
+    }
+
+    //mp find_ns_name
+    /// Find a full name from a hierarchical path, such as "dut.counter"
+    pub fn find_ns_name(&self, path: &str) -> Option<SimNsName> {
+        self.control.borrow().names.find_ns_name(path)
+    }
+
+    //mp find_instance
+    /// Find an instance from its hierarchical path
+    ///
+    /// This can only be used after prepare_simulation
+    pub fn find_instance(&self, path: &str) -> Option<InstanceHandle> {
+        self.find_ns_name(path)
+            .and_then(|n| self.body.find_instance(n))
+    }
+
+    //mp find_state
+    /// Find the state of an instance from a hierarchical path, such
+    /// as "dut.counter.q"; the last element of the path is the name
+    /// of the state within the instance
+    ///
+    /// This can only be used after prepare_simulation
+    pub fn find_state(&self, path: &str) -> Option<(InstanceHandle, SimStateIndex)> {
+        let (instance, state) = path.rsplit_once('.')?;
+        let handle = self.find_instance(instance)?;
+        let name = self.find_name(state)?;
+        let state_index = self.body.instance(handle).state_index(name)?;
+        Some((handle, state_index))
+    }
+
+    //mp find_value
+    /// Find a value from a hierarchical path, which may be state (as
+    /// for find_state) or a field or element within state, such as
+    /// "dut.apb_request.psel" or "dut.q.lines[2].x"
+    ///
+    /// Returns the instance, state index, and the path through the
+    /// subelements of the state to the value
+    ///
+    /// This can only be used after prepare_simulation
+    pub fn find_value(&self, path: &str) -> Option<(InstanceHandle, SimStateIndex, Vec<usize>)> {
+        if let Some((handle, state_index)) = self.find_state(path) {
+            return Some((handle, state_index, vec![]));
+        }
+        let mut split = path.len();
+        while let Some(n) = path[..split].rfind('.') {
+            split = n;
+            if let Some((handle, state_index)) = self.find_state(&path[..n]) {
+                let subelements = self
+                    .with_state(handle, state_index, |v| {
+                        find_subelements(v.sim_value(), &path[n + 1..])
+                    })
+                    .flatten()?;
+                return Some((handle, state_index, subelements));
+            }
+        }
         None
-        // self
-        // .clocks
-        // .add_clock(full_name, delay, period, negedge_offset))
+    }
+
+    //mp with_value
+    /// Invoke a function with a value within the state data of an
+    /// instance, given the path through its subelements (as returned
+    /// by find_value)
+    ///
+    /// This can only be used after prepare_simulation
+    pub fn with_value<R, F: FnOnce(&dyn SimValueObject) -> R>(
+        &self,
+        handle: InstanceHandle,
+        state_index: SimStateIndex,
+        subelements: &[usize],
+        f: F,
+    ) -> Option<R> {
+        let s = self.body.instance(handle).borrow_sim()?;
+        let state = s.try_state_data(state_index)?;
+        let mut value = state.sim_value();
+        for i in subelements {
+            value = value.get_subelement(*i)?.1;
+        }
+        Some(f(value))
+    }
+
+    //mp with_state
+    /// Invoke a function with the state data of an instance, if the
+    /// instance is not already borrowed and it provides the data
+    ///
+    /// This can only be used after prepare_simulation
+    pub fn with_state<R, F: FnOnce(SimValueRef) -> R>(
+        &self,
+        handle: InstanceHandle,
+        state_index: SimStateIndex,
+        f: F,
+    ) -> Option<R> {
+        let s = self.body.instance(handle).borrow_sim()?;
+        s.try_state_data(state_index).map(f)
+    }
+
+    //mp with_state_mut
+    /// Invoke a function with the mutable state data of an instance,
+    /// if the instance is not already borrowed and it provides the
+    /// data
+    ///
+    /// This can only be used after prepare_simulation
+    pub fn with_state_mut<R, F: FnOnce(SimValueRefMut) -> R>(
+        &self,
+        handle: InstanceHandle,
+        state_index: SimStateIndex,
+        f: F,
+    ) -> Option<R> {
+        let mut s = self.body.instance(handle).borrow_sim_mut()?;
+        s.try_state_data_mut(state_index).map(f)
+    }
+
+    //mp ns_name_string
+    /// Get the full hierarchical name of a [SimNsName] as a String
+    pub fn ns_name_string(&self, name: SimNsName) -> String {
+        self.control.borrow().ns_name_fmt(name).to_string()
+    }
+
+    //mp state_paths
+    /// Get the hierarchical path, instance handle and state index of
+    /// every piece of exposed state of every instance
+    ///
+    /// This can only be used after prepare_simulation
+    pub fn state_paths(&self) -> Vec<(String, InstanceHandle, SimStateIndex)> {
+        let control = self.control.borrow();
+        let mut paths = vec![];
+        for handle in self.body.iter_handles() {
+            let instance = self.body.instance(handle);
+            for (name, state_index) in instance.state_names() {
+                let path = format!(
+                    "{}.{}",
+                    control.ns_name_fmt(instance.name()),
+                    control.name_fmt(name)
+                );
+                paths.push((path, handle, state_index));
+            }
+        }
+        paths
     }
 
     //mp instantiate
@@ -251,6 +465,7 @@ impl Simulation<'_> {
         build
             .instance(handle)
             .configure::<C, _>(&mut *control, handle, config_fn)?;
+        control.check_input_uses(handle)?;
         Ok(handle)
     }
 
@@ -277,3 +492,29 @@ impl Simulation<'_> {
             .connect_clock(clock, instance, input);
     }
 }
+
+//a Support functions
+//fi find_subelements
+/// Find the path through the subelements of a value given the names
+/// of fields and array indices, such as "lines[2].x"
+fn find_subelements(value: &dyn SimValueObject, fields: &str) -> Option<Vec<usize>> {
+    let mut value = value;
+    let mut subelements = vec![];
+    for field in fields.split('.') {
+        let (name, mut indices) = field.split_at(field.find('[').unwrap_or(field.len()));
+        if !name.is_empty() {
+            let n = (0..value.num_subelements())
+                .find(|i| value.get_subelement(*i).is_some_and(|(f, _)| f == name))?;
+            value = value.get_subelement(n)?.1;
+            subelements.push(n);
+        }
+        while !indices.is_empty() {
+            let (index, rest) = indices.strip_prefix('[')?.split_once(']')?;
+            let n: usize = index.parse().ok()?;
+            value = value.get_subelement(n)?.1;
+            subelements.push(n);
+            indices = rest;
+        }
+    }
+    Some(subelements)
+}
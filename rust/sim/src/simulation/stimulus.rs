@@ -0,0 +1,399 @@
+//a Documentation
+//! Constrained-random stimulus with reproducible seeds
+//!
+//! A simulation has a master seed, which is taken from the
+//! `HGL_SEED` environment variable if it is set, and is otherwise
+//! chosen at random; it may also be set explicitly with
+//! [Simulation::set_seed]. The seed is printed (to stderr) when the
+//! first random stream is created, so that a failing test can be
+//! replayed by setting `HGL_SEED`. The variable may be decimal or
+//! hex (with a `0x` prefix); any other value is an error (returned
+//! by [Simulation::seed], and by the creation of any random stream),
+//! rather than a reason to pick a different seed.
+//!
+//! Each user of random numbers (such as each instance driving
+//! stimulus) derives its own stream with [Simulation::rng], given a
+//! name (such as the instance path); the stream depends only on the
+//! master seed and the name, so adding another stream does not
+//! change the values of the others.
+//!
+//! A [Stimulus] generates values of a structure (such as an APB
+//! request) from a stream. Each field may be fully random, or drawn
+//! from a [Dist] (a range, a set of values, or weighted ranges);
+//! relations between fields are applied after the fields are
+//! generated, and constraints reject values (which are then
+//! regenerated).
+
+//a Imports
+use std::cell::Cell;
+
+use rand::{Rng, RngCore};
+
+use crate::simulation::Simulation;
+use crate::traits::{SimBit, SimBv, SimCopyValue};
+use crate::values::SimValueRefMut;
+
+//a SimRng
+//tp SimRng
+/// A random number stream for a simulation
+///
+/// This is a SplitMix64 generator, so that a seed produces the same
+/// stream on every platform and with every version of the crate; it
+/// implements [RngCore] so that it can be used with the `rand` crate
+#[derive(Debug, Clone, Default)]
+pub struct SimRng {
+    state: u64,
+}
+
+//ip SimRng
+impl SimRng {
+    //cp new
+    /// Create a stream from a seed
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    //cp derive
+    /// Create a stream from a seed and a name
+    pub fn derive(seed: u64, name: &str) -> Self {
+        // FNV-1a of the name, mixed with the seed
+        let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+        for b in name.bytes() {
+            h ^= b as u64;
+            h = h.wrapping_mul(0x100_0000_01b3);
+        }
+        let mut s = Self::new(seed ^ h);
+        Self::new(s.next_u64())
+    }
+}
+
+//ip RngCore for SimRng
+impl RngCore for SimRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let v = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&v[0..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+//a Dist
+//tp Dist
+/// A distribution of values for a field of a [Stimulus]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dist {
+    /// Uniform over an inclusive range
+    Range(u64, u64),
+    /// Uniform over a set of values
+    Values(Vec<u64>),
+    /// Inclusive ranges with weights; a range is picked with
+    /// probability proportional to its weight, then a value uniformly
+    /// within it
+    Weighted(Vec<(u64, u64, u32)>),
+}
+
+//ip Dist
+impl Dist {
+    //cp range
+    /// A uniform distribution over an inclusive range
+    pub fn range(lo: u64, hi: u64) -> Self {
+        Self::Range(lo, hi)
+    }
+
+    //cp values
+    /// A uniform distribution over a set of values
+    pub fn values(values: &[u64]) -> Self {
+        Self::Values(values.to_vec())
+    }
+
+    //cp weighted
+    /// A distribution of weighted inclusive ranges
+    pub fn weighted(ranges: &[(u64, u64, u32)]) -> Self {
+        Self::Weighted(ranges.to_vec())
+    }
+
+    //mi check
+    /// Check that the distribution is valid for a field of a bit
+    /// width
+    fn check(&self, bit_width: usize) -> Result<(), String> {
+        let max = if bit_width >= 64 {
+            u64::MAX
+        } else {
+            (1 << bit_width) - 1
+        };
+        let ranges: Vec<(u64, u64)> = match self {
+            Self::Range(lo, hi) => vec![(*lo, *hi)],
+            Self::Values(v) => v.iter().map(|v| (*v, *v)).collect(),
+            Self::Weighted(w) => {
+                if w.iter().all(|(_, _, w)| *w == 0) {
+                    return Err(format!("Distribution {self:?} has no weights"));
+                }
+                w.iter().map(|(lo, hi, _)| (*lo, *hi)).collect()
+            }
+        };
+        if ranges.is_empty() {
+            return Err("Distribution has no values".into());
+        }
+        for (lo, hi) in ranges {
+            if lo > hi {
+                return Err(format!("Distribution range {lo}..={hi} is empty"));
+            }
+            if hi > max {
+                return Err(format!(
+                    "Distribution value {hi} does not fit in {bit_width} bits"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    //mp sample
+    /// Sample a value from the distribution
+    pub fn sample(&self, rng: &mut SimRng) -> u64 {
+        match self {
+            Self::Range(lo, hi) => rng.gen_range(*lo..=*hi),
+            Self::Values(v) => v[rng.gen_range(0..v.len())],
+            Self::Weighted(w) => {
+                let total: u64 = w.iter().map(|(_, _, w)| *w as u64).sum();
+                let mut n = rng.gen_range(0..total);
+                for (lo, hi, weight) in w {
+                    if n < *weight as u64 {
+                        return rng.gen_range(*lo..=*hi);
+                    }
+                    n -= *weight as u64;
+                }
+                unreachable!("Sampled beyond the total weight");
+            }
+        }
+    }
+}
+
+//a Stimulus
+//ti FieldFn, RelationFn, ConstraintFn
+type FieldFn<T> = Box<dyn Fn(&mut T, &mut SimRng)>;
+type RelationFn<T> = Box<dyn Fn(&mut T)>;
+type ConstraintFn<T> = Box<dyn Fn(&T) -> bool>;
+
+//tp Stimulus
+/// A generator of constrained-random values of a structure
+///
+/// ```ignore
+/// let mut requests = Stimulus::new(sim.rng("apb")?, t_apb_request::default())
+///     .dist(|r| &mut r.paddr, Dist::range(0, 3))?
+///     .random_bit(|r| &mut r.pwrite)
+///     .random(|r| &mut r.pwdata)
+///     .relate(|r| if r.pwrite.is_false() { r.pwdata = 0.into() })
+///     .constrain(|r| r.paddr != 1.into() || r.pwrite.is_false());
+/// let request = requests.generate()?;
+/// ```
+pub struct Stimulus<T: SimCopyValue> {
+    rng: SimRng,
+    base: T,
+    fields: Vec<FieldFn<T>>,
+    relations: Vec<RelationFn<T>>,
+    constraints: Vec<ConstraintFn<T>>,
+    max_attempts: usize,
+}
+
+//ip Stimulus
+impl<T: SimCopyValue> Stimulus<T> {
+    //cp new
+    /// Create a generator using a random stream, where every value
+    /// starts as the base value
+    pub fn new(rng: SimRng, base: T) -> Self {
+        Self {
+            rng,
+            base,
+            fields: vec![],
+            relations: vec![],
+            constraints: vec![],
+            max_attempts: 1000,
+        }
+    }
+
+    //cp random
+    /// Make a bit vector field fully random
+    #[must_use]
+    pub fn random<F: SimBv>(mut self, field: fn(&mut T) -> &mut F) -> Self {
+        self.fields.push(Box::new(move |t, rng| {
+            *field(t) = F::randomize(&mut || rng.next_u64());
+        }));
+        self
+    }
+
+    //cp random_bit
+    /// Make a bit field fully random
+    #[must_use]
+    pub fn random_bit<F: SimBit>(mut self, field: fn(&mut T) -> &mut F) -> Self
+    where
+        bool: From<F>,
+        for<'a> &'a bool: From<&'a F>,
+    {
+        self.fields.push(Box::new(move |t, rng| {
+            *field(t) = F::randomize(&mut || rng.next_u64());
+        }));
+        self
+    }
+
+    //cp dist
+    /// Draw a field (of at most 64 bits) from a distribution
+    pub fn dist<F: SimCopyValue>(
+        mut self,
+        field: fn(&mut T) -> &mut F,
+        dist: Dist,
+    ) -> Result<Self, String> {
+        if F::BIT_WIDTH == 0 || F::BIT_WIDTH > 64 || F::NUM_SUBELEMENTS != 0 {
+            return Err(format!(
+                "A distribution can only be used for a field of 1 to 64 bits, not {}",
+                F::BIT_WIDTH
+            ));
+        }
+        dist.check(F::BIT_WIDTH)?;
+        self.fields.push(Box::new(move |t, rng| {
+            let v = dist.sample(rng);
+            SimValueRefMut::of(field(t)).set_u64(v);
+        }));
+        Ok(self)
+    }
+
+    //cp relate
+    /// Add a relation between fields, applied (in order) after the
+    /// fields are generated
+    #[must_use]
+    pub fn relate<F: Fn(&mut T) + 'static>(mut self, relation: F) -> Self {
+        self.relations.push(Box::new(relation));
+        self
+    }
+
+    //cp constrain
+    /// Add a constraint; generated values for which it is false are
+    /// rejected
+    #[must_use]
+    pub fn constrain<F: Fn(&T) -> bool + 'static>(mut self, constraint: F) -> Self {
+        self.constraints.push(Box::new(constraint));
+        self
+    }
+
+    //cp max_attempts
+    /// Set the number of values that may be rejected by the
+    /// constraints before generation fails
+    #[must_use]
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    //mp generate
+    /// Generate the next value
+    pub fn generate(&mut self) -> Result<T, String> {
+        for _ in 0..self.max_attempts {
+            let mut t = self.base;
+            for f in &self.fields {
+                f(&mut t, &mut self.rng);
+            }
+            for r in &self.relations {
+                r(&mut t);
+            }
+            if self.constraints.iter().all(|c| c(&t)) {
+                return Ok(t);
+            }
+        }
+        Err(format!(
+            "Failed to satisfy the constraints in {} attempts",
+            self.max_attempts
+        ))
+    }
+}
+
+//a SimSeed
+//tp SimSeed
+/// The master seed of a simulation
+#[derive(Debug, Default)]
+pub struct SimSeed {
+    seed: Cell<Option<u64>>,
+    reported: Cell<bool>,
+}
+
+//ip SimSeed
+impl SimSeed {
+    //fp parse
+    /// Parse a seed, as decimal or as hex with a `0x` prefix
+    pub fn parse(text: &str) -> Result<u64, String> {
+        let text = text.trim();
+        let result = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => text.parse::<u64>(),
+        };
+        result.map_err(|e| format!("Bad seed '{text}': {e}"))
+    }
+}
+
+//a Simulation
+//ip Simulation
+impl Simulation<'_> {
+    //mp set_seed
+    /// Set the master seed for the random streams of the simulation
+    ///
+    /// This overrides the `HGL_SEED` environment variable; it should
+    /// be used before any stream is created
+    pub fn set_seed(&self, seed: u64) {
+        self.seed.seed.set(Some(seed));
+    }
+
+    //ap seed
+    /// Get the master seed for the random streams of the simulation
+    ///
+    /// If it has not been set then it is taken from the `HGL_SEED`
+    /// environment variable, or chosen at random if that is not set;
+    /// an error is returned if the variable is not a valid seed, as
+    /// the run could not then be replayed
+    pub fn seed(&self) -> Result<u64, String> {
+        if let Some(seed) = self.seed.seed.get() {
+            return Ok(seed);
+        }
+        let seed = match std::env::var("HGL_SEED") {
+            Ok(s) => SimSeed::parse(&s).map_err(|e| format!("HGL_SEED rejected: {e}"))?,
+            Err(std::env::VarError::NotPresent) => rand::thread_rng().next_u64(),
+            Err(e) => return Err(format!("HGL_SEED rejected: {e}")),
+        };
+        self.seed.seed.set(Some(seed));
+        Ok(seed)
+    }
+
+    //mp rng
+    /// Create the random stream for a name (such as an instance path)
+    /// derived from the master seed
+    ///
+    /// The master seed is printed when the first stream is created
+    pub fn rng(&self, name: &str) -> Result<SimRng, String> {
+        let seed = self.seed()?;
+        if !self.seed.reported.replace(true) {
+            eprintln!("hgl_sim: random seed {seed} (replay with HGL_SEED={seed})");
+        }
+        Ok(SimRng::derive(seed, name))
+    }
+
+    //mp stimulus
+    /// Create a [Stimulus] generator using the random stream for a
+    /// name
+    pub fn stimulus<T: SimCopyValue>(&self, name: &str, base: T) -> Result<Stimulus<T>, String> {
+        Ok(Stimulus::new(self.rng(name)?, base))
+    }
+}
@@ -0,0 +1,205 @@
+//a Documentation
+//! Async testbenches driven by the clock edges of a simulation
+//!
+//! Testbench code may be written as async functions (or blocks) that
+//! wait for clock edges, rather than as long sequences of calls to
+//! [Simulation::fire_next_edges]:
+//!
+//! * `sim.posedge(clk).await` and `sim.negedge(clk).await` wait for
+//!   the next rising or falling edge of a clock
+//!
+//! * `sim.cycles(clk, n).await` waits for the next `n` rising edges
+//!
+//! * `sim.until(|sim| condition).await` waits until a condition of
+//!   the simulation holds (which it may do already)
+//!
+//! Any number of such tasks are spawned on a [Testbench], which runs
+//! them until they have all completed: it polls each task in turn, in
+//! the order in which they were spawned, and then fires the next
+//! clock edges of the simulation, repeatedly. Tasks therefore run
+//! between edges, after the monitors and drivers for an edge (so
+//! changes they make to inputs are seen at the next edge), and the
+//! interleaving of tasks is the same on every run.
+//!
+//! The edges of each clock are counted as they are fired, so a task
+//! waiting for an edge sees edges fired by other tasks (for example
+//! by a transaction-level bridge) as well as by the testbench.
+
+//a Imports
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+use hgl_indexed_vec::Idx;
+
+use crate::simulation::{ClockIndex, SimEdgeMask, Simulation};
+
+//a EdgeCounts
+//tp EdgeCounts
+/// The number of rising and falling edges of each clock that have
+/// been fired in a simulation
+#[derive(Debug, Default)]
+pub struct EdgeCounts {
+    counts: Vec<[usize; 2]>,
+}
+
+//ip EdgeCounts
+impl EdgeCounts {
+    //mp resize
+    /// Size the counts for the number of system clocks
+    pub(crate) fn resize(&mut self, num_clocks: usize) {
+        self.counts.resize(num_clocks, [0, 0]);
+    }
+
+    //mp record
+    /// Record the system clock edges that have been fired
+    pub(crate) fn record(&mut self, edges: SimEdgeMask) {
+        for (clock, posedge) in edges.iter_edges() {
+            if let Some(c) = self.counts.get_mut(clock) {
+                c[if posedge { 0 } else { 1 }] += 1;
+            }
+        }
+    }
+
+    //ap count
+    /// The number of rising (or falling) edges of a clock
+    pub fn count(&self, clock: ClockIndex, posedge: bool) -> usize {
+        self.counts
+            .get(clock.index())
+            .map_or(0, |c| c[if posedge { 0 } else { 1 }])
+    }
+}
+
+//a Simulation
+//ip Simulation
+impl<'s> Simulation<'s> {
+    //ap edge_count
+    /// The number of rising (or falling) edges of a clock that have
+    /// been fired
+    pub fn edge_count(&self, clock: ClockIndex, posedge: bool) -> usize {
+        self.edge_counts.borrow().count(clock, posedge)
+    }
+
+    //mi edges
+    /// A future that completes once n more edges of a clock have been
+    /// fired, counting from when it is first polled
+    fn edges<'a>(
+        &'a self,
+        clock: ClockIndex,
+        posedge: bool,
+        n: usize,
+    ) -> impl Future<Output = ()> + use<'a, 's> {
+        let mut target = None;
+        poll_fn(move |_| {
+            let count = self.edge_count(clock, posedge);
+            if count >= *target.get_or_insert(count + n) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+    }
+
+    //mp posedge
+    /// A future that completes after the next rising edge of a clock
+    pub fn posedge<'a>(&'a self, clock: ClockIndex) -> impl Future<Output = ()> + use<'a, 's> {
+        self.edges(clock, true, 1)
+    }
+
+    //mp negedge
+    /// A future that completes after the next falling edge of a clock
+    pub fn negedge<'a>(&'a self, clock: ClockIndex) -> impl Future<Output = ()> + use<'a, 's> {
+        self.edges(clock, false, 1)
+    }
+
+    //mp cycles
+    /// A future that completes after the next n rising edges of a clock
+    pub fn cycles<'a>(
+        &'a self,
+        clock: ClockIndex,
+        n: usize,
+    ) -> impl Future<Output = ()> + use<'a, 's> {
+        self.edges(clock, true, n)
+    }
+
+    //mp until
+    /// A future that completes when a condition of the simulation
+    /// holds; it is checked whenever the task awaiting it is polled
+    pub fn until<'a, F: FnMut(&Simulation) -> bool + 'a>(
+        &'a self,
+        mut condition: F,
+    ) -> impl Future<Output = ()> + use<'a, 's, F> {
+        poll_fn(move |_| {
+            if condition(self) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+    }
+}
+
+//a Testbench
+//ti Task
+type Task<'a> = Pin<Box<dyn Future<Output = ()> + 'a>>;
+
+//tp Testbench
+/// An executor for testbench tasks, which fires the clock edges of a
+/// simulation while any task has not completed
+#[derive(Default)]
+pub struct Testbench<'a> {
+    tasks: Vec<Task<'a>>,
+}
+
+//ip Testbench
+impl<'a> Testbench<'a> {
+    //cp new
+    /// Create a new testbench, with no tasks
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //mp spawn
+    /// Add a task to the testbench
+    pub fn spawn<F: Future<Output = ()> + 'a>(&mut self, task: F) {
+        self.tasks.push(Box::pin(task));
+    }
+
+    //mi poll_tasks
+    /// Poll every task once, in order, dropping those that complete
+    fn poll_tasks(&mut self) {
+        let mut cx = Context::from_waker(Waker::noop());
+        self.tasks
+            .retain_mut(|task| task.as_mut().poll(&mut cx).is_pending());
+    }
+
+    //mp run
+    /// Run the tasks until they have all completed, firing at most
+    /// max_edges clock edges, and returning the number fired
+    ///
+    /// Tasks spawned before a run that does not complete them remain
+    /// in the testbench, and continue on the next run
+    pub fn run(&mut self, sim: &Simulation, max_edges: usize) -> Result<usize, String> {
+        let mut edges = 0;
+        loop {
+            self.poll_tasks();
+            if self.tasks.is_empty() {
+                return Ok(edges);
+            }
+            if edges >= max_edges {
+                return Err(format!(
+                    "{} testbench tasks still running after {max_edges} edges",
+                    self.tasks.len()
+                ));
+            }
+            sim.fire_next_edges();
+            edges += 1;
+        }
+    }
+
+    //ap is_empty
+    /// Return true if all the tasks have completed
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+}
@@ -0,0 +1,160 @@
+//a Documentation
+//! Worker threads synchronized to the clock edges of a simulation
+//!
+//! A testbench (or model) may run code in OS threads of its own,
+//! which block until particular clock edges of the simulation have
+//! fired. A [SimulationWorker] is created by
+//! [Simulation::spawn_worker] with a filter (a [SimBlah], such as a
+//! [SimEdgeMask] of the system clock edges it requires), and moved to
+//! the thread; the thread then repeatedly invokes
+//! [SimulationWorker::wait_for_edge], and acts on the edges.
+//!
+//! After firing clock edges (and invoking monitors and drivers), the
+//! main thread runs every worker whose filter matches the edges, and
+//! then waits on the [crate::sync::TockBarrier] until they have all
+//! waited for their next edge; so the workers for an edge always run
+//! to completion before the simulation moves on.
+//!
+//! The simulation is not Send, so a worker accesses it (for example
+//! to read or write the state of an instance) with
+//! [SimulationWorker::with_sim], which passes a function to the main
+//! thread to invoke while it waits for the workers. A worker that
+//! has not yet waited for its first edge is waited for (and its
+//! accesses served) at the start of the next firing of edges.
+//!
+//! When the simulation is stopped, the workers are told to finish:
+//! [SimulationWorker::wait_for_edge] then returns None, and the
+//! thread should drop its worker and exit.
+
+//a Imports
+use std::sync::mpsc;
+
+use crate::simulation::{SimEdgeMask, Simulation};
+use crate::sync::{Barrier, BarrierWaitResult, SimBlah, SimRequest, Worker};
+
+//a Constants
+/// The maximum number of workers of a simulation
+pub const MAX_WORKERS: usize = 64;
+
+//a WorkerSet
+//tp WorkerSet
+/// The barrier for the workers of a simulation, created when the
+/// first worker is spawned
+#[derive(Default)]
+pub struct WorkerSet {
+    barrier: Option<Barrier>,
+}
+
+//ip Drop for WorkerSet
+impl Drop for WorkerSet {
+    /// Release any workers still waiting, so their threads can exit
+    fn drop(&mut self) {
+        if let Some(barrier) = self.barrier.take() {
+            barrier.finish();
+        }
+    }
+}
+
+//a SimulationWorker
+//tp SimulationWorker
+/// The handle of a worker thread of a simulation
+pub struct SimulationWorker {
+    worker: Worker,
+    /// The filter, until the first wait
+    filter: Option<Box<dyn SimBlah>>,
+}
+
+//ip SimulationWorker
+impl SimulationWorker {
+    //mp wait_for_edge
+    /// Wait until edges that match the filter of the worker have
+    /// fired, returning them; None if the simulation has stopped
+    pub fn wait_for_edge(&mut self) -> Option<SimEdgeMask> {
+        let result = match self.filter.take() {
+            Some(filter) => self.worker.wait_poll(filter),
+            None => self.worker.wait_again(),
+        };
+        match result {
+            BarrierWaitResult::Finish => None,
+            _ => Some(self.worker.edges()),
+        }
+    }
+
+    //mp with_sim
+    /// Invoke a function with the simulation, in the main thread,
+    /// returning its result; None if the simulation has stopped
+    ///
+    /// This blocks until the main thread serves it, which it does
+    /// while it waits for the workers; the function must not fire
+    /// clock edges
+    pub fn with_sim<R, F>(&self, f: F) -> Option<R>
+    where
+        R: Send + 'static,
+        F: FnOnce(&Simulation) -> R + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let request: SimRequest = Box::new(move |sim| {
+            let _ = tx.send(f(sim));
+        });
+        if !self.worker.request(request) {
+            return None;
+        }
+        rx.recv().ok()
+    }
+}
+
+//a Simulation
+//ip Simulation
+impl Simulation<'_> {
+    //mp spawn_worker
+    /// Create a worker, for a thread that is to wait for clock edges
+    /// that match the filter
+    ///
+    /// The thread must wait for an edge (or drop the worker) before
+    /// the simulation can fire edges
+    #[track_caller]
+    pub fn spawn_worker<T: SimBlah>(&self, filter: T) -> SimulationWorker {
+        let mut workers = self.workers.borrow_mut();
+        let barrier = workers
+            .barrier
+            .get_or_insert_with(|| Barrier::new(MAX_WORKERS));
+        SimulationWorker {
+            worker: barrier.add_worker(),
+            filter: Some(Box::new(filter)),
+        }
+    }
+
+    //mi worker_barrier
+    fn worker_barrier(&self) -> Option<Barrier> {
+        self.workers.borrow().barrier.clone()
+    }
+
+    //mp sync_workers
+    /// Wait for all the workers to be waiting for an edge, serving
+    /// their requests in the meantime
+    pub(crate) fn sync_workers(&self) {
+        if let Some(barrier) = self.worker_barrier() {
+            barrier.sync_serving(|request| request(self));
+        }
+    }
+
+    //mp run_workers
+    /// Run the workers whose filters match the edges, and wait for
+    /// them to complete
+    pub(crate) fn run_workers(&self, edges: SimEdgeMask) {
+        if let Some(barrier) = self.worker_barrier() {
+            barrier.run_workers::<{ MAX_WORKERS / 8 }>(&edges);
+            barrier.sync_serving(|request| request(self));
+        }
+    }
+
+    //mp finish_workers
+    /// Tell the workers to finish, once they are all waiting
+    pub(crate) fn finish_workers(&self) {
+        let barrier = self.workers.borrow_mut().barrier.take();
+        if let Some(barrier) = barrier {
+            barrier.sync_serving(|request| request(self));
+            barrier.finish();
+        }
+    }
+}
@@ -31,9 +31,16 @@
 //!
 
 //a Imports
+use std::collections::VecDeque;
 use std::sync::{Arc, Condvar, Mutex, MutexGuard};
 
-use crate::simulation::SimEdgeMask;
+use crate::simulation::{SimEdgeMask, Simulation};
+
+//a SimRequest
+//tp SimRequest
+/// A request from a worker thread, to be invoked by the main thread
+/// with the simulation while it waits for the workers
+pub type SimRequest = Box<dyn FnOnce(&Simulation) + Send>;
 
 //a Types
 //a BitSet
@@ -100,6 +107,13 @@ pub trait SimBlah: Send + 'static {
 //ip SimBlah for () - enable a null poll
 impl SimBlah for () {}
 
+//ip SimBlah for Box<dyn SimBlah> - enable a boxed poll
+impl SimBlah for Box<dyn SimBlah> {
+    fn poll(&mut self, edges: &SimEdgeMask) -> SimWaitResult {
+        self.as_mut().poll(edges)
+    }
+}
+
 //ip SimBlah for SimEdgeMask - enable a null poll
 impl SimBlah for SimEdgeMask {
     fn poll(&mut self, edges: &SimEdgeMask) -> SimWaitResult {
@@ -145,6 +159,9 @@ struct WorkerBarrierState {
     /// Function to invoke at the next sim clock firing, given a set
     /// of SimEdges
     poll: Box<dyn SimBlah>,
+
+    /// The edges at which the worker was last run
+    edges: SimEdgeMask,
 }
 
 //ip WorkerBarrierState
@@ -158,6 +175,7 @@ impl WorkerBarrierState {
             added: false,
             completed: false,
             poll: Box::new(()),
+            edges: SimEdgeMask::none(),
         }
     }
 
@@ -210,6 +228,25 @@ impl WorkerBarrierInner {
     fn wait<T: SimBlah>(&self, tock_barrier: &TockBarrier, t: T) -> BarrierWaitResult {
         let mut lock = self.lock.lock().unwrap();
         lock.poll = Box::new(t);
+        self.wait_locked(tock_barrier, lock)
+    }
+
+    //mp wait_again
+    /// Invoked by the worker thread
+    ///
+    /// As wait, but keeping the poll function of the last wait
+    fn wait_again(&self, tock_barrier: &TockBarrier) -> BarrierWaitResult {
+        let lock = self.lock.lock().unwrap();
+        self.wait_locked(tock_barrier, lock)
+    }
+
+    //mi wait_locked
+    /// Wait, with the worker barrier state locked
+    fn wait_locked(
+        &self,
+        tock_barrier: &TockBarrier,
+        mut lock: MutexGuard<WorkerBarrierState>,
+    ) -> BarrierWaitResult {
         lock.run = false;
         tock_barrier.worker_waiting(self.worker);
         if !lock.run && !lock.finish {
@@ -235,11 +272,18 @@ impl WorkerBarrierInner {
         tock_barrier.worker_completed(self.worker, lock.run);
     }
 
+    //ap edges
+    /// The edges at which the worker was last run
+    fn edges(&self) -> SimEdgeMask {
+        self.lock.lock().unwrap().edges
+    }
+
     //mp run
     /// Invoked by the main thread
-    fn run(&self) {
+    fn run(&self, edges: &SimEdgeMask) {
         let mut lock = self.lock.lock().unwrap();
         lock.run = true;
+        lock.edges = *edges;
         self.cvar.notify_all();
     }
 
@@ -273,6 +317,9 @@ struct TockBarrierState {
 
     /// Maximum number of workers
     max_workers: usize,
+
+    /// Requests from workers for the main thread
+    requests: VecDeque<SimRequest>,
 }
 
 //ip TockBarrierState
@@ -284,6 +331,7 @@ impl TockBarrierState {
             finish: false,
             added_workers: 0,
             max_workers,
+            requests: VecDeque::new(),
         }
     }
 }
@@ -345,6 +393,44 @@ impl TockBarrier {
         }
     }
 
+    //mp wait_for_all_workers_serving
+    /// Invoked by the main thread to ensure all threads are waiting,
+    /// serving their requests in the meantime
+    ///
+    /// Returns when all worker threads are at the 'wait' point, and
+    /// there are no requests outstanding
+    pub fn wait_for_all_workers_serving<F: FnMut(SimRequest)>(&self, mut serve: F) {
+        loop {
+            let tbs = self.lock.lock().unwrap();
+            let mut tbs = self
+                .cvar
+                .wait_while(tbs, |tbs| {
+                    tbs.num_running > tbs.waiting && tbs.requests.is_empty()
+                })
+                .unwrap();
+            let Some(request) = tbs.requests.pop_front() else {
+                return;
+            };
+            drop(tbs);
+            serve(request);
+        }
+    }
+
+    //mp request
+    /// Invoked by a worker to add a request for the main thread
+    ///
+    /// Returns false (dropping the request) if the workers have been
+    /// told to finish
+    pub fn request(&self, request: SimRequest) -> bool {
+        let mut lock = self.lock.lock().unwrap();
+        if lock.finish {
+            return false;
+        }
+        lock.requests.push_back(request);
+        self.cvar.notify_all();
+        true
+    }
+
     //mp worker_waiting
     pub fn worker_waiting(&self, _worker: usize) {
         let mut lock = self.lock.lock().unwrap();
@@ -451,7 +537,7 @@ impl BarrierInner {
             if finish {
                 w.finish();
             } else if bits.is_set(i) {
-                w.run();
+                w.run(edges);
             }
         }
         running
@@ -464,6 +550,7 @@ impl BarrierInner {
     fn finish(&self) {
         let n = self.workers.len();
         self.tock_barrier.set_finish();
+        self.tock_barrier.lock.lock().unwrap().requests.clear();
         self.tock_barrier.set_workers_running(n);
         for w in self.workers.iter() {
             w.finish();
@@ -478,6 +565,13 @@ impl BarrierInner {
         self.tock_barrier.wait_for_all_workers();
     }
 
+    //mp wait_for_all_workers_serving
+    /// Invoked by the main thread to ensure workers are waiting,
+    /// serving their requests in the meantime
+    pub fn wait_for_all_workers_serving<F: FnMut(SimRequest)>(&self, serve: F) {
+        self.tock_barrier.wait_for_all_workers_serving(serve);
+    }
+
     //mp wait_poll
     /// Wait until a simulation edge hits where a poll condition is met
     ///
@@ -526,6 +620,7 @@ impl BarrierInner {
 
 //tp Barrier
 /// A clonable barrier
+#[derive(Clone)]
 pub struct Barrier {
     inner: Arc<BarrierInner>,
 }
@@ -573,6 +668,13 @@ impl Barrier {
         let _ = self.inner.wait_for_all_workers();
     }
 
+    //mp sync_serving
+    /// Invoked by the main thread, as sync, serving the requests of
+    /// the workers until they are all at the 'wait' point
+    pub fn sync_serving<F: FnMut(SimRequest)>(&self, serve: F) {
+        self.inner.wait_for_all_workers_serving(serve);
+    }
+
     //mp run_workers
     /// Invoked by the main thread when all workers are at the 'wait' point
     ///
@@ -607,9 +709,30 @@ impl Worker {
     fn wait(&self) -> BarrierWaitResult {
         self.barrier.inner.worker_wait(self.worker)
     }
-    fn wait_poll<T: SimBlah>(&self, t: T) -> BarrierWaitResult {
+    pub fn wait_poll<T: SimBlah>(&self, t: T) -> BarrierWaitResult {
         self.barrier.inner.worker_wait_poll(self.worker, t)
     }
+
+    //mp wait_again
+    /// Wait until a simulation edge hits where the poll condition of
+    /// the last wait is met
+    pub fn wait_again(&self) -> BarrierWaitResult {
+        let inner = &self.barrier.inner;
+        inner.workers[self.worker].wait_again(&inner.tock_barrier)
+    }
+
+    //ap edges
+    /// The edges at which the worker was last run
+    pub fn edges(&self) -> SimEdgeMask {
+        self.barrier.inner.workers[self.worker].edges()
+    }
+
+    //mp request
+    /// Add a request for the main thread, returning false if the
+    /// workers have been told to finish
+    pub fn request(&self, request: SimRequest) -> bool {
+        self.barrier.inner.tock_barrier.request(request)
+    }
 }
 
 //ip Drop for Worker
@@ -0,0 +1,399 @@
+//a Documentation
+//! Models that run in their own threads
+//!
+//! Some models - such as instruction set simulators, or software
+//! stacks - are best run in a thread of their own, alongside the
+//! cycle simulation. Such a model implements [ThreadedModel], and is
+//! wrapped in a [Model], which owns the model thread and provides the
+//! handshakes with it from the simulation thread.
+//!
+//! A component that uses a threaded model holds the [Model], and
+//! forwards its [crate::traits::Simulatable] start, pause, resume and
+//! stop to it; the thread then invokes the corresponding methods of
+//! the [ThreadedModel], and [Model::state] reports its [ModelState].
+//!
+//! Data is exchanged with the model thread deterministically, with a
+//! request and response per clock edge: while the model is running,
+//! the simulation thread posts a request for an edge with
+//! [Model::post], and collects the response with [Model::collect]
+//! (which waits for the model thread to produce it); [Model::edge]
+//! does both. Posting the request before evaluating the rest of the
+//! edge, and collecting the response after it, lets the model thread
+//! work in parallel with the simulation; either way, the model sees
+//! exactly one request per edge, in order, and the simulation sees
+//! its responses at the same edges, so runs are repeatable.
+
+//a Imports
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{spawn, JoinHandle};
+
+use crate::simulation::SimEdgeMask;
+
+//a ModelState and Action
+//tp ModelState
+/// The state of the thread of a [Model]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelState {
+    /// The thread has not been started
+    #[default]
+    Idle,
+    /// The thread is running, and handles requests
+    Running,
+    /// The thread is paused; requests are not posted
+    Paused,
+    /// The thread has stopped (and will not be restarted)
+    Stopped,
+}
+
+//ip ModelState
+impl ModelState {
+    fn is_stopped(&self) -> bool {
+        matches!(self, ModelState::Stopped)
+    }
+}
+
+//ti Action
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+enum Action {
+    #[default]
+    Idle,
+    Resume,
+    Pause,
+    Stop,
+}
+
+//ii Action
+impl Action {
+    fn is_idle(&self) -> bool {
+        matches!(self, Action::Idle)
+    }
+}
+
+//a ThreadedModel
+//tt ThreadedModel
+/// Trait for a model that runs in its own thread
+///
+/// All methods are invoked from within the model thread, separate
+/// from the simulation thread
+pub trait ThreadedModel: Send + 'static {
+    /// The data sent to the model for a clock edge
+    type Request: Send + 'static;
+    /// The data returned by the model for a clock edge
+    type Response: Send + 'static;
+
+    /// The thread has started
+    fn start(&mut self) {}
+
+    /// The simulation has paused the model
+    fn pause(&mut self) {}
+
+    /// The simulation has resumed the model
+    fn resume(&mut self) {}
+
+    /// The simulation has stopped the model; the thread then exits
+    fn stop(&mut self) {}
+
+    /// Handle the request for a clock edge (only while running)
+    fn edge(&mut self, mask: SimEdgeMask, request: Self::Request) -> Self::Response;
+}
+
+//a Model
+//ti ModelControl
+/// The state of a [Model] shared between the threads, protected by
+/// its mutex
+struct ModelControl<T: ThreadedModel> {
+    state: ModelState,
+    action: Action,
+    thread_ready: bool,
+    thread: Option<JoinHandle<()>>,
+    request: Option<(SimEdgeMask, T::Request)>,
+    response: Option<T::Response>,
+}
+
+//ii Default for ModelControl
+impl<T: ThreadedModel> Default for ModelControl<T> {
+    fn default() -> Self {
+        Self {
+            state: ModelState::Idle,
+            action: Action::Idle,
+            thread_ready: false,
+            thread: None,
+            request: None,
+            response: None,
+        }
+    }
+}
+
+//ti ModelInner
+/// The mutex (of the control and the model), and the condition
+/// variables signalled to and from the thread
+type ModelInner<T> = (Mutex<(ModelControl<T>, T)>, Condvar, Condvar);
+
+//tp Model
+/// A [ThreadedModel] and the thread that runs it
+///
+/// The model is cloneable, with clones sharing the model and thread
+pub struct Model<T>
+where
+    T: ThreadedModel,
+{
+    inner: Arc<ModelInner<T>>,
+}
+
+//ip Clone for Model
+impl<T> std::clone::Clone for Model<T>
+where
+    T: ThreadedModel,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+//ip Debug for Model
+impl<T> std::fmt::Debug for Model<T>
+where
+    T: ThreadedModel,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Model({:?})", self.state())
+    }
+}
+
+//ip Model
+impl<T> Model<T>
+where
+    T: ThreadedModel,
+{
+    //cp new
+    /// Create a new model; its thread is not started
+    pub fn new(inner: T) -> Self {
+        let control = ModelControl::default();
+        let inner = Arc::new((Mutex::new((control, inner)), Condvar::new(), Condvar::new()));
+        Self { inner }
+    }
+
+    //mi lock
+    fn lock(&self) -> MutexGuard<'_, (ModelControl<T>, T)> {
+        self.inner.0.lock().unwrap()
+    }
+
+    //ap state
+    /// The state of the model thread
+    pub fn state(&self) -> ModelState {
+        self.lock().0.state
+    }
+
+    //ap get
+    /// Access the model (from the simulation thread), waiting for
+    /// the model thread to release it
+    pub fn get<R, F: FnOnce(&T) -> R>(&self, f: F) -> R {
+        f(&self.lock().1)
+    }
+
+    //ap get_mut
+    /// Mutably access the model (from the simulation thread), waiting
+    /// for the model thread to release it
+    pub fn get_mut<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        f(&mut self.lock().1)
+    }
+
+    //mp start
+    /// Start the model thread, paused or running
+    ///
+    /// This must be invoked only once
+    pub fn start(&self, running: bool) {
+        let s = self.clone();
+        let mut mg = self.lock();
+        assert!(mg.0.state == ModelState::Idle, "Model started twice");
+        mg.0.action = Action::Idle;
+        mg.0.state = ModelState::Paused;
+        mg.0.thread_ready = false;
+        mg.0.thread = Some(spawn(move || s.thread_run()));
+        drop(mg);
+        if running {
+            self.update_state(Action::Resume);
+        };
+    }
+
+    //mp pause
+    /// Pause the model, if it is running, waiting for the model
+    /// thread to have paused
+    pub fn pause(&self) {
+        self.update_state(Action::Pause);
+        self.wait_for_thread_ready();
+    }
+
+    //mp resume
+    /// Resume the model, if it is paused
+    pub fn resume(&self) {
+        self.update_state(Action::Resume);
+    }
+
+    //mp stop
+    /// Stop the model (if it has been started), and wait for its
+    /// thread to exit
+    pub fn stop(&self) {
+        if self.state() == ModelState::Idle {
+            return;
+        }
+        self.update_state(Action::Stop);
+        self.wait_for_thread_ready();
+        let thread = self.lock().0.thread.take();
+        if let Some(thread) = thread {
+            thread.join().unwrap();
+        }
+    }
+
+    //mp post
+    /// Post the request for a clock edge to the model thread,
+    /// returning false (and dropping the request) if the model is not
+    /// running
+    ///
+    /// A response must be collected before another request is posted
+    pub fn post(&self, mask: SimEdgeMask, request: T::Request) -> bool {
+        self.wait_for_thread_ready();
+        let (_m, t, _f) = &*self.inner;
+        let mut mg = self.lock();
+        if mg.0.state != ModelState::Running {
+            return false;
+        }
+        assert!(
+            mg.0.request.is_none() && mg.0.response.is_none(),
+            "Request posted to a model before the last response was collected"
+        );
+        mg.0.request = Some((mask, request));
+        t.notify_all();
+        true
+    }
+
+    //mp collect
+    /// Collect the response to a posted request, waiting for the model
+    /// thread to produce it; None if no request was posted
+    pub fn collect(&self) -> Option<T::Response> {
+        let (_m, _t, f) = &*self.inner;
+        let mut mg = self.lock();
+        while mg.0.request.is_some() {
+            mg = f.wait(mg).unwrap();
+        }
+        mg.0.response.take()
+    }
+
+    //mp edge
+    /// Post the request for a clock edge and collect its response;
+    /// None if the model is not running
+    pub fn edge(&self, mask: SimEdgeMask, request: T::Request) -> Option<T::Response> {
+        if self.post(mask, request) {
+            self.collect()
+        } else {
+            None
+        }
+    }
+
+    //mp wait_for_thread_ready
+    /// Wait (in the simulation thread) for the model thread to have
+    /// handled the last change of state
+    pub fn wait_for_thread_ready(&self) {
+        let (_m, _t, f) = &*self.inner;
+        let mut mg = self.lock();
+        while !mg.0.thread_ready && mg.0.state != ModelState::Idle {
+            mg = f.wait(mg).unwrap();
+        }
+    }
+
+    //mi update_state
+    /// Request a change of state of the model thread (invoked from the
+    /// simulation thread); changes that are not valid from the current
+    /// state are ignored
+    fn update_state(&self, action: Action) {
+        self.wait_for_thread_ready();
+        let (_m, t, _f) = &*self.inner;
+        let mut mg = self.lock();
+        assert!(
+            mg.0.action.is_idle(),
+            "Action should have been cleared if thread is ready!"
+        );
+        match (mg.0.state, action) {
+            (ModelState::Running, Action::Pause) => (),
+            (ModelState::Paused, Action::Resume) => (),
+            (ModelState::Running, Action::Stop) => (),
+            (ModelState::Paused, Action::Stop) => (),
+            _ => {
+                return;
+            }
+        }
+        mg.0.action = action;
+        mg.0.thread_ready = false;
+        t.notify_all();
+    }
+
+    //mi thread_run
+    /// The body of the model thread
+    fn thread_run(&self) {
+        {
+            let (_m, _t, f) = &*self.inner;
+            let mut mg = self.lock();
+            mg.1.start();
+            mg.0.thread_ready = true;
+            f.notify_all();
+        }
+        while !self.lock().0.state.is_stopped() {
+            self.wait_for_event();
+        }
+    }
+
+    //mi handle_state_change
+    /// Handle a change of state, in the model thread
+    ///
+    /// Cannot be in Idle; this is only invoked from within the thread,
+    /// so it must be either Running or Paused
+    fn handle_state_change(mci: &mut (ModelControl<T>, T)) {
+        match (mci.0.state, mci.0.action) {
+            (ModelState::Running, Action::Pause) => {
+                mci.1.pause();
+                mci.0.state = ModelState::Paused;
+            }
+            (ModelState::Running, Action::Stop) => {
+                mci.1.pause();
+                mci.1.stop();
+                mci.0.state = ModelState::Stopped;
+            }
+            (ModelState::Paused, Action::Resume) => {
+                mci.1.resume();
+                mci.0.state = ModelState::Running;
+            }
+            (ModelState::Paused, Action::Stop) => {
+                mci.1.stop();
+                mci.0.state = ModelState::Stopped;
+            }
+            _ => {
+                panic!(
+                    "Unexpected state/action {:?}, {:?}",
+                    mci.0.state, mci.0.action
+                );
+            }
+        }
+    }
+
+    //mi wait_for_event
+    /// Wait (in the model thread) for a change of state or a request,
+    /// and handle it
+    fn wait_for_event(&self) {
+        let (_m, t, f) = &*self.inner;
+        let mut mg = self.lock();
+        while mg.0.thread_ready && mg.0.request.is_none() {
+            mg = t.wait(mg).unwrap();
+        }
+        if let Some((mask, request)) = mg.0.request.take() {
+            let response = mg.1.edge(mask, request);
+            mg.0.response = Some(response);
+        } else if !mg.0.action.is_idle() {
+            Self::handle_state_change(&mut mg);
+            mg.0.action = Action::Idle;
+            mg.0.thread_ready = true;
+        }
+        f.notify_all();
+    }
+}
@@ -0,0 +1,220 @@
+//a Documentation
+//! Transaction-level modelling
+//!
+//! Alongside the pin-level [Component] traits, models may communicate
+//! with transactions: an initiator passes a typed payload (such as a
+//! bus read or write, which carries its own response status) through
+//! a [TlmSocket] to a target, with a delay annotation in units of
+//! simulation time. This follows the style of SystemC TLM-2.0.
+//!
+//! * The blocking transport call, `b_transport`, completes the
+//!   transaction before it returns; the target adds the time the
+//!   transaction takes to the delay (a target that advances the
+//!   simulation itself, such as an adaptor to a pin-level bus, first
+//!   consumes the delay and then returns a zero delay)
+//!
+//! * The non-blocking transport call on the forward path,
+//!   `nb_transport_fw`, starts a transaction with the phase
+//!   [TlmPhase::BeginReq]. The target either completes it at once
+//!   (returning [TlmSync::Completed] with the phase
+//!   [TlmPhase::BeginResp]), or accepts it (returning
+//!   [TlmSync::Accepted]) and completes it later, when the initiator
+//!   polls the backward path with `nb_transport_bw`. Responses on the
+//!   backward path are returned in the order of the requests.
+//!
+//! A model that is a simulation instance implements [TlmTarget] for
+//! its payload type, and an initiator binds a socket to the instance
+//! with [TlmSocket::instance]. A target that needs access to the
+//! simulation (for example to fire clock edges) implements
+//! [TlmTransport], and is bound to a socket with [TlmSocket::new].
+//!
+//! Fast runs may then connect an initiator directly to transaction-level
+//! models, while cycle-accurate runs connect it to pin-level models
+//! through adaptors.
+
+//a Imports
+use std::rc::Rc;
+
+use crate::simulation::{InstanceHandle, Simulation};
+use crate::traits::Component;
+
+//a TlmPhase, TlmSync
+//tp TlmPhase
+/// The phase of a non-blocking transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlmPhase {
+    /// The initiator starts the request
+    BeginReq,
+    /// The target has accepted the request
+    EndReq,
+    /// The target starts the response; the payload holds the response
+    BeginResp,
+    /// The initiator has accepted the response
+    EndResp,
+}
+
+//tp TlmSync
+/// The result of a non-blocking transport call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlmSync {
+    /// The callee has taken the transaction; the phase is unchanged
+    Accepted,
+    /// The callee has updated the phase (and possibly the payload)
+    Updated,
+    /// The transaction is complete; the payload holds the response
+    Completed,
+}
+
+//a TlmTarget, TlmTransport
+//tt TlmTarget
+/// A transaction-level target model that is a simulation instance
+pub trait TlmTarget<P> {
+    /// Perform a transaction, adding the time it takes to the delay
+    fn b_transport(&mut self, payload: &mut P, delay: &mut usize);
+
+    /// Start a transaction on the forward path
+    ///
+    /// The default performs the transaction with `b_transport`,
+    /// completing it immediately
+    fn nb_transport_fw(
+        &mut self,
+        payload: &mut P,
+        phase: &mut TlmPhase,
+        delay: &mut usize,
+    ) -> TlmSync {
+        if *phase == TlmPhase::BeginReq {
+            self.b_transport(payload, delay);
+            *phase = TlmPhase::BeginResp;
+        }
+        TlmSync::Completed
+    }
+
+    /// Poll the backward path for the response to an accepted
+    /// transaction
+    ///
+    /// The default has no accepted transactions
+    fn nb_transport_bw(&mut self) -> Option<(P, TlmPhase, usize)> {
+        None
+    }
+}
+
+//tt TlmTransport
+/// The target of a [TlmSocket], which has access to the simulation
+pub trait TlmTransport<P> {
+    /// Perform a transaction, as [TlmTarget::b_transport]
+    fn b_transport(&self, sim: &Simulation, payload: &mut P, delay: &mut usize);
+
+    /// Start a transaction on the forward path, as
+    /// [TlmTarget::nb_transport_fw]
+    fn nb_transport_fw(
+        &self,
+        sim: &Simulation,
+        payload: &mut P,
+        phase: &mut TlmPhase,
+        delay: &mut usize,
+    ) -> TlmSync;
+
+    /// Poll the backward path, as [TlmTarget::nb_transport_bw]
+    fn nb_transport_bw(&self, sim: &Simulation) -> Option<(P, TlmPhase, usize)>;
+}
+
+//a TlmInstance
+//ti TlmInstance
+/// A [TlmTransport] to a simulation instance of a [TlmTarget]
+struct TlmInstance<T> {
+    handle: InstanceHandle,
+    phantom: std::marker::PhantomData<fn() -> T>,
+}
+
+//ii TlmTransport for TlmInstance
+impl<P, T: Component + TlmTarget<P>> TlmTransport<P> for TlmInstance<T> {
+    fn b_transport(&self, sim: &Simulation, payload: &mut P, delay: &mut usize) {
+        let instances = sim.instances();
+        instances
+            .inst_mut::<T>(self.handle)
+            .b_transport(payload, delay);
+    }
+    fn nb_transport_fw(
+        &self,
+        sim: &Simulation,
+        payload: &mut P,
+        phase: &mut TlmPhase,
+        delay: &mut usize,
+    ) -> TlmSync {
+        let instances = sim.instances();
+        let sync = instances
+            .inst_mut::<T>(self.handle)
+            .nb_transport_fw(payload, phase, delay);
+        sync
+    }
+    fn nb_transport_bw(&self, sim: &Simulation) -> Option<(P, TlmPhase, usize)> {
+        let instances = sim.instances();
+        let response = instances.inst_mut::<T>(self.handle).nb_transport_bw();
+        response
+    }
+}
+
+//a TlmSocket
+//tp TlmSocket
+/// An initiator socket, bound to a target, for payloads of type P
+///
+/// Sockets may be cloned, so that more than one initiator can use
+/// the same target
+pub struct TlmSocket<'a, P> {
+    target: Rc<dyn TlmTransport<P> + 'a>,
+}
+
+//ip Clone for TlmSocket
+impl<P> Clone for TlmSocket<'_, P> {
+    fn clone(&self) -> Self {
+        Self {
+            target: self.target.clone(),
+        }
+    }
+}
+
+//ip TlmSocket
+impl<'a, P: 'a> TlmSocket<'a, P> {
+    //cp new
+    /// Create a socket bound to a target
+    pub fn new<T: TlmTransport<P> + 'a>(target: T) -> Self {
+        Self {
+            target: Rc::new(target),
+        }
+    }
+
+    //cp instance
+    /// Create a socket bound to a simulation instance of a
+    /// [TlmTarget]; the instance must be of type T
+    pub fn instance<T: Component + TlmTarget<P>>(handle: InstanceHandle) -> Self {
+        Self::new(TlmInstance::<T> {
+            handle,
+            phantom: std::marker::PhantomData,
+        })
+    }
+
+    //mp b_transport
+    /// Perform a transaction, adding the time it takes to the delay
+    pub fn b_transport(&self, sim: &Simulation, payload: &mut P, delay: &mut usize) {
+        self.target.b_transport(sim, payload, delay)
+    }
+
+    //mp nb_transport_fw
+    /// Start (or continue) a transaction on the forward path
+    pub fn nb_transport_fw(
+        &self,
+        sim: &Simulation,
+        payload: &mut P,
+        phase: &mut TlmPhase,
+        delay: &mut usize,
+    ) -> TlmSync {
+        self.target.nb_transport_fw(sim, payload, phase, delay)
+    }
+
+    //mp nb_transport_bw
+    /// Poll the backward path for the response to an accepted
+    /// transaction
+    pub fn nb_transport_bw(&self, sim: &Simulation) -> Option<(P, TlmPhase, usize)> {
+        self.target.nb_transport_bw(sim)
+    }
+}
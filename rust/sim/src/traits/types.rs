@@ -2,7 +2,7 @@
 use hgl_utils::{bit_ops, refs};
 
 use crate::data::{BitRange, BitRangeMut, U8Ops};
-use crate::values::fmt;
+use crate::values::{fmt, fmt_radix, parse_u8s};
 
 //a Traits
 //tt SimValueObject
@@ -182,7 +182,20 @@ where
     /// Format the value with a given style
     ///
     /// This is used to generate VCD file values, for example.
+    ///
+    /// Structures and arrays are formatted by field or element; bits
+    /// and vectors may be formatted in decimal, signed decimal or
+    /// octal as well as hex and binary
     fn fmt_with(&self, fmt: &mut std::fmt::Formatter, style: usize) -> Result<(), std::fmt::Error> {
+        if <Self as SimCopyValue>::NUM_SUBELEMENTS != 0 {
+            return fmt::fmt_structured(self, fmt, style);
+        }
+        if (style & (fmt::AS_DEC | fmt::AS_SIGNED | fmt::AS_OCT)) != 0
+            && (<Self as SimCopyValue>::FMT_HEX || <Self as SimCopyValue>::FMT_BIN)
+        {
+            let data = unsafe { refs::as_u8s(self) };
+            return fmt_radix(fmt, data, <Self as SimCopyValue>::BIT_WIDTH, style);
+        }
         let mut ascii_store = [b'0'; fmt::MAX_STRING_LENGTH];
         let mut ascii = ascii_store.as_mut_slice();
         let mut hdr_char = 'b';
@@ -417,6 +430,22 @@ where
         ((f() & 1) != 0).into()
     }
 
+    //cp parse_str
+    /// Create from text, which may be `true` or `false`, or a value
+    /// of `0` or `1` in any form accepted by [parse_u8s] (such as
+    /// `1b1`)
+    fn parse_str(text: &str) -> Result<Self, String> {
+        match text.trim() {
+            "true" => Ok(true.into()),
+            "false" => Ok(false.into()),
+            _ => {
+                let mut data = [0];
+                parse_u8s(text, 1, &mut data)?;
+                Ok((data[0] != 0).into())
+            }
+        }
+    }
+
     #[inline]
     fn is_true(&self) -> bool {
         (*self).into()
@@ -472,6 +501,19 @@ pub trait SimBv:
         BitRangeMut::of_u8s(s.as_u8s_mut(), 0, nb).set_rt(br);
     }
 
+    //cp parse_str
+    /// Create from text, in any form accepted by [parse_u8s] - such
+    /// as `1234`, `0x4d2`, `16h04d2` or `0b100_1101_0010`
+    ///
+    /// This returns an error if the text is malformed, if it provides
+    /// a width that does not match, or if the value is too large
+    fn parse_str(text: &str) -> Result<Self, String> {
+        let mut s = Self::default();
+        let nb = s.num_bits();
+        parse_u8s(text, nb, s.as_u8s_mut())?;
+        Ok(s)
+    }
+
     //ap num_bits - return size of the data in number of bits
     fn num_bits(&self) -> usize;
 
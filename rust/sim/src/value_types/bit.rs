@@ -181,3 +181,11 @@ impl SimCopyValue for Bit {
 
 //ip SimBit for Bit
 impl SimBit for Bit {}
+
+//ip FromStr for Bit
+impl std::str::FromStr for Bit {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        <Self as SimBit>::parse_str(s)
+    }
+}
@@ -384,6 +384,17 @@ where
     }
 }
 
+//ip FromStr for Bv
+impl<const NB: usize> std::str::FromStr for Bv<NB>
+where
+    BvN<{ NB }>: IsBv,
+{
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        <Self as SimBv>::parse_str(s)
+    }
+}
+
 //ip From<u64> for Bv
 impl<const NB: usize> From<u64> for Bv<NB>
 where
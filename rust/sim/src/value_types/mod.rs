@@ -2,6 +2,7 @@ mod bit;
 mod bv;
 mod bv_sim64;
 mod bv_sim64_array;
+mod sim_struct;
 mod std;
 
 pub use bit::Bit;
@@ -0,0 +1,36 @@
+//a Macro impl_sim_struct
+//mp impl_sim_struct
+/// Implement [SimCopyValue](crate::prelude::sim::SimCopyValue) for a
+/// structure whose fields are all [SimCopyValue](crate::prelude::sim::SimCopyValue)
+///
+/// The fields are listed with their types; they are provided as the
+/// subelements of the value, so that the structure can be formatted
+/// by field (and recorded in waveforms by field).
+///
+/// The structure must also derive (or implement) Copy, Default,
+/// PartialEq, Eq, Hash, Serialize and Deserialize.
+///
+/// ```ignore
+/// impl_sim_struct!(t_apb_response { prdata: Bv<32>, pready: Bit, perr: Bit });
+/// ```
+#[macro_export]
+macro_rules! impl_sim_struct {
+    {$t:ty { $($f:ident : $ft:ty),* $(,)? }} => {
+        impl $crate::prelude::sim::SimCopyValue for $t {
+            const BIT_WIDTH: usize =
+                0 $(+ <$ft as $crate::prelude::sim::SimCopyValue>::BIT_WIDTH)*;
+            const NYBBLE_WIDTH: usize = Self::BIT_WIDTH.div_ceil(4);
+            const BYTE_WIDTH: usize = Self::BIT_WIDTH.div_ceil(8);
+            const NUM_SUBELEMENTS: usize = [$(stringify!($f)),*].len();
+
+            fn get_subelement(
+                &self,
+                n: usize,
+            ) -> Option<(&str, &dyn $crate::prelude::sim::SimValueObject)> {
+                let fields: &[(&str, &dyn $crate::prelude::sim::SimValueObject)] =
+                    &[$((stringify!($f), &self.$f)),*];
+                fields.get(n).copied()
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
 mod sim_format_value;
+mod sim_parse_value;
 mod sim_value_ref;
 
 pub mod fmt {
@@ -7,8 +8,20 @@ pub mod fmt {
     pub const AS_HEX: usize = 1;
     pub const AS_BIN: usize = 2;
     pub const HDR: usize = 4;
+    /// Unsigned decimal; this takes precedence over hex and binary
+    pub const AS_DEC: usize = 8;
+    /// Signed (two's complement) decimal; this takes precedence over
+    /// the other styles. This is for display only; a negative value
+    /// cannot be parsed back
+    pub const AS_SIGNED: usize = 16;
+    /// Octal; this takes precedence over hex and binary
+    pub const AS_OCT: usize = 32;
     pub const FULL: usize = AS_BIN | HDR | AS_HEX;
+
+    pub use super::sim_format_value::fmt_structured;
 }
 
-pub use sim_format_value::SimFormatValue;
+pub(crate) use sim_format_value::fmt_radix;
+pub use sim_format_value::{SimFormatObject, SimFormatValue};
+pub use sim_parse_value::parse_u8s;
 pub use sim_value_ref::{SimValueRef, SimValueRefMut};
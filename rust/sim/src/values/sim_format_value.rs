@@ -1,5 +1,116 @@
 //a Imports
-use crate::traits::SimCopyValue;
+use crate::traits::{SimCopyValue, SimValueObject};
+use crate::values::fmt;
+
+//a Formatting functions
+//fp fmt_structured
+/// Format a value with a style, using the fields of structures and
+/// the elements of arrays
+///
+/// A structure is formatted as `{paddr: 32h00000010, penable: 1, ...}`
+/// and an array as `[4h1, 4h2, ...]`; single bits within these are
+/// formatted without a header. Values without subelements use their
+/// own `fmt_with`.
+pub fn fmt_structured(
+    value: &dyn SimValueObject,
+    f: &mut std::fmt::Formatter,
+    style: usize,
+) -> Result<(), std::fmt::Error> {
+    let n = value.num_subelements();
+    if n == 0 {
+        return value.fmt_with(f, style);
+    }
+    let is_array = value
+        .get_subelement(0)
+        .is_some_and(|(name, _)| name.is_empty());
+    f.write_str(if is_array { "[" } else { "{" })?;
+    for i in 0..n {
+        let Some((name, v)) = value.get_subelement(i) else {
+            continue;
+        };
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        if !is_array {
+            write!(f, "{name}: ")?;
+        }
+        if v.bit_width() == 1 && v.num_subelements() == 0 {
+            // A single bit is the same in hex or binary, and is shown
+            // without a header
+            fmt_structured(v, f, (style & !fmt::HDR) | fmt::AS_BIN)?;
+        } else {
+            fmt_structured(v, f, style)?;
+        }
+    }
+    f.write_str(if is_array { "]" } else { "}" })
+}
+
+//fp fmt_radix
+/// Format little-endian data of a given bit width as decimal, signed
+/// decimal, or octal, depending on the style
+///
+/// With a header, a signed negative value is formatted as (for
+/// example) `-8d5`; this is for display only, as negative values are
+/// not accepted by [crate::values::parse_u8s]
+pub(crate) fn fmt_radix(
+    f: &mut std::fmt::Formatter,
+    data: &[u8],
+    bit_width: usize,
+    style: usize,
+) -> Result<(), std::fmt::Error> {
+    let mut v: Vec<u8> = data.iter().take(bit_width.div_ceil(8)).copied().collect();
+    if !bit_width.is_multiple_of(8) {
+        if let Some(top) = v.last_mut() {
+            *top &= (1 << (bit_width % 8)) - 1;
+        }
+    }
+    let negative = (style & fmt::AS_SIGNED) != 0
+        && bit_width > 0
+        && (v[(bit_width - 1) / 8] >> ((bit_width - 1) % 8)) & 1 != 0;
+    if negative {
+        // Two's complement negate within bit_width; the value is then
+        // the magnitude
+        let mut carry = 1;
+        for d in v.iter_mut() {
+            let x = (!*d as u16) + carry;
+            *d = x as u8;
+            carry = x >> 8;
+        }
+        if !bit_width.is_multiple_of(8) {
+            if let Some(top) = v.last_mut() {
+                *top &= (1 << (bit_width % 8)) - 1;
+            }
+        }
+    }
+    let (radix, hdr_char) = {
+        if (style & (fmt::AS_SIGNED | fmt::AS_DEC)) != 0 {
+            (10, 'd')
+        } else {
+            (8, 'o')
+        }
+    };
+    let mut digits = vec![];
+    loop {
+        let mut rem = 0_u32;
+        for d in v.iter_mut().rev() {
+            let x = (rem << 8) | (*d as u32);
+            *d = (x / radix) as u8;
+            rem = x % radix;
+        }
+        digits.push(b'0' + rem as u8);
+        if v.iter().all(|d| *d == 0) {
+            break;
+        }
+    }
+    digits.reverse();
+    let digits = unsafe { std::str::from_utf8_unchecked(&digits) };
+    let sign = if negative { "-" } else { "" };
+    if (style & fmt::HDR) == 0 {
+        write!(f, "{sign}{digits}")
+    } else {
+        write!(f, "{sign}{bit_width}{hdr_char}{digits}")
+    }
+}
 
 //a SimFormatValue
 //tp SimFormatValue
@@ -24,6 +135,33 @@ impl<'a, T: SimCopyValue> SimFormatValue<'a, T> {
 //ip Display for SimFormatValue
 impl<T: SimCopyValue> std::fmt::Display for SimFormatValue<'_, T> {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        self.value.fmt_with(fmt, self.style)
+        fmt_structured(self.value, fmt, self.style)
+    }
+}
+
+//a SimFormatObject
+//tp SimFormatObject
+/// A wrapper to permit formatting of a dyn [SimValueObject] as a
+/// string, such as the state data of an instance
+///
+/// Structures and arrays are formatted using [fmt_structured]
+///
+/// SimFormatObject::new(state.sim_value(), fmt::AS_HEX | fmt::HDR)
+pub struct SimFormatObject<'a> {
+    value: &'a dyn SimValueObject,
+    style: usize,
+}
+
+//ip SimFormatObject
+impl<'a> SimFormatObject<'a> {
+    pub fn new(value: &'a dyn SimValueObject, style: usize) -> Self {
+        Self { value, style }
+    }
+}
+
+//ip Display for SimFormatObject
+impl std::fmt::Display for SimFormatObject<'_> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        fmt_structured(self.value, fmt, self.style)
     }
 }
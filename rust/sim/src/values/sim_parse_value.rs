@@ -0,0 +1,105 @@
+//a Documentation
+//! Parsing of simulation values from text
+//!
+//! This is the inverse of formatting a value with `fmt_with`. The
+//! text may be:
+//!
+//! * decimal, such as `1234`
+//!
+//! * hex, binary or octal with a `0x`, `0b` or `0o` prefix, such as
+//!   `0x4d2`
+//!
+//! * the `HDR` form produced by formatting, with a width and a radix
+//!   character of `h`, `b`, `o` or `d`, such as `16h04d2` or `4b1010`;
+//!   the width must match the width of the value
+//!
+//! Underscores may be used to separate digits, and the value must fit
+//! in the width of the value being parsed.
+
+//a parse_u8s
+//fp parse_u8s
+/// Parse text into a little-endian slice of u8 holding a value of
+/// `bit_width` bits
+///
+/// The slice is zeroed first; it must be large enough to hold the
+/// bit width.
+pub fn parse_u8s(text: &str, bit_width: usize, data: &mut [u8]) -> Result<(), String> {
+    let text = text.trim();
+    let (width, radix, digits) = split_radix(text)?;
+    if let Some(width) = width {
+        if width != bit_width {
+            return Err(format!(
+                "Width of '{text}' does not match value width of {bit_width}"
+            ));
+        }
+    }
+
+    data.fill(0);
+    let mut num_digits = 0;
+    for c in digits.chars() {
+        if c == '_' {
+            continue;
+        }
+        let Some(mut carry) = c.to_digit(radix) else {
+            return Err(format!("Bad digit '{c}' in '{text}'"));
+        };
+        num_digits += 1;
+        for d in data.iter_mut() {
+            let v = (*d as u32) * radix + carry;
+            *d = v as u8;
+            carry = v >> 8;
+        }
+        if carry != 0 {
+            return Err(format!("Value '{text}' is too large for {bit_width} bits"));
+        }
+    }
+    if num_digits == 0 {
+        return Err(format!("No digits in value '{text}'"));
+    }
+
+    for (i, d) in data.iter().enumerate() {
+        let lsb = i * 8;
+        let excess = {
+            if lsb >= bit_width {
+                *d
+            } else if bit_width - lsb < 8 {
+                *d >> (bit_width - lsb)
+            } else {
+                0
+            }
+        };
+        if excess != 0 {
+            return Err(format!("Value '{text}' is too large for {bit_width} bits"));
+        }
+    }
+    Ok(())
+}
+
+//fi split_radix
+/// Split text into an optional width, the radix, and the digits
+fn split_radix(text: &str) -> Result<(Option<usize>, u32, &str), String> {
+    for (prefix, radix) in [("0x", 16), ("0b", 2), ("0o", 8)] {
+        if let Some(digits) = text.strip_prefix(prefix) {
+            return Ok((None, radix, digits));
+        }
+    }
+    let Some(n) = text.find(|c: char| !c.is_ascii_digit()) else {
+        return Ok((None, 10, text));
+    };
+    if n == 0 || text.as_bytes()[n] == b'_' {
+        return Ok((None, 10, text));
+    }
+    let radix = match text.as_bytes()[n] {
+        b'h' => 16,
+        b'b' => 2,
+        b'o' => 8,
+        b'd' => 10,
+        _ => {
+            return Err(format!("Bad value '{text}'"));
+        }
+    };
+    let width = text[0..n]
+        .parse::<usize>()
+        .map_err(|e| format!("Bad width in '{text}': {e}"))?;
+    Ok((Some(width), radix, &text[n + 1..]))
+}
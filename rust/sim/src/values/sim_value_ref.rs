@@ -1,5 +1,25 @@
 //a Imports
 use crate::traits::{SimBit, SimBv, SimCopyValue, SimValueObject};
+use crate::values::parse_u8s;
+
+//a Support functions
+//fi get_u64
+/// Get a bit-copyable value of at most 64 bits as a u64
+fn get_u64(value: &dyn SimValueObject) -> Option<u64> {
+    let bit_width = value.bit_width();
+    if bit_width == 0 || bit_width > 64 {
+        return None;
+    }
+    let data = value.try_as_u8s()?;
+    let mut v = 0;
+    for (i, d) in data.iter().enumerate().take(8) {
+        v |= (*d as u64) << (i * 8);
+    }
+    if bit_width < 64 {
+        v &= (1 << bit_width) - 1;
+    }
+    Some(v)
+}
 
 //a SimValueRef
 //tp SimValueRef
@@ -49,6 +69,10 @@ impl<'a> SimValueRef<'a> {
     pub fn as_any(&self) -> &dyn std::any::Any {
         self.value.as_any()
     }
+    /// Get the value as a u64, if it is bit-copyable and at most 64 bits wide
+    pub fn get_u64(&self) -> Option<u64> {
+        get_u64(self.value)
+    }
 }
 
 #[derive(Debug)]
@@ -90,6 +114,53 @@ impl<'a> SimValueRefMut<'a> {
         size.copy_from_slice(data);
         true
     }
+    /// Set the value from a u64, truncated to the bit width of the
+    /// value; returns false if the value is not bit-copyable
+    pub fn set_u64(&mut self, value: u64) -> bool {
+        let bit_width = self.value.bit_width();
+        let Some(n) = self.value.try_as_u8s().map(|d| d.len()) else {
+            return false;
+        };
+        let mut data = vec![0; n];
+        for (i, d) in data.iter_mut().enumerate().take(8) {
+            let lsb = i * 8;
+            if lsb >= bit_width {
+                break;
+            }
+            *d = (value >> lsb) as u8;
+            if bit_width - lsb < 8 {
+                *d &= (1 << (bit_width - lsb)) - 1;
+            }
+        }
+        self.set_u8s(&data)
+    }
+    /// Get the value as a u64, if it is bit-copyable and at most 64 bits wide
+    pub fn get_u64(&self) -> Option<u64> {
+        get_u64(self.value)
+    }
+    /// Set the value from text, in any form accepted by [parse_u8s]
+    /// (such as `16h1234`)
+    ///
+    /// The value must be a bit-copyable bit or bit vector; the value
+    /// is unchanged if an error is returned
+    pub fn set_from_str(&mut self, text: &str) -> Result<(), String> {
+        let bit_width = self.value.bit_width();
+        if bit_width == 0 || self.value.num_subelements() != 0 {
+            return Err(format!("Cannot set a structured value from '{text}'"));
+        }
+        let Some(n) = self.value.try_as_u8s().map(|d| d.len()) else {
+            return Err(format!(
+                "Cannot set a value that is not bit-copyable from '{text}'"
+            ));
+        };
+        let mut data = vec![0; n];
+        parse_u8s(text, bit_width, &mut data)?;
+        if self.set_u8s(&data) {
+            Ok(())
+        } else {
+            Err(format!("Failed to set value from '{text}'"))
+        }
+    }
     pub fn sim_value(&self) -> &dyn SimValueObject {
         self.value
     }
@@ -0,0 +1,207 @@
+//a Documentation
+//! Waveform generation for simulations
+//!
+//! A [Vcd] is created for a prepared [Simulation]; it records every
+//! piece of exposed state of every instance that can be bit-copied,
+//! and writes a Value Change Dump file header. Structures and arrays
+//! are recorded as one signal per field or element, with names such
+//! as `req.paddr` or `data[3]`. Each time the
+//! simulation moves on, the [Vcd] should be sampled, and the values
+//! that have changed since the last sample are written out.
+
+//a Imports
+use std::io::Write;
+
+use crate::simulation::{InstanceHandle, SimStateIndex, Simulation};
+use crate::traits::SimValueObject;
+use crate::values::{fmt, SimFormatObject};
+
+//a VcdSignal
+//ti VcdSignal
+/// A signal that is being recorded in the VCD file
+struct VcdSignal {
+    /// The VCD short identifier
+    id: String,
+    handle: InstanceHandle,
+    state_index: SimStateIndex,
+    /// The path through the subelements of the state to the value
+    subelements: Vec<usize>,
+    bit_width: usize,
+    /// The last value written, if any
+    last: Option<Vec<u8>>,
+}
+
+//ii VcdSignal
+impl VcdSignal {
+    //mp value
+    /// Get the value of the signal from the state
+    fn value<'a>(&self, state: &'a dyn SimValueObject) -> Option<&'a dyn SimValueObject> {
+        let mut value = state;
+        for i in self.subelements.iter() {
+            value = value.get_subelement(*i)?.1;
+        }
+        Some(value)
+    }
+}
+
+//fi leaf_values
+/// Find the bit-copyable leaf values within a value, with their names
+/// and paths through the subelements
+fn leaf_values(
+    value: &dyn SimValueObject,
+    name: &str,
+    path: &mut Vec<usize>,
+    leaves: &mut Vec<(String, Vec<usize>, usize)>,
+) {
+    if value.num_subelements() == 0 {
+        let bit_width = value.bit_width();
+        if bit_width != 0 && bit_width < fmt::MAX_STRING_LENGTH && value.try_as_u8s().is_some() {
+            leaves.push((name.to_string(), path.clone(), bit_width));
+        }
+        return;
+    }
+    for i in 0..value.num_subelements() {
+        let Some((sub_name, sub_value)) = value.get_subelement(i) else {
+            continue;
+        };
+        let sub_name = {
+            if sub_name.is_empty() {
+                format!("{name}[{i}]")
+            } else {
+                format!("{name}.{sub_name}")
+            }
+        };
+        path.push(i);
+        leaf_values(sub_value, &sub_name, path, leaves);
+        path.pop();
+    }
+}
+
+//fi vcd_id
+/// Generate the VCD identifier for the nth signal, using the
+/// printable ASCII characters
+fn vcd_id(mut n: usize) -> String {
+    let mut s = String::new();
+    loop {
+        s.push((b'!' + (n % 94) as u8) as char);
+        n /= 94;
+        if n == 0 {
+            break;
+        }
+        n -= 1;
+    }
+    s
+}
+
+//a Vcd
+//tp Vcd
+/// A Value Change Dump writer for a [Simulation]
+pub struct Vcd<W: Write> {
+    writer: W,
+    signals: Vec<VcdSignal>,
+}
+
+//ip Vcd
+impl<W: Write> Vcd<W> {
+    //cp new
+    /// Create a new [Vcd] for a simulation, writing the header to the
+    /// writer
+    ///
+    /// The simulation must have been prepared
+    pub fn new(mut writer: W, sim: &Simulation) -> std::io::Result<Self> {
+        let instances = sim.instances();
+        let mut signals = vec![];
+        writeln!(writer, "$timescale 1 ns $end")?;
+        let mut scope: Vec<String> = vec![];
+        for (path, handle, state_index) in sim.state_paths() {
+            let Some(s) = instances.instance(handle).borrow_sim() else {
+                continue;
+            };
+            let Some(value) = s.try_state_data(state_index) else {
+                continue;
+            };
+            let mut path: Vec<String> = path.split('.').map(|s| s.to_string()).collect();
+            let name = path.pop().unwrap();
+            let mut leaves = vec![];
+            leaf_values(value.sim_value(), &name, &mut vec![], &mut leaves);
+            if leaves.is_empty() {
+                continue;
+            }
+            let common = scope
+                .iter()
+                .zip(path.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            while scope.len() > common {
+                scope.pop();
+                writeln!(writer, "$upscope $end")?;
+            }
+            for p in &path[common..] {
+                writeln!(writer, "$scope module {p} $end")?;
+                scope.push(p.clone());
+            }
+            for (name, subelements, bit_width) in leaves {
+                let id = vcd_id(signals.len());
+                writeln!(writer, "$var wire {bit_width} {id} {name} $end")?;
+                signals.push(VcdSignal {
+                    id,
+                    handle,
+                    state_index,
+                    subelements,
+                    bit_width,
+                    last: None,
+                });
+            }
+        }
+        for _ in scope {
+            writeln!(writer, "$upscope $end")?;
+        }
+        writeln!(writer, "$enddefinitions $end")?;
+        Ok(Self { writer, signals })
+    }
+
+    //mp sample
+    /// Sample the values of all the signals at the current simulation
+    /// time, and write out those that have changed
+    pub fn sample(&mut self, sim: &Simulation) -> std::io::Result<()> {
+        let instances = sim.instances();
+        writeln!(self.writer, "#{}", sim.time())?;
+        for signal in self.signals.iter_mut() {
+            let Some(s) = instances.instance(signal.handle).borrow_sim() else {
+                continue;
+            };
+            let Some(state) = s.try_state_data(signal.state_index) else {
+                continue;
+            };
+            let Some(value) = signal.value(state.sim_value()) else {
+                continue;
+            };
+            let Some(data) = value.try_as_u8s() else {
+                continue;
+            };
+            if signal.last.as_deref() == Some(data) {
+                continue;
+            }
+            signal.last = Some(data.to_vec());
+            let v = SimFormatObject::new(value, fmt::AS_BIN);
+            if signal.bit_width == 1 {
+                writeln!(self.writer, "{v}{}", signal.id)?;
+            } else {
+                writeln!(self.writer, "b{v} {}", signal.id)?;
+            }
+        }
+        Ok(())
+    }
+
+    //mp flush
+    /// Flush the underlying writer
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    //dp into_inner
+    /// Drop the [Vcd], returning the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
@@ -0,0 +1,53 @@
+use std::num::Wrapping;
+
+use hgl_sim::prelude::sim::*;
+
+#[test]
+fn format_radix() -> Result<(), String> {
+    let v = Bv::<16>::of_u64(4660);
+    assert_eq!(SimFormatValue::value_string(&v, fmt::AS_DEC), "4660");
+    assert_eq!(
+        SimFormatValue::value_string(&v, fmt::AS_DEC | fmt::HDR),
+        "16d4660"
+    );
+    assert_eq!(
+        SimFormatValue::value_string(&v, fmt::AS_OCT | fmt::HDR),
+        "16o11064"
+    );
+    assert_eq!(SimFormatValue::value_string(&v, fmt::AS_SIGNED), "4660");
+
+    let v = Bv::<8>::of_u64(0xfb);
+    assert_eq!(SimFormatValue::value_string(&v, fmt::AS_DEC), "251");
+    assert_eq!(SimFormatValue::value_string(&v, fmt::AS_SIGNED), "-5");
+    assert_eq!(
+        SimFormatValue::value_string(&v, fmt::AS_SIGNED | fmt::HDR),
+        "-8d5"
+    );
+    assert_eq!(
+        SimFormatValue::value_string(&Bv::<8>::of_u64(0x80), fmt::AS_SIGNED),
+        "-128"
+    );
+    assert_eq!(
+        SimFormatValue::value_string(&Bv::<4>::default(), fmt::AS_SIGNED | fmt::HDR),
+        "4d0"
+    );
+    assert_eq!(SimFormatValue::value_string(&Bit::T, fmt::AS_DEC), "1");
+    assert_eq!(
+        SimFormatValue::value_string(&Wrapping(u128::MAX), fmt::AS_DEC),
+        "340282366920938463463374607431768211455"
+    );
+    assert_eq!(
+        SimFormatValue::value_string(&Wrapping(u128::MAX), fmt::AS_SIGNED),
+        "-1"
+    );
+
+    // Decimal and octal formatted values parse back to the same value
+    for i in 0..128 {
+        let v = Bv::<7>::of_u64(i);
+        for style in [fmt::AS_DEC, fmt::AS_OCT] {
+            let s = SimFormatValue::value_string(&v, style | fmt::HDR);
+            assert_eq!(s.parse::<Bv<7>>()?, v, "Round trip of {s}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use std::num::Wrapping;
+
+use hgl_sim::prelude::component::*;
+
+#[test]
+fn parse_bv() -> Result<(), String> {
+    type T = Bv<16>;
+    for s in [
+        "4660",
+        "0x1234",
+        "0b0001_0010_0011_0100",
+        "0o11064",
+        "16h1234",
+        "16h12_34",
+        "16d4660",
+        " 4_660 ",
+    ] {
+        assert_eq!(s.parse::<T>()?, T::of_u64(0x1234), "Parsing {s}");
+    }
+    assert_eq!("0xffff".parse::<T>()?, T::of_u64(0xffff));
+    assert_eq!("4b1010".parse::<Bv<4>>()?, Bv::<4>::of_u64(10));
+
+    for s in [
+        "", "0x", "16h", "0x10000", "65536", "8h12", "16q1234", "12z", "0x12g", "-1",
+    ] {
+        assert!(s.parse::<T>().is_err(), "Parsing '{s}' should fail");
+    }
+    assert!("4b10000".parse::<Bv<4>>().is_err());
+    assert!("0x10".parse::<Bv<4>>().is_err());
+    Ok(())
+}
+
+#[test]
+fn parse_round_trip() -> Result<(), String> {
+    for i in [0_u64, 1, 0x55, 0x7f] {
+        let v = Bv::<7>::of_u64(i);
+        for style in [fmt::AS_BIN, fmt::AS_HEX] {
+            let s = SimFormatValue::value_string(&v, style | fmt::HDR);
+            assert_eq!(s.parse::<Bv<7>>()?, v, "Round trip of {s}");
+        }
+    }
+    // Values wider than 64 bits
+    let v = "128hffeeddccbbaa99887766554433221100".parse::<Bv<128>>()?;
+    assert_eq!(
+        SimFormatValue::value_string(&v, fmt::AS_HEX | fmt::HDR),
+        "128hffeeddccbbaa99887766554433221100"
+    );
+    let v = "340282366920938463463374607431768211455".parse::<Bv<128>>()?;
+    assert_eq!(v, !Bv::<128>::default());
+    assert!("340282366920938463463374607431768211456"
+        .parse::<Bv<128>>()
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn parse_bit_and_wrapping() -> Result<(), String> {
+    assert_eq!("1".parse::<Bit>()?, Bit::T);
+    assert_eq!("1b0".parse::<Bit>()?, Bit::F);
+    assert_eq!("true".parse::<Bit>()?, Bit::T);
+    assert!("2".parse::<Bit>().is_err());
+    assert!("2b01".parse::<Bit>().is_err());
+
+    assert_eq!(<Wrapping<u8> as SimBv>::parse_str("8hff")?, Wrapping(255));
+    assert!(<Wrapping<u8> as SimBv>::parse_str("256").is_err());
+    assert_eq!(
+        <Wrapping<u64> as SimBv>::parse_str("0xfedc_ba98_7654_3210")?,
+        Wrapping(0xfedc_ba98_7654_3210)
+    );
+    assert_eq!(
+        <Wrapping<u128> as SimBv>::parse_str("128h1")?,
+        Wrapping(1_u128)
+    );
+    Ok(())
+}
+
+#[test]
+fn parse_set_from_str() -> Result<(), String> {
+    let mut v = Bv::<12>::default();
+    SimValueRefMut::of(&mut v).set_from_str("12habc")?;
+    assert_eq!(v, Bv::<12>::of_u64(0xabc));
+    assert!(SimValueRefMut::of(&mut v).set_from_str("0x1000").is_err());
+    assert_eq!(v, Bv::<12>::of_u64(0xabc), "Value unchanged on error");
+
+    let mut b = Bit::F;
+    SimValueRefMut::of(&mut b).set_from_str("1")?;
+    assert_eq!(b, Bit::T);
+
+    let mut w = Wrapping(0_u32);
+    SimValueRefMut::of(&mut w).set_from_str("32h1234_5678")?;
+    assert_eq!(w, Wrapping(0x1234_5678));
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::cell::{Cell, RefCell};
+
+use hgl_sim::prelude::sim::*;
+
+#[test]
+fn testbench() -> Result<(), String> {
+    let mut sim = Simulation::new();
+    let fast = sim.add_clock("fast", 0, 2, 1)?;
+    let slow = sim.add_clock("slow", 1, 6, 0)?;
+    sim.prepare_simulation();
+    sim.start(true)?;
+
+    let events = RefCell::new(vec![]);
+    let flag = Cell::new(false);
+    let log = |name: &'static str| events.borrow_mut().push((sim.time(), name));
+
+    let mut testbench = Testbench::new();
+    testbench.spawn(async {
+        sim.posedge(fast).await;
+        log("fast");
+        sim.negedge(fast).await;
+        log("fast negedge");
+        sim.cycles(fast, 3).await;
+        log("fast 3");
+        flag.set(true);
+        sim.cycles(fast, 0).await;
+        log("fast 0");
+    });
+    testbench.spawn(async {
+        sim.posedge(slow).await;
+        log("slow");
+        sim.until(|_| flag.get()).await;
+        log("flag");
+        sim.until(|sim| sim.time() >= 20).await;
+        log("time");
+    });
+    let edges = testbench.run(&sim, 100)?;
+    assert!(testbench.is_empty());
+    assert_eq!(sim.edge_count(fast, true), 11);
+    assert_eq!(sim.edge_count(slow, false), 0);
+    assert_eq!(edges, 21);
+
+    // Tasks run in the order they were spawned, between edges; the
+    // flag set by the first task is seen by the second in the same
+    // pass
+    assert_eq!(
+        events.take(),
+        vec![
+            (0, "fast"),
+            (1, "fast negedge"),
+            (1, "slow"),
+            (6, "fast 3"),
+            (6, "fast 0"),
+            (6, "flag"),
+            (20, "time"),
+        ]
+    );
+
+    testbench.spawn(sim.cycles(slow, 10));
+    assert!(testbench.run(&sim, 5).is_err());
+    assert!(!testbench.is_empty());
+    testbench.run(&sim, 100)?;
+    sim.stop()?;
+    Ok(())
+}
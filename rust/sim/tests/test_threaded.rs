@@ -0,0 +1,108 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use hgl_sim::prelude::component::*;
+
+//tp Lfsr
+/// A threaded model that steps an LFSR once for every request,
+/// mixing in the request data
+#[derive(Default)]
+struct Lfsr {
+    state: u32,
+    edges: usize,
+    events: Vec<&'static str>,
+}
+
+//ip ThreadedModel for Lfsr
+impl ThreadedModel for Lfsr {
+    type Request = u32;
+    type Response = (usize, u32);
+    fn start(&mut self) {
+        self.events.push("start");
+        self.state = 1;
+    }
+    fn pause(&mut self) {
+        self.events.push("pause");
+    }
+    fn resume(&mut self) {
+        self.events.push("resume");
+    }
+    fn stop(&mut self) {
+        self.events.push("stop");
+    }
+    fn edge(&mut self, mask: SimEdgeMask, data: u32) -> (usize, u32) {
+        assert!(!mask.is_none());
+        self.edges += 1;
+        let bit = (self.state ^ (self.state >> 2) ^ (self.state >> 3) ^ (self.state >> 5)) & 1;
+        self.state = ((self.state >> 1) | (bit << 31)) ^ data;
+        (self.edges, self.state)
+    }
+}
+
+/// The responses of a run, and the events seen by the model
+type RunResult = (Vec<(usize, u32)>, Vec<&'static str>);
+
+//fi run
+/// Run a simulation whose driver exchanges data with an [Lfsr] on
+/// every clock edge, pausing it part way through, and return the
+/// responses
+fn run() -> Result<RunResult, String> {
+    let mut sim = Simulation::new();
+    let clk = sim.add_clock("clk", 0, 1, 0)?;
+    sim.prepare_simulation();
+
+    let model = Model::new(Lfsr::default());
+    assert_eq!(model.state(), ModelState::Idle);
+    model.start(false);
+    model.wait_for_thread_ready();
+    assert_eq!(model.state(), ModelState::Paused);
+    assert!(!model.post(SimEdgeMask::none().add_posedge(0), 0));
+    model.resume();
+
+    let responses = Rc::new(RefCell::new(vec![]));
+    let m = model.clone();
+    let r = responses.clone();
+    let mask = SimEdgeMask::none().add_posedge(0);
+    sim.add_driver(clk, move |sim| {
+        // Post the request, do the simulation work for the edge, and
+        // then collect the response
+        if m.post(mask, sim.time() as u32) {
+            r.borrow_mut().push(m.collect().unwrap());
+        }
+    });
+    sim.start(true)?;
+    for _ in 0..100 {
+        sim.fire_next_edges();
+    }
+    model.pause();
+    assert_eq!(model.state(), ModelState::Paused);
+    for _ in 0..100 {
+        sim.fire_next_edges();
+    }
+    model.resume();
+    assert_eq!(model.edge(mask, 0).map(|(n, _)| n), Some(101));
+    model.stop();
+    assert_eq!(model.state(), ModelState::Stopped);
+    assert_eq!(model.edge(mask, 0), None);
+    sim.stop()?;
+
+    let responses = responses.take();
+    let events = model.get(|m| m.events.clone());
+    Ok((responses, events))
+}
+
+#[test]
+fn threaded_model() -> Result<(), String> {
+    let (responses, events) = run()?;
+    assert_eq!(
+        events,
+        vec!["start", "resume", "pause", "resume", "pause", "stop"]
+    );
+    assert_eq!(responses.len(), 100, "One response per rising edge");
+    for (i, (n, _)) in responses.iter().enumerate() {
+        assert_eq!(*n, i + 1);
+    }
+    let (again, _) = run()?;
+    assert_eq!(responses, again, "Runs are deterministic");
+    Ok(())
+}
@@ -0,0 +1,252 @@
+//a Documentation
+//! Command-line simulation runner
+//!
+//! Loads a topology file (see [hgl::topology]), runs the simulation
+//! for a number of cycles of a clock or until a given time, and
+//! optionally writes a VCD waveform file and a checkpoint at the end
+//! of the run. The final state of selected signals is printed, and
+//! the process exits with a failure code if any of the assertions in
+//! the topology failed during the run, or if any of the expected
+//! values do not match.
+//!
+//! With `--interactive` the simulation is instead driven by debugger
+//! commands read from stdin (see [hgl::sim::prelude::sim::Debugger]);
+//! with `--serve` it is driven by JSON-RPC requests on a socket (see
+//! [hgl::sim::prelude::sim::RpcServer]); assertion failures are
+//! reported, and set the exit code, once the server finishes.
+
+//a Imports
+use std::process::ExitCode;
+
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use hgl::indexed_vec::Idx;
+use hgl::sim::prelude::sim::*;
+use hgl::topology::{ComponentRegistry, Topology};
+
+//a Command line
+//fi command
+fn command() -> Command {
+    Command::new("hgl-sim")
+        .about("Run a simulation described by a topology file")
+        .arg(
+            Arg::new("topology")
+                .required(true)
+                .help("JSON topology file describing the simulation"),
+        )
+        .arg(
+            Arg::new("cycles")
+                .long("cycles")
+                .short('n')
+                .value_parser(value_parser!(usize))
+                .help("Number of cycles of the clock to run for"),
+        )
+        .arg(
+            Arg::new("time")
+                .long("time")
+                .short('t')
+                .value_parser(value_parser!(usize))
+                .help("Simulation time to run until"),
+        )
+        .arg(
+            Arg::new("clock")
+                .long("clock")
+                .help("Clock whose posedges are counted as cycles; defaults to the first clock"),
+        )
+        .arg(
+            Arg::new("vcd")
+                .long("vcd")
+                .help("Write a VCD waveform file"),
+        )
+        .arg(
+            Arg::new("checkpoint")
+                .long("checkpoint")
+                .help("Write a JSON checkpoint of the state at the end of the run"),
+        )
+        .arg(
+            Arg::new("restore")
+                .long("restore")
+                .help("Restore state from a JSON checkpoint at the start of the run"),
+        )
+        .arg(
+            Arg::new("print")
+                .long("print")
+                .short('p')
+                .action(ArgAction::Append)
+                .help("Print the final value of a signal (hierarchical name); may be repeated"),
+        )
+        .arg(
+            Arg::new("interactive")
+                .long("interactive")
+                .short('i')
+                .action(ArgAction::SetTrue)
+                .help("Run an interactive debugger shell on stdin instead of a batch run"),
+        )
+        .arg(Arg::new("serve").long("serve").help(
+            "Serve JSON-RPC requests on a TCP address (host:port), or a Unix socket (unix:path)",
+        ))
+        .arg(
+            Arg::new("bin")
+                .long("bin")
+                .action(ArgAction::SetTrue)
+                .help("Print values in binary rather than hex"),
+        )
+}
+
+//a Run
+//fi report_assertion_failures
+/// Report the assertion failures of a simulation, returning how many
+/// there were
+fn report_assertion_failures(sim: &Simulation) -> usize {
+    let failures = sim.assertion_failures();
+    for f in failures.iter() {
+        eprintln!("FAIL: {f}");
+    }
+    failures.len()
+}
+
+//fi run
+/// Run the simulation; returns the number of failed assertions and
+/// expectations
+fn run(matches: &ArgMatches) -> Result<usize, String> {
+    let topology = Topology::from_file(matches.get_one::<String>("topology").unwrap())?;
+    let cycles = matches.get_one::<usize>("cycles").copied();
+    let end_time = matches.get_one::<usize>("time").copied();
+    let serve = matches.get_one::<String>("serve");
+    let interactive = matches.get_flag("interactive");
+    if cycles.is_none() && end_time.is_none() && !interactive && serve.is_none() {
+        return Err("One of --cycles or --time must be given".into());
+    }
+
+    let registry = ComponentRegistry::default();
+    let sim = topology.build(&registry)?;
+
+    let clock = {
+        let name = matches
+            .get_one::<String>("clock")
+            .unwrap_or(&topology.clocks[0].name);
+        sim.find_ns_name(name)
+            .and_then(|n| sim.find_clock(n))
+            .ok_or_else(|| format!("Unknown clock '{name}'"))?
+    };
+
+    let mut vcd = {
+        if let Some(filename) = matches.get_one::<String>("vcd") {
+            let f = std::fs::File::create(filename)
+                .map_err(|e| format!("Failed to create {filename}: {e}"))?;
+            let vcd = Vcd::new(std::io::BufWriter::new(f), &sim)
+                .map_err(|e| format!("Failed to write {filename}: {e}"))?;
+            Some(vcd)
+        } else {
+            None
+        }
+    };
+
+    sim.start(true)?;
+    topology.apply_deposits(&sim)?;
+    if let Some(filename) = matches.get_one::<String>("restore") {
+        let f =
+            std::fs::File::open(filename).map_err(|e| format!("Failed to open {filename}: {e}"))?;
+        let checkpoint: Checkpoint = serde_json::from_reader(std::io::BufReader::new(f))
+            .map_err(|e| format!("Failed to read checkpoint {filename}: {e}"))?;
+        sim.restore(&checkpoint)?;
+    }
+
+    if let Some(addr) = serve {
+        let mut server = RpcServer::new(&sim);
+        if let Some(path) = addr.strip_prefix("unix:") {
+            #[cfg(unix)]
+            {
+                let listener = std::os::unix::net::UnixListener::bind(path)
+                    .map_err(|e| format!("Failed to bind {path}: {e}"))?;
+                server.serve_unix(&listener).map_err(|e| format!("{e}"))?;
+            }
+            #[cfg(not(unix))]
+            return Err(format!("Unix sockets are not supported for {path}"));
+        } else {
+            let listener = std::net::TcpListener::bind(addr)
+                .map_err(|e| format!("Failed to bind {addr}: {e}"))?;
+            eprintln!("hgl-sim: serving on {}", listener.local_addr().unwrap());
+            server.serve_tcp(&listener).map_err(|e| format!("{e}"))?;
+        }
+        // The client controls the simulation; it may already have stopped it
+        let _ = sim.stop();
+        return Ok(report_assertion_failures(&sim));
+    }
+    if interactive {
+        Debugger::new(&sim)
+            .run_shell(std::io::stdin().lock(), &mut std::io::stdout())
+            .map_err(|e| format!("{e}"))?;
+    }
+
+    let mut n = 0;
+    loop {
+        if let Some(vcd) = &mut vcd {
+            vcd.sample(&sim).map_err(|e| format!("{e}"))?;
+        }
+        if interactive
+            || cycles.is_some_and(|c| n >= c)
+            || end_time.is_some_and(|t| sim.time() >= t)
+        {
+            break;
+        }
+        let edges = sim.fire_next_edges();
+        if edges.is_posedge(clock.index()) {
+            n += 1;
+        }
+    }
+    if let Some(vcd) = &mut vcd {
+        vcd.flush().map_err(|e| format!("{e}"))?;
+    }
+
+    sim.pause()?;
+    let style = {
+        if matches.get_flag("bin") {
+            fmt::AS_BIN | fmt::HDR
+        } else {
+            fmt::FULL
+        }
+    };
+    let instances = sim.instances();
+    if let Some(signals) = matches.get_many::<String>("print") {
+        for signal in signals {
+            let Some((handle, state_index)) = sim.find_state(signal) else {
+                return Err(format!("Failed to find signal '{signal}'"));
+            };
+            let s = instances.instance(handle).borrow_sim().unwrap();
+            if let Some(v) = s.try_state_data(state_index) {
+                println!("{signal} = {}", SimFormatObject::new(v.sim_value(), style));
+            }
+        }
+    }
+
+    if let Some(filename) = matches.get_one::<String>("checkpoint") {
+        let f = std::fs::File::create(filename)
+            .map_err(|e| format!("Failed to create {filename}: {e}"))?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(f), &sim.checkpoint())
+            .map_err(|e| format!("Failed to write checkpoint {filename}: {e}"))?;
+    }
+
+    let num_failed = report_assertion_failures(&sim);
+    let failures = topology.check_expectations(&sim);
+    for f in failures.iter() {
+        eprintln!("FAIL: {f}");
+    }
+    sim.stop()?;
+    println!("Finished at time {} after {n} cycles", sim.time());
+    Ok(num_failed + failures.len())
+}
+
+//a Main
+//fi main
+fn main() -> ExitCode {
+    let matches = command().get_matches();
+    match run(&matches) {
+        Ok(0) => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("hgl-sim: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
@@ -2,3 +2,5 @@ pub use hgl_indexed_vec as indexed_vec;
 pub use hgl_models as models;
 pub use hgl_sim as sim;
 pub use hgl_utils as utils;
+
+pub mod topology;
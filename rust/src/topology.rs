@@ -0,0 +1,423 @@
+//a Documentation
+//! Simulation topologies described in JSON
+//!
+//! A topology file describes the clocks of a simulation, the
+//! component instances (by registered component name, with
+//! configuration), which clocks drive which instance clock inputs,
+//! values to deposit into state after the simulation starts,
+//! assertions that are checked as the simulation runs, and values
+//! expected at the end of the run:
+//!
+//! ```json
+//! {
+//!   "clocks": [ { "name": "clk", "period": 2, "negedge_offset": 1 } ],
+//!   "instances": [
+//!     { "name": "counter", "component": "counter",
+//!       "config": { "width": 32, "reset_value": 4 },
+//!       "clocks": [ { "clock": "clk", "input": 0 } ] }
+//!   ],
+//!   "deposits": [ { "signal": "counter.reset_n", "value": 1 },
+//!                 { "signal": "counter.increment", "value": 1 } ],
+//!   "assertions": [ { "name": "bounded", "always": "counter.q < 100" },
+//!                   { "name": "counts", "clock": "clk",
+//!                     "antecedent": "counter.increment",
+//!                     "consequent": "counter.q != 0", "max_cycles": 2 } ],
+//!   "expect": [ { "signal": "counter.q", "value": 14 } ]
+//! }
+//! ```
+//!
+//! An assertion is checked on every posedge of its clock (by default
+//! the first clock); it either has an `always` expression, or an
+//! `antecedent` and `consequent` with the consequent required between
+//! `min_cycles` (default 1) and `max_cycles` (default `min_cycles`)
+//! checks after the antecedent (see [Property]).
+//!
+//! Components are instantiated through a [ComponentRegistry], which
+//! maps a component name to a function that instantiates it in a
+//! [Simulation] given its JSON configuration.
+
+//a Imports
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use hgl_models::apb_target_gpio::apb_target_gpio;
+use hgl_models::{Counter, Memory, Register, Threaded};
+use hgl_sim::prelude::sim::*;
+
+//a Topology description
+//tp ClockDesc
+/// Description of a clock in a [Topology]
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClockDesc {
+    pub name: String,
+    #[serde(default)]
+    pub delay: usize,
+    pub period: usize,
+    #[serde(default)]
+    pub negedge_offset: usize,
+}
+
+//tp ClockConnection
+/// Connection of a clock to a clock input of an instance
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClockConnection {
+    pub clock: String,
+    #[serde(default)]
+    pub input: usize,
+}
+
+//tp InstanceDesc
+/// Description of an instance of a registered component
+#[derive(Debug, Clone, Deserialize)]
+pub struct InstanceDesc {
+    pub name: String,
+    pub component: String,
+    #[serde(default)]
+    pub config: serde_json::Value,
+    #[serde(default)]
+    pub clocks: Vec<ClockConnection>,
+}
+
+//tp SignalValue
+/// A value for a signal given by its hierarchical path
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignalValue {
+    pub signal: String,
+    pub value: u64,
+}
+
+//tp AssertionDesc
+/// Description of an assertion in a [Topology]
+#[derive(Debug, Clone, Deserialize)]
+pub struct AssertionDesc {
+    pub name: String,
+    #[serde(default)]
+    pub clock: Option<String>,
+    #[serde(default)]
+    pub always: Option<String>,
+    #[serde(default)]
+    pub antecedent: Option<String>,
+    #[serde(default)]
+    pub consequent: Option<String>,
+    #[serde(default)]
+    pub min_cycles: Option<usize>,
+    #[serde(default)]
+    pub max_cycles: Option<usize>,
+}
+
+//ip AssertionDesc
+impl AssertionDesc {
+    //mp property
+    /// The property described
+    pub fn property(&self) -> Result<Property, String> {
+        match (&self.always, &self.antecedent, &self.consequent) {
+            (Some(e), None, None) => Property::always(e),
+            (None, Some(a), Some(c)) => {
+                let min_cycles = self.min_cycles.unwrap_or(1);
+                let max_cycles = self.max_cycles.unwrap_or(min_cycles);
+                Property::implies(a, c, min_cycles, max_cycles)
+            }
+            _ => Err(format!(
+                "Assertion '{}' must have either 'always', or 'antecedent' and 'consequent'",
+                self.name
+            )),
+        }
+    }
+}
+
+//tp Topology
+/// A complete simulation topology
+#[derive(Debug, Clone, Deserialize)]
+pub struct Topology {
+    pub clocks: Vec<ClockDesc>,
+    pub instances: Vec<InstanceDesc>,
+    #[serde(default)]
+    pub deposits: Vec<SignalValue>,
+    #[serde(default)]
+    pub assertions: Vec<AssertionDesc>,
+    #[serde(default)]
+    pub expect: Vec<SignalValue>,
+}
+
+//ip Topology
+impl Topology {
+    //cp from_json
+    /// Parse a topology from a JSON string
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse topology: {e}"))
+    }
+
+    //cp from_file
+    /// Read a topology from a JSON file
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read topology {}: {e}", path.display()))?;
+        Self::from_json(&json)
+    }
+
+    //mp build
+    /// Build a prepared simulation from the topology, using the
+    /// registry to instantiate the components, with its assertions
+    /// added
+    pub fn build(&self, registry: &ComponentRegistry) -> Result<Simulation<'static>, String> {
+        if self.clocks.is_empty() {
+            return Err("Topology must have at least one clock".into());
+        }
+        let mut sim = Simulation::new();
+        let mut clocks = HashMap::new();
+        for c in self.clocks.iter() {
+            let clk = sim.add_clock(&c.name, c.delay, c.period, c.negedge_offset)?;
+            clocks.insert(c.name.as_str(), clk);
+        }
+        for i in self.instances.iter() {
+            let handle = registry.instantiate(&mut sim, &i.component, &i.name, &i.config)?;
+            for c in i.clocks.iter() {
+                let Some(clk) = clocks.get(c.clock.as_str()) else {
+                    return Err(format!(
+                        "Unknown clock '{}' for instance '{}'",
+                        c.clock, i.name
+                    ));
+                };
+                sim.connect_clock(*clk, handle, c.input);
+            }
+        }
+        sim.prepare_simulation();
+        for a in self.assertions.iter() {
+            let clock = a.clock.as_deref().unwrap_or(&self.clocks[0].name);
+            let Some(clk) = clocks.get(clock) else {
+                return Err(format!(
+                    "Unknown clock '{clock}' for assertion '{}'",
+                    a.name
+                ));
+            };
+            sim.add_assertion(&a.name, *clk, a.property()?)?;
+        }
+        Ok(sim)
+    }
+
+    //mp apply_deposits
+    /// Deposit the topology's initial values into the simulation
+    /// state
+    pub fn apply_deposits(&self, sim: &Simulation) -> Result<(), String> {
+        for d in self.deposits.iter() {
+            deposit_u64(sim, &d.signal, d.value)?;
+        }
+        Ok(())
+    }
+
+    //mp check_expectations
+    /// Check the expected values of signals, returning a description
+    /// of each mismatch
+    pub fn check_expectations(&self, sim: &Simulation) -> Vec<String> {
+        let mut failures = vec![];
+        for e in self.expect.iter() {
+            match state_as_u64(sim, &e.signal) {
+                Ok(v) if v == e.value => (),
+                Ok(v) => failures.push(format!(
+                    "{}: expected {:#x} but got {v:#x}",
+                    e.signal, e.value
+                )),
+                Err(s) => failures.push(s),
+            }
+        }
+        failures
+    }
+}
+
+//a State access by u64
+//fp deposit_u64
+/// Deposit a u64 value into some state of a simulation, given its
+/// hierarchical path; the value is truncated to the width of the
+/// state
+pub fn deposit_u64(sim: &Simulation, path: &str, value: u64) -> Result<(), String> {
+    let Some((handle, state_index)) = sim.find_state(path) else {
+        return Err(format!("Failed to find state '{path}'"));
+    };
+    if sim
+        .with_state_mut(handle, state_index, |mut v| v.set_u64(value))
+        .unwrap_or(false)
+    {
+        Ok(())
+    } else {
+        Err(format!("Failed to deposit into '{path}'"))
+    }
+}
+
+//fp state_as_u64
+/// Read some state of a simulation as a u64, given its hierarchical
+/// path; the state must be at most 64 bits wide
+pub fn state_as_u64(sim: &Simulation, path: &str) -> Result<u64, String> {
+    let Some((handle, state_index)) = sim.find_state(path) else {
+        return Err(format!("Failed to find state '{path}'"));
+    };
+    sim.with_state(handle, state_index, |v| v.get_u64())
+        .flatten()
+        .ok_or_else(|| format!("State '{path}' cannot be read as a u64"))
+}
+
+//a ComponentRegistry
+//tp InstantiateFn
+/// Function used to instantiate a component in a simulation, given
+/// the instance name and the JSON configuration
+pub type InstantiateFn =
+    fn(&mut Simulation, &str, &serde_json::Value) -> Result<InstanceHandle, String>;
+
+//tp ComponentRegistry
+/// A registry of components that may be instantiated by name from a
+/// [Topology]
+pub struct ComponentRegistry {
+    components: HashMap<String, InstantiateFn>,
+}
+
+//ip Default for ComponentRegistry
+impl std::default::Default for ComponentRegistry {
+    /// Create a registry with the components provided by [hgl_models]
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register("counter", instantiate_counter);
+        registry.register("register", instantiate_register);
+        registry.register("memory", instantiate_memory);
+        registry.register("apb_target_gpio", |sim, name, _| {
+            sim.instantiate::<apb_target_gpio, _, _>(name, || ())
+        });
+        registry.register("threaded", |sim, name, _| {
+            sim.instantiate::<Threaded, _, _>(name, || ())
+        });
+        registry
+    }
+}
+
+//ip ComponentRegistry
+impl ComponentRegistry {
+    //cp empty
+    /// Create an empty registry
+    pub fn empty() -> Self {
+        Self {
+            components: HashMap::new(),
+        }
+    }
+
+    //mp register
+    /// Register a component by name
+    pub fn register(&mut self, component: &str, f: InstantiateFn) {
+        self.components.insert(component.into(), f);
+    }
+
+    //mp instantiate
+    /// Instantiate a registered component in a simulation
+    pub fn instantiate(
+        &self,
+        sim: &mut Simulation,
+        component: &str,
+        name: &str,
+        config: &serde_json::Value,
+    ) -> Result<InstanceHandle, String> {
+        let Some(f) = self.components.get(component) else {
+            return Err(format!("Unknown component '{component}' for '{name}'"));
+        };
+        f(sim, name, config)
+    }
+}
+
+//a Built-in component instantiation
+//tp WidthConfig
+/// Configuration for components that are generic on a bit-vector
+/// width, with an optional reset value
+#[derive(Debug, Default, Deserialize)]
+struct WidthConfig {
+    #[serde(default = "default_width")]
+    width: usize,
+    reset_value: Option<u64>,
+    #[serde(default)]
+    size: usize,
+}
+
+//fi default_width
+fn default_width() -> usize {
+    32
+}
+
+//fi width_config
+fn width_config(config: &serde_json::Value) -> Result<WidthConfig, String> {
+    if config.is_null() {
+        return Ok(WidthConfig {
+            width: default_width(),
+            ..Default::default()
+        });
+    }
+    WidthConfig::deserialize(config).map_err(|e| format!("Bad configuration: {e}"))
+}
+
+//mi dispatch_width
+/// Invoke an expression with the type 'T' bound to the `Bv` of the
+/// width given by the configuration
+macro_rules! dispatch_width {
+    ($config:expr, $T:ident, $e:expr) => {
+        match $config.width {
+            8 => {
+                type $T = Bv<8>;
+                $e
+            }
+            16 => {
+                type $T = Bv<16>;
+                $e
+            }
+            32 => {
+                type $T = Bv<32>;
+                $e
+            }
+            64 => {
+                type $T = Bv<64>;
+                $e
+            }
+            w => Err(format!("Unsupported width {w}, must be 8, 16, 32 or 64")),
+        }
+    };
+}
+
+//fi instantiate_counter
+fn instantiate_counter(
+    sim: &mut Simulation,
+    name: &str,
+    config: &serde_json::Value,
+) -> Result<InstanceHandle, String> {
+    let config = width_config(config)?;
+    let reset_value = config.reset_value;
+    dispatch_width!(
+        config,
+        T,
+        sim.instantiate::<Counter<T>, _, _>(name, || reset_value.map(T::of_u64))
+    )
+}
+
+//fi instantiate_register
+fn instantiate_register(
+    sim: &mut Simulation,
+    name: &str,
+    config: &serde_json::Value,
+) -> Result<InstanceHandle, String> {
+    let config = width_config(config)?;
+    let reset_value = config.reset_value;
+    dispatch_width!(
+        config,
+        T,
+        sim.instantiate::<Register<T>, _, _>(name, || reset_value.map(T::of_u64))
+    )
+}
+
+//fi instantiate_memory
+fn instantiate_memory(
+    sim: &mut Simulation,
+    name: &str,
+    config: &serde_json::Value,
+) -> Result<InstanceHandle, String> {
+    let config = width_config(config)?;
+    let size = config.size;
+    dispatch_width!(
+        config,
+        T,
+        sim.instantiate::<Memory<T, Bv<32>>, _, _>(name, || size)
+    )
+}
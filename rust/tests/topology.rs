@@ -0,0 +1,197 @@
+use hgl::sim::prelude::sim::*;
+use hgl::topology::{deposit_u64, state_as_u64, ComponentRegistry, Topology};
+
+const COUNTER_TOPOLOGY: &str = r#"
+{
+  "clocks": [ { "name": "clk", "period": 2, "negedge_offset": 1 } ],
+  "instances": [
+    { "name": "counter", "component": "counter",
+      "config": { "width": 32, "reset_value": 4 },
+      "clocks": [ { "clock": "clk", "input": 0 } ] },
+    { "name": "reg", "component": "register",
+      "config": { "width": 8 },
+      "clocks": [ { "clock": "clk" } ] }
+  ],
+  "deposits": [ { "signal": "counter.reset_n", "value": 1 },
+                { "signal": "counter.increment", "value": 1 },
+                { "signal": "reg.data", "value": 4660 } ],
+  "expect": [ { "signal": "counter.q", "value": 14 } ]
+}
+"#;
+
+const ASSERTION_TOPOLOGY: &str = r#"
+{
+  "clocks": [ { "name": "clk", "period": 2, "negedge_offset": 1 } ],
+  "instances": [
+    { "name": "counter", "component": "counter",
+      "config": { "width": 32, "reset_value": 4 },
+      "clocks": [ { "clock": "clk", "input": 0 } ] }
+  ],
+  "deposits": [ { "signal": "counter.reset_n", "value": 1 },
+                { "signal": "counter.increment", "value": 1 } ],
+  "assertions": [ { "name": "bounded", "always": "counter.q < 10" },
+                  { "name": "counts", "clock": "clk",
+                    "antecedent": "counter.increment",
+                    "consequent": "counter.q > 4" } ]
+}
+"#;
+
+#[test]
+fn topology_counter() -> Result<(), String> {
+    let topology = Topology::from_json(COUNTER_TOPOLOGY)?;
+    let sim = topology.build(&ComponentRegistry::default())?;
+    sim.start(true)?;
+    topology.apply_deposits(&sim)?;
+    assert_eq!(
+        state_as_u64(&sim, "reg.data")?,
+        0x34,
+        "Deposits must be truncated to the width of the state"
+    );
+
+    let mut vcd = Vcd::new(vec![], &sim).map_err(|e| e.to_string())?;
+    let mut posedges = 0;
+    while posedges < 10 {
+        if sim.fire_next_edges().is_posedge(0) {
+            posedges += 1;
+        }
+        vcd.sample(&sim).map_err(|e| e.to_string())?;
+    }
+    // Ten posedges, starting at time 0, and nine negedges
+    assert_eq!(sim.time(), 18);
+    assert!(topology.check_expectations(&sim).is_empty());
+
+    let checkpoint = sim.checkpoint();
+    deposit_u64(&sim, "counter.q", 0)?;
+    assert_eq!(topology.check_expectations(&sim).len(), 1);
+    sim.restore(&checkpoint)?;
+    assert!(topology.check_expectations(&sim).is_empty());
+
+    let vcd = String::from_utf8(vcd.into_inner()).unwrap();
+    assert!(vcd.contains("$scope module counter $end"));
+    assert!(vcd.contains("$var wire 32 "));
+    assert!(vcd.contains("b00000000000000000000000000001110 "));
+    sim.stop()?;
+    Ok(())
+}
+
+#[test]
+fn topology_assertions() -> Result<(), String> {
+    let topology = Topology::from_json(ASSERTION_TOPOLOGY)?;
+    let sim = topology.build(&ComponentRegistry::default())?;
+    sim.start(true)?;
+    topology.apply_deposits(&sim)?;
+    while sim.time() < 20 {
+        sim.fire_next_edges();
+    }
+    let failures = sim.assertion_failures();
+    assert!(!failures.is_empty());
+    assert!(failures.iter().all(|f| f.name == "bounded"));
+    assert_eq!(failures[0].time, 10);
+    assert_eq!(failures[0].instance, "counter");
+    sim.stop()?;
+    Ok(())
+}
+
+#[test]
+fn topology_errors() {
+    let registry = ComponentRegistry::default();
+    assert!(Topology::from_json("{").is_err());
+    let t = Topology::from_json(
+        r#"{"clocks":[{"name":"clk","period":1}], "instances":[{"name":"x", "component":"unknown"}]}"#,
+    )
+    .unwrap();
+    assert!(t.build(&registry).is_err());
+    let t = Topology::from_json(
+        r#"{"clocks":[{"name":"clk","period":1}], "instances":[{"name":"x", "component":"counter", "config":{"width":7}}]}"#,
+    )
+    .unwrap();
+    assert!(t.build(&registry).is_err());
+    let t = Topology::from_json(
+        r#"{"clocks":[{"name":"clk","period":1}], "instances":[], "assertions":[{"name":"a"}]}"#,
+    )
+    .unwrap();
+    assert!(t.build(&registry).is_err());
+    let t = Topology::from_json(
+        r#"{"clocks":[{"name":"clk","period":1}], "instances":[], "assertions":[{"name":"a", "clock":"x", "always":"1"}]}"#,
+    )
+    .unwrap();
+    assert!(t.build(&registry).is_err());
+}
+
+#[test]
+fn hgl_sim_exit_codes() {
+    let dir = std::env::temp_dir().join(format!("hgl_sim_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let topology = dir.join("counter.json");
+    std::fs::write(&topology, COUNTER_TOPOLOGY).unwrap();
+
+    let run = |cycles: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_hgl-sim"))
+            .arg(&topology)
+            .args(["--cycles", cycles, "--print", "counter.q"])
+            .arg("--checkpoint")
+            .arg(dir.join("checkpoint.json"))
+            .arg("--vcd")
+            .arg(dir.join("waves.vcd"))
+            .output()
+            .unwrap()
+    };
+    let output = run("10");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("counter.q = 32h0000000e"), "{stdout}");
+    assert!(dir.join("checkpoint.json").exists());
+    assert!(dir.join("waves.vcd").exists());
+
+    let output = run("11");
+    assert_eq!(output.status.code(), Some(1));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn hgl_sim_assertion_exit_codes() {
+    let dir = std::env::temp_dir().join(format!("hgl_sim_assert_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let topology = dir.join("counter.json");
+    std::fs::write(&topology, ASSERTION_TOPOLOGY).unwrap();
+
+    let run = |cycles: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_hgl-sim"))
+            .arg(&topology)
+            .args(["--cycles", cycles])
+            .output()
+            .unwrap()
+    };
+    assert!(run("5").status.success());
+    let output = run("10");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("10: assertion 'bounded' failed in 'counter'"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("counter.q=32h0000000a"), "{stderr}");
+
+    // Assertions are also checked when a client drives the simulation
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_hgl-sim"))
+        .arg(&topology)
+        .args(["--serve", "127.0.0.1:0"])
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = std::io::BufReader::new(child.stderr.take().unwrap());
+    let mut line = String::new();
+    std::io::BufRead::read_line(&mut stderr, &mut line).unwrap();
+    let addr = line.trim().strip_prefix("hgl-sim: serving on ").unwrap();
+    let mut client = RpcClient::connect_tcp(addr).unwrap();
+    client
+        .call("step", serde_json::json!({"edges": 20}))
+        .unwrap();
+    client.call("shutdown", serde_json::Value::Null).unwrap();
+    drop(client);
+    let mut rest = String::new();
+    std::io::Read::read_to_string(&mut stderr, &mut rest).unwrap();
+    assert_eq!(child.wait().unwrap().code(), Some(1));
+    assert!(rest.contains("assertion 'bounded' failed"), "{rest}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}